@@ -1,62 +1,229 @@
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Local};
 use egui::Context;
 use log::{debug, info, warn, error};
 use egui_twemoji::EmojiLabel;
 use egui::RichText;
 use egui_extras;
 
-use crate::constants::{UI_STATUS_MESSAGE_DURATION, OutputFormat, DEFAULT_OUTPUT_FORMAT, DEFAULT_OUTPUT_FILENAME_BASE};
-use crate::error::Result;
+use crate::budget::{TokenBudget, chars_per_token, estimate_tokens, set_chars_per_token};
+use crate::compression::{compressed_path, write_compressed_copies};
+use crate::constants::{FILE_PREVIEW_MAX_BYTES, LLM_STREAM_REPAINT_INTERVAL, SHUTDOWN_POLL_INTERVAL, TOKEN_COUNT_PROGRESS_INTERVAL, UI_STATUS_MESSAGE_DURATION, WATCHER_POLLING_FALLBACK_ATTEMPTS, WATCHER_RESTART_INITIAL_BACKOFF, WATCHER_RESTART_MAX_BACKOFF, OutputFormat, DEFAULT_OUTPUT_FILENAME_BASE, DEFAULT_MAX_FILE_LINES, DEFAULT_MAX_FILE_BYTES, DEFAULT_STRUCTURE_MAX_DEPTH, DEFAULT_STRUCTURE_MAX_ENTRIES, USAGE_HABIT_TAG, USAGE_TOP_FILES, LAZY_SCAN_DEPTH};
+use crate::error::{AppError, Result};
 use crate::events::AppEvent;
-use crate::file_handler::{FileHandler, FileNode};
+use crate::exporter::export_pdf;
+use crate::external_open::{open_in_editor, reveal_in_file_manager};
+use crate::file_handler::{is_ignore_rule_file, matches_ignore_patterns, FileHandler, FileNode};
 use crate::file_monitor::FileMonitor;
-use crate::document_generator::DocumentGenerator;
-use crate::ui_tree_handler::UITreeHandler;
-
-// Initial default ignore patterns
-const DEFAULT_IGNORE_PATTERNS_ARRAY: &[&str] = &[
-    // Common VCS and build artifacts
-    ".git/", ".hg/", ".svn/",
-    "target/", "build/", "dist/", "pkg/", "node_modules/",
-    // Python specific
-    "__pycache__/", "*.pyc", "*.pyo", "*.pyd",
-    ".env", ".venv", "venv/", "env/",
-    // "requirements.txt", // Often useful to see, but can be configured if user wants it ignored
-    // Node specific
-    "package-lock.json", "yarn.lock",
-    // Common OS files
-    ".DS_Store", "Thumbs.db",
-    // Log files
-    "*.log",
-    // Temporary files
-    "*.tmp", "*.swp", "*.swo",
-    // Compiled outputs & binaries from various languages/tools
-    "*.o", "*.so", "*.a", "*.dylib",
-    "*.exe", "*.dll", "*.lib", "*.exp", "*.obj", "*.def",
-    // Archives & compressed files
-    "*.zip", "*.tar", "*.gz", "*.rar",
-    // Image/Media (usually not context for code)
-    "*.ico", "*.png", "*.jpg", "*.jpeg", "*.gif", "*.bmp", "*.tiff", "*.svg",
-    "*.mp3", "*.mp4", "*.avi",
-    // Database files
-    "*.db", "*.sqlite", "*.sqlite3",
-    // IDE specific
-    ".idea/", ".vscode/", "*.sublime-project", "*.sublime-workspace",
-];
+use crate::file_summaries::FileSummarizer;
+use crate::generation_history::{GenerationHistory, HistoryEntry, diff_generations, record_generation, section_diff};
+use crate::gitignored_files::GitignoredFileDetector;
+use crate::http_api::{ApiReply, ApiRequest};
+use crate::llm_chat::{LlmChat, LlmProvider};
+use crate::log_references::{find_log_references, LogReference};
+use crate::dependencies::{ContextReason, minimal_context};
+use crate::directory_compare::{DirectoryComparer, DirectoryComparison};
+use crate::document_generator::{DocumentGenerator, FileSnapshot, OutlineEntry, existing_chunk_paths, is_output_document_path, is_replaceable_document, next_free_output_path};
+use crate::document_index::document_index_path;
+use crate::document_template::default_template;
+use crate::document_worker::{DocumentJob, DocumentWorker, companion_targets, generate_document};
+use crate::project_settings::{excluded_extension_patterns, ProjectSettings};
+use crate::project_type::{detect_project_types, ProjectType};
+use crate::remote_source::RemoteSource;
+use crate::scan_cache::ScanCache;
+use crate::schedule::{next_scheduled_time, parse_schedule_times, timestamped_output_path};
+use crate::section_cache::SectionCache;
+use crate::settings::{AppSettings, PanelLayout, SelectionPreset, ThemePreference, TokenEstimator};
+use crate::temp_files::cleanup_orphaned_temp_files;
+use crate::ui_tree_handler::{OpenTarget, UITreeHandler};
+use crate::update_check::{CURRENT_VERSION, ReleaseInfo, fetch_latest_release};
+use crate::usage_stats::UsageStats;
+use crate::utils::{format_byte_size, format_token_count, read_file_consistently};
+use crate::workspace_config::{WorkspaceConfig, run_after_generate};
+
+/// Files suggested for a focal file, each with a checkbox before the user accepts them
+struct ContextSuggestion {
+    focal_file: PathBuf,
+    files: Vec<(PathBuf, ContextReason, bool)>,
+}
+
+/// Text pasted into the Select From Log window and the project files it mentions
+#[derive(Default)]
+struct LogSelection {
+    log_text: String,
+    references: Vec<(LogReference, bool)>, // Each with its checkbox
+    annotate: bool,                        // Show the excerpts below the selected files' content
+}
+
+/// Setup suggested for a newly opened project of a recognized type
+struct ProjectSetup {
+    project_types: Vec<ProjectType>,
+    ignore_patterns: Vec<String>, // Suggested patterns that are not ignored yet
+}
+
+/// Background count of the selected files' tokens, from their content as the document shows it
+struct TokenCount {
+    id: u64,
+    selection: HashSet<PathBuf>, // Restarted once the selection no longer matches
+    counted_files: usize,
+    tokens: usize,
+    done: bool,
+    cancel_flag: Arc<AtomicBool>,
+}
+
+/// What the Retry button of the error banner does
+#[derive(Debug, Clone, PartialEq)]
+enum RetryAction {
+    Scan(PathBuf), // A failed scan clears the current directory
+    Rescan,
+    Generation,
+    RestartWatcher,
+}
+
+impl RetryAction {
+    fn label(&self) -> &'static str {
+        match self {
+            RetryAction::Scan(_) => "Retry scan",
+            RetryAction::Rescan => "Retry rescan",
+            RetryAction::Generation => "Retry generation",
+            RetryAction::RestartWatcher => "Restart watcher",
+        }
+    }
+}
+
+/// The error shown in the banner: a one-line message and, when expanded, the details behind it
+struct ErrorReport {
+    message: String,
+    causes: Vec<String>, // The error and its sources, outermost first
+    path: Option<PathBuf>,
+    suggestion: Option<&'static str>,
+    retry: Option<RetryAction>,
+}
+
+impl ErrorReport {
+    fn has_details(&self) -> bool {
+        !self.causes.is_empty() || self.path.is_some() || self.suggestion.is_some()
+    }
+
+    /// Plain text of the report, for bug reports
+    fn to_text(&self) -> String {
+        let mut lines = vec![self.message.clone()];
+        lines.extend(self.causes.iter().map(|cause| format!("  caused by: {}", cause)));
+        if let Some(path) = &self.path {
+            lines.push(format!("  path: {}", path.display()));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Sections of the Settings window, filtered by the search box
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SettingsCategory {
+    Output,
+    BudgetAndLimits,
+    Monitoring,
+    Scanning,
+    Selection,
+    Appearance,
+    Updates,
+    Llm,
+}
+
+impl SettingsCategory {
+    const ALL: [SettingsCategory; 8] = [
+        SettingsCategory::Output,
+        SettingsCategory::BudgetAndLimits,
+        SettingsCategory::Monitoring,
+        SettingsCategory::Scanning,
+        SettingsCategory::Selection,
+        SettingsCategory::Appearance,
+        SettingsCategory::Updates,
+        SettingsCategory::Llm,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            SettingsCategory::Output => "Output",
+            SettingsCategory::BudgetAndLimits => "Budget & Limits",
+            SettingsCategory::Monitoring => "Monitoring",
+            SettingsCategory::Scanning => "Scanning",
+            SettingsCategory::Selection => "Selection & Tree",
+            SettingsCategory::Appearance => "Appearance",
+            SettingsCategory::Updates => "Updates",
+            SettingsCategory::Llm => "LLM",
+        }
+    }
+
+    /// Words matched by the search box besides the category name
+    fn keywords(&self) -> &'static [&'static str] {
+        match self {
+            SettingsCategory::Output => &["snapshot", "split", "parts", "chunk", "template", "tera", "anchor", "links", "table of contents", "footer", "lines", "count", "index", "json", "offset", "agent", "compress", "gzip", "gz", "archive", "history", "diff", "copies", "metadata", "git", "commit", "branch", "version", "summary", "language", "statistics", "fence", "shebang", "dockerfile", "makefile", "format", "asciidoc", "markdown", "org", "restructuredtext", "rst", "include", "directive", "link", "wiki", "transclusion", "anchor", "structure", "tree", "files", "contents", "sections", "whole", "full", "layout", "depth", "entries", "monorepo", "modified", "age", "date"],
+            SettingsCategory::BudgetAndLimits => &["token", "budget", "tokenizer", "estimate", "priority", "lines", "bytes", "truncate", "size", "minify", "comments", "strip", "blank", "binary", "notebook", "jupyter", "ipynb"],
+            SettingsCategory::Monitoring => &["watch", "regenerate", "throttle", "size", "partial", "poll", "network", "nfs", "sshfs", "debounce", "batch", "delay", "schedule", "daily", "timestamp", "growth", "alert", "regression", "percent", "http", "api", "server", "port", "localhost", "curl", "integration"],
+            SettingsCategory::Scanning => &["generated", "linguist", "gitattributes", "exclude", "size", "lines", "count", "lazy", "shallow", "monorepo", "expand"],
+            SettingsCategory::Selection => &["quick select", "preset", "glob", "extension", "group", "pattern", "editor", "open", "reveal", "file manager"],
+            SettingsCategory::Appearance => &["theme", "dark", "light", "system", "colors", "layout", "panel", "columns", "stacked", "dock", "wide"],
+            SettingsCategory::Updates => &["version", "release", "github", "changelog", "new"],
+            SettingsCategory::Llm => &["ai", "chat", "ask", "question", "openai", "ollama", "model", "endpoint", "api", "key", "gpt", "summary", "summarize"],
+        }
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        query.is_empty()
+            || self.name().to_lowercase().contains(query)
+            || self.keywords().iter().any(|keyword| keyword.contains(query))
+    }
+}
 
 pub struct ContextBuilderApp {
     // Core state
     current_directory: Option<PathBuf>,
     root_file_node: Option<FileNode>,
-    selected_output_format: OutputFormat,
     output_file_path: Option<PathBuf>,
+    settings: AppSettings, // Saved whenever it changes
+    custom_header_text: String, // Persisted per project
+    scratch_text: String,       // Persisted per project
+    excluded_extensions: BTreeSet<String>, // Persisted per project, applied on every scan
+    new_excluded_extension: String,        // Being typed in the Quick select menu
+    usage_stats: UsageStats,    // Local only, per project
+    generation_history: GenerationHistory, // Local only, per project
     
     // UI state
     ui_tree_handler: UITreeHandler,
-    ignore_patterns_text: String, // New field for mutable ignore patterns
+    ignore_patterns_text: String, // Edited patterns, applied to `settings` on rescan
+    show_outline_panel: bool,
+    show_history_panel: bool,
+    history_comparison: (Option<i64>, Option<i64>), // Ids of the generations picked as A and B
+    history_section_diff: Option<(i64, i64, String, String)>, // A, B, section title and its unified diff
+    show_settings_window: bool,
+    show_insights_window: bool,
+    show_llm_window: bool,
+    settings_search: String,
+    tree_filter: String,     // Name filter above the file tree
+    focus_tree_filter: bool, // Ctrl+F was pressed; the filter box takes focus when rendered
+    document_outline: Vec<OutlineEntry>,
+    file_preview: Option<(PathBuf, Option<String>)>, // File shown in the preview panel, with its content once read
+    context_suggestion: Option<ContextSuggestion>,
+    log_selection: Option<LogSelection>,
+    log_excerpts: HashMap<PathBuf, Vec<String>>, // From the last log selection, if annotated
+    external_files: BTreeSet<PathBuf>, // Attached from outside the project, always included
+    watched_output_path: Option<PathBuf>, // Document the watcher and scans currently leave out
+    token_count: Option<TokenCount>,
+    project_setup: Option<ProjectSetup>, // Shown as a banner until applied or dismissed
+    setup_banner_dismissed: bool,        // For this project, saved in its project settings
+    workspace_config: Option<WorkspaceConfig>, // The project's .contextbuilder.toml, used only once trusted
+    workspace_trusted: Option<bool>,           // For this project, saved in its project settings; `None` until asked
+    token_count_runs: u64, // Tells progress of a cancelled count from the current one
+    overwrite_confirmation: Option<PathBuf>, // Existing foreign file the next generation would replace
+    selection_locked: bool,   // Selection changes ask to unlock first
+    show_unlock_prompt: bool, // A change was attempted while locked
+    confirmed_output_paths: HashSet<PathBuf>, // Foreign files the user agreed to overwrite
     
     // Communication
     event_sender: mpsc::Sender<AppEvent>,
@@ -64,95 +231,503 @@ pub struct ContextBuilderApp {
     
     // File monitoring
     file_monitor: FileMonitor,
+    document_worker: DocumentWorker, // Serializes all writes to the output document
+    worker_threads: Vec<thread::JoinHandle<()>>, // Background threads that write files, waited for at exit
     monitoring_active: bool,
+    document_size_bytes: u64, // Size of the last generated document (all parts)
+    monitoring_baseline_bytes: Option<u64>, // Document size at the first write since monitoring started
+    growth_alert_shown: bool,
+    growth_alert_dismissed: bool, // No more alerts until monitoring restarts
+    file_snapshot: Arc<FileSnapshot>, // Snapshot mode: contents of the selected files
+    section_cache: Arc<Mutex<SectionCache>>, // Rendered sections reused by later generations
+    snapshot_taken_at: Option<Instant>,
+    selection_regeneration_pending: bool, // Selection changed while auto-regeneration was throttled
+    pending_usage_record: Option<Vec<PathBuf>>, // Relative files of an explicit generation, recorded once it succeeds
+    watcher_started_at: Option<Instant>,
+    watcher_restart_at: Option<Instant>, // Scheduled restart of a failed watcher
+    watcher_restart_attempts: u32,       // Consecutive restarts; reset once a watcher keeps running
+    next_scheduled_generation: Option<DateTime<Local>>, // Recomputed when the schedule changes
+    available_update: Option<ReleaseInfo>, // Newer release found by the update check
+    show_update_changelog: bool,
+    remote_source: Option<RemoteSource>, // The open directory is the local mirror of this server directory
+    remote_address_input: Option<String>, // The `user@host:path` field, while it is shown
     
     // UI feedback
     status_message: Option<(String, Instant)>,
-    error_message: Option<String>,
+    error: Option<ErrorReport>,
+    error_expanded: bool,
     
     // Operation states
     is_loading_directory: bool,
+    is_fetching_remote: bool,
+    is_validating_cached_scan: bool, // A cached tree is shown while the real scan runs
+    is_rescanning_structure: bool,   // Background rescan after a structural change
+    structure_rescan_pending: bool,  // Another change arrived during that rescan
+    unscanned_paths: BTreeMap<PathBuf, bool>, // Created while monitoring was off, with whether each is a directory
+    ignore_rules_changed: bool, // An ignore file changed while monitoring was off
+    scan_cancel_flag: Arc<AtomicBool>, // Belongs to the most recent scan
+    scan_progress: usize, // Entries walked by the running scan
     is_generating_document: bool,
+    is_checking_for_updates: bool,
+
+    // LLM chat
+    llm_question: String,
+    llm_answer: String,
+    llm_chat: Option<LlmChat>, // The running request
+    llm_error: Option<String>,
+    llm_runs: u64, // Tells chunks of a cancelled request from the current one
 }
 
 impl ContextBuilderApp {
     pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        let (event_sender, event_receiver) = mpsc::channel();
-        let file_monitor = FileMonitor::new(event_sender.clone());
-        
         // Install image loaders for egui-twemoji (required for rendering SVG and PNG emotes)
         egui_extras::install_image_loaders(&_cc.egui_ctx);
         
+        let settings = AppSettings::load();
+        set_chars_per_token(settings.token_estimator.chars_per_token());
+
+        // Remove temp files orphaned by a crash in an earlier run
+        thread::spawn(cleanup_orphaned_temp_files);
+        
+        let mut app = Self::new_tab(settings);
+        if app.settings.update_check_enabled {
+            app.start_update_check(false);
+        }
+        app
+    }
+
+    /// An app without a project, e.g. for another project tab, sharing the given settings
+    pub fn new_tab(settings: AppSettings) -> Self {
+        let (event_sender, event_receiver) = mpsc::channel();
+        let file_monitor = FileMonitor::new(event_sender.clone());
+        let document_worker = DocumentWorker::new(event_sender.clone());
+
         Self {
             current_directory: None,
             root_file_node: None,
-            selected_output_format: DEFAULT_OUTPUT_FORMAT,
             output_file_path: None,
+            ignore_patterns_text: settings.ignore_patterns.join("\n"),
+            settings,
+            custom_header_text: String::new(),
+            scratch_text: String::new(),
+            excluded_extensions: BTreeSet::new(),
+            new_excluded_extension: String::new(),
+            usage_stats: UsageStats::default(),
+            generation_history: GenerationHistory::default(),
             ui_tree_handler: UITreeHandler::new(),
-            ignore_patterns_text: DEFAULT_IGNORE_PATTERNS_ARRAY.join("\n"), // Initialize with default patterns
+            show_outline_panel: false,
+            show_history_panel: false,
+            history_comparison: (None, None),
+            history_section_diff: None,
+            show_settings_window: false,
+            show_insights_window: false,
+            show_llm_window: false,
+            settings_search: String::new(),
+            tree_filter: String::new(),
+            focus_tree_filter: false,
+            document_outline: Vec::new(),
+            file_preview: None,
+            context_suggestion: None,
+            log_selection: None,
+            log_excerpts: HashMap::new(),
+            external_files: BTreeSet::new(),
+            watched_output_path: None,
+            token_count: None,
+            project_setup: None,
+            setup_banner_dismissed: false,
+            workspace_config: None,
+            workspace_trusted: None,
+            token_count_runs: 0,
+            overwrite_confirmation: None,
+            selection_locked: false,
+            show_unlock_prompt: false,
+            confirmed_output_paths: HashSet::new(),
             event_sender,
             event_receiver,
             file_monitor,
+            document_worker,
+            worker_threads: Vec::new(),
             monitoring_active: false,
+            document_size_bytes: 0,
+            monitoring_baseline_bytes: None,
+            growth_alert_shown: false,
+            growth_alert_dismissed: false,
+            file_snapshot: Arc::new(FileSnapshot::new()),
+            section_cache: Arc::new(Mutex::new(SectionCache::default())),
+            snapshot_taken_at: None,
+            selection_regeneration_pending: false,
+            pending_usage_record: None,
+            watcher_started_at: None,
+            watcher_restart_at: None,
+            watcher_restart_attempts: 0,
+            next_scheduled_generation: None,
+            available_update: None,
+            show_update_changelog: false,
+            remote_source: None,
+            remote_address_input: None,
             status_message: None,
-            error_message: None,
+            error: None,
+            error_expanded: false,
             is_loading_directory: false,
+            is_fetching_remote: false,
+            is_validating_cached_scan: false,
+            is_rescanning_structure: false,
+            structure_rescan_pending: false,
+            unscanned_paths: BTreeMap::new(),
+            ignore_rules_changed: false,
+            scan_cancel_flag: Arc::new(AtomicBool::new(false)),
+            scan_progress: 0,
             is_generating_document: false,
+            is_checking_for_updates: false,
+            llm_question: String::new(),
+            llm_answer: String::new(),
+            llm_chat: None,
+            llm_error: None,
+            llm_runs: 0,
+        }
+    }
+
+    /// Name of the project's directory, for its tab
+    pub fn tab_title(&self) -> String {
+        self.current_directory.as_ref()
+            .and_then(|directory| directory.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "New project".to_string())
+    }
+
+    /// Whether the tab has work in progress or monitoring running, shown as a dot on its tab
+    pub fn is_busy(&self) -> bool {
+        self.monitoring_active || self.is_loading_directory || self.is_generating_document
+    }
+
+    /// Answers a request of the local HTTP API from this tab
+    pub fn answer_api_request(&mut self, request: ApiRequest) -> ApiReply {
+        match request {
+            ApiRequest::Context => {
+                let paths: Vec<PathBuf> = self.current_document_paths().into_iter().filter(|path| path.is_file()).collect();
+                if paths.is_empty() {
+                    return ApiReply::Unavailable("No document has been generated yet".to_string());
+                }
+                ApiReply::Documents { paths, format: self.settings.output_format }
+            }
+            ApiRequest::Tree => match &self.root_file_node {
+                Some(root_node) => ApiReply::Tree(root_node.clone()),
+                None => ApiReply::Unavailable("No directory is open".to_string()),
+            },
+            ApiRequest::Regenerate => {
+                if !self.can_generate() {
+                    return ApiReply::Unavailable("Cannot generate now: a directory must be scanned, files selected and an output path set, with no generation running".to_string());
+                }
+                info!("Regenerating on request of the local API");
+                self.generate_document(false);
+                ApiReply::Accepted("Generation started".to_string())
+            }
+        }
+    }
+
+    /// What the tab is doing, for crash reports
+    pub fn state_summary(&self) -> String {
+        format!(
+            "directory: {:?}, selected files: {}, output: {:?} ({}), monitoring: {}, loading: {}, rescanning: {}, generating: {}, watcher restarts: {}",
+            self.current_directory,
+            self.ui_tree_handler.selected_files.len(),
+            self.output_file_path,
+            self.settings.output_format.name(),
+            self.monitoring_active,
+            self.is_loading_directory,
+            self.is_rescanning_structure,
+            self.is_generating_document,
+            self.watcher_restart_attempts,
+        )
+    }
+
+    pub fn settings(&self) -> &AppSettings {
+        &self.settings
+    }
+
+    /// Takes over settings changed in another tab
+    pub fn set_settings(&mut self, settings: AppSettings) {
+        if self.settings.ignore_patterns != settings.ignore_patterns {
+            self.ignore_patterns_text = settings.ignore_patterns.join("\n");
         }
+        self.settings = settings;
+    }
+
+    /// What a tab does while another one is shown: file events are still handled, so
+    /// monitoring keeps its document up to date, and watcher restarts and scheduled
+    /// generations still run
+    pub fn update_in_background(&mut self) {
+        self.process_events();
+        self.sync_excluded_output_path();
+        self.restart_watcher_if_due();
+        self.run_scheduled_generation_if_due();
+    }
+
+    /// Stops the tab's background work before it is dropped; the watcher and the document
+    /// worker stop on drop
+    pub fn close(&mut self) {
+        self.cancel_scan();
+        self.cancel_token_count();
+    }
+
+    /// Stops the tab's work when the window closes: scans and counts are cancelled, and the
+    /// writes in flight get until `deadline` to finish. `false` when some were still running.
+    pub fn shutdown(&mut self, deadline: Instant) -> bool {
+        self.scan_cancel_flag.store(true, Ordering::Relaxed);
+        self.cancel_token_count();
+        if let Some(chat) = self.llm_chat.take() {
+            chat.cancel();
+        }
+        if let Err(e) = self.file_monitor.stop_monitoring() {
+            warn!("Error stopping file monitor: {}", e);
+        }
+
+        let mut finished = self.document_worker.shutdown(deadline);
+        for handle in self.worker_threads.drain(..) {
+            while !handle.is_finished() && Instant::now() < deadline {
+                thread::sleep(SHUTDOWN_POLL_INTERVAL);
+            }
+            if handle.is_finished() {
+                let _ = handle.join();
+            } else {
+                finished = false;
+            }
+        }
+        finished
+    }
+
+    /// Runs `work` on a background thread that closing the window waits for, for work that
+    /// writes files
+    fn spawn_worker(&mut self, work: impl FnOnce() + Send + 'static) {
+        self.worker_threads.retain(|handle| !handle.is_finished());
+        self.worker_threads.push(thread::spawn(work));
     }
 
     fn set_status_message(&mut self, message: String) {
         self.status_message = Some((message, Instant::now()));
-        self.error_message = None; // Clear error when showing status
+        self.error = None; // Clear error when showing status
     }
 
     fn set_error_message(&mut self, message: String) {
-        self.error_message = Some(message);
+        self.show_error(ErrorReport { message, causes: Vec::new(), path: None, suggestion: None, retry: None });
+    }
+
+    /// Shows `context` in the banner, with the error's causes, path and suggestion behind it
+    fn set_error(&mut self, context: &str, error: &AppError, retry: Option<RetryAction>) {
+        let mut causes = Vec::new();
+        let mut source: Option<&dyn std::error::Error> = Some(error);
+        while let Some(cause) = source {
+            causes.push(cause.to_string());
+            source = cause.source();
+        }
+        self.show_error(ErrorReport {
+            message: format!("{}: {}", context, error),
+            causes,
+            path: error.path().map(Path::to_path_buf),
+            suggestion: error.suggestion(),
+            retry,
+        });
+    }
+
+    fn show_error(&mut self, report: ErrorReport) {
+        self.error = Some(report);
+        self.error_expanded = false;
         self.status_message = None; // Clear status when showing error
     }
 
     fn clear_messages(&mut self) {
         self.status_message = None;
-        self.error_message = None;
+        self.error = None;
+    }
+
+    fn retry(&mut self, action: RetryAction) {
+        info!("Retrying: {}", action.label());
+        self.clear_messages();
+        match action {
+            RetryAction::Scan(directory) => self.open_directory(directory, self.settings.ignore_patterns.clone()),
+            RetryAction::Rescan => self.rescan_directory(),
+            RetryAction::Generation => self.generate_document(true),
+            RetryAction::RestartWatcher => {
+                self.watcher_restart_at = Some(Instant::now());
+                self.restart_watcher_if_due();
+            }
+        }
+    }
+
+    fn can_generate(&self) -> bool {
+        self.ui_tree_handler.has_selection() && self.output_file_path.is_some() && !self.is_generating_document && !self.is_loading_directory
+    }
+
+    /// Ctrl+G generates, Ctrl+F focuses the tree filter and Ctrl+O opens a directory (Cmd on macOS).
+    /// Arrow keys, Space and Enter in the tree are handled by `UITreeHandler`.
+    fn handle_shortcuts(&mut self, ctx: &Context) {
+        let shortcut = |key| ctx.input_mut(|i| i.consume_shortcut(&egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, key)));
+        if shortcut(egui::Key::G) && self.can_generate() {
+            self.generate_document(true);
+        }
+        if shortcut(egui::Key::F) && !self.ui_tree_handler.tree_nodes.is_empty() {
+            self.focus_tree_filter = true;
+        }
+        if shortcut(egui::Key::O) {
+            self.open_directory_dialog();
+        }
     }
 
     fn open_directory_dialog(&mut self) {
         if let Some(path) = rfd::FileDialog::new().pick_folder() {
-            self.open_directory(path, self.ignore_patterns_text.lines().map(|s| s.to_string()).collect());
+            self.open_directory(path, self.settings.ignore_patterns.clone());
+        }
+    }
+
+    /// Mirrors the server directory in the background, then opens the mirror
+    fn fetch_remote_source(&mut self, source: RemoteSource) {
+        self.is_fetching_remote = true;
+        self.set_status_message(format!("Fetching {}...", source.address()));
+        let sender = self.event_sender.clone();
+        self.spawn_worker(move || {
+            let result = source.fetch();
+            if let Err(e) = sender.send(AppEvent::RemoteSourceFetched { source, result }) {
+                error!("Failed to send remote fetch result: {}", e);
+            }
+        });
+    }
+
+    fn handle_remote_source_fetched(&mut self, source: RemoteSource, result: Result<PathBuf>) {
+        self.is_fetching_remote = false;
+        match result {
+            Ok(mirror_dir) if self.monitoring_active && self.current_directory.as_ref() == Some(&mirror_dir) => {
+                // Only changed files were rewritten, so the monitor patches their sections
+                self.set_status_message(format!("Fetched {}", source.address()));
+            }
+            Ok(mirror_dir) => {
+                self.remote_address_input = None;
+                self.remote_source = Some(source);
+                self.open_directory(mirror_dir, self.settings.ignore_patterns.clone());
+            }
+            Err(e) => {
+                error!("Fetching {} failed: {}", source.address(), e);
+                self.set_error("Failed to fetch the remote directory", &e, None);
+            }
         }
     }
 
-    fn open_directory(&mut self, directory: PathBuf, ignore_patterns: Vec<String>) {
+    fn open_directory(&mut self, directory: PathBuf, mut ignore_patterns: Vec<String>) {
         info!("Opening directory: {:?}", directory);
+        if self.remote_source.as_ref().and_then(RemoteSource::mirror_dir).as_ref() != Some(&directory) {
+            self.remote_source = None;
+        }
         self.is_loading_directory = true;
+        self.is_validating_cached_scan = false;
+        self.scan_progress = 0;
         self.set_status_message("Scanning directory...".to_string());
+
+        // A newer scan supersedes any scan that is still running
+        self.scan_cancel_flag.store(true, Ordering::Relaxed);
+        self.scan_cancel_flag = Arc::new(AtomicBool::new(false));
+        let cancel_flag = self.scan_cancel_flag.clone();
+        self.is_rescanning_structure = false;
+        self.structure_rescan_pending = false;
         
         // Stop any existing monitoring (for structural changes)
         if let Err(e) = self.file_monitor.stop_monitoring() {
             warn!("Error stopping file monitor: {}", e);
         }
+        self.watcher_restart_at = None;
+        self.watcher_restart_attempts = 0;
         
         // Start monitoring for structural changes immediately
-        let dir_for_monitor = directory.clone();
-        if let Err(e) = self.file_monitor.start_monitoring(dir_for_monitor) {
-            error!("Failed to start directory monitoring: {}", e);
-            self.set_error_message(format!("Failed to start directory monitoring: {}", e));
-            // Proceed without monitoring if it fails, but inform the user
-        }
+        self.start_file_monitor(directory.clone());
 
         self.monitoring_active = false; // Document monitoring is off by default
+        self.document_size_bytes = 0;
+        self.monitoring_baseline_bytes = None;
+        self.growth_alert_shown = false;
+        self.growth_alert_dismissed = false;
+        self.selection_regeneration_pending = false;
+        self.file_snapshot = Arc::new(FileSnapshot::new());
+        self.snapshot_taken_at = None;
+
+        // Rescans of the same directory keep the header and tags as edited
+        let file_tags = if self.current_directory.as_ref() != Some(&directory) {
+            let project_settings = ProjectSettings::load(&directory);
+            self.custom_header_text = project_settings.custom_header;
+            self.scratch_text = project_settings.scratch_text;
+            self.excluded_extensions = project_settings.excluded_extensions;
+            self.setup_banner_dismissed = project_settings.setup_banner_dismissed;
+            self.workspace_trusted = project_settings.workspace_trusted;
+            // Parsing is harmless; nothing in it is used before the trust prompt was answered
+            self.workspace_config = WorkspaceConfig::load(&directory).unwrap_or_else(|e| {
+                warn!("Ignoring the project configuration: {}", e);
+                None
+            });
+            self.external_files = project_settings.external_files;
+            self.file_monitor.set_extra_files(self.external_files.clone());
+            self.project_setup = if self.setup_banner_dismissed { None } else { self.detect_project_setup(&directory) };
+            self.usage_stats = UsageStats::load(&directory);
+            self.generation_history = GenerationHistory::load(&directory);
+            self.history_comparison = (None, None);
+            self.history_section_diff = None;
+            let root = directory.canonicalize().unwrap_or_else(|_| directory.clone());
+            project_settings.file_tags.into_iter()
+                .map(|(relative_path, tags)| (root.join(relative_path), tags))
+                .collect()
+        } else {
+            self.ui_tree_handler.get_file_tags()
+        };
+        ignore_patterns.extend(excluded_extension_patterns(&self.excluded_extensions));
         
         // Clear current state
         self.current_directory = Some(directory.clone());
         self.root_file_node = None;
         self.output_file_path = None;
+        self.document_outline.clear();
+        self.context_suggestion = None;
+        self.log_selection = None;
+        self.log_excerpts.clear();
+        self.unscanned_paths.clear();
+        self.ignore_rules_changed = false;
+        self.tree_filter.clear();
+        self.file_preview = None;
+        self.cancel_token_count();
+        self.selection_locked = false;
         self.ui_tree_handler = UITreeHandler::new();
+        self.ui_tree_handler.set_file_tags(file_tags);
         
         // Start directory scan in background thread
         let sender = self.event_sender.clone();
-        thread::spawn(move || {
-            let result = FileHandler::new(directory)
-                .and_then(|handler| handler.scan_directory(ignore_patterns));
+        let exclude_generated = self.settings.exclude_generated_files;
+        let count_lines = self.settings.line_counts_enabled;
+        let lazy_scan = self.settings.lazy_scan_enabled;
+        let output_path = self.excluded_output_path();
+        self.spawn_worker(move || {
+            // Show the cached tree right away, then validate it with a real scan. Shallow trees
+            // are quick to scan and not worth caching.
+            let scan_cache = if lazy_scan { None } else { ScanCache::new(&directory, &ignore_patterns, exclude_generated) };
+            if let Some(cached_root) = scan_cache.as_ref().and_then(|cache| cache.load(&directory)) {
+                if let Err(e) = sender.send(AppEvent::CachedScanLoaded(cached_root)) {
+                    error!("Failed to send cached scan result: {}", e);
+                }
+            }
+
+            let result = FileHandler::new(directory.clone())
+                .and_then(|handler| handler
+                    .with_generated_files_excluded(exclude_generated)
+                    .with_line_counts(count_lines)
+                    .with_max_depth(lazy_scan.then_some(LAZY_SCAN_DEPTH))
+                    .with_output_path(output_path)
+                    .with_progress_sender(sender.clone())
+                    .with_cancel_flag(cancel_flag)
+                    .scan_directory(ignore_patterns));
+
+            if let Err(AppError::ScanCancelled) = result {
+                return; // The UI already moved on when it cancelled this scan
+            }
+
+            if let (Ok(root_node), Some(cache)) = (&result, &scan_cache) {
+                if let Err(e) = cache.save(&directory, root_node) {
+                    warn!("Failed to save scan cache: {}", e);
+                }
+            }
             
             if let Err(e) = sender.send(AppEvent::DirectoryScanComplete(result)) {
                 error!("Failed to send directory scan result: {}", e);
@@ -160,24 +735,133 @@ impl ContextBuilderApp {
         });
     }
 
+    fn start_file_monitor(&mut self, directory: PathBuf) {
+        let poll_interval = Duration::from_secs(self.settings.poll_interval_secs);
+        // Native events that keep failing (e.g. exhausted inotify watches) are replaced by polling
+        let force_polling = self.settings.force_polling_watcher
+            || self.watcher_restart_attempts >= WATCHER_POLLING_FALLBACK_ATTEMPTS;
+        self.file_monitor.set_debounce(
+            Duration::from_millis(self.settings.debounce_ms),
+            Duration::from_millis(self.settings.debounce_check_interval_ms),
+        );
+        match self.file_monitor.start_monitoring(directory, poll_interval, force_polling) {
+            Ok(_) => {
+                self.watcher_started_at = Some(Instant::now());
+                self.watcher_restart_at = None;
+            }
+            Err(e) => {
+                error!("Failed to start directory monitoring: {}", e);
+                self.handle_watcher_error(e);
+            }
+        }
+    }
+
+    /// Schedules a restart of the failed watcher, backing off exponentially while it keeps failing
+    fn handle_watcher_error(&mut self, error: AppError) {
+        if self.watcher_restart_at.is_some() {
+            return; // Errors tend to come in bursts
+        }
+        if self.watcher_started_at.is_some_and(|started_at| started_at.elapsed() >= WATCHER_RESTART_MAX_BACKOFF) {
+            self.watcher_restart_attempts = 0; // The last watcher held up for a while
+        }
+
+        let backoff = WATCHER_RESTART_INITIAL_BACKOFF
+            .saturating_mul(2u32.saturating_pow(self.watcher_restart_attempts))
+            .min(WATCHER_RESTART_MAX_BACKOFF);
+        self.watcher_restart_attempts += 1;
+        self.watcher_restart_at = Some(Instant::now() + backoff);
+        warn!("Restarting file watcher in {:?} (attempt {})", backoff, self.watcher_restart_attempts);
+        self.set_error(&format!("File watcher error (restarting in {} s)", backoff.as_secs()), &error, Some(RetryAction::RestartWatcher));
+    }
+
+    fn restart_watcher_if_due(&mut self) {
+        let Some(restart_at) = self.watcher_restart_at else { return };
+        if Instant::now() < restart_at {
+            return;
+        }
+        self.watcher_restart_at = None;
+        let Some(directory) = self.current_directory.clone() else { return };
+
+        self.start_file_monitor(directory);
+        if self.watcher_restart_at.is_some() {
+            return; // Failed again
+        }
+        if let Some(backend) = self.file_monitor.backend() {
+            self.set_status_message(format!("File watcher restarted ({})", backend.description()));
+        }
+
+        // Catch up on changes made while nothing was watching
+        self.rescan_directory();
+        if self.monitoring_active {
+            self.generate_document(false);
+        }
+    }
+
+    fn cancel_scan(&mut self) {
+        info!("Cancelling directory scan");
+        self.scan_cancel_flag.store(true, Ordering::Relaxed);
+
+        if self.is_validating_cached_scan {
+            // Keep working with the cached tree
+            self.is_validating_cached_scan = false;
+            self.set_status_message("Validation cancelled; showing the cached tree".to_string());
+        } else {
+            if let Err(e) = self.file_monitor.stop_monitoring() {
+                warn!("Error stopping file monitor: {}", e);
+            }
+            self.is_loading_directory = false;
+            self.current_directory = None;
+            self.output_file_path = None;
+            self.set_status_message("Directory scan cancelled".to_string());
+        }
+    }
+
+    fn handle_cached_scan_loaded(&mut self, root_node: FileNode) {
+        if !self.is_loading_directory {
+            return; // The real scan already finished
+        }
+
+        info!("Showing cached directory tree while validating");
+        self.is_loading_directory = false;
+        self.is_validating_cached_scan = true;
+        self.root_file_node = Some(root_node.clone());
+        self.ui_tree_handler.build_from_file_node(&root_node);
+        self.set_status_message("Loaded cached tree, validating in background...".to_string());
+
+        if let Some(dir) = &self.current_directory {
+            self.output_file_path = Some(dir.join(format!("{}.{}", DEFAULT_OUTPUT_FILENAME_BASE, self.settings.output_format.extension())));
+        }
+    }
+
     fn handle_directory_scan_complete(&mut self, result: Result<FileNode>) {
         self.is_loading_directory = false;
+        let replaces_cached_tree = std::mem::take(&mut self.is_validating_cached_scan);
         
         match result {
             Ok(root_node) => {
                 info!("Directory scan completed successfully");
                 self.root_file_node = Some(root_node.clone());
+                if replaces_cached_tree {
+                    // Keep what the user selected in the cached tree, minus files that no longer exist
+                    let previous_selection = self.ui_tree_handler.selected_files.clone();
+                    self.ui_tree_handler.build_from_file_node(&root_node);
+                    self.ui_tree_handler.set_selected_files(previous_selection);
+                    self.set_status_message("Cached tree validated".to_string());
+                    return;
+                }
                 self.ui_tree_handler.build_from_file_node(&root_node);
                 self.set_status_message("Directory loaded successfully".to_string());
                 
                 // Suggest default output path based on directory and default format
                 if let Some(dir) = &self.current_directory {
-                    self.output_file_path = Some(dir.join(format!("{}.{}", DEFAULT_OUTPUT_FILENAME_BASE, DEFAULT_OUTPUT_FORMAT.extension())));
+                    self.output_file_path = Some(dir.join(format!("{}.{}", DEFAULT_OUTPUT_FILENAME_BASE, self.settings.output_format.extension())));
                 }
+                self.refresh_document_outline();
             }
             Err(e) => {
                 error!("Directory scan failed: {}", e);
-                self.set_error_message(format!("Failed to scan directory: {}", e));
+                let retry = self.current_directory.clone().map(RetryAction::Scan);
+                self.set_error("Failed to scan directory", &e, retry);
                 self.current_directory = None;
                 self.output_file_path = None; // Clear path on scan failure
             }
@@ -191,6 +875,12 @@ impl ContextBuilderApp {
 
             // Enable automatic document updates on file modifications
             self.monitoring_active = true;
+            self.monitoring_baseline_bytes = None; // Taken from the document just requested
+            self.growth_alert_shown = false;
+            self.growth_alert_dismissed = false;
+            if !self.unscanned_paths.is_empty() || self.ignore_rules_changed {
+                self.rescan_directory(); // Catch up on what was only counted so far
+            }
             self.set_status_message("Monitoring selected files for changes and updating document".to_string());
         } else {
             self.set_error_message("Cannot start monitoring: Current directory not set.".to_string());
@@ -200,13 +890,16 @@ impl ContextBuilderApp {
     fn stop_monitoring(&mut self) {
         // Only disable automatic document updates
         self.monitoring_active = false;
+        self.monitoring_baseline_bytes = None;
+        self.growth_alert_shown = false;
+        self.growth_alert_dismissed = false;
         self.set_status_message("Document updates stopped".to_string());
         // The underlying file monitor for structural changes remains active
     }
 
     fn generate_document(&mut self, show_completion_message: bool) {
         if let (Some(directory), Some(root_node), Some(output_path)) = (&self.current_directory, &self.root_file_node, &self.output_file_path) {
-            let selected_files = self.ui_tree_handler.get_selected_files();
+            let selected_files = self.document_files();
 
             if selected_files.is_empty() {
                 if show_completion_message {
@@ -215,28 +908,66 @@ impl ContextBuilderApp {
                 return;
             }
 
+            // The document would embed itself and grow with every regeneration
+            if let Some(selected_output) = self.selected_output_document() {
+                let message = format!(
+                    "{} is selected and is where the document gets written. Deselect it or choose another output path.",
+                    selected_output.display()
+                );
+                if show_completion_message {
+                    self.set_error_message(message);
+                } else {
+                    warn!("Not regenerating: {}", message);
+                }
+                return;
+            }
+
+            // Never replace a hand-written file that happens to have the output's name. Split
+            // output only writes `_partN` files.
+            let mut target_paths = Vec::new();
+            if !self.settings.split_output_enabled {
+                target_paths.push(output_path.clone());
+                if self.settings.all_formats_enabled {
+                    target_paths.extend(companion_targets(output_path, self.settings.output_format).into_iter().map(|(path, _)| path));
+                }
+            }
+            let foreign_path = target_paths.into_iter()
+                .find(|path| !self.confirmed_output_paths.contains(path) && !is_replaceable_document(path));
+            if let Some(foreign_path) = foreign_path {
+                if show_completion_message {
+                    self.overwrite_confirmation = Some(foreign_path);
+                } else {
+                    warn!("Not regenerating: {:?} was not written by this app", foreign_path);
+                }
+                return;
+            }
+
             // Clone values before setting status message to avoid borrow issues
-            let directory = directory.clone();
-            let root_node = root_node.clone();
-            let output_path = output_path.clone();
-            let output_format = self.selected_output_format;
+            let generator = self.configured_generator(directory.clone(), selected_files);
+            let job = DocumentJob::Generate {
+                generator,
+                root_node: root_node.clone(),
+                output_path: output_path.clone(),
+                format: self.settings.output_format,
+                chunk_token_limit: self.settings.split_output_enabled.then_some(self.settings.max_tokens_per_chunk),
+                template_path: self.template_path(),
+                companion_targets: if self.settings.all_formats_enabled {
+                    companion_targets(output_path, self.settings.output_format)
+                } else {
+                    Vec::new()
+                },
+            };
 
             self.is_generating_document = true;
             if show_completion_message {
+                // Only explicit generations count for the insights, not every auto-regeneration
+                let root = directory.canonicalize().unwrap_or_else(|_| directory.clone());
+                self.pending_usage_record = Some(self.ui_tree_handler.get_selected_files().iter()
+                    .filter_map(|path| Some(path.strip_prefix(&root).ok()?.to_path_buf()))
+                    .collect());
                 self.set_status_message("Generating document...".to_string());
             }
-
-            let sender = self.event_sender.clone();
-
-            thread::spawn(move || {
-                let generator = DocumentGenerator::new(directory.clone(), selected_files);
-                
-                let result = generator.generate_full_document(&root_node, &output_path, output_format);
-
-                if let Err(e) = sender.send(AppEvent::DocumentGenerationComplete(result)) {
-                    error!("Failed to send document generation result: {}", e);
-                }
-            });
+            self.document_worker.submit(job);
         } else if self.current_directory.is_none() {
              if show_completion_message {
                  self.set_error_message("Please select a directory first".to_string());
@@ -252,91 +983,985 @@ impl ContextBuilderApp {
         }
     }
 
-    fn handle_document_generation_complete(&mut self, result: Result<()>) {
-        self.is_generating_document = false;
+    /// The document to leave out of scans and watcher events: the output path, or the default
+    /// one while none is set
+    fn excluded_output_path(&self) -> Option<PathBuf> {
+        self.output_file_path.clone().or_else(|| {
+            let directory = self.current_directory.as_ref()?;
+            Some(directory.join(format!("{}.{}", DEFAULT_OUTPUT_FILENAME_BASE, self.settings.output_format.extension())))
+        })
+    }
 
-        match result {
-            Ok(()) => {
-                if let Some(output_path) = &self.output_file_path {
-                    self.set_status_message(format!("Document generated: {}", output_path.display()));
+    /// Points the watcher at a changed output path, rescanning if the tree shows the new document
+    fn sync_excluded_output_path(&mut self) {
+        let output_path = self.excluded_output_path();
+        if output_path == self.watched_output_path {
+            return;
+        }
+        self.file_monitor.set_output_path(output_path.clone());
+        let shown_in_tree = match (&output_path, &self.root_file_node) {
+            (Some(output_path), Some(root_node)) => root_node.file_paths().iter()
+                .any(|path| is_output_document_path(path, output_path)),
+            _ => false,
+        };
+        self.watched_output_path = output_path;
+        if shown_in_tree {
+            self.rescan_directory();
+        }
+    }
+
+    /// The files the document embeds: the tree selection plus the attached outside files that
+    /// still exist
+    fn document_files(&self) -> Vec<PathBuf> {
+        let mut files = self.ui_tree_handler.get_selected_files();
+        files.extend(self.external_files.iter().filter(|path| path.is_file()).cloned());
+        files
+    }
+
+    fn attach_external_file(&mut self) {
+        let Some(directory) = self.current_directory.clone() else { return };
+        if !self.ensure_selection_unlocked() {
+            return;
+        }
+        let Some(path) = rfd::FileDialog::new().set_title("Attach a file from outside the project").pick_file() else {
+            return;
+        };
+        let path = path.canonicalize().unwrap_or(path);
+        let root = directory.canonicalize().unwrap_or(directory);
+        if path.starts_with(&root) {
+            self.set_error_message(format!("{} is inside the project; select it in the tree instead", path.display()));
+            return;
+        }
+        if self.external_files.insert(path.clone()) {
+            self.set_status_message(format!("Attached {}", path.display()));
+            self.external_files_changed();
+        }
+    }
+
+    fn detach_external_file(&mut self, path: &Path) {
+        if !self.ensure_selection_unlocked() {
+            return;
+        }
+        if self.external_files.remove(path) {
+            self.external_files_changed();
+        }
+    }
+
+    fn external_files_changed(&mut self) {
+        self.file_monitor.set_extra_files(self.external_files.clone());
+        self.save_project_settings();
+        self.handle_selection_changed();
+    }
+
+    /// Attached outside files, each with a button to detach it
+    fn render_external_files(&mut self, ui: &mut egui::Ui) {
+        if self.external_files.is_empty() {
+            return;
+        }
+        let mut detached = None;
+        ui.horizontal_wrapped(|ui| {
+            ui.weak("Attached:");
+            for path in &self.external_files {
+                let exists = path.is_file();
+                let label = if exists {
+                    RichText::new(path.display().to_string()).monospace()
+                } else {
+                    RichText::new(path.display().to_string()).monospace().strikethrough()
+                };
+                ui.label(label).on_hover_text(if exists {
+                    "From outside the project; always included and watched while monitoring"
                 } else {
-                    self.set_status_message("Document generated successfully (path unknown)".to_string());
+                    "Missing; left out of the document"
+                });
+                if ui.small_button("✖").on_hover_text("Detach").clicked() {
+                    detached = Some(path.clone());
                 }
             }
-            Err(e) => {
-                error!("Document generation failed: {}", e);
-                self.set_error_message(format!("Failed to generate document: {}", e));
-            }
+        });
+        if let Some(path) = detached {
+            self.detach_external_file(&path);
         }
     }
 
-    fn handle_file_modified(&mut self, file_path: PathBuf) {
-        debug!("Handling file modification: {:?}", file_path);
+    /// A selected file that generating would overwrite: the document itself, its other format
+    /// or one of its parts
+    fn selected_output_document(&self) -> Option<PathBuf> {
+        let output_path = self.output_file_path.as_ref()?;
+        self.ui_tree_handler.selected_files.iter()
+            .find(|path| is_output_document_path(path, output_path))
+            .cloned()
+    }
 
-        if let (Some(directory), Some(output_path)) = (&self.current_directory, &self.output_file_path) {
-            let selected_files = self.ui_tree_handler.get_selected_files();
+    /// A generator with every output setting applied, for whole documents and partial updates
+    /// alike, so their sections render the same
+    fn configured_generator(&self, directory: PathBuf, selected_files: Vec<PathBuf>) -> DocumentGenerator {
+        let token_budget = self.settings.token_budget_enabled.then(|| TokenBudget {
+            max_tokens: self.settings.max_token_budget,
+            priorities: self.ui_tree_handler.get_file_priorities(),
+        });
+        DocumentGenerator::new(directory, selected_files)
+            .with_external_files(self.external_files.iter().cloned().collect())
+            .with_token_budget(token_budget)
+            .with_file_size_limits(self.settings.file_size_limits)
+            .with_minified_extensions(self.settings.minified_extension_list())
+            .with_binary_files_excluded(self.settings.exclude_binary_files)
+            .with_notebook_markdown_cells(self.settings.notebook_markdown_cells)
+            .with_outline_files(self.ui_tree_handler.get_outline_files())
+            .with_file_summaries(self.ui_tree_handler.get_summary_files(), FileSummarizer::new(self.settings.summary_endpoint_config()))
+            .with_line_ranges(self.ui_tree_handler.get_line_ranges())
+            .with_log_excerpts(self.log_excerpts.clone())
+            .with_custom_header(&self.custom_header_text)
+            .with_scratch_text(&self.scratch_text)
+            .with_anchor_links(self.settings.anchor_links_enabled)
+            .with_section_footers(self.settings.section_footers_enabled)
+            .with_metadata_header(self.settings.metadata_header_enabled)
+            .with_language_summary(self.settings.language_summary_enabled)
+            .with_fence_languages(self.settings.fence_language_list())
+            .with_structure_section(self.settings.structure_section_enabled)
+            .with_full_tree_structure(self.settings.full_tree_structure_enabled)
+            .with_structure_limits(self.settings.structure_max_depth, self.settings.structure_max_entries)
+            .with_structure_annotations(self.settings.structure_annotations_enabled)
+            .with_files_section(self.settings.files_section_enabled)
+            .with_snapshot(self.settings.snapshot_mode_enabled.then(|| self.file_snapshot.clone()))
+            .with_section_cache(Some(self.section_cache.clone()))
+            .with_dependency_sources(self.settings.dependency_sources_enabled)
+            .with_document_index(self.current_document_index_path())
+            .with_adoc_includes(self.current_adoc_include_dir())
+            .with_markdown_links(self.current_markdown_link_dir())
+            .with_line_anchors(self.settings.markdown_line_anchors_enabled)
+    }
 
-            if selected_files.contains(&file_path) {
-                let directory = directory.clone();
-                let sender = self.event_sender.clone();
-                let markdown_path = output_path.clone();
+    /// Scheduled generation: writes a timestamped copy of the document next to the output
+    /// path once a scheduled time has passed, e.g. so a fresh document waits each morning.
+    /// Missed times (the machine was asleep) are caught up once, on the next frame.
+    fn run_scheduled_generation_if_due(&mut self) {
+        if !self.settings.scheduled_generation_enabled {
+            self.next_scheduled_generation = None;
+            return;
+        }
+        let Ok(times) = parse_schedule_times(&self.settings.scheduled_generation_times) else {
+            self.next_scheduled_generation = None;
+            return;
+        };
 
-                let output_format = self.selected_output_format;
+        let now = Local::now();
+        match self.next_scheduled_generation {
+            Some(due) if now >= due => {
+                self.next_scheduled_generation = next_scheduled_time(&times, now);
+                self.generate_scheduled_document(due);
+            }
+            Some(_) => {}
+            None => self.next_scheduled_generation = next_scheduled_time(&times, now),
+        }
+    }
 
-                thread::spawn(move || {
-                    let generator = DocumentGenerator::new(directory.clone(), selected_files);
+    fn generate_scheduled_document(&mut self, scheduled_at: DateTime<Local>) {
+        let (Some(directory), Some(root_node), Some(output_path)) = (&self.current_directory, &self.root_file_node, &self.output_file_path) else {
+            info!("Skipping scheduled generation: no directory, tree or output path");
+            return;
+        };
+        let selected_files = self.document_files();
+        if selected_files.is_empty() {
+            info!("Skipping scheduled generation: no files selected");
+            return;
+        }
 
-                    let result = generator.update_file_section_in_document(&markdown_path, &file_path, output_format);
+        // A fresh file no other write touches, so it doesn't need the document worker
+        let generator = self.configured_generator(directory.clone(), selected_files);
+        let root_node = root_node.clone();
+        let output_path = timestamped_output_path(output_path, scheduled_at);
+        let output_format = self.settings.output_format;
+        let chunk_token_limit = self.settings.split_output_enabled.then_some(self.settings.max_tokens_per_chunk);
+        let template_path = self.template_path();
+        let sender = self.event_sender.clone();
+        info!("Running scheduled generation into {:?}", output_path);
 
-                    if let Err(e) = sender.send(AppEvent::PartialDocumentUpdateComplete(result)) {
-                        error!("Failed to send partial document update result: {}", e);
-                    }
-                });
-            } else {
-                debug!("Modified file {:?} not in selected files. Skipping partial update.", file_path);
+        self.spawn_worker(move || {
+            let result = generate_document(&generator, &root_node, &output_path, output_format, chunk_token_limit, template_path.as_deref())
+                .map(|()| output_path);
+            if let Err(e) = sender.send(AppEvent::ScheduledGenerationComplete(result)) {
+                error!("Failed to send scheduled generation result: {}", e);
             }
-        } else {
-             debug!("Modified file {:?} received, but directory or output path not set. Skipping partial update.", file_path);
+        });
+    }
+
+    /// The file(s) the current settings write to: the output path, or its numbered parts
+    fn current_document_index_path(&self) -> Option<PathBuf> {
+        if !self.settings.document_index_enabled {
+            return None;
         }
+        self.output_file_path.as_deref().map(document_index_path)
     }
 
-    fn handle_partial_document_update_complete(&mut self, result: Result<()>) {
-        match result {
-            Ok(()) => {
-                debug!("Partial document update completed successfully");
-            }
-            Err(e) => {
-                warn!("Partial document update failed: {}", e);
-            }
+    /// Directory the `include::` paths of AsciiDoc documents are relative to
+    fn current_adoc_include_dir(&self) -> Option<PathBuf> {
+        self.settings.adoc_includes_enabled.then(|| self.current_document_dir()).flatten()
+    }
+
+    /// Directory the file links of Markdown documents are relative to
+    fn current_markdown_link_dir(&self) -> Option<PathBuf> {
+        self.settings.markdown_links_enabled.then(|| self.current_document_dir()).flatten()
+    }
+
+    fn current_document_dir(&self) -> Option<PathBuf> {
+        let document_dir = self.output_file_path.as_deref()?.parent()?;
+        Some(document_dir.canonicalize().unwrap_or_else(|_| document_dir.to_path_buf()))
+    }
+
+    fn current_document_paths(&self) -> Vec<PathBuf> {
+        match &self.output_file_path {
+            Some(output_path) if self.settings.split_output_enabled => existing_chunk_paths(output_path),
+            Some(output_path) => vec![output_path.clone()],
+            None => Vec::new(),
         }
     }
 
-    fn process_events(&mut self) {
-        while let Ok(event) = self.event_receiver.try_recv() {
-            match event {
-                AppEvent::DirectoryScanComplete(result) => {
-                    self.handle_directory_scan_complete(result);
-                }
-                AppEvent::FileModifiedDebounced(file_path) => {
-                    self.handle_file_modified(file_path);
-                }
-                AppEvent::DocumentGenerationComplete(result) => {
+    /// Refreshes the gzip copies of the document(s) after a write, when enabled
+    fn write_compressed_copies(&mut self, document_paths: Vec<PathBuf>) {
+        if !self.settings.compressed_copy_enabled || document_paths.is_empty() {
+            return;
+        }
+        self.spawn_worker(move || {
+            if let Err(e) = write_compressed_copies(&document_paths) {
+                warn!("Failed to write compressed copy of the document: {}", e);
+            }
+        });
+    }
+
+    /// Re-reads the output document's outline, for the outline panel and the tree badges
+    fn refresh_document_outline(&self) {
+        let document_paths: Vec<PathBuf> = self.current_document_paths()
+            .into_iter()
+            .filter(|path| path.exists() || compressed_path(path).is_file())
+            .collect();
+        let Some(directory) = self.current_directory.clone() else { return };
+        if document_paths.is_empty() {
+            return;
+        }
+
+        let sender = self.event_sender.clone();
+        let output_format = self.settings.output_format;
+        thread::spawn(move || {
+            let generator = DocumentGenerator::new(directory, Vec::new());
+            let result = generator.document_outline(&document_paths, output_format);
+            if let Err(e) = sender.send(AppEvent::DocumentOutlineParsed(result)) {
+                error!("Failed to send document outline result: {}", e);
+            }
+        });
+    }
+
+    fn handle_document_outline_parsed(&mut self, result: Result<Vec<OutlineEntry>>) {
+        match result {
+            Ok(outline) => {
+                let documented_files = outline.iter()
+                    .filter_map(|entry| entry.file_path.clone())
+                    .collect();
+                debug!("Output document outline has {} entries", outline.len());
+                self.ui_tree_handler.set_documented_files(documented_files);
+                self.document_outline = outline;
+            }
+            Err(e) => {
+                debug!("Could not read the output document outline: {}", e);
+            }
+        }
+    }
+
+    /// Re-reads the size of the written document and, while monitoring, compares it with the
+    /// size when monitoring started
+    fn update_document_size(&mut self) {
+        self.document_size_bytes = self.current_document_paths().iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        if !self.monitoring_active {
+            return;
+        }
+        let baseline_bytes = *self.monitoring_baseline_bytes.get_or_insert(self.document_size_bytes);
+        let limit_bytes = baseline_bytes * (100 + self.settings.growth_alert_percent as u64) / 100;
+        if self.settings.growth_alert_enabled && !self.growth_alert_shown && !self.growth_alert_dismissed && self.document_size_bytes > limit_bytes {
+            warn!("Document grew from {} to {} bytes since monitoring started", baseline_bytes, self.document_size_bytes);
+            self.growth_alert_shown = true;
+        }
+    }
+
+    /// Whether watch mode should stop regenerating the whole document on selection changes
+    fn is_auto_regeneration_throttled(&self) -> bool {
+        self.document_size_bytes > self.settings.auto_regeneration_limit_bytes as u64
+    }
+
+    fn handle_selection_changed(&mut self) {
+        self.update_snapshot(false);
+
+        if !self.monitoring_active {
+            return;
+        }
+        if self.is_auto_regeneration_throttled() {
+            // Rewriting a huge document on every click causes multi-second write storms;
+            // file edits keep updating their sections and the user regenerates explicitly
+            self.selection_regeneration_pending = true;
+        } else {
+            self.generate_document(false);
+        }
+    }
+
+    /// Snapshot mode: reads the selected files that are not in the snapshot yet, or all of
+    /// them when `refresh` is set, in the background
+    fn update_snapshot(&mut self, refresh: bool) {
+        if !self.settings.snapshot_mode_enabled {
+            return;
+        }
+
+        let paths: Vec<PathBuf> = self.document_files()
+            .into_iter()
+            .filter(|path| refresh || !self.file_snapshot.contains_key(path))
+            .collect();
+        if paths.is_empty() && !refresh {
+            return;
+        }
+
+        let sender = self.event_sender.clone();
+        thread::spawn(move || {
+            let mut files = FileSnapshot::with_capacity(paths.len());
+            for path in paths {
+                match read_file_consistently(&path) {
+                    Ok(bytes) => {
+                        files.insert(path, bytes);
+                    }
+                    // Generation falls back to reading the file and reports the error then
+                    Err(e) => warn!("Failed to snapshot {:?}: {}", path, e),
+                }
+            }
+            if let Err(e) = sender.send(AppEvent::SnapshotTaken { files, replace: refresh }) {
+                error!("Failed to send snapshot: {}", e);
+            }
+        });
+    }
+
+    fn handle_snapshot_taken(&mut self, files: FileSnapshot, replace: bool) {
+        if !self.settings.snapshot_mode_enabled {
+            return;
+        }
+
+        let selected_files = &self.ui_tree_handler.selected_files;
+        let snapshot = Arc::make_mut(&mut self.file_snapshot);
+        if replace {
+            *snapshot = files;
+            self.snapshot_taken_at = Some(Instant::now());
+        } else {
+            snapshot.extend(files);
+            self.snapshot_taken_at.get_or_insert_with(Instant::now);
+        }
+        // Deselected files don't need to be kept in memory
+        snapshot.retain(|path, _| selected_files.contains(path));
+        debug!("Snapshot holds {} files", snapshot.len());
+        if replace && self.monitoring_active {
+            // Edits are only picked up by refreshing while monitoring
+            self.generate_document(false);
+        }
+    }
+
+    fn handle_document_generation_complete(&mut self, result: Result<()>) {
+        self.is_generating_document = false;
+
+        match result {
+            Ok(()) => {
+                self.update_document_size();
+                self.selection_regeneration_pending = false;
+                self.record_usage();
+
+                let mut message = if let (true, Some(output_path)) = (self.settings.split_output_enabled, &self.output_file_path) {
+                    let part_count = existing_chunk_paths(output_path).len();
+                    format!("Document generated in {} parts next to {}", part_count, output_path.display())
+                } else if let Some(output_path) = &self.output_file_path {
+                    format!("Document generated: {}", output_path.display())
+                } else {
+                    "Document generated successfully (path unknown)".to_string()
+                };
+                match self.ui_tree_handler.read_error_count() {
+                    0 => {}
+                    1 => message.push_str(" (1 file could not be read, marked ⛔ in the tree)"),
+                    count => message.push_str(&format!(" ({} files could not be read, marked ⛔ in the tree)", count)),
+                }
+                self.set_status_message(message);
+                self.write_compressed_copies(self.current_document_paths());
+                self.record_history();
+                self.refresh_document_outline();
+                self.run_after_generate_hook();
+            }
+            Err(e) => {
+                self.pending_usage_record = None;
+                error!("Document generation failed: {}", e);
+                self.set_error("Failed to generate document", &e, Some(RetryAction::Generation));
+            }
+        }
+    }
+
+    /// Runs the trusted project's `after_generate` command in the background
+    fn run_after_generate_hook(&mut self) {
+        let Some(command) = self.trusted_workspace_config().and_then(WorkspaceConfig::after_generate).map(str::to_string) else {
+            return;
+        };
+        let (Some(directory), Some(output_path)) = (self.current_directory.clone(), self.output_file_path.clone()) else {
+            return;
+        };
+        let sender = self.event_sender.clone();
+        self.spawn_worker(move || {
+            if let Err(e) = run_after_generate(&command, &directory, &output_path) {
+                error!("{}", e);
+                if let Err(e) = sender.send(AppEvent::ErrorMessage(e.to_string())) {
+                    error!("Failed to send after_generate result: {}", e);
+                }
+            }
+        });
+    }
+
+    fn trusted_workspace_config(&self) -> Option<&WorkspaceConfig> {
+        self.workspace_config.as_ref().filter(|_| self.workspace_trusted == Some(true))
+    }
+
+    /// The trusted project's template, else the one chosen in the settings
+    fn template_path(&self) -> Option<PathBuf> {
+        self.trusted_workspace_config()
+            .zip(self.current_directory.as_ref())
+            .and_then(|(config, directory)| config.template_path(directory))
+            .or_else(|| self.settings.custom_template_path.clone())
+    }
+
+    /// Adds the document just generated to the generation history, read in the background
+    fn record_history(&mut self) {
+        let (Some(directory), Some(output_path)) = (self.current_directory.clone(), self.output_file_path.clone()) else {
+            return;
+        };
+        if self.settings.history_length == 0 {
+            return;
+        }
+        let document_paths = self.current_document_paths();
+        let format = self.settings.output_format;
+        let file_count = self.document_files().len();
+        let keep_copy = self.settings.history_copies_enabled;
+        let sender = self.event_sender.clone();
+        self.spawn_worker(move || {
+            let result = record_generation(&directory, &output_path, &document_paths, format, file_count, keep_copy);
+            if let Err(e) = sender.send(AppEvent::GenerationRecorded(result)) {
+                error!("Failed to send generation history entry: {}", e);
+            }
+        });
+    }
+
+    fn handle_generation_recorded(&mut self, result: Result<HistoryEntry>) {
+        let Some(directory) = self.current_directory.clone() else { return };
+        match result {
+            Ok(entry) => {
+                self.generation_history.push(&directory, entry, self.settings.history_length);
+                if let Err(e) = self.generation_history.save(&directory) {
+                    warn!("Failed to save generation history: {}", e);
+                }
+            }
+            Err(e) => warn!("Failed to record generation history: {}", e),
+        }
+    }
+
+    fn record_usage(&mut self) {
+        let (Some(relative_files), Some(directory)) = (self.pending_usage_record.take(), &self.current_directory) else {
+            return;
+        };
+        self.usage_stats.record_generation(&relative_files, self.document_size_bytes);
+        if let Err(e) = self.usage_stats.save(directory) {
+            warn!("Failed to save usage stats: {}", e);
+        }
+    }
+
+    /// Updates the sections of modified selected files, all in one background pass
+    fn handle_files_modified(&mut self, file_paths: Vec<PathBuf>) {
+        debug!("Handling modification of {} files", file_paths.len());
+        if self.token_count.as_ref().is_some_and(|count| file_paths.iter().any(|path| count.selection.contains(path))) {
+            self.cancel_token_count(); // Recounted on the next frame
+        }
+
+        if self.selected_output_document().is_some() {
+            warn!("Not updating the document: it is one of the selected files");
+            return;
+        }
+        if let (Some(directory), Some(_)) = (&self.current_directory, &self.output_file_path) {
+            let selected_files = self.document_files();
+            let modified_selected_files: Vec<PathBuf> = file_paths.iter()
+                .filter(|path| selected_files.contains(path))
+                .cloned()
+                .collect();
+
+            // Budget decisions depend on every file, so one file's section can't be re-planned alone
+            let full_regeneration_needed = self.settings.all_formats_enabled
+                || self.settings.token_budget_enabled
+                || (self.template_path().is_some() && !self.settings.split_output_enabled);
+            if !modified_selected_files.is_empty() && !self.settings.files_section_enabled {
+                debug!("Files section disabled, no sections to update for {:?}", modified_selected_files);
+            } else if !modified_selected_files.is_empty() && self.settings.snapshot_mode_enabled {
+                // Reading the edited files now could capture a build's half-written output, which
+                // is what the snapshot avoids; the document changes with the next refresh
+                debug!("Snapshot mode, not updating sections for {:?}", modified_selected_files);
+                self.set_status_message(format!(
+                    "{} selected file(s) changed; refresh the snapshot to include the changes",
+                    modified_selected_files.len()
+                ));
+            } else if !modified_selected_files.is_empty() && full_regeneration_needed {
+                // Sections of a custom layout can't be located reliably, partial updates only patch
+                // one format, and a budget must be re-planned, so regenerate everything
+                debug!("Regenerating full document for {:?}", modified_selected_files);
+                self.generate_document(false);
+            } else if !modified_selected_files.is_empty() {
+                let generator = self.configured_generator(directory.clone(), selected_files);
+                self.document_worker.submit(DocumentJob::UpdateSections {
+                    generator,
+                    document_paths: self.current_document_paths(),
+                    file_paths: modified_selected_files,
+                    format: self.settings.output_format,
+                });
+            } else {
+                debug!("Modified files {:?} not in selected files. Skipping partial update.", file_paths);
+            }
+        } else {
+             debug!("Modified files {:?} received, but directory or output path not set. Skipping partial update.", file_paths);
+        }
+    }
+
+    /// Rescans in the background after structural changes. Unlike `open_directory`, the result is
+    /// merged into the current tree, keeping the selection, expansion state and monitoring.
+    /// Global ignore patterns plus the extensions excluded in this project
+    fn scan_ignore_patterns(&self) -> Vec<String> {
+        let mut ignore_patterns = self.settings.ignore_patterns.clone();
+        ignore_patterns.extend(excluded_extension_patterns(&self.excluded_extensions));
+        ignore_patterns
+    }
+
+    fn rescan_directory(&mut self) {
+        let Some(directory) = self.current_directory.clone() else { return };
+        if self.is_loading_directory {
+            return; // The running scan will pick up the change
+        }
+        if self.is_rescanning_structure {
+            self.structure_rescan_pending = true;
+            return;
+        }
+        self.is_rescanning_structure = true;
+
+        let sender = self.event_sender.clone();
+        // Re-scan with current ignore patterns
+        let ignore_patterns = self.scan_ignore_patterns();
+        let exclude_generated = self.settings.exclude_generated_files;
+        let count_lines = self.settings.line_counts_enabled;
+        let cancel_flag = self.scan_cancel_flag.clone(); // Set when a full scan takes over
+        let output_path = self.excluded_output_path();
+        // Shallow trees: directories the user expanded are rescanned too
+        let loaded_directories = match &self.root_file_node {
+            Some(root_node) if self.settings.lazy_scan_enabled => Some(root_node.loaded_directories()),
+            _ => None,
+        };
+        self.spawn_worker(move || {
+            let result = FileHandler::new(directory.clone())
+                .and_then(|handler| {
+                    let handler = handler
+                        .with_generated_files_excluded(exclude_generated)
+                        .with_line_counts(count_lines)
+                        .with_output_path(output_path)
+                        .with_cancel_flag(cancel_flag);
+                    match &loaded_directories {
+                        Some(loaded_directories) => handler
+                            .with_max_depth(Some(LAZY_SCAN_DEPTH))
+                            .scan_directory_keeping_loaded(ignore_patterns.clone(), loaded_directories),
+                        None => handler.scan_directory(ignore_patterns.clone()),
+                    }
+                });
+
+            let scan_cache = ScanCache::new(&directory, &ignore_patterns, exclude_generated);
+            if let (Ok(root_node), Some(cache), None) = (&result, scan_cache, &loaded_directories) {
+                if let Err(e) = cache.save(&directory, root_node) {
+                    warn!("Failed to save scan cache: {}", e);
+                }
+            }
+
+            if let Err(e) = sender.send(AppEvent::StructureRescanComplete(result)) {
+                error!("Failed to send rescan result: {}", e);
+            }
+        });
+    }
+
+    /// Shallow trees: scans the directories expanded (one level) or checked (the whole subtree)
+    fn load_requested_children(&mut self) {
+        let Some(directory) = self.current_directory.clone() else { return };
+        for request in self.ui_tree_handler.take_children_requests() {
+            let sender = self.event_sender.clone();
+            let directory = directory.clone();
+            let ignore_patterns = self.scan_ignore_patterns();
+            let exclude_generated = self.settings.exclude_generated_files;
+            let count_lines = self.settings.line_counts_enabled;
+            let cancel_flag = self.scan_cancel_flag.clone(); // Set when another project is opened
+            let output_path = self.excluded_output_path();
+            thread::spawn(move || {
+                let result = FileHandler::new(directory)
+                    .and_then(|handler| handler
+                        .with_generated_files_excluded(exclude_generated)
+                        .with_line_counts(count_lines)
+                        .with_max_depth((!request.select_files).then_some(LAZY_SCAN_DEPTH))
+                        .with_output_path(output_path)
+                        .with_cancel_flag(cancel_flag)
+                        .scan_subdirectory(&request.directory, ignore_patterns));
+
+                let event = AppEvent::DirectoryChildrenLoaded { directory: request.directory, result, select_files: request.select_files };
+                if let Err(e) = sender.send(event) {
+                    error!("Failed to send loaded directory children: {}", e);
+                }
+            });
+        }
+    }
+
+    fn handle_directory_children_loaded(&mut self, directory: PathBuf, result: Result<FileNode>, select_files: bool) {
+        let directory_node = match result {
+            Ok(directory_node) => directory_node,
+            Err(AppError::ScanCancelled) => return,
+            Err(e) => {
+                error!("Failed to load {:?}: {}", directory, e);
+                self.set_error(&format!("Failed to load {}", directory.display()), &e, None);
+                return;
+            }
+        };
+        let Some(root_node) = &mut self.root_file_node else { return };
+
+        let loaded_files = directory_node.file_paths();
+        if root_node.replace(directory_node).is_err() {
+            debug!("Loaded directory {:?} is no longer in the tree", directory);
+            return;
+        }
+        root_node.update_directory_sizes();
+        debug!("Loaded {} files below {:?}", loaded_files.len(), directory);
+
+        let mut selection = self.ui_tree_handler.selected_files.clone();
+        if select_files {
+            selection.extend(loaded_files);
+        }
+        let root_node = root_node.clone();
+        self.ui_tree_handler.build_from_file_node(&root_node);
+        self.ui_tree_handler.set_selected_files(selection);
+        if select_files {
+            self.handle_selection_changed();
+        }
+    }
+
+    fn handle_structure_rescan_complete(&mut self, result: Result<FileNode>) {
+        if !std::mem::take(&mut self.is_rescanning_structure) {
+            return; // A full scan took over in the meantime
+        }
+
+        match result {
+            Ok(mut root_node) => {
+                self.unscanned_paths.clear();
+                self.ignore_rules_changed = false;
+                // Keep directories of a shallow tree that were loaded while the rescan ran
+                if let Some(current_root) = &self.root_file_node {
+                    root_node.fill_pending_from(current_root);
+                    root_node.update_directory_sizes();
+                }
+                self.merge_rescanned_tree(root_node);
+            }
+            Err(AppError::ScanCancelled) => {}
+            Err(e) => {
+                error!("Rescan after structural change failed: {}", e);
+                self.set_error("Failed to rescan directory", &e, Some(RetryAction::Rescan));
+            }
+        }
+
+        if std::mem::take(&mut self.structure_rescan_pending) {
+            self.rescan_directory();
+        }
+    }
+
+    /// Rescans right away while monitoring. Otherwise new paths the scan would show are only
+    /// counted, and the user decides when the rescan is worth it.
+    fn handle_directory_content_changed(&mut self, created_paths: Vec<PathBuf>) {
+        if self.monitoring_active {
+            info!("Directory content changed, re-scanning...");
+            self.rescan_directory();
+            return;
+        }
+        let Some(directory) = self.current_directory.clone() else { return };
+        let ignore_patterns = self.scan_ignore_patterns();
+        for path in created_paths {
+            if is_ignore_rule_file(&path) {
+                self.ignore_rules_changed = true;
+                continue;
+            }
+            if !path.exists() || self.ui_tree_handler.contains_path(&path) || matches_ignore_patterns(&directory, &path, &ignore_patterns) {
+                continue;
+            }
+            let is_dir = path.is_dir();
+            self.unscanned_paths.insert(path, is_dir);
+        }
+        debug!("{} paths added since the last scan", self.unscanned_paths.len());
+    }
+
+    /// `8 files added since the last scan`, or `ignore rules changed since the last scan`
+    fn unscanned_paths_summary(&self) -> String {
+        let count = self.unscanned_paths.len();
+        if count == 0 {
+            return "Ignore rules changed since the last scan".to_string();
+        }
+        let noun = match (self.unscanned_paths.values().any(|is_dir| *is_dir), count) {
+            (true, _) => "files and folders",
+            (false, 1) => "file",
+            (false, _) => "files",
+        };
+        match self.ignore_rules_changed {
+            true => format!("{} {} added and ignore rules changed since the last scan", count, noun),
+            false => format!("{} {} added since the last scan", count, noun),
+        }
+    }
+
+    /// Replaces the tree with a rescanned one, keeping the selection of files that still exist.
+    /// Unless it shows the whole tree, the structure block only lists selected files, so the
+    /// document is only patched when selected files disappeared.
+    fn merge_rescanned_tree(&mut self, root_node: FileNode) {
+        let old_paths = self.root_file_node.as_ref().map(FileNode::all_paths).unwrap_or_default();
+        let new_paths = root_node.all_paths();
+        let added_count = new_paths.difference(&old_paths).count();
+        let removed_paths: Vec<PathBuf> = old_paths.difference(&new_paths).cloned().collect();
+        if added_count == 0 && removed_paths.is_empty() {
+            debug!("Rescan found no structural changes");
+            return;
+        }
+
+        let selection = self.ui_tree_handler.selected_files.clone();
+        let removed_selected_files: Vec<(PathBuf, Option<PathBuf>)> = removed_paths.iter()
+            .filter(|path| selection.contains(*path))
+            .map(|path| (path.clone(), None))
+            .collect();
+
+        self.root_file_node = Some(root_node.clone());
+        self.ui_tree_handler.build_from_file_node(&root_node);
+        self.ui_tree_handler.set_selected_files(selection);
+        self.set_status_message(format!(
+            "Project tree updated: {} added, {} removed",
+            added_count,
+            removed_paths.len()
+        ));
+
+        let whole_tree_shown = self.settings.structure_section_enabled && self.settings.full_tree_structure_enabled;
+        if !removed_selected_files.is_empty() || whole_tree_shown {
+            self.move_sections_in_document(removed_selected_files, root_node);
+        }
+    }
+
+    fn handle_file_renamed(&mut self, from: PathBuf, to: PathBuf) {
+        if !self.ui_tree_handler.contains_path(&from) && self.ui_tree_handler.contains_path(&to) {
+            // Editors often save by renaming a new file over the old one
+            debug!("{:?} was replaced by {:?}, treating it as a modification", to, from);
+            self.handle_files_modified(vec![to]);
+        } else {
+            self.handle_file_moved(from, Some(to));
+        }
+    }
+
+    /// Removes (`to` is `None`) or renames a file in the tree and, if it is selected, in the
+    /// document. Directories and files moved in from outside the tree need a full rescan.
+    fn handle_file_moved(&mut self, from: PathBuf, to: Option<PathBuf>) {
+        let Some(root_node) = &mut self.root_file_node else { return };
+
+        let Some(node) = root_node.find(&from).filter(|node| !node.is_dir) else {
+            let is_known_directory = root_node.find(&from).is_some();
+            if is_known_directory || to.is_some() {
+                debug!("Move of {:?} needs a rescan", from);
+                self.rescan_directory();
+            }
+            return; // A file we don't show, e.g. an ignored one
+        };
+        let (is_generated, size_bytes, modified, line_count) = (node.is_generated, node.size_bytes, node.modified, node.line_count);
+        root_node.remove(&from);
+
+        if let Some(to) = &to {
+            let renamed_node = FileNode {
+                name: to.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default(),
+                path: to.clone(),
+                is_dir: false,
+                is_generated,
+                is_gitignored: GitignoredFileDetector::new(root_node.path.clone()).is_gitignored(to, false),
+                size_bytes,
+                modified,
+                line_count,
+                children_pending: false,
+                children: Vec::new(),
+            };
+            if root_node.insert(renamed_node).is_err() {
+                debug!("Target directory of {:?} is not in the tree, rescanning", to);
+                self.rescan_directory();
+                return;
+            }
+        }
+        root_node.update_directory_sizes();
+
+        let mut selection = self.ui_tree_handler.selected_files.clone();
+        let was_selected = selection.remove(&from);
+        if let (true, Some(to)) = (was_selected, &to) {
+            selection.insert(to.clone());
+        }
+        if let Some(to) = &to {
+            self.ui_tree_handler.move_file_tags(&from, to);
+        }
+        if let Some(bytes) = Arc::make_mut(&mut self.file_snapshot).remove(&from) {
+            if let (true, Some(to)) = (was_selected, &to) {
+                Arc::make_mut(&mut self.file_snapshot).insert(to.clone(), bytes); // Same content, new path
+            }
+        }
+        let root_node = root_node.clone();
+        self.ui_tree_handler.build_from_file_node(&root_node);
+        self.ui_tree_handler.set_selected_files(selection);
+        info!("Tracked {} of {:?} without a rescan", if to.is_some() { "rename" } else { "removal" }, from);
+
+        if was_selected {
+            self.move_sections_in_document(vec![(from, to)], root_node);
+        }
+    }
+
+    /// Removes or re-titles the sections of moved selected files and rewrites the structure block.
+    /// The tree and selection must already reflect the moves.
+    fn move_sections_in_document(&mut self, moves: Vec<(PathBuf, Option<PathBuf>)>, root_node: FileNode) {
+        let (Some(directory), Some(_)) = (&self.current_directory, &self.output_file_path) else {
+            return;
+        };
+        let full_regeneration_needed = self.settings.all_formats_enabled
+            || self.settings.token_budget_enabled
+            || (!self.settings.split_output_enabled
+                && (self.template_path().is_some()
+                    || (self.settings.anchor_links_enabled && self.settings.output_format == OutputFormat::Markdown)));
+        if full_regeneration_needed {
+            // Custom layouts and anchor links can't be patched reliably, nor other formats at all,
+            // and a budget must be re-planned without the moved files
+            self.generate_document(false);
+            return;
+        }
+
+        let generator = self.configured_generator(directory.clone(), self.document_files());
+        self.document_worker.submit(DocumentJob::MoveSections {
+            generator,
+            document_paths: self.current_document_paths(),
+            moves,
+            root_node,
+            format: self.settings.output_format,
+        });
+    }
+
+    fn handle_partial_document_update_complete(&mut self, result: Result<()>) {
+        match result {
+            Ok(()) => {
+                debug!("Partial document update completed successfully");
+                self.update_document_size();
+                self.write_compressed_copies(self.current_document_paths());
+            }
+            Err(e) => {
+                warn!("Partial document update failed: {}", e);
+            }
+        }
+    }
+
+    fn process_events(&mut self) {
+        while let Ok(event) = self.event_receiver.try_recv() {
+            match event {
+                AppEvent::CachedScanLoaded(root_node) => {
+                    self.handle_cached_scan_loaded(root_node);
+                }
+                AppEvent::ScanProgress(entry_count) => {
+                    self.scan_progress = entry_count;
+                }
+                AppEvent::DirectoryScanComplete(result) => {
+                    self.handle_directory_scan_complete(result);
+                }
+                AppEvent::DirectoryChildrenLoaded { directory, result, select_files } => {
+                    self.handle_directory_children_loaded(directory, result, select_files);
+                }
+                AppEvent::FilesModifiedDebounced(file_paths) => {
+                    self.handle_files_modified(file_paths);
+                }
+                AppEvent::FileRemoved(file_path) => {
+                    self.handle_file_moved(file_path, None);
+                }
+                AppEvent::FileRenamed { from, to } => {
+                    self.handle_file_renamed(from, to);
+                }
+                AppEvent::DocumentGenerationComplete(result) => {
                     self.handle_document_generation_complete(result);
                 }
+                AppEvent::FilesRead { outcomes, replace } => {
+                    self.ui_tree_handler.update_read_errors(outcomes, replace);
+                }
                 AppEvent::PartialDocumentUpdateComplete(result) => {
                     self.handle_partial_document_update_complete(result);
                 }
-                AppEvent::DirectoryContentChanged => {
-                    info!("Directory content changed, re-scanning...");
-                    if let Some(dir) = self.current_directory.clone() {
-                        // Re-scan with current ignore patterns
-                        self.open_directory(dir, self.ignore_patterns_text.lines().map(|s| s.to_string()).collect());
+                AppEvent::GenerationRecorded(result) => {
+                    self.handle_generation_recorded(result);
+                }
+                AppEvent::DocumentOutlineParsed(result) => {
+                    self.handle_document_outline_parsed(result);
+                }
+                AppEvent::TokenCountProgress { id, counted_files, tokens, done } => {
+                    if let Some(count) = self.token_count.as_mut().filter(|count| count.id == id) {
+                        count.counted_files = counted_files;
+                        count.tokens = tokens;
+                        count.done = done;
+                    }
+                }
+                AppEvent::FilePreviewLoaded { path, content } => {
+                    if let Some((preview_path, preview_content)) = &mut self.file_preview {
+                        if *preview_path == path {
+                            *preview_content = Some(content);
+                        }
+                    }
+                }
+                AppEvent::SnapshotTaken { files, replace } => {
+                    self.handle_snapshot_taken(files, replace);
+                }
+                AppEvent::StructureRescanComplete(result) => {
+                    self.handle_structure_rescan_complete(result);
+                }
+                AppEvent::ComparisonComplete(result) => {
+                    self.handle_comparison_complete(result);
+                }
+                AppEvent::PdfExported(result) => {
+                    self.handle_pdf_exported(result);
+                }
+                AppEvent::RemoteSourceFetched { source, result } => {
+                    self.handle_remote_source_fetched(source, result);
+                }
+                AppEvent::ScheduledGenerationComplete(result) => match result {
+                    Ok(output_path) => {
+                        let document_paths = if self.settings.split_output_enabled {
+                            existing_chunk_paths(&output_path)
+                        } else {
+                            vec![output_path.clone()]
+                        };
+                        self.write_compressed_copies(document_paths);
+                        self.set_status_message(format!("Scheduled document written: {}", output_path.display()));
+                    }
+                    Err(e) => {
+                        error!("Scheduled generation failed: {}", e);
+                        self.set_error("Scheduled generation failed", &e, None);
+                    }
+                },
+                AppEvent::UpdateCheckComplete { result, manual } => {
+                    self.handle_update_check_complete(result, manual);
+                }
+                AppEvent::LlmAnswerChunk { id, text } => {
+                    if self.llm_chat.as_ref().is_some_and(|chat| chat.id == id) {
+                        self.llm_answer.push_str(&text);
                     }
                 }
+                AppEvent::LlmAnswerDone { id, result } => {
+                    if self.llm_chat.as_ref().is_some_and(|chat| chat.id == id) {
+                        self.llm_chat = None;
+                        self.llm_error = result.err().map(|e| e.to_string());
+                    }
+                }
+                AppEvent::DirectoryContentChanged(created_paths) => {
+                    self.handle_directory_content_changed(created_paths);
+                }
                 AppEvent::WatcherError(error) => {
-                    error!("File watcher error: {}", error);
-                    self.set_error_message(format!("File watcher error: {}", error));
-                    self.monitoring_active = false;
+                    self.handle_watcher_error(error);
                 }
                 AppEvent::StatusMessage(message) => {
                     self.set_status_message(message);
@@ -358,7 +1983,10 @@ impl ContextBuilderApp {
                     ui.label("Current directory:");
                     ui.add_space(10.0);
                     
-                    if let Some(directory) = &self.current_directory {
+                    if let Some(source) = &self.remote_source {
+                        ui.monospace(source.address());
+                        ui.weak("(local mirror)");
+                    } else if let Some(directory) = &self.current_directory {
                         ui.monospace(directory.display().to_string());
                     } else {
                         ui.weak("No directory selected");
@@ -368,24 +1996,162 @@ impl ContextBuilderApp {
                 ui.add_space(8.0);
                 
                 ui.horizontal(|ui| {
-                    if ui.add_sized([120.0, 30.0], egui::Button::new("Browse...")).clicked() {
+                    if ui.add_sized([120.0, 30.0], egui::Button::new("Browse...")).on_hover_text("Ctrl+O").clicked() {
                         self.open_directory_dialog();
                     }
+
+                    ui.add_space(10.0);
+                    if ui.add_sized([120.0, 30.0], egui::Button::new("🌐 Remote..."))
+                        .on_hover_text("Open a directory on a server over SSH, e.g. user@host:/srv/app")
+                        .clicked()
+                    {
+                        self.remote_address_input = match self.remote_address_input.take() {
+                            Some(_) => None,
+                            None => Some(self.remote_source.as_ref().map(RemoteSource::address).unwrap_or_default()),
+                        };
+                    }
                     
                     if self.current_directory.is_some() {
                         ui.add_space(10.0);
-                        if ui.add_sized([100.0, 30.0], egui::Button::new("🔄 Refresh")).clicked() {
-                            if let Some(dir) = self.current_directory.clone() {
+                        let refresh_hover = match self.remote_source.is_some() {
+                            true => "Fetch the remote directory again and rescan",
+                            false => "Rescan the directory",
+                        };
+                        if ui.add_sized([100.0, 30.0], egui::Button::new("🔄 Refresh")).on_hover_text(refresh_hover).clicked() {
+                            if let Some(source) = self.remote_source.clone() {
+                                if !self.is_fetching_remote {
+                                    self.fetch_remote_source(source);
+                                }
+                            } else if let Some(dir) = self.current_directory.clone() {
                                 // Refresh with current ignore patterns
-                                self.open_directory(dir, self.ignore_patterns_text.lines().map(|s| s.to_string()).collect());
+                                self.open_directory(dir, self.settings.ignore_patterns.clone());
                             }
                         }
+
+                        ui.add_space(10.0);
+                        if ui.add_sized([180.0, 30.0], egui::Button::new("🔀 Compare with..."))
+                            .on_hover_text("Generate a document with per-file diffs against another directory, e.g. v1/ and v2/ of a service")
+                            .clicked()
+                        {
+                            self.compare_directories_dialog();
+                        }
                     }
                 });
+
+                let fetching = self.is_fetching_remote;
+                let mut fetch_source = None;
+                if let Some(address) = &mut self.remote_address_input {
+                    ui.add_space(5.0);
+                    ui.horizontal(|ui| {
+                        ui.label("Remote directory:");
+                        let response = ui.add(egui::TextEdit::singleline(address)
+                            .hint_text("user@host:/srv/app")
+                            .desired_width(300.0));
+                        let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                        let source = RemoteSource::parse(address);
+                        let fetch_clicked = ui.add_enabled(source.is_some() && !fetching, egui::Button::new("Fetch"))
+                            .on_hover_text("Copies the directory (without .git) over ssh into a local mirror, then opens it")
+                            .clicked();
+                        if (fetch_clicked || submitted) && !fetching {
+                            fetch_source = source;
+                        }
+                        if fetching {
+                            ui.spinner();
+                        }
+                    });
+                }
+                if let Some(source) = fetch_source {
+                    self.fetch_remote_source(source);
+                }
             });
         });
     }
 
+    /// Asks for a second directory and an output file, then compares in the background
+    fn compare_directories_dialog(&mut self) {
+        let Some(directory) = self.current_directory.clone() else { return };
+        let Some(other_directory) = rfd::FileDialog::new()
+            .set_title("Directory to compare with")
+            .set_directory(directory.parent().unwrap_or(&directory))
+            .pick_folder()
+        else {
+            return;
+        };
+        let format = self.settings.output_format;
+        let Some(output_path) = rfd::FileDialog::new()
+            .set_directory(&directory)
+            .set_file_name(format!("comparison.{}", format.extension()))
+            .save_file()
+        else {
+            return;
+        };
+
+        let comparer = DirectoryComparer::new(directory, other_directory)
+            .with_ignore_patterns(self.settings.ignore_patterns.clone());
+        let sender = self.event_sender.clone();
+        self.set_status_message("Comparing directories...".to_string());
+
+        self.spawn_worker(move || {
+            let result = comparer.generate_comparison_document(&output_path, format)
+                .map(|comparison| (output_path, comparison));
+            if let Err(e) = sender.send(AppEvent::ComparisonComplete(result)) {
+                error!("Failed to send comparison result: {}", e);
+            }
+        });
+    }
+
+    fn export_pdf_dialog(&mut self) {
+        let Some(directory) = self.current_directory.clone() else { return };
+        let Some(output_path) = self.output_file_path.clone() else { return };
+        let document_paths: Vec<PathBuf> = self.current_document_paths()
+            .into_iter()
+            .filter(|path| path.exists() || compressed_path(path).is_file())
+            .collect();
+        let file_name = output_path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("context");
+        let Some(pdf_path) = rfd::FileDialog::new()
+            .set_directory(output_path.parent().unwrap_or(&directory))
+            .set_file_name(format!("{}.pdf", file_name))
+            .add_filter("PDF", &["pdf"])
+            .save_file()
+        else {
+            return;
+        };
+
+        let format = self.settings.output_format;
+        let sender = self.event_sender.clone();
+        self.set_status_message("Exporting PDF...".to_string());
+
+        self.spawn_worker(move || {
+            let result = export_pdf(&directory, &document_paths, format, &pdf_path).map(|_| pdf_path);
+            if let Err(e) = sender.send(AppEvent::PdfExported(result)) {
+                error!("Failed to send PDF export result: {}", e);
+            }
+        });
+    }
+
+    fn handle_pdf_exported(&mut self, result: Result<PathBuf>) {
+        match result {
+            Ok(pdf_path) => self.set_status_message(format!("PDF written to {}", pdf_path.display())),
+            Err(e) => {
+                error!("PDF export failed: {}", e);
+                self.set_error("Failed to export PDF", &e, None);
+            }
+        }
+    }
+
+    fn handle_comparison_complete(&mut self, result: Result<(PathBuf, DirectoryComparison)>) {
+        match result {
+            Ok((output_path, comparison)) => self.set_status_message(format!(
+                "Comparison written to {}: {} changed, {} only here, {} only in the other directory",
+                output_path.display(), comparison.changed.len(), comparison.only_in_a.len(), comparison.only_in_b.len()
+            )),
+            Err(e) => {
+                error!("Directory comparison failed: {}", e);
+                self.set_error("Failed to compare directories", &e, None);
+            }
+        }
+    }
+
     fn render_file_tree(&mut self, ui: &mut egui::Ui) {
         ui.add_space(10.0);
         
@@ -393,6 +2159,18 @@ impl ContextBuilderApp {
             ui.vertical(|ui| {
                 ui.horizontal(|ui| {
                     ui.heading("File Selection");
+                    if !self.ui_tree_handler.tree_nodes.is_empty() && !self.is_loading_directory {
+                        self.render_quick_select_menu(ui);
+                        let lock_label = if self.selection_locked { "🔒 Locked" } else { "🔓 Lock" };
+                        ui.toggle_value(&mut self.selection_locked, lock_label)
+                            .on_hover_text("Lock the selection against accidental changes, e.g. during a long monitoring session");
+                    }
+                    if (!self.unscanned_paths.is_empty() || self.ignore_rules_changed) && !self.is_rescanning_structure {
+                        ui.weak(format!("{} —", self.unscanned_paths_summary()));
+                        if ui.small_button("Refresh").on_hover_text("Rescan the project to show them in the tree").clicked() {
+                            self.rescan_directory();
+                        }
+                    }
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                         if self.ui_tree_handler.has_selection() {
                             ui.colored_label(
@@ -402,6 +2180,13 @@ impl ContextBuilderApp {
                         } else if self.current_directory.is_some() {
                             ui.weak("No files selected");
                         }
+                        if self.is_validating_cached_scan {
+                            if ui.small_button("Cancel").clicked() {
+                                self.cancel_scan();
+                            }
+                            ui.spinner();
+                            ui.weak(format!("Validating cached tree... ({} entries)", self.scan_progress));
+                        }
                     });
                 });
                 
@@ -415,13 +2200,35 @@ impl ContextBuilderApp {
                         ui.spinner();
                         ui.add_space(10.0);
                         ui.label("🔍 Scanning directory...");
+                        ui.weak(format!("{} entries scanned", self.scan_progress));
+                        ui.add_space(5.0);
+                        if ui.button("Cancel").clicked() {
+                            self.cancel_scan();
+                        }
                         ui.add_space(20.0);
                     });
                 } else if self.current_directory.is_some() {
+                    self.render_tag_chips(ui);
+                    self.render_external_files(ui);
+                    ui.horizontal(|ui| {
+                        ui.label("🔍");
+                        let response = ui.add(egui::TextEdit::singleline(&mut self.tree_filter)
+                            .hint_text("Filter by name (Ctrl+F, Enter to move to the tree)")
+                            .desired_width(ui.available_width()));
+                        if std::mem::take(&mut self.focus_tree_filter) {
+                            response.request_focus();
+                        }
+                        if response.lost_focus() && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Enter)) {
+                            self.ui_tree_handler.focus_first_node();
+                        }
+                    });
+                    self.ui_tree_handler.set_filter(&self.tree_filter);
+                    // In its own panel the tree takes the full height
+                    let in_panel = self.settings.panel_layout == PanelLayout::Columns;
                     egui::ScrollArea::vertical()
                         .id_source("file_tree_scroll_area")
-                        .max_height(350.0)
-                        .auto_shrink([false, true])
+                        .max_height(if in_panel { f32::INFINITY } else { 350.0 })
+                        .auto_shrink([false, !in_panel])
                         .show(ui, |ui| {
                             if self.ui_tree_handler.tree_nodes.is_empty() {
                                 ui.vertical_centered(|ui| {
@@ -430,11 +2237,40 @@ impl ContextBuilderApp {
                                     ui.add_space(20.0);
                                 });
                             } else {
+                                self.ui_tree_handler.set_show_sizes(self.settings.show_file_sizes);
+                                self.ui_tree_handler.set_selection_locked(self.selection_locked);
                                 let selection_changed = self.ui_tree_handler.render_tree(ui);
+                                if self.ui_tree_handler.take_unlock_request() {
+                                    self.show_unlock_prompt = true;
+                                }
                                 
                                 // If automatic document updating is active and selection changed, regenerate document
-                                if selection_changed && self.monitoring_active {
-                                    self.generate_document(false);
+                                if selection_changed {
+                                    self.handle_selection_changed();
+                                }
+
+                                self.load_requested_children();
+
+                                if let Some(focal_file) = self.ui_tree_handler.take_context_request() {
+                                    self.suggest_minimal_context(focal_file);
+                                }
+
+                                if let Some(path) = self.ui_tree_handler.take_preview_request() {
+                                    self.load_file_preview(path);
+                                }
+
+                                if let Some((path, target)) = self.ui_tree_handler.take_open_request() {
+                                    let result = match target {
+                                        OpenTarget::Editor => open_in_editor(&path, &self.settings.editor_command),
+                                        OpenTarget::FileManager => reveal_in_file_manager(&path),
+                                    };
+                                    if let Err(e) = result {
+                                        self.set_error(&format!("Failed to open {}", path.display()), &e, None);
+                                    }
+                                }
+
+                                if self.ui_tree_handler.take_tags_changed() {
+                                    self.save_project_settings();
                                 }
                             }
                         });
@@ -451,6 +2287,185 @@ impl ContextBuilderApp {
         });
     }
 
+    /// One chip per tag; clicking selects all files with the tag, or deselects them if all are selected
+    fn render_tag_chips(&mut self, ui: &mut egui::Ui) {
+        let tag_counts = self.ui_tree_handler.tag_selection_counts();
+        if tag_counts.is_empty() {
+            return;
+        }
+
+        let mut clicked_tag = None;
+        ui.horizontal_wrapped(|ui| {
+            ui.weak("Tags:");
+            for (tag, (selected, total)) in tag_counts {
+                let all_selected = selected == total;
+                let chip = ui.selectable_label(all_selected, format!("#{} {}/{}", tag, selected, total))
+                    .on_hover_text(if all_selected { "Deselect the files with this tag" } else { "Select all files with this tag" });
+                if chip.clicked() {
+                    clicked_tag = Some((tag, !all_selected));
+                }
+            }
+        });
+        ui.add_space(5.0);
+
+        if let Some((tag, select)) = clicked_tag {
+            if self.ensure_selection_unlocked() {
+                self.ui_tree_handler.set_tag_selected(&tag, select);
+                self.handle_selection_changed();
+            }
+        }
+    }
+
+    /// Local usage of the current project: generation frequency, context size and the files
+    /// included most, with a shortcut for tagging the ones that are part of nearly every generation
+    fn render_insights_window(&mut self, ctx: &Context) {
+        let mut open = self.show_insights_window;
+        egui::Window::new("📊 Usage Insights")
+            .open(&mut open)
+            .resizable(true)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                let Some(directory) = self.current_directory.clone() else {
+                    ui.weak("Open a project to see its usage.");
+                    return;
+                };
+                let stats = &self.usage_stats;
+                if stats.total_generations == 0 {
+                    ui.weak("No generations recorded for this project yet. Only explicit `Generate Document` runs are counted.");
+                    return;
+                }
+
+                ui.label(format!(
+                    "{} generations, {} in the last 7 days, {} in the last 30",
+                    stats.total_generations,
+                    stats.generations_since(chrono::Duration::days(7)),
+                    stats.generations_since(chrono::Duration::days(30)),
+                ));
+                if let Some((file_count, size_bytes)) = stats.average_context() {
+                    ui.label(format!(
+                        "Average context: {} files, {} ({} tokens)",
+                        file_count,
+                        format_byte_size(size_bytes),
+                        format_token_count(size_bytes as usize / chars_per_token())
+                    ));
+                }
+
+                ui.add_space(8.0);
+                ui.strong("Most included files");
+                for (path, count) in stats.most_included_files(USAGE_TOP_FILES) {
+                    ui.horizontal(|ui| {
+                        ui.monospace(path.to_string_lossy().replace('\\', "/"));
+                        ui.weak(format!("{}×", count));
+                    });
+                }
+
+                let habitual_files = stats.habitual_files();
+                if !habitual_files.is_empty() {
+                    ui.add_space(8.0);
+                    ui.label(format!(
+                        "{} files are part of nearly every generation. Tagging them lets you select them with one click.",
+                        habitual_files.len()
+                    ));
+                    if ui.button(format!("Tag them #{}", USAGE_HABIT_TAG)).clicked() {
+                        let root = directory.canonicalize().unwrap_or_else(|_| directory.clone());
+                        let paths: Vec<PathBuf> = habitual_files.iter()
+                            .map(|relative_path| root.join(relative_path))
+                            .filter(|path| self.ui_tree_handler.contains_path(path))
+                            .collect();
+                        self.ui_tree_handler.add_tag_to_files(&paths, USAGE_HABIT_TAG);
+                        self.save_project_settings();
+                        self.set_status_message(format!("Tagged {} files #{}", paths.len(), USAGE_HABIT_TAG));
+                    }
+                }
+
+                ui.separator();
+                if ui.button("Clear statistics").clicked() {
+                    self.usage_stats = UsageStats::default();
+                    if let Err(e) = self.usage_stats.save(&directory) {
+                        warn!("Failed to save usage stats: {}", e);
+                    }
+                }
+            });
+        self.show_insights_window = open;
+    }
+
+    /// Asks the configured model a question about the current document and shows the answer as
+    /// it streams in
+    fn render_llm_window(&mut self, ctx: &Context) {
+        let mut open = self.show_llm_window;
+        egui::Window::new("💬 Ask LLM")
+            .open(&mut open)
+            .resizable(true)
+            .default_width(520.0)
+            .show(ctx, |ui| {
+                let document_paths: Vec<PathBuf> = self.current_document_paths().into_iter().filter(|path| path.is_file()).collect();
+                let document_bytes: u64 = document_paths.iter()
+                    .filter_map(|path| std::fs::metadata(path).ok())
+                    .map(|metadata| metadata.len())
+                    .sum();
+                let model = self.settings.llm_model.trim();
+                if model.is_empty() {
+                    ui.colored_label(egui::Color32::from_rgb(200, 120, 0), "Set the endpoint and model under Settings → LLM first.");
+                } else if document_paths.is_empty() {
+                    ui.weak("Generate the document first; it is sent along with the question.");
+                } else {
+                    ui.weak(format!(
+                        "Sends the document ({}, ~{} tokens) to {} via {}",
+                        format_byte_size(document_bytes),
+                        format_token_count(document_bytes as usize / chars_per_token()),
+                        model,
+                        self.settings.llm_provider.name(),
+                    ));
+                }
+
+                let running = self.llm_chat.is_some();
+                ui.add_enabled(!running, egui::TextEdit::multiline(&mut self.llm_question)
+                    .hint_text("Ask a question about the project...")
+                    .desired_rows(3)
+                    .desired_width(f32::INFINITY));
+                ui.horizontal(|ui| {
+                    let can_send = !model.is_empty() && !document_paths.is_empty() && !self.llm_question.trim().is_empty();
+                    if running {
+                        if ui.button("Cancel").clicked() {
+                            if let Some(chat) = &self.llm_chat {
+                                chat.cancel();
+                            }
+                        }
+                        ui.spinner();
+                    } else if ui.add_enabled(can_send, egui::Button::new("Send")).clicked() {
+                        self.llm_runs += 1;
+                        self.llm_answer.clear();
+                        self.llm_error = None;
+                        self.llm_chat = Some(LlmChat::start(
+                            self.llm_runs,
+                            self.settings.llm_endpoint_config(),
+                            document_paths.clone(),
+                            self.llm_question.clone(),
+                            self.event_sender.clone(),
+                        ));
+                    }
+                    if !self.llm_answer.is_empty() && ui.button("📋 Copy answer").clicked() {
+                        ui.output_mut(|o| o.copied_text = self.llm_answer.clone());
+                    }
+                });
+
+                if let Some(error) = &self.llm_error {
+                    ui.colored_label(egui::Color32::from_rgb(200, 40, 40), error);
+                }
+                if !self.llm_answer.is_empty() {
+                    ui.separator();
+                    egui::ScrollArea::vertical()
+                        .id_source("llm_answer_scroll_area")
+                        .max_height(400.0)
+                        .stick_to_bottom(running)
+                        .show(ui, |ui| {
+                            ui.add(egui::Label::new(&self.llm_answer).selectable(true));
+                        });
+                }
+            });
+        self.show_llm_window = open;
+    }
+
     fn render_output_settings(&mut self, ui: &mut egui::Ui) {
         ui.add_space(10.0);
 
@@ -462,14 +2477,15 @@ impl ContextBuilderApp {
                 // Output Format Selection
                 ui.horizontal(|ui| {
                     ui.label("Format:");
-                    let old_format = self.selected_output_format;
-                    ui.radio_value(&mut self.selected_output_format, OutputFormat::Markdown, OutputFormat::Markdown.name());
-                    ui.radio_value(&mut self.selected_output_format, OutputFormat::Adoc, OutputFormat::Adoc.name());
+                    let old_format = self.settings.output_format;
+                    for format in OutputFormat::ALL {
+                        ui.radio_value(&mut self.settings.output_format, format, format.name());
+                    }
                     
                     // If the format changed and a path is set, update the path extension
-                    if old_format != self.selected_output_format {
+                    if old_format != self.settings.output_format {
                         if let Some(path) = &mut self.output_file_path {
-                             let new_extension = self.selected_output_format.extension();
+                             let new_extension = self.settings.output_format.extension();
                              // Only change the extension if the current path has one, or if it's the default base name
                              if path.extension().is_some() || path.file_name().and_then(|name| name.to_str()).map_or(false, |name| name.starts_with(DEFAULT_OUTPUT_FILENAME_BASE)) {
                                  path.set_extension(new_extension);
@@ -482,6 +2498,48 @@ impl ContextBuilderApp {
                 });
                 ui.add_space(8.0);
 
+                // Custom header / instructions
+                ui.label("Header text (inserted at the top of the document):");
+                let header_response = ui.add_enabled(
+                    self.current_directory.is_some(),
+                    egui::TextEdit::multiline(&mut self.custom_header_text)
+                        .desired_rows(3)
+                        .desired_width(ui.available_width())
+                        .hint_text("e.g. You are reviewing this codebase; focus on error handling"),
+                );
+                if header_response.changed() {
+                    self.save_project_settings();
+                }
+                ui.add_space(8.0);
+
+                // Scratch pseudo-file
+                let scratch_title = if self.scratch_text.trim().is_empty() { "📝 Scratch" } else { "📝 Scratch (included)" };
+                egui::CollapsingHeader::new(scratch_title)
+                    .id_source("scratch_text")
+                    .show(ui, |ui| {
+                        ui.weak("Included as a \"Scratch\" section after the selected files, e.g. for an error log or a stack trace.");
+                        let scratch_response = ui.add_enabled(
+                            self.current_directory.is_some(),
+                            egui::TextEdit::multiline(&mut self.scratch_text)
+                                .code_editor()
+                                .desired_rows(6)
+                                .desired_width(ui.available_width())
+                                .hint_text("Paste text here"),
+                        );
+                        if scratch_response.changed() {
+                            self.save_project_settings();
+                        }
+                    });
+                ui.add_space(8.0);
+
+                ui.horizontal(|ui| {
+                    ui.weak("Budgets, splitting, templates and monitoring options are in");
+                    if ui.small_button("⚙ Settings").clicked() {
+                        self.show_settings_window = true;
+                    }
+                });
+                ui.add_space(8.0);
+
                 // Output File Path Selection
                 ui.horizontal(|ui| {
                     ui.label("Save to:");
@@ -507,7 +2565,7 @@ impl ContextBuilderApp {
                          // Add 'Suggest Default' button if directory is set and current path is not the default suggestion
                          if ui.button("Suggest Default").clicked() {
                              if let Some(dir) = &self.current_directory {
-                                 self.output_file_path = Some(dir.join(format!("{}.{}", DEFAULT_OUTPUT_FILENAME_BASE, self.selected_output_format.extension())));
+                                 self.output_file_path = Some(dir.join(format!("{}.{}", DEFAULT_OUTPUT_FILENAME_BASE, self.settings.output_format.extension())));
                              }
                          }
                      }
@@ -523,19 +2581,20 @@ impl ContextBuilderApp {
             dialog = dialog.set_directory(dir);
         }
 
-        // Add filters for both Markdown and AsciiDoc
-        dialog = dialog.add_filter(OutputFormat::Markdown.name(), &[OutputFormat::Markdown.extension()]);
-        dialog = dialog.add_filter(OutputFormat::Adoc.name(), &[OutputFormat::Adoc.extension()]);
+        // Add a filter for each output format
+        for format in OutputFormat::ALL {
+            dialog = dialog.add_filter(format.name(), &[format.extension()]);
+        }
 
         if let Some(mut path) = dialog.save_file() { // Use mut path to allow modification
             // Check if the path already has a file extension
             if path.extension().is_none() {
                 // If not, append the extension of the currently selected format
-                if let Some(ext) = self.selected_output_format.extension().strip_prefix('.') { // Get extension without leading dot
+                if let Some(ext) = self.settings.output_format.extension().strip_prefix('.') { // Get extension without leading dot
                     path.set_extension(ext);
                 } else {
                     // Handle cases where extension() might return an empty string or no prefix
-                    path.set_extension(self.selected_output_format.extension());
+                    path.set_extension(self.settings.output_format.extension());
                 }
                  debug!("Appended extension to path: {:?}", path);
             } else {
@@ -546,26 +2605,853 @@ impl ContextBuilderApp {
             
             // Determine the format from the selected file's extension (keep existing logic)
             if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
-                self.selected_output_format = match ext.to_lowercase().as_str() {
-                    "md" => OutputFormat::Markdown,
-                    "adoc" => OutputFormat::Adoc,
-                    _ => {
+                let ext = ext.to_lowercase();
+                self.settings.output_format = match OutputFormat::ALL.into_iter().find(|format| format.extension() == ext) {
+                    Some(format) => format,
+                    None => {
                         // If extension is unknown, keep the current selection and maybe warn
                         warn!("Selected file has unknown extension: {}. Keeping current format selection.", ext);
-                        self.selected_output_format // Keep current
+                        self.settings.output_format // Keep current
                     }
                 };
             } else {
                  // This case should ideally not be reached if we appended an extension above,
                  // but handle defensively if the selected path had no extension initially.
                  warn!("Selected file has no extension after processing. Keeping current format selection.");
-                 // self.selected_output_format // Keep current
+                 // self.settings.output_format // Keep current
             }
 
             // Note: We don't trigger generation immediately, user clicks 'Generate'
         }
     }
 
+    fn render_settings_window(&mut self, ctx: &Context) {
+        let mut open = self.show_settings_window;
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .resizable(true)
+            .default_width(520.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("🔍");
+                    ui.add(egui::TextEdit::singleline(&mut self.settings_search)
+                        .hint_text("Search settings...")
+                        .desired_width(ui.available_width()));
+                });
+                ui.separator();
+
+                let query = self.settings_search.trim().to_lowercase();
+                let matching: Vec<SettingsCategory> = SettingsCategory::ALL.into_iter()
+                    .filter(|category| category.matches(&query))
+                    .collect();
+
+                egui::ScrollArea::vertical()
+                    .id_source("settings_scroll_area")
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        if matching.is_empty() {
+                            ui.weak("No settings match your search");
+                        }
+                        for category in matching {
+                            egui::CollapsingHeader::new(RichText::new(category.name()).strong())
+                                .id_source(("settings_category", category.name()))
+                                .default_open(true)
+                                .open((!query.is_empty()).then_some(true))
+                                .show(ui, |ui| match category {
+                                    SettingsCategory::Output => self.render_output_category(ui),
+                                    SettingsCategory::BudgetAndLimits => self.render_limits_category(ui),
+                                    SettingsCategory::Monitoring => self.render_monitoring_category(ui),
+                                    SettingsCategory::Scanning => self.render_scanning_category(ui),
+                                    SettingsCategory::Selection => self.render_selection_presets_category(ui),
+                                    SettingsCategory::Appearance => self.render_appearance_category(ui),
+                                    SettingsCategory::Updates => self.render_updates_category(ui),
+                                    SettingsCategory::Llm => self.render_llm_category(ui),
+                                });
+                            ui.add_space(5.0);
+                        }
+
+                        ui.separator();
+                        if ui.button("Restore defaults").clicked() {
+                            self.settings = AppSettings::default();
+                            self.ignore_patterns_text = self.settings.ignore_patterns.join("\n");
+                        }
+                    });
+            });
+        self.show_settings_window = open;
+    }
+
+    fn render_output_category(&mut self, ui: &mut egui::Ui) {
+        // Snapshot mode
+        if ui.checkbox(&mut self.settings.snapshot_mode_enabled, "Snapshot file contents when they are selected")
+            .on_hover_text("Generate from the captured contents, so a build rewriting files can't leave half-written content in the document. While monitoring, edited files keep their snapshotted content until the snapshot is refreshed, which regenerates the document.")
+            .changed()
+        {
+            if self.settings.snapshot_mode_enabled {
+                self.update_snapshot(true);
+            } else {
+                self.file_snapshot = Arc::new(FileSnapshot::new());
+                self.snapshot_taken_at = None;
+            }
+        }
+        ui.add_space(8.0);
+
+        // Chunked output
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.split_output_enabled, "Split into parts of at most:");
+            ui.add_enabled(
+                self.settings.split_output_enabled,
+                egui::DragValue::new(&mut self.settings.max_tokens_per_chunk)
+                    .speed(1000)
+                    .clamp_range(1000..=10_000_000)
+                    .suffix(" tokens"),
+            );
+        });
+        if self.settings.split_output_enabled {
+            ui.weak("Parts are written as <name>_part1, <name>_part2, ... next to the output path; each repeats the project structure.");
+        }
+        ui.add_space(8.0);
+
+        // Document template
+        ui.horizontal(|ui| {
+            ui.label("Template:");
+            let project_template = self.trusted_workspace_config()
+                .zip(self.current_directory.as_ref())
+                .and_then(|(config, directory)| config.template_path(directory));
+            if let Some(path) = project_template {
+                ui.monospace(path.display().to_string());
+                ui.weak("(from the project's .contextbuilder.toml)");
+                return;
+            }
+            match &self.settings.custom_template_path {
+                Some(path) => {
+                    ui.monospace(path.display().to_string());
+                }
+                None => {
+                    ui.weak("Built-in layout");
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Custom template…").clicked() {
+                self.open_template_dialog();
+            }
+            if self.settings.custom_template_path.is_some() && ui.button("Use built-in").clicked() {
+                self.settings.custom_template_path = None;
+            }
+            if ui.button("Export default template…")
+                .on_hover_text("Save the built-in layout as a template file to start customizing")
+                .clicked()
+            {
+                self.export_default_template();
+            }
+        });
+        if self.template_path().is_some() && self.settings.split_output_enabled {
+            ui.weak("Custom templates are not applied to split output.");
+        }
+
+        ui.checkbox(&mut self.settings.structure_section_enabled, "Include the Project Structure section")
+            .on_hover_text("Uncheck for a document of file contents only.");
+        ui.add_enabled(
+            self.settings.structure_section_enabled,
+            egui::Checkbox::new(&mut self.settings.full_tree_structure_enabled, "Show the whole project tree"),
+        )
+        .on_hover_text("The structure lists every scanned file and directory, not only the selection, so the model sees the \
+                        full layout. Contents are still embedded for selected files only; gitignored entries stay out.");
+        ui.add_enabled_ui(self.settings.structure_section_enabled, |ui| {
+            ui.horizontal(|ui| {
+                let mut limit_depth = self.settings.structure_max_depth.is_some();
+                if ui.checkbox(&mut limit_depth, "Max tree depth:").changed() {
+                    self.settings.structure_max_depth = limit_depth.then_some(DEFAULT_STRUCTURE_MAX_DEPTH);
+                }
+                if let Some(max_depth) = &mut self.settings.structure_max_depth {
+                    ui.add(egui::DragValue::new(max_depth).clamp_range(1..=100));
+                }
+
+                ui.add_space(10.0);
+
+                let mut limit_entries = self.settings.structure_max_entries.is_some();
+                if ui.checkbox(&mut limit_entries, "Max entries per directory:").changed() {
+                    self.settings.structure_max_entries = limit_entries.then_some(DEFAULT_STRUCTURE_MAX_ENTRIES);
+                }
+                if let Some(max_entries) = &mut self.settings.structure_max_entries {
+                    ui.add(egui::DragValue::new(max_entries).clamp_range(1..=100_000));
+                }
+            })
+            .response
+            .on_hover_text("Keeps the tree of a large project short: hidden entries are summarized in rows such as \
+                            \"… and 42 more\". Limits apply in tree order and may hide selected files.");
+            ui.checkbox(&mut self.settings.structure_annotations_enabled, "Annotate entries with size and age")
+                .on_hover_text("e.g. main.rs (14.2 KB, 2d ago), as of the last scan. Directories show their total size and \
+                                latest change. Hints at what is actively developed.");
+        });
+        ui.checkbox(&mut self.settings.files_section_enabled, "Include the Files section")
+            .on_hover_text("Uncheck for a document of the project tree only, e.g. to discuss architecture. \
+                            Monitoring then has no file sections to update.");
+        if !self.settings.structure_section_enabled && !self.settings.files_section_enabled {
+            ui.colored_label(egui::Color32::from_rgb(200, 120, 0), "With both sections off, the document only holds its title and the optional blocks.");
+        }
+
+        ui.checkbox(&mut self.settings.section_footers_enabled, "Add line and token counts below each file section")
+            .on_hover_text("e.g. \"142 lines, ~1.1k tokens\". Not applied to custom templates.");
+
+        ui.checkbox(&mut self.settings.metadata_header_enabled, "Add a metadata block below the title")
+            .on_hover_text("A comment with the generation time, project path, git branch and commit, file, line and token \
+                            counts and the tool version. Refreshed by partial updates. Not applied to custom templates.");
+
+        ui.checkbox(&mut self.settings.language_summary_enabled, "Add a language summary after the project structure")
+            .on_hover_text("A table of the file count, line count and share of the content of each language in the selection. \
+                            Reads every selected file once more; refreshed by full generations only. Not applied to custom templates.");
+
+        ui.label("Code fence languages, one `pattern = language` per line:")
+            .on_hover_text("`*` matches any text, e.g. `*.tpl = html` or `Justfile = makefile`. Checked before the built-in \
+                            mappings of names such as Dockerfile and Makefile, of extensions and of shebang lines.");
+        ui.add(egui::TextEdit::multiline(&mut self.settings.fence_languages).desired_rows(2).hint_text("*.tpl = html"));
+
+        if self.settings.output_format == OutputFormat::Markdown {
+            ui.checkbox(&mut self.settings.anchor_links_enabled, "Link structure entries to file sections and add a table of contents")
+                .on_hover_text("Uses GitHub-style heading anchors. Not applied to split output or custom templates.");
+        }
+
+        ui.checkbox(&mut self.settings.dependency_sources_enabled, "Include sources of used Rust dependencies")
+            .on_hover_text("Resolves `use` statements of selected Rust files to crates in ~/.cargo/registry and appends \
+                            the matching module files, read-only. Not counted against the token budget or applied to custom templates.");
+
+        ui.checkbox(&mut self.settings.adoc_includes_enabled, "AsciiDoc: reference files with include:: directives")
+            .on_hover_text("Writes a small master document whose file sections are include::path[] directives relative to \
+                            the document, resolved by Asciidoctor at render time. Not applied to custom templates.");
+
+        ui.checkbox(&mut self.settings.markdown_links_enabled, "Markdown: link to files instead of embedding them")
+            .on_hover_text("File sections become links relative to the document, e.g. [src/main.rs](../src/main.rs), \
+                            for wikis where embedding whole files is undesirable. Not applied to custom templates.");
+        ui.add_enabled(
+            self.settings.markdown_links_enabled,
+            egui::Checkbox::new(&mut self.settings.markdown_line_anchors_enabled, "Add line anchors (#L1-L142)"),
+        );
+
+        ui.checkbox(&mut self.settings.all_formats_enabled, "Also write the other formats next to the document")
+            .on_hover_text("e.g. context.adoc, context.org and context.rst next to context.md, written concurrently. \
+                            Monitoring then regenerates all documents instead of patching sections.");
+
+        ui.checkbox(&mut self.settings.document_index_enabled, "Write a section index next to the document")
+            .on_hover_text("e.g. context.index.json: the byte and line range of each file section, so agents can seek to a file \
+                            without parsing the document. Updated after every write, including partial updates.");
+
+        ui.horizontal(|ui| {
+            ui.label("Generation history:");
+            ui.add(egui::DragValue::new(&mut self.settings.history_length).clamp_range(0..=500).suffix(" generations"));
+            ui.checkbox(&mut self.settings.history_copies_enabled, "Keep copies for section diffs");
+        }).response.on_hover_text("Listed in the 🕘 History panel; 0 turns the history off. Copies of the documents are \
+                                   kept in the cache directory and removed with their entries.");
+
+        ui.checkbox(&mut self.settings.compressed_copy_enabled, "Keep a compressed copy next to the document")
+            .on_hover_text("e.g. context.md.gz, gzip-compressed for archiving large contexts. Refreshed after every write; \
+                            the outline is read from it when the document itself was deleted.");
+    }
+
+    fn render_limits_category(&mut self, ui: &mut egui::Ui) {
+        // Token budget
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.token_budget_enabled, "Limit to token budget:");
+            ui.add_enabled(
+                self.settings.token_budget_enabled,
+                egui::DragValue::new(&mut self.settings.max_token_budget)
+                    .speed(1000)
+                    .clamp_range(1000..=10_000_000)
+                    .suffix(" tokens"),
+            );
+        });
+        if self.settings.token_budget_enabled {
+            ui.weak("Right-click a file in the tree to change its priority. Files that don't fit are truncated or omitted.");
+        }
+        ui.horizontal(|ui| {
+            ui.label("Token estimate:");
+            for estimator in TokenEstimator::ALL {
+                ui.radio_value(&mut self.settings.token_estimator, estimator, estimator.name());
+            }
+        })
+        .response
+        .on_hover_text("No tokenizer is bundled; counts are estimated from characters. The conservative estimate suits \
+                        dense code or non-English text and keeps budgets and split parts on the safe side.");
+        ui.add_space(8.0);
+
+        // Per-file truncation limits
+        ui.horizontal(|ui| {
+            let mut limit_lines = self.settings.file_size_limits.max_lines.is_some();
+            if ui.checkbox(&mut limit_lines, "Max lines per file:").changed() {
+                self.settings.file_size_limits.max_lines = limit_lines.then_some(DEFAULT_MAX_FILE_LINES);
+            }
+            if let Some(max_lines) = &mut self.settings.file_size_limits.max_lines {
+                ui.add(egui::DragValue::new(max_lines).speed(10).clamp_range(10..=1_000_000));
+            }
+
+            ui.add_space(10.0);
+
+            let mut limit_bytes = self.settings.file_size_limits.max_bytes.is_some();
+            if ui.checkbox(&mut limit_bytes, "Max bytes per file:").changed() {
+                self.settings.file_size_limits.max_bytes = limit_bytes.then_some(DEFAULT_MAX_FILE_BYTES);
+            }
+            if let Some(max_bytes) = &mut self.settings.file_size_limits.max_bytes {
+                ui.add(egui::DragValue::new(max_bytes).speed(1024).clamp_range(1024..=usize::MAX));
+            }
+        });
+
+        ui.checkbox(&mut self.settings.exclude_binary_files, "Leave selected binary files out entirely")
+            .on_hover_text("Otherwise each gets a one-line entry such as \"binary, 1.2 MB, skipped\"");
+        ui.checkbox(&mut self.settings.notebook_markdown_cells, "Include Markdown cells of Jupyter notebooks")
+            .on_hover_text("Notebooks are shown as their code cells in `# %%` form, without outputs; this adds the Markdown cells as comments");
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.minify_enabled, "Strip comments and blank lines of:")
+                .on_hover_text("Minified sections start with a note saying so. Supported: C-like languages, Rust, Go, \
+                                JavaScript/TypeScript, CSS, Python, Ruby, shell, TOML and YAML.");
+            ui.add_enabled(
+                self.settings.minify_enabled,
+                egui::TextEdit::singleline(&mut self.settings.minified_extensions).hint_text("rs, py, js"),
+            );
+        });
+    }
+
+    fn render_monitoring_category(&mut self, ui: &mut egui::Ui) {
+        // Watch mode throttling
+        ui.horizontal(|ui| {
+            ui.label("While monitoring, regenerate on selection change up to:");
+            let mut limit_mib = self.settings.auto_regeneration_limit_bytes / (1024 * 1024);
+            if ui.add(egui::DragValue::new(&mut limit_mib).clamp_range(1..=1024).suffix(" MiB")).changed() {
+                self.settings.auto_regeneration_limit_bytes = limit_mib * 1024 * 1024;
+            }
+        }).response.on_hover_text("Larger documents only get partial updates for edited files");
+
+        // Growth alert
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.growth_alert_enabled, "Alert when the document grows by more than");
+            ui.add_enabled(
+                self.settings.growth_alert_enabled,
+                egui::DragValue::new(&mut self.settings.growth_alert_percent).clamp_range(5..=1000).suffix(" %"),
+            );
+        }).response.on_hover_text("Compared with its size when monitoring started, to catch build artifacts or generated \
+                                   files pulled into the selection early");
+
+        // Watcher backend
+        let mut watcher_changed = ui.checkbox(&mut self.settings.force_polling_watcher, "Always use the polling watcher")
+            .on_hover_text("Network file systems (NFS, SMB, SSHFS) are detected on Linux and polled automatically")
+            .changed();
+        ui.horizontal(|ui| {
+            ui.label("Poll interval:");
+            let response = ui.add(egui::DragValue::new(&mut self.settings.poll_interval_secs).clamp_range(1..=300).suffix(" s"));
+            // Restart the watcher once editing is done rather than on every drag step
+            watcher_changed |= response.drag_stopped() || response.lost_focus();
+        });
+
+        // Debounce timing
+        ui.horizontal(|ui| {
+            ui.label("Debounce:");
+            let response = ui.add(egui::DragValue::new(&mut self.settings.debounce_ms).clamp_range(50..=10_000).speed(10).suffix(" ms"))
+                .on_hover_text("Quiet time after the last change to a file before its section is updated");
+            watcher_changed |= response.drag_stopped() || response.lost_focus();
+            ui.label("checked every");
+            let response = ui.add(egui::DragValue::new(&mut self.settings.debounce_check_interval_ms).clamp_range(10..=2_000).speed(5).suffix(" ms"))
+                .on_hover_text("Files that settle within one check are updated together in a single pass");
+            watcher_changed |= response.drag_stopped() || response.lost_focus();
+        });
+
+        if watcher_changed {
+            if let Some(directory) = self.current_directory.clone() {
+                self.start_file_monitor(directory);
+            }
+        }
+
+        // Scheduled generation
+        ui.add_space(5.0);
+        let mut schedule_changed = ui.checkbox(&mut self.settings.scheduled_generation_enabled, "Write a timestamped copy of the document daily at:")
+            .on_hover_text("While the app is running, e.g. so a fresh document is waiting at the start of the workday")
+            .changed();
+        ui.horizontal(|ui| {
+            schedule_changed |= ui.add(egui::TextEdit::singleline(&mut self.settings.scheduled_generation_times)
+                .hint_text("09:00, 13:30")
+                .desired_width(150.0))
+                .changed();
+            match (self.settings.scheduled_generation_enabled, parse_schedule_times(&self.settings.scheduled_generation_times)) {
+                (_, Err(message)) => {
+                    ui.colored_label(egui::Color32::from_rgb(150, 0, 0), message);
+                }
+                (true, Ok(_)) => {
+                    if let Some(due) = self.next_scheduled_generation {
+                        ui.weak(format!("Next: {}", due.format("%a %H:%M")));
+                    }
+                }
+                (false, Ok(_)) => {}
+            }
+        });
+        if schedule_changed {
+            self.next_scheduled_generation = None; // Recomputed on the next frame
+        }
+
+        // Local HTTP API
+        ui.add_space(5.0);
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.http_api_enabled, "Serve the document on http://127.0.0.1, port")
+                .on_hover_text("For other tools on this machine: GET /context.md returns the latest document, GET /tree.json \
+                                the scanned tree and POST /regenerate starts a generation. The active tab answers. If the \
+                                port is taken, the log says so.");
+            ui.add_enabled(
+                self.settings.http_api_enabled,
+                egui::DragValue::new(&mut self.settings.http_api_port).clamp_range(1024..=65535),
+            );
+        });
+    }
+
+    fn render_scanning_category(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.settings.exclude_generated_files, "Exclude files marked linguist-generated in .gitattributes")
+            .on_hover_text("When unchecked, generated files are still shown but flagged in the tree");
+        ui.checkbox(&mut self.settings.lazy_scan_enabled, "Load directory contents when expanded")
+            .on_hover_text("For huge trees such as monorepos: only the top level is scanned up front. Checking an unloaded \
+                            directory loads and selects all of it. Quick select and tag chips only see loaded directories.");
+        ui.checkbox(&mut self.settings.show_file_sizes, "Show file and directory sizes in the tree");
+        ui.add_enabled(self.settings.show_file_sizes, egui::Checkbox::new(&mut self.settings.line_counts_enabled, "Count lines of text files"))
+            .on_hover_text("Reads every file up to 4 MB while scanning, which slows down scans of large trees");
+        ui.weak("Ignore patterns are edited next to the file tree. Scanning changes apply on the next scan.");
+    }
+
+    fn render_selection_presets_category(&mut self, ui: &mut egui::Ui) {
+        ui.weak("Presets appear in the Quick select menu above the file tree. Patterns are comma-separated globs, e.g. src/**/*.rs, *.toml");
+        let mut removed_index = None;
+        for (index, preset) in self.settings.selection_presets.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut preset.name).hint_text("Name").desired_width(120.0));
+                ui.add(egui::TextEdit::singleline(&mut preset.patterns).hint_text("*.rs, docs/**").desired_width(260.0));
+                if ui.small_button("🗑").on_hover_text("Remove preset").clicked() {
+                    removed_index = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed_index {
+            self.settings.selection_presets.remove(index);
+        }
+        if ui.button("➕ Add preset").clicked() {
+            self.settings.selection_presets.push(SelectionPreset::default());
+        }
+        ui.add_space(8.0);
+
+        // Right-click "Open in editor" in the tree
+        ui.horizontal(|ui| {
+            ui.label("Editor command:");
+            ui.add(egui::TextEdit::singleline(&mut self.settings.editor_command).hint_text("code -g {path}").desired_width(220.0));
+        }).response.on_hover_text("Used by \"Open in editor\" in the tree's right-click menu. {path} is replaced by the file's path, \
+                                   or the path is appended. When blank, $VISUAL or $EDITOR is used unless it is a terminal editor, \
+                                   otherwise the OS default application.");
+    }
+
+    /// Bulk selection by built-in presets, the user's presets or file extension
+    fn render_quick_select_menu(&mut self, ui: &mut egui::Ui) {
+        let mut patterns: Option<Vec<String>> = None;
+        ui.menu_button("Quick select ▾", |ui| {
+            let user_presets = self.settings.selection_presets.iter()
+                .filter(|preset| !preset.name.trim().is_empty() && !preset.pattern_list().is_empty());
+            for preset in SelectionPreset::builtin().iter().chain(user_presets) {
+                if ui.button(&preset.name).on_hover_text(&preset.patterns).clicked() {
+                    patterns = Some(preset.pattern_list().into_iter().map(str::to_string).collect());
+                    ui.close_menu();
+                }
+            }
+            ui.separator();
+            ui.menu_button("By extension", |ui| {
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    for (extension, count) in self.ui_tree_handler.extension_counts() {
+                        if ui.button(format!("*.{} ({})", extension, count)).clicked() {
+                            patterns = Some(vec![format!("*.{}", extension)]);
+                            ui.close_menu();
+                        }
+                    }
+                });
+            });
+            if ui.button("Attach file outside the project…")
+                .on_hover_text("Include a standalone file such as /etc/nginx/nginx.conf or a shared .proto")
+                .clicked()
+            {
+                self.attach_external_file();
+                ui.close_menu();
+            }
+            if ui.button("From a log…").on_hover_text("Select the files mentioned in compiler or test output").clicked() {
+                self.log_selection = Some(LogSelection { annotate: !self.log_excerpts.is_empty(), ..Default::default() });
+                ui.close_menu();
+            }
+            ui.menu_button("Exclude in this project", |ui| {
+                if self.render_excluded_extensions_menu(ui) {
+                    self.save_project_settings();
+                    self.rescan_directory();
+                }
+            });
+            ui.separator();
+            if ui.button("Edit presets…").clicked() {
+                self.show_settings_window = true;
+                self.settings_search = "preset".to_string();
+                ui.close_menu();
+            }
+        });
+
+        if let Some(patterns) = patterns {
+            self.select_patterns(&patterns);
+        }
+    }
+
+    /// Adds the files matching the globs to the selection, reporting how many matched
+    fn select_patterns(&mut self, patterns: &[String]) {
+        if !self.ensure_selection_unlocked() {
+            return;
+        }
+        let patterns: Vec<&str> = patterns.iter().map(String::as_str).collect();
+        let before = self.ui_tree_handler.get_selected_files().len();
+        let matched = self.ui_tree_handler.select_matching(&patterns);
+        let added = self.ui_tree_handler.get_selected_files().len() - before;
+        self.set_status_message(format!("{} files match {}, {} newly selected", matched, patterns.join(", "), added));
+        if added > 0 {
+            self.handle_selection_changed();
+        }
+    }
+
+    /// Extensions left out of every scan of the project. Returns whether they changed.
+    fn render_excluded_extensions_menu(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.weak("Hidden from the tree on every scan, like ignore patterns");
+        let mut extensions: BTreeMap<String, usize> = self.excluded_extensions.iter()
+            .map(|extension| (extension.clone(), 0))
+            .collect();
+        extensions.extend(self.ui_tree_handler.extension_counts());
+
+        let mut changed = false;
+        egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+            for (extension, count) in extensions {
+                let mut excluded = self.excluded_extensions.contains(&extension);
+                let label = if excluded { format!("*.{}", extension) } else { format!("*.{} ({})", extension, count) };
+                if ui.checkbox(&mut excluded, label).changed() {
+                    if excluded {
+                        self.excluded_extensions.insert(extension);
+                    } else {
+                        self.excluded_extensions.remove(&extension);
+                    }
+                    changed = true;
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            let response = ui.add(egui::TextEdit::singleline(&mut self.new_excluded_extension)
+                .hint_text("e.g. min.js")
+                .desired_width(100.0));
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button("Exclude").clicked() || submitted {
+                let extension = self.new_excluded_extension.trim().trim_start_matches("*.").trim_start_matches('.').to_string();
+                if !extension.is_empty() {
+                    changed |= self.excluded_extensions.insert(extension);
+                }
+                self.new_excluded_extension.clear();
+            }
+        });
+        changed
+    }
+
+    fn render_appearance_category(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Theme:");
+            for theme in ThemePreference::ALL {
+                ui.radio_value(&mut self.settings.theme, theme, theme.name());
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Layout:");
+            for layout in PanelLayout::ALL {
+                ui.radio_value(&mut self.settings.panel_layout, layout, layout.name());
+            }
+        })
+        .response
+        .on_hover_text("Columns puts the file tree in a resizable panel of full height on the left, for large monitors. \
+                        Preview, outline and history panels open on the right in both layouts.");
+    }
+
+    fn render_updates_category(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.settings.update_check_enabled, "Check for updates on startup")
+            .on_hover_text("Asks GitHub for the latest release once per start. Nothing but the request itself (with the app version as user agent) is sent.");
+        ui.horizontal(|ui| {
+            ui.label(format!("Current version: {}", CURRENT_VERSION));
+            if ui.add_enabled(!self.is_checking_for_updates, egui::Button::new("Check now")).clicked() {
+                self.start_update_check(true);
+            }
+            if self.is_checking_for_updates {
+                ui.spinner();
+            }
+        });
+        if let Some(skipped_version) = self.settings.skipped_update_version.clone() {
+            ui.horizontal(|ui| {
+                ui.weak(format!("Skipping version {}", skipped_version));
+                if ui.small_button("Stop skipping").clicked() {
+                    self.settings.skipped_update_version = None;
+                }
+            });
+        }
+    }
+
+    fn render_llm_category(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Provider:");
+            egui::ComboBox::from_id_source("llm_provider")
+                .selected_text(self.settings.llm_provider.name())
+                .show_ui(ui, |ui| {
+                    for provider in LlmProvider::ALL {
+                        ui.selectable_value(&mut self.settings.llm_provider, provider, provider.name());
+                    }
+                });
+        }).response.on_hover_text("OpenAI-compatible also covers llama.cpp, vLLM, LM Studio and most hosted APIs");
+        ui.horizontal(|ui| {
+            ui.label("Endpoint:");
+            ui.add(egui::TextEdit::singleline(&mut self.settings.llm_endpoint)
+                .hint_text(self.settings.llm_provider.default_endpoint())
+                .desired_width(260.0));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Model:");
+            ui.add(egui::TextEdit::singleline(&mut self.settings.llm_model)
+                .hint_text(match self.settings.llm_provider {
+                    LlmProvider::OpenAiCompatible => "gpt-4o-mini",
+                    LlmProvider::Ollama => "llama3.1",
+                })
+                .desired_width(260.0));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Summary model:");
+            ui.add(egui::TextEdit::singleline(&mut self.settings.llm_summary_model)
+                .hint_text("Same as above")
+                .desired_width(260.0));
+        }).response.on_hover_text("Writes the summaries of files marked `LLM summary` in the tree (right-click a file), \
+                                   e.g. a small local model. Each file is summarized once per content and model.");
+        ui.horizontal(|ui| {
+            ui.label("API key:");
+            ui.add(egui::TextEdit::singleline(&mut self.settings.llm_api_key)
+                .password(true)
+                .hint_text("$OPENAI_API_KEY")
+                .desired_width(260.0));
+        }).response.on_hover_text("Stored in plain text in the settings file. Leave empty to use the OPENAI_API_KEY \
+                                   environment variable; local Ollama needs no key.");
+        ui.weak("The whole generated document is sent with each question, and summarized files with their requests. \
+                 Use a local model for code that must not leave this machine.");
+    }
+
+    /// Fetches the latest release in the background; automatic checks stay silent unless
+    /// there is an update
+    fn start_update_check(&mut self, manual: bool) {
+        self.is_checking_for_updates = true;
+        let sender = self.event_sender.clone();
+        thread::spawn(move || {
+            let result = fetch_latest_release();
+            if let Err(e) = sender.send(AppEvent::UpdateCheckComplete { result, manual }) {
+                error!("Failed to send update check result: {}", e);
+            }
+        });
+    }
+
+    fn handle_update_check_complete(&mut self, result: Result<ReleaseInfo>, manual: bool) {
+        self.is_checking_for_updates = false;
+        match result {
+            Ok(release) if release.is_newer_than_current() => {
+                if manual {
+                    // Asking explicitly shows the banner even for a skipped version
+                    self.settings.skipped_update_version = None;
+                }
+                self.available_update = Some(release);
+            }
+            Ok(release) => {
+                self.available_update = None;
+                if manual {
+                    self.set_status_message(format!("You are on the latest version ({})", release.version));
+                }
+            }
+            Err(e) => {
+                warn!("Update check failed: {}", e);
+                if manual {
+                    self.set_error("Update check failed", &e, None);
+                }
+            }
+        }
+    }
+
+    fn render_update_banner(&mut self, ui: &mut egui::Ui) {
+        let Some(release) = &self.available_update else { return };
+        if self.settings.skipped_update_version.as_ref() == Some(&release.version) {
+            return;
+        }
+        let version = release.version.clone();
+        let url = release.url.clone();
+
+        egui::Frame::group(ui.style())
+            .fill(ui.visuals().faint_bg_color)
+            .show(ui, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    ui.label(RichText::new(format!("🆕 Version {} is available", version)).strong());
+                    ui.weak(format!("(you have {})", CURRENT_VERSION));
+                    if ui.small_button("What's new").clicked() {
+                        self.show_update_changelog = true;
+                    }
+                    ui.hyperlink_to("Download", url);
+                    if ui.small_button("Skip this version").clicked() {
+                        self.settings.skipped_update_version = Some(version.clone());
+                        self.show_update_changelog = false;
+                    }
+                });
+            });
+        ui.add_space(8.0);
+    }
+
+    fn render_update_changelog(&mut self, ctx: &Context) {
+        let Some(release) = &self.available_update else { return };
+        let mut open = self.show_update_changelog;
+        egui::Window::new(format!("What's new in {}", release.version))
+            .open(&mut open)
+            .resizable(true)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        if release.changelog.is_empty() {
+                            ui.weak("This release has no notes.");
+                        } else {
+                            ui.label(&release.changelog);
+                        }
+                    });
+                ui.separator();
+                ui.hyperlink_to("Open release page", &release.url);
+            });
+        self.show_update_changelog = open;
+    }
+
+    /// Asks before a generation replaces an existing file that wasn't written by this app
+    /// Whether the selection may change; while it is locked, asks to unlock instead
+    fn ensure_selection_unlocked(&mut self) -> bool {
+        if self.selection_locked {
+            self.show_unlock_prompt = true;
+        }
+        !self.selection_locked
+    }
+
+    fn render_unlock_prompt(&mut self, ctx: &Context) {
+        if !self.show_unlock_prompt {
+            return;
+        }
+        let (mut unlock, mut keep_locked) = (false, false);
+        egui::Window::new("Selection is locked")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label("The selection is locked so it can't change by accident. Unlock it to make changes?");
+                ui.weak("After unlocking, repeat the change.");
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    unlock = ui.button("Unlock").clicked();
+                    keep_locked = ui.button("Keep locked").clicked();
+                });
+            });
+
+        if unlock {
+            self.selection_locked = false;
+            self.set_status_message("Selection unlocked".to_string());
+        }
+        if unlock || keep_locked {
+            self.show_unlock_prompt = false;
+        }
+    }
+
+    fn render_overwrite_confirmation(&mut self, ctx: &Context) {
+        let Some(foreign_path) = self.overwrite_confirmation.clone() else { return };
+        let Some(output_path) = self.output_file_path.clone() else {
+            self.overwrite_confirmation = None;
+            return;
+        };
+        let alternative_path = next_free_output_path(&output_path);
+        let (mut overwrite, mut use_alternative, mut cancel) = (false, false, false);
+
+        egui::Window::new("Overwrite existing file?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(format!("{} already exists and doesn't look like a document generated by this app.", foreign_path.display()));
+                ui.weak("Overwriting it replaces its current content.");
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    let alternative_name = alternative_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                    use_alternative = ui.button(format!("Save as {}", alternative_name)).clicked();
+                    overwrite = ui.button("Overwrite").clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        if overwrite {
+            info!("Overwriting {:?} as confirmed", foreign_path);
+            self.confirmed_output_paths.insert(foreign_path);
+        } else if use_alternative {
+            info!("Writing to {:?} instead of replacing {:?}", alternative_path, foreign_path);
+            self.output_file_path = Some(alternative_path);
+        }
+        if overwrite || use_alternative || cancel {
+            self.overwrite_confirmation = None;
+        }
+        if overwrite || use_alternative {
+            self.generate_document(true);
+        }
+    }
+
+    fn save_project_settings(&self) {
+        let Some(directory) = &self.current_directory else { return };
+        let root = directory.canonicalize().unwrap_or_else(|_| directory.clone());
+        let settings = ProjectSettings {
+            custom_header: self.custom_header_text.clone(),
+            scratch_text: self.scratch_text.clone(),
+            excluded_extensions: self.excluded_extensions.clone(),
+            setup_banner_dismissed: self.setup_banner_dismissed,
+            workspace_trusted: self.workspace_trusted,
+            external_files: self.external_files.clone(),
+            file_tags: self.ui_tree_handler.get_file_tags().into_iter()
+                .filter_map(|(path, tags)| Some((path.strip_prefix(&root).ok()?.to_path_buf(), tags)))
+                .collect(),
+        };
+        if let Err(e) = settings.save(directory) {
+            warn!("Failed to save project settings: {}", e);
+        }
+    }
+
+    fn open_template_dialog(&mut self) {
+        let mut dialog = rfd::FileDialog::new().add_filter("Tera template", &["tera"]);
+        if let Some(dir) = &self.current_directory {
+            dialog = dialog.set_directory(dir);
+        }
+
+        if let Some(path) = dialog.pick_file() {
+            info!("Using custom document template: {:?}", path);
+            self.settings.custom_template_path = Some(path);
+        }
+    }
+
+    fn export_default_template(&mut self) {
+        let format = self.settings.output_format;
+        let mut dialog = rfd::FileDialog::new()
+            .add_filter("Tera template", &["tera"])
+            .set_file_name(format!("{}.{}.tera", DEFAULT_OUTPUT_FILENAME_BASE, format.extension()));
+        if let Some(dir) = &self.current_directory {
+            dialog = dialog.set_directory(dir);
+        }
+
+        if let Some(path) = dialog.save_file() {
+            match std::fs::write(&path, default_template(format)) {
+                Ok(()) => {
+                    self.set_status_message(format!("Default {} template saved to {}", format.name(), path.display()));
+                    self.settings.custom_template_path = Some(path);
+                }
+                Err(e) => {
+                    error!("Failed to export default template: {}", e);
+                    self.set_error_message(format!("Failed to save template: {}", e));
+                }
+            }
+        }
+    }
+
     fn render_ignore_settings(&mut self, ui: &mut egui::Ui) {
         ui.add_space(10.0);
 
@@ -590,8 +3476,9 @@ impl ContextBuilderApp {
                 ui.add_space(8.0);
                 
                 if ui.button("Apply Patterns & Rescan").clicked() {
+                    self.settings.ignore_patterns = self.ignore_patterns_text.lines().map(|s| s.to_string()).collect();
                     if let Some(dir) = self.current_directory.clone() {
-                        self.open_directory(dir, self.ignore_patterns_text.lines().map(|s| s.to_string()).collect());
+                        self.open_directory(dir, self.settings.ignore_patterns.clone());
                     } else {
                         self.set_error_message("Please select a directory first to apply ignore patterns.".to_string());
                     }
@@ -599,6 +3486,43 @@ impl ContextBuilderApp {
             });
     }
 
+    /// Size of the next document from the scanned sizes of the selected files, before any limits
+    /// or budget are applied. Tokens are estimated from the sizes until the background count of
+    /// the file contents finishes; a partial count is shown as a lower bound.
+    fn render_output_estimate(&self, ui: &mut egui::Ui) {
+        let size_bytes = self.ui_tree_handler.selected_size_bytes();
+        let (tokens, token_text) = match &self.token_count {
+            Some(count) if count.done => (count.tokens, format!("{} tokens", format_token_count(count.tokens))),
+            Some(count) if count.counted_files > 0 => {
+                let tokens = count.tokens.max(size_bytes as usize / chars_per_token());
+                (tokens, format!("≥{} tokens, counting…", format_token_count(count.tokens)))
+            }
+            _ => {
+                let tokens = size_bytes as usize / chars_per_token();
+                (tokens, format!("~{} tokens", format_token_count(tokens)))
+            }
+        };
+        let file_count = self.ui_tree_handler.selected_files.len();
+        let estimate = format!(
+            "Estimated output: ~{} / {} across {} {}",
+            format_byte_size(size_bytes),
+            token_text,
+            file_count,
+            if file_count == 1 { "file" } else { "files" },
+        );
+
+        if self.settings.token_budget_enabled && tokens > self.settings.max_token_budget {
+            ui.colored_label(egui::Color32::from_rgb(200, 120, 0), estimate)
+                .on_hover_text(format!(
+                    "Exceeds the token budget of {}: files will be truncated or omitted",
+                    format_token_count(self.settings.max_token_budget)
+                ));
+        } else {
+            ui.weak(estimate)
+                .on_hover_text("Tokens are counted from the file contents as the document shows them; the structure block and headings add a little");
+        }
+    }
+
     fn render_control_buttons(&mut self, ui: &mut egui::Ui) {
         ui.add_space(10.0);
         
@@ -613,16 +3537,61 @@ impl ContextBuilderApp {
                         } else {
                             EmojiLabel::new("⚫ Monitoring Inactive").show(ui);
                         }
+                        if let Some(restart_at) = self.watcher_restart_at {
+                            ui.colored_label(
+                                egui::Color32::from_rgb(150, 0, 0),
+                                format!("Watcher: restarting in {} s", restart_at.saturating_duration_since(Instant::now()).as_secs() + 1),
+                            ).on_hover_text("The file watcher failed and is restarted with increasing delays");
+                        } else if let Some(backend) = self.file_monitor.backend() {
+                            ui.weak(format!("Watcher: {}", backend.description()))
+                                .on_hover_text("Polling is used on network file systems, when native events are unavailable or keep failing, or when enabled in Settings");
+                        }
                     });
                 });
                 
                 ui.add_space(5.0);
                 ui.separator();
                 ui.add_space(8.0);
+
+                if self.monitoring_active && self.is_auto_regeneration_throttled() {
+                    self.render_throttling_banner(ui);
+                    ui.add_space(8.0);
+                }
+
+                if self.growth_alert_shown {
+                    self.render_growth_alert_banner(ui);
+                    ui.add_space(8.0);
+                }
+
+                if self.workspace_trusted.is_none() && self.workspace_config.as_ref().is_some_and(|config| !config.describe().is_empty()) {
+                    self.render_workspace_trust_banner(ui);
+                    ui.add_space(8.0);
+                }
+
+                if self.project_setup.is_some() && self.root_file_node.is_some() {
+                    self.render_project_setup_banner(ui);
+                    ui.add_space(8.0);
+                }
+
+                if let Some(selected_output) = self.selected_output_document() {
+                    self.render_selected_output_banner(ui, &selected_output);
+                    ui.add_space(8.0);
+                }
+
+                let gitignored_files = self.ui_tree_handler.selected_gitignored_files();
+                if !gitignored_files.is_empty() {
+                    self.render_gitignored_banner(ui, &gitignored_files);
+                    ui.add_space(8.0);
+                }
                 
                 let has_selection = self.ui_tree_handler.has_selection();
+                if has_selection {
+                    self.render_output_estimate(ui);
+                    ui.add_space(8.0);
+                }
+
                 let output_path_set = self.output_file_path.is_some(); // Check if output path is set
-                let can_generate = has_selection && output_path_set && !self.is_generating_document && !self.is_loading_directory; // Use renamed field
+                let can_generate = self.can_generate();
                 let can_start = has_selection && output_path_set && !self.monitoring_active && !self.is_generating_document && !self.is_loading_directory; // Ensure output path is set before monitoring
                 let can_stop = self.monitoring_active;
                 
@@ -632,7 +3601,7 @@ impl ContextBuilderApp {
                     let generate_button = egui::Button::new(RichText::new("📝 Generate Document"))
                         .min_size(egui::vec2(180.0, 35.0));
 
-                    if ui.add_enabled(can_generate, generate_button).clicked() { // Use can_generate
+                    if ui.add_enabled(can_generate, generate_button).on_hover_text("Ctrl+G").clicked() { // Use can_generate
                         self.generate_document(true); // Call renamed method
                     }
                     
@@ -653,7 +3622,37 @@ impl ContextBuilderApp {
                     if ui.add_enabled(can_stop, stop_button).clicked() {
                         self.stop_monitoring();
                     }
+
+                    ui.add_space(10.0);
+
+                    let can_export = !self.is_generating_document
+                        && self.current_document_paths().iter().any(|path| path.exists() || compressed_path(path).is_file());
+                    let export_button = egui::Button::new("📄 Export PDF...")
+                        .min_size(egui::vec2(110.0, 35.0));
+                    if ui.add_enabled(can_export, export_button)
+                        .on_hover_text("Render the generated document to a PDF, e.g. to attach to a design review ticket")
+                        .clicked()
+                    {
+                        self.export_pdf_dialog();
+                    }
                 });
+
+                if self.settings.snapshot_mode_enabled {
+                    ui.add_space(5.0);
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(has_selection, egui::Button::new("📸 Refresh Snapshot")).clicked() {
+                            self.update_snapshot(true);
+                        }
+                        match self.snapshot_taken_at {
+                            Some(taken_at) => ui.weak(format!(
+                                "Snapshot of {} files, taken {}s ago",
+                                self.file_snapshot.len(),
+                                taken_at.elapsed().as_secs()
+                            )),
+                            None => ui.weak("No snapshot yet"),
+                        };
+                    });
+                }
                 
                 ui.add_space(5.0);
                 
@@ -681,6 +3680,651 @@ impl ContextBuilderApp {
         });
     }
 
+    /// Lists selected files matched by .gitignore before they end up in the document
+    fn render_selected_output_banner(&self, ui: &mut egui::Ui, selected_output: &Path) {
+        let display_path = self.current_directory.as_ref()
+            .and_then(|directory| selected_output.strip_prefix(directory).ok())
+            .unwrap_or(selected_output);
+        egui::Frame::none()
+            .fill(egui::Color32::from_rgb(255, 235, 235))
+            .stroke(egui::Stroke::new(1.0, egui::Color32::from_rgb(200, 40, 40)))
+            .inner_margin(egui::Margin::same(8.0))
+            .rounding(egui::Rounding::same(5.0))
+            .show(ui, |ui| {
+                ui.colored_label(
+                    egui::Color32::from_rgb(150, 0, 0),
+                    format!(
+                        "⛔ {} is selected, but the document is written there: it would embed itself and grow \
+                         with every update. Generation is blocked until it is deselected or the output path changes.",
+                        display_path.display(),
+                    ),
+                );
+            });
+    }
+
+    fn render_gitignored_banner(&self, ui: &mut egui::Ui, gitignored_files: &[PathBuf]) {
+        egui::Frame::none()
+            .fill(egui::Color32::from_rgb(255, 235, 235))
+            .stroke(egui::Stroke::new(1.0, egui::Color32::from_rgb(200, 40, 40)))
+            .inner_margin(egui::Margin::same(8.0))
+            .rounding(egui::Rounding::same(5.0))
+            .show(ui, |ui| {
+                ui.colored_label(
+                    egui::Color32::from_rgb(150, 0, 0),
+                    format!(
+                        "⚠ {} selected file{} ignored by .gitignore and will be included in the document. \
+                         These are often local secrets or build outputs:",
+                        gitignored_files.len(),
+                        if gitignored_files.len() == 1 { " is" } else { "s are" },
+                    ),
+                );
+                for path in gitignored_files {
+                    let display_path = self.current_directory.as_ref()
+                        .and_then(|directory| path.strip_prefix(directory).ok())
+                        .unwrap_or(path);
+                    ui.monospace(display_path.display().to_string());
+                }
+            });
+    }
+
+    /// Suggestions for the ignore list and selection from the manifests in the project root
+    fn detect_project_setup(&self, directory: &Path) -> Option<ProjectSetup> {
+        let project_types = detect_project_types(directory);
+        if project_types.is_empty() {
+            return None;
+        }
+        let ignore_patterns = project_types.iter()
+            .flat_map(|project_type| project_type.ignore_patterns())
+            .filter(|pattern| !self.settings.ignore_patterns.iter().any(|ignored| ignored == *pattern))
+            .map(|pattern| pattern.to_string())
+            .collect();
+        Some(ProjectSetup { project_types, ignore_patterns })
+    }
+
+    fn render_project_setup_banner(&mut self, ui: &mut egui::Ui) {
+        let Some(setup) = &self.project_setup else { return };
+        let names: Vec<&str> = setup.project_types.iter().map(|project_type| project_type.name()).collect();
+        let title = match names.as_slice() {
+            [name] => format!("{} project detected", name),
+            _ => format!("Mixed project detected ({})", names.join(", ")),
+        };
+        let source_patterns: Vec<String> = setup.project_types.iter()
+            .flat_map(|project_type| project_type.source_patterns())
+            .map(|pattern| pattern.to_string())
+            .collect();
+        let outline_extensions: Vec<&str> = setup.project_types.iter()
+            .map(|project_type| project_type.outline_extensions())
+            .collect();
+        let ignore_patterns = setup.ignore_patterns.clone();
+
+        let (mut select_sources, mut add_ignore_patterns, mut dismiss) = (false, false, false);
+        egui::Frame::none()
+            .fill(egui::Color32::from_rgb(230, 240, 255))
+            .stroke(egui::Stroke::new(1.0, egui::Color32::from_rgb(70, 120, 200)))
+            .inner_margin(egui::Margin::same(8.0))
+            .rounding(egui::Rounding::same(5.0))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.colored_label(egui::Color32::from_rgb(30, 70, 140), format!("💡 {}", title));
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        dismiss = ui.small_button("✖").on_hover_text("Don't suggest this again for the project").clicked();
+                    });
+                });
+                ui.horizontal_wrapped(|ui| {
+                    select_sources = ui.button("Select sources & manifests")
+                        .on_hover_text(source_patterns.join(", "))
+                        .clicked();
+                    if !ignore_patterns.is_empty() {
+                        add_ignore_patterns = ui.button(format!("Ignore {}", ignore_patterns.join(", ")))
+                            .on_hover_text("Adds them to the ignore patterns and rescans")
+                            .clicked();
+                    }
+                });
+                ui.weak(format!(
+                    "Signatures-only rendering is built in for {} files: right-click a file and check \"Signatures only\".",
+                    outline_extensions.join(", ")
+                ));
+            });
+
+        if select_sources {
+            self.select_patterns(&source_patterns);
+        }
+        if add_ignore_patterns {
+            self.settings.ignore_patterns.extend(ignore_patterns);
+            self.ignore_patterns_text = self.settings.ignore_patterns.join("\n");
+            if let Some(setup) = &mut self.project_setup {
+                setup.ignore_patterns.clear();
+            }
+            self.rescan_directory();
+        }
+        if dismiss {
+            self.project_setup = None;
+            self.setup_banner_dismissed = true;
+            self.save_project_settings();
+        }
+    }
+
+    /// Asks before the project's `.contextbuilder.toml` is used, listing what it would do
+    fn render_workspace_trust_banner(&mut self, ui: &mut egui::Ui) {
+        let Some(config) = &self.workspace_config else { return };
+        let effects = config.describe();
+        let (mut trust, mut distrust) = (false, false);
+        egui::Frame::none()
+            .fill(egui::Color32::from_rgb(255, 245, 220))
+            .stroke(egui::Stroke::new(1.0, egui::Color32::from_rgb(200, 150, 40)))
+            .inner_margin(egui::Margin::same(8.0))
+            .rounding(egui::Rounding::same(5.0))
+            .show(ui, |ui| {
+                ui.colored_label(egui::Color32::from_rgb(130, 80, 0), "🛡 This project has a .contextbuilder.toml. Do you trust its authors? It would:");
+                for effect in &effects {
+                    ui.label(format!("• {}", effect));
+                }
+                ui.horizontal(|ui| {
+                    trust = ui.button("Trust this directory").clicked();
+                    distrust = ui.button("Don't trust")
+                        .on_hover_text("The file is ignored; the app's own settings apply")
+                        .clicked();
+                });
+            });
+
+        if trust || distrust {
+            self.workspace_trusted = Some(trust);
+            self.save_project_settings();
+            if trust {
+                self.set_status_message("Using the project's .contextbuilder.toml".to_string());
+            }
+        }
+    }
+
+    fn render_growth_alert_banner(&mut self, ui: &mut egui::Ui) {
+        let baseline_bytes = self.monitoring_baseline_bytes.unwrap_or_default();
+        let growth_percent = (self.document_size_bytes.saturating_sub(baseline_bytes) * 100)
+            .checked_div(baseline_bytes)
+            .unwrap_or_default();
+        let (mut accept, mut dismiss) = (false, false);
+        egui::Frame::none()
+            .fill(egui::Color32::from_rgb(255, 235, 235))
+            .stroke(egui::Stroke::new(1.0, egui::Color32::from_rgb(200, 60, 60)))
+            .inner_margin(egui::Margin::same(8.0))
+            .rounding(egui::Rounding::same(5.0))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.colored_label(
+                        egui::Color32::from_rgb(150, 0, 0),
+                        format!("⚠ The document grew by {}% since monitoring started", growth_percent),
+                    );
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        dismiss = ui.small_button("✖").on_hover_text("Don't alert again until monitoring restarts").clicked();
+                    });
+                });
+                ui.label(format!(
+                    "Baseline: {} (~{} tokens). Now: {} (~{} tokens).",
+                    format_byte_size(baseline_bytes),
+                    format_token_count(baseline_bytes as usize / chars_per_token()),
+                    format_byte_size(self.document_size_bytes),
+                    format_token_count(self.document_size_bytes as usize / chars_per_token()),
+                ));
+                ui.label("Check that no generated files or build artifacts were added to the selection.");
+                accept = ui.button("Accept the new size")
+                    .on_hover_text("Alerts again when the document grows as much from here")
+                    .clicked();
+            });
+        if accept {
+            self.monitoring_baseline_bytes = Some(self.document_size_bytes);
+            self.growth_alert_shown = false;
+        }
+        if dismiss {
+            self.growth_alert_shown = false;
+            self.growth_alert_dismissed = true;
+        }
+    }
+
+    fn render_throttling_banner(&self, ui: &mut egui::Ui) {
+        egui::Frame::none()
+            .fill(egui::Color32::from_rgb(255, 248, 225))
+            .stroke(egui::Stroke::new(1.0, egui::Color32::from_rgb(200, 140, 0)))
+            .inner_margin(egui::Margin::same(8.0))
+            .rounding(egui::Rounding::same(5.0))
+            .show(ui, |ui| {
+                ui.colored_label(
+                    egui::Color32::from_rgb(140, 90, 0),
+                    format!(
+                        "The document is {} (limit {}), so monitoring only updates the sections of edited files. \
+                         Selection changes are applied when you click Generate Document.",
+                        format_byte_size(self.document_size_bytes),
+                        format_byte_size(self.settings.auto_regeneration_limit_bytes as u64),
+                    ),
+                );
+                if self.selection_regeneration_pending {
+                    ui.weak("The selection has changed since the last generation.");
+                }
+            });
+    }
+
+    fn render_outline_panel(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.heading("Document Outline");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.small_button("🔄").on_hover_text("Re-read the output document").clicked() {
+                    self.refresh_document_outline();
+                }
+            });
+        });
+        ui.separator();
+
+        if self.document_outline.is_empty() {
+            ui.weak("Generate a document to see its outline");
+            return;
+        }
+
+        let total_bytes: usize = self.document_outline.iter().map(|entry| entry.size_bytes).sum();
+        ui.weak(format!(
+            "{} total, {} tokens",
+            format_byte_size(total_bytes as u64),
+            format_token_count(total_bytes / chars_per_token())
+        ));
+        ui.add_space(5.0);
+
+        let mut path_to_reveal = None;
+        egui::ScrollArea::vertical()
+            .id_source("document_outline_scroll_area")
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                for entry in &self.document_outline {
+                    ui.horizontal(|ui| {
+                        ui.add_space(entry.level.saturating_sub(1) as f32 * 12.0);
+                        let size_text = format_byte_size(entry.size_bytes as u64);
+                        if let Some(file_path) = &entry.file_path {
+                            if ui.link(&entry.title).on_hover_text("Reveal in file tree").clicked() {
+                                path_to_reveal = Some(file_path.clone());
+                            }
+                        } else {
+                            ui.strong(&entry.title);
+                        }
+                        ui.weak(size_text);
+                    });
+                }
+            });
+
+        if let Some(path) = path_to_reveal {
+            self.ui_tree_handler.reveal(path);
+        }
+    }
+
+    fn render_history_panel(&mut self, ui: &mut egui::Ui) {
+        let mut clear = false;
+        ui.horizontal(|ui| {
+            ui.heading("Generation History");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                clear = !self.generation_history.entries.is_empty()
+                    && ui.small_button("🗑").on_hover_text("Clear the history and its document copies").clicked();
+            });
+        });
+        ui.separator();
+
+        if self.generation_history.entries.is_empty() {
+            ui.weak("Generated documents are listed here");
+            return;
+        }
+        ui.weak("Pick A and B to see which sections changed between two generations");
+        ui.add_space(5.0);
+
+        let mut section_to_diff = None;
+        egui::ScrollArea::vertical()
+            .id_source("generation_history_scroll_area")
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                for entry in self.generation_history.entries.iter().rev() {
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut self.history_comparison.0, Some(entry.id), "A");
+                        ui.radio_value(&mut self.history_comparison.1, Some(entry.id), "B");
+                        ui.label(entry.time_label()).on_hover_text(entry.output_path.display().to_string());
+                        ui.weak(format!("{} files, {}", entry.file_count, format_byte_size(entry.size_bytes)));
+                        if entry.has_copy {
+                            ui.weak("📄").on_hover_text("A copy of the document was kept");
+                        }
+                    });
+                }
+
+                let find = |id: Option<i64>| self.generation_history.entries.iter().find(|entry| Some(entry.id) == id);
+                let (Some(a), Some(b)) = (find(self.history_comparison.0), find(self.history_comparison.1)) else {
+                    return;
+                };
+                ui.separator();
+                let diff = diff_generations(a, b);
+                ui.strong(format!(
+                    "{} changed, {} added, {} removed, {} unchanged",
+                    diff.changed.len(), diff.added.len(), diff.removed.len(), diff.unchanged_count
+                ));
+                let diffable = a.has_copy && b.has_copy;
+                for (marker, titles) in [("~", &diff.changed), ("+", &diff.added), ("-", &diff.removed)] {
+                    for title in titles {
+                        ui.horizontal(|ui| {
+                            ui.monospace(marker);
+                            if marker == "~" && diffable {
+                                if ui.link(title).on_hover_text("Show the changes of this section").clicked() {
+                                    section_to_diff = Some((a.clone(), b.clone(), title.clone()));
+                                }
+                            } else {
+                                ui.label(title);
+                            }
+                        });
+                    }
+                }
+                if !diffable && !diff.changed.is_empty() {
+                    ui.weak("Enable \"Keep copies for section diffs\" in Settings → Output to see what changed inside sections");
+                }
+
+                if let Some((_, _, title, text)) = self.history_section_diff.as_ref()
+                    .filter(|(id_a, id_b, _, _)| (*id_a, *id_b) == (a.id, b.id))
+                {
+                    ui.separator();
+                    ui.strong(title);
+                    ui.add(egui::Label::new(RichText::new(text).monospace()).wrap(false));
+                }
+            });
+
+        if let (Some((a, b, title)), Some(directory)) = (section_to_diff, &self.current_directory) {
+            match section_diff(directory, &a, &b, &title) {
+                Ok(text) => self.history_section_diff = Some((a.id, b.id, title, text)),
+                Err(e) => self.set_error("Failed to compare the sections", &e, None),
+            }
+        }
+        if clear {
+            if let Some(directory) = &self.current_directory {
+                self.generation_history.clear(directory);
+            }
+            self.history_comparison = (None, None);
+            self.history_section_diff = None;
+        }
+    }
+
+    /// Starts counting the tokens of the selected files in the background unless the current
+    /// count already covers this selection. The previous count is cancelled.
+    fn refresh_token_count(&mut self) {
+        if self.token_count.as_ref().is_some_and(|count| count.selection == self.ui_tree_handler.selected_files) {
+            return;
+        }
+        self.cancel_token_count();
+        let Some(directory) = self.current_directory.clone() else { return };
+        let selection = self.ui_tree_handler.selected_files.clone();
+        if selection.is_empty() {
+            return;
+        }
+
+        let mut files: Vec<PathBuf> = selection.iter().cloned().collect();
+        files.sort();
+        self.token_count_runs += 1;
+        let id = self.token_count_runs;
+        let cancel_flag = Arc::new(AtomicBool::new(false));
+        self.token_count = Some(TokenCount {
+            id,
+            selection,
+            counted_files: 0,
+            tokens: 0,
+            done: false,
+            cancel_flag: cancel_flag.clone(),
+        });
+        // Estimates must not send requests; files without a cached summary count in full
+        let generator = self.configured_generator(directory, files.clone())
+            .with_file_summaries(
+                self.ui_tree_handler.get_summary_files(),
+                FileSummarizer::new(self.settings.summary_endpoint_config()).with_cached_only(true),
+            );
+        let sender = self.event_sender.clone();
+
+        thread::spawn(move || {
+            let mut tokens = 0;
+            let mut last_report = Instant::now();
+            for (index, path) in files.iter().enumerate() {
+                if cancel_flag.load(Ordering::Relaxed) {
+                    return;
+                }
+                tokens += estimate_tokens(&generator.read_file_content(path));
+                let done = index + 1 == files.len();
+                if done || last_report.elapsed() >= TOKEN_COUNT_PROGRESS_INTERVAL {
+                    last_report = Instant::now();
+                    let progress = AppEvent::TokenCountProgress { id, counted_files: index + 1, tokens, done };
+                    if sender.send(progress).is_err() {
+                        return; // The app is closing
+                    }
+                }
+            }
+        });
+    }
+
+    fn cancel_token_count(&mut self) {
+        if let Some(count) = self.token_count.take() {
+            count.cancel_flag.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Reads the file in the background the way the generator would, for the preview panel
+    fn load_file_preview(&mut self, path: PathBuf) {
+        let Some(directory) = self.current_directory.clone() else { return };
+        let generator = self.configured_generator(directory, vec![path.clone()]);
+        let sender = self.event_sender.clone();
+        self.file_preview = Some((path.clone(), None));
+
+        thread::spawn(move || {
+            let mut content = generator.read_file_content(&path);
+            if content.len() > FILE_PREVIEW_MAX_BYTES {
+                let mut cut = FILE_PREVIEW_MAX_BYTES;
+                while !content.is_char_boundary(cut) {
+                    cut -= 1;
+                }
+                let omitted = content.len() - cut;
+                content.truncate(cut);
+                content.push_str(&format!("\n\n[... preview ends here, {} more ...]", format_byte_size(omitted as u64)));
+            }
+            if let Err(e) = sender.send(AppEvent::FilePreviewLoaded { path, content }) {
+                error!("Failed to send file preview: {}", e);
+            }
+        });
+    }
+
+    fn render_preview_panel(&mut self, ui: &mut egui::Ui) {
+        let Some((path, content)) = &self.file_preview else { return };
+        let mut close = false;
+        ui.horizontal(|ui| {
+            ui.heading("Preview");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                close = ui.small_button("✖").on_hover_text("Close the preview").clicked();
+            });
+        });
+        let directory = self.current_directory.clone().unwrap_or_default();
+        ui.weak(path.strip_prefix(&directory).unwrap_or(path).display().to_string());
+        ui.separator();
+
+        match content {
+            Some(content) => {
+                egui::ScrollArea::both()
+                    .id_source("file_preview_scroll_area")
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        ui.add(egui::Label::new(RichText::new(content).monospace()).wrap(false));
+                    });
+            }
+            None => {
+                ui.spinner();
+            }
+        }
+
+        if close {
+            self.file_preview = None;
+        }
+    }
+
+    fn suggest_minimal_context(&mut self, focal_file: PathBuf) {
+        let Some(directory) = &self.current_directory else { return };
+
+        // Only files visible in the tree can be selected; the rest are ignored or filtered out
+        let files = minimal_context(&focal_file, directory)
+            .into_iter()
+            .filter(|(path, _)| self.ui_tree_handler.contains_path(path))
+            .map(|(path, reason)| (path, reason, true))
+            .collect();
+        self.context_suggestion = Some(ContextSuggestion { focal_file, files });
+    }
+
+    fn render_context_suggestion(&mut self, ctx: &Context) {
+        let Some(suggestion) = &mut self.context_suggestion else { return };
+        let directory = self.current_directory.clone().unwrap_or_default();
+
+        let mut new_selection = None;
+        let mut keep_open = true;
+        egui::Window::new("Minimal Context")
+            .collapsible(false)
+            .resizable(true)
+            .open(&mut keep_open)
+            .show(ctx, |ui| {
+                let focal_name = suggestion.focal_file.strip_prefix(&directory).unwrap_or(&suggestion.focal_file);
+                ui.label(format!("Suggested selection for {}:", focal_name.display()));
+                ui.add_space(5.0);
+
+                egui::ScrollArea::vertical()
+                    .id_source("context_suggestion_scroll_area")
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for (path, reason, accepted) in &mut suggestion.files {
+                            ui.horizontal(|ui| {
+                                let display_path = path.strip_prefix(&directory).unwrap_or(path);
+                                ui.checkbox(accepted, display_path.display().to_string());
+                                ui.weak(format!("({})", reason.name()));
+                            });
+                        }
+                    });
+
+                ui.add_space(8.0);
+                let accepted_files: Vec<PathBuf> = suggestion.files.iter()
+                    .filter(|(_, _, accepted)| *accepted)
+                    .map(|(path, _, _)| path.clone())
+                    .collect();
+                ui.horizontal(|ui| {
+                    if ui.button("Replace selection").clicked() {
+                        new_selection = Some(accepted_files.iter().cloned().collect());
+                    }
+                    if ui.button("Add to selection").clicked() {
+                        let mut selection = self.ui_tree_handler.selected_files.clone();
+                        selection.extend(accepted_files.iter().cloned());
+                        new_selection = Some(selection);
+                    }
+                });
+            });
+
+        if new_selection.is_some() && !self.ensure_selection_unlocked() {
+            return; // Stays open until the selection is unlocked
+        }
+        if let Some(selection) = new_selection {
+            self.ui_tree_handler.set_selected_files(selection);
+            self.context_suggestion = None;
+            self.set_status_message("Applied minimal context selection".to_string());
+            self.handle_selection_changed();
+        } else if !keep_open {
+            self.context_suggestion = None;
+        }
+    }
+
+    fn render_log_selection(&mut self, ctx: &Context) {
+        let Some(log_selection) = &mut self.log_selection else { return };
+        let Some(directory) = self.current_directory.clone() else {
+            self.log_selection = None;
+            return;
+        };
+
+        let mut new_selection = None;
+        let mut clear_annotations = false;
+        let mut keep_open = true;
+        egui::Window::new("Select From Log")
+            .collapsible(false)
+            .resizable(true)
+            .open(&mut keep_open)
+            .show(ctx, |ui| {
+                ui.label("Paste compiler or test output; the project files it mentions can then be selected.");
+                egui::ScrollArea::vertical()
+                    .id_source("log_selection_text_scroll_area")
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        ui.add(egui::TextEdit::multiline(&mut log_selection.log_text)
+                            .hint_text("error[E0308]: mismatched types\n  --> src/main.rs:10:5")
+                            .code_editor()
+                            .desired_rows(8)
+                            .desired_width(f32::INFINITY));
+                    });
+                if ui.button("Find files").clicked() {
+                    // Only files visible in the tree can be selected; the rest are ignored or filtered out
+                    log_selection.references = find_log_references(&log_selection.log_text, &directory)
+                        .into_iter()
+                        .filter(|reference| self.ui_tree_handler.contains_path(&reference.path))
+                        .map(|reference| (reference, true))
+                        .collect();
+                }
+                if log_selection.references.is_empty() {
+                    return;
+                }
+
+                ui.add_space(5.0);
+                egui::ScrollArea::vertical()
+                    .id_source("log_selection_files_scroll_area")
+                    .max_height(250.0)
+                    .show(ui, |ui| {
+                        for (reference, accepted) in &mut log_selection.references {
+                            ui.horizontal(|ui| {
+                                let display_path = reference.path.strip_prefix(&directory).unwrap_or(&reference.path);
+                                ui.checkbox(accepted, display_path.display().to_string())
+                                    .on_hover_text(reference.excerpts.join("\n\n"));
+                                if !reference.lines.is_empty() {
+                                    let lines: Vec<String> = reference.lines.iter().map(usize::to_string).collect();
+                                    ui.weak(format!("(line {})", lines.join(", ")));
+                                }
+                            });
+                        }
+                    });
+
+                ui.add_space(8.0);
+                ui.checkbox(&mut log_selection.annotate, "Show the log excerpts below each file's content");
+                let accepted: Vec<LogReference> = log_selection.references.iter()
+                    .filter(|(_, accepted)| *accepted)
+                    .map(|(reference, _)| reference.clone())
+                    .collect();
+                ui.horizontal(|ui| {
+                    if ui.button("Replace selection").clicked() {
+                        new_selection = Some((HashSet::new(), accepted.clone()));
+                    }
+                    if ui.button("Add to selection").clicked() {
+                        new_selection = Some((self.ui_tree_handler.selected_files.clone(), accepted.clone()));
+                    }
+                    if !self.log_excerpts.is_empty() && ui.button("Clear excerpts").clicked() {
+                        clear_annotations = true;
+                    }
+                });
+            });
+
+        let annotate = log_selection.annotate;
+        if new_selection.is_some() && !self.ensure_selection_unlocked() {
+            return; // Stays open until the selection is unlocked
+        }
+        if let Some((mut selection, accepted)) = new_selection {
+            let file_count = accepted.len();
+            selection.extend(accepted.iter().map(|reference| reference.path.clone()));
+            self.log_excerpts = if annotate {
+                accepted.into_iter().map(|reference| (reference.path, reference.excerpts)).collect()
+            } else {
+                HashMap::new()
+            };
+            self.ui_tree_handler.set_selected_files(selection);
+            self.log_selection = None;
+            self.set_status_message(format!("Selected {} files mentioned in the log", file_count));
+            self.handle_selection_changed();
+        } else if clear_annotations {
+            self.log_excerpts.clear();
+            self.set_status_message("Log excerpts removed from the document".to_string());
+            self.handle_selection_changed();
+        } else if !keep_open {
+            self.log_selection = None;
+        }
+    }
+
     fn render_status_messages(&mut self, ui: &mut egui::Ui) {
         // Clean up expired status messages
         if let Some((_, timestamp)) = &self.status_message {
@@ -706,8 +4350,9 @@ impl ContextBuilderApp {
                         ui.colored_label(egui::Color32::from_rgb(0, 120, 0), message);
                     });
                 });
-        } else if let Some(error_message) = &self.error_message {
-            let error_message = error_message.clone(); // Clone to avoid borrowing issues
+        } else if let Some(error) = &self.error {
+            let mut retry = None;
+            let mut dismiss = false;
             
             ui.add_space(10.0);
             
@@ -719,24 +4364,132 @@ impl ContextBuilderApp {
                 .show(ui, |ui| {
                     ui.horizontal(|ui| {
                         ui.label("❌");
-                        ui.colored_label(egui::Color32::from_rgb(150, 0, 0), &error_message);
+                        ui.colored_label(egui::Color32::from_rgb(150, 0, 0), &error.message);
                         
                         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                             if ui.small_button("✖").clicked() {
-                                self.clear_messages();
+                                dismiss = true;
+                            }
+                            if let Some(action) = &error.retry {
+                                if ui.small_button(action.label()).clicked() {
+                                    retry = Some(action.clone());
+                                }
+                            }
+                            if error.has_details() {
+                                let toggle = if self.error_expanded { "Hide details" } else { "Details" };
+                                if ui.small_button(toggle).clicked() {
+                                    self.error_expanded = !self.error_expanded;
+                                }
                             }
                         });
                     });
+
+                    if self.error_expanded {
+                        ui.separator();
+                        let dark_red = egui::Color32::from_rgb(150, 0, 0);
+                        for (depth, cause) in error.causes.iter().enumerate() {
+                            let prefix = if depth == 0 { "" } else { "caused by: " };
+                            ui.label(RichText::new(format!("{}{}", prefix, cause)).color(dark_red).monospace().small());
+                        }
+                        if let Some(path) = &error.path {
+                            ui.label(RichText::new(format!("Path: {}", path.display())).color(dark_red).small());
+                        }
+                        if let Some(suggestion) = error.suggestion {
+                            ui.label(RichText::new(format!("💡 {}", suggestion)).color(dark_red));
+                        }
+                        if ui.small_button("📋 Copy details").clicked() {
+                            let text = error.to_text();
+                            ui.output_mut(|output| output.copied_text = text);
+                        }
+                    }
                 });
+
+            if dismiss {
+                self.clear_messages();
+            } else if let Some(action) = retry {
+                self.retry(action);
+            }
+        }
+    }
+
+    /// Keeps the visuals in line with the theme setting; `System` follows the OS where eframe
+    /// can tell its theme
+    fn apply_theme(&self, ctx: &Context, frame: &eframe::Frame) {
+        let visuals = match self.settings.theme {
+            ThemePreference::System => frame.info().system_theme.unwrap_or(eframe::Theme::Dark).egui_visuals(),
+            ThemePreference::Dark => egui::Visuals::dark(),
+            ThemePreference::Light => egui::Visuals::light(),
+        };
+        if ctx.style().visuals.dark_mode != visuals.dark_mode {
+            ctx.set_visuals(visuals);
         }
     }
 }
 
 impl eframe::App for ContextBuilderApp {
-    fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
+        self.apply_theme(ctx, frame);
         // Process background events
         self.process_events();
+        self.handle_shortcuts(ctx);
+        self.sync_excluded_output_path();
+        self.refresh_token_count();
+        if self.token_count.as_ref().is_some_and(|count| !count.done) {
+            ctx.request_repaint_after(TOKEN_COUNT_PROGRESS_INTERVAL);
+        }
+        self.restart_watcher_if_due();
+        self.run_scheduled_generation_if_due();
+        let settings_before = self.settings.clone();
         
+        self.render_context_suggestion(ctx);
+        self.render_log_selection(ctx);
+        self.render_settings_window(ctx);
+        self.render_update_changelog(ctx);
+        self.render_insights_window(ctx);
+        self.render_llm_window(ctx);
+        self.render_overwrite_confirmation(ctx);
+        self.render_unlock_prompt(ctx);
+
+        if self.file_preview.is_some() {
+            egui::SidePanel::right("file_preview_panel")
+                .resizable(true)
+                .default_width(420.0)
+                .show(ctx, |ui| {
+                    self.render_preview_panel(ui);
+                });
+        }
+
+        // Optional outline of the output document next to the main content
+        if self.show_outline_panel {
+            egui::SidePanel::right("document_outline_panel")
+                .resizable(true)
+                .default_width(280.0)
+                .show(ctx, |ui| {
+                    self.render_outline_panel(ui);
+                });
+        }
+
+        if self.show_history_panel {
+            egui::SidePanel::right("generation_history_panel")
+                .resizable(true)
+                .default_width(320.0)
+                .show(ctx, |ui| {
+                    self.render_history_panel(ui);
+                });
+        }
+
+        let columns = self.settings.panel_layout == PanelLayout::Columns;
+        if columns {
+            egui::SidePanel::left("file_tree_panel")
+                .resizable(true)
+                .default_width(460.0)
+                .width_range(280.0..=1600.0)
+                .show(ctx, |ui| {
+                    self.render_directory_selection(ui);
+                    self.render_file_tree(ui);
+                });
+        }
+
         // Main UI with better layout
         egui::CentralPanel::default().show(ctx, |ui| {
             // Title bar using RichText for emojis
@@ -747,16 +4500,28 @@ impl eframe::App for ContextBuilderApp {
                 ui.weak("Generate markdown documentation from your project files");
                 ui.add_space(10.0);
             });
+            ui.horizontal(|ui| {
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.toggle_value(&mut self.show_outline_panel, "📑 Outline");
+                    ui.toggle_value(&mut self.show_history_panel, "🕘 History");
+                    ui.toggle_value(&mut self.show_settings_window, "⚙ Settings");
+                    ui.toggle_value(&mut self.show_insights_window, "📊 Insights");
+                    ui.toggle_value(&mut self.show_llm_window, "💬 Ask LLM");
+                });
+            });
             
             ui.separator();
             ui.add_space(8.0);
+            self.render_update_banner(ui);
             
             // Main content with proper spacing
             egui::ScrollArea::vertical()
                 .auto_shrink([false, true])
                 .show(ui, |ui| {
-                    self.render_directory_selection(ui);
-                    self.render_file_tree(ui);
+                    if !columns {
+                        self.render_directory_selection(ui);
+                        self.render_file_tree(ui);
+                    }
                     self.render_ignore_settings(ui);
                     self.render_output_settings(ui); 
                     self.render_control_buttons(ui);
@@ -766,9 +4531,30 @@ impl eframe::App for ContextBuilderApp {
                 });
         });
         
+        if self.settings.token_estimator != settings_before.token_estimator {
+            set_chars_per_token(self.settings.token_estimator.chars_per_token());
+            self.cancel_token_count(); // Recounted with the new estimate
+        }
+        if self.settings != settings_before {
+            if let Err(e) = self.settings.save() {
+                warn!("Failed to save settings: {}", e);
+            }
+        }
+
         // Request repaint for animations (spinner, etc.)
-        if self.is_loading_directory || self.is_generating_document {
+        let preview_loading = self.file_preview.as_ref().is_some_and(|(_, content)| content.is_none());
+        if self.is_loading_directory || self.is_validating_cached_scan || self.is_generating_document || self.is_rescanning_structure || preview_loading {
             ctx.request_repaint();
         }
+        if self.llm_chat.is_some() {
+            ctx.request_repaint_after(LLM_STREAM_REPAINT_INTERVAL);
+        }
+        if self.watcher_restart_at.is_some() {
+            ctx.request_repaint_after(Duration::from_secs(1)); // Restart countdown
+        }
+        if let Some(due) = self.next_scheduled_generation {
+            let until_due = (due - Local::now()).to_std().unwrap_or_default();
+            ctx.request_repaint_after(until_due.min(Duration::from_secs(60)));
+        }
     }
 }
\ No newline at end of file
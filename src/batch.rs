@@ -0,0 +1,246 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use ignore::overrides::OverrideBuilder;
+use log::{info, warn};
+use serde::Deserialize;
+
+use crate::budget::{TokenBudget, set_chars_per_token};
+use crate::compression::write_compressed_copies;
+use crate::constants::{OutputFormat, DEFAULT_OUTPUT_FILENAME_BASE};
+use crate::document_generator::{existing_chunk_paths, is_output_document_path, is_replaceable_document, DocumentGenerator};
+use crate::document_index::{document_index_path, write_document_index};
+use crate::document_worker::generate_documents;
+use crate::error::{AppError, Result};
+use crate::file_handler::FileHandler;
+use crate::progress::{ProgressEvent, ProgressReporter};
+use crate::project_settings::ProjectSettings;
+use crate::settings::AppSettings;
+use crate::utils::format_byte_size;
+
+/// A `projects.toml` listing the projects to generate, e.g.
+///
+/// ```toml
+/// [[project]]
+/// directory = "services/auth"
+/// output = "context/auth.md"
+/// include = ["src/**/*.rs", "Cargo.toml"]
+/// max_tokens = 64000
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchFile {
+    #[serde(rename = "project")]
+    projects: Vec<BatchProject>,
+}
+
+/// Relative paths are resolved against the directory of the batch file; everything not set
+/// here comes from the saved app settings
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchProject {
+    directory: PathBuf,
+    output: Option<PathBuf>, // Defaults to `project_structure.<ext>` in the project directory
+    format: Option<OutputFormat>,
+    #[serde(default)]
+    include: Vec<String>, // Globs relative to the project directory; every scanned file when empty
+    max_tokens: Option<usize>,
+}
+
+/// How generating one project went, for the summary table
+pub struct BatchOutcome {
+    pub directory: PathBuf,
+    pub output_path: Option<PathBuf>,
+    pub file_count: usize,
+    pub size_bytes: u64,
+    pub elapsed: Duration,
+    pub error: Option<AppError>,
+}
+
+/// Generates the document of every project in the batch file, one after the other. A failing
+/// project is reported in its outcome and doesn't stop the others.
+pub fn run_batch(batch_path: &Path, progress: ProgressReporter) -> Result<Vec<BatchOutcome>> {
+    let text = fs::read_to_string(batch_path)
+        .map_err(|e| AppError::new_io_error(e, Some(batch_path.to_path_buf()), "Failed to read batch file".to_string()))?;
+    let batch: BatchFile = toml::from_str(&text).map_err(|e| AppError::SettingsError {
+        path: batch_path.to_path_buf(),
+        details: e.to_string(),
+    })?;
+    let base_dir = batch_path.parent().unwrap_or(Path::new("."));
+    let settings = AppSettings::load();
+    set_chars_per_token(settings.token_estimator.chars_per_token());
+
+    Ok(batch.projects.iter()
+        .map(|project| {
+            let started = Instant::now();
+            let directory = base_dir.join(&project.directory);
+            let mut outcome = BatchOutcome {
+                directory: project.directory.clone(),
+                output_path: None,
+                file_count: 0,
+                size_bytes: 0,
+                elapsed: Duration::ZERO,
+                error: None,
+            };
+            if let Err(e) = generate_project(project, &directory, base_dir, &settings, progress, &mut outcome) {
+                warn!("Batch generation of {:?} failed: {}", directory, e);
+                progress.emit(ProgressEvent::Error { directory: Some(&directory), message: e.to_string() });
+                outcome.error = Some(e);
+            }
+            outcome.elapsed = started.elapsed();
+            outcome
+        })
+        .collect())
+}
+
+fn generate_project(
+    project: &BatchProject,
+    directory: &Path,
+    base_dir: &Path,
+    settings: &AppSettings,
+    progress: ProgressReporter,
+    outcome: &mut BatchOutcome,
+) -> Result<()> {
+    let directory = directory.canonicalize()
+        .map_err(|e| AppError::new_io_error(e, Some(directory.to_path_buf()), "Failed to open project directory".to_string()))?;
+    let format = project.format.unwrap_or(settings.output_format);
+    let output_path = match &project.output {
+        Some(output) => base_dir.join(output),
+        None => directory.join(format!("{}.{}", DEFAULT_OUTPUT_FILENAME_BASE, format.extension())),
+    };
+    outcome.output_path = Some(output_path.clone());
+    if !is_replaceable_document(&output_path) {
+        return Err(AppError::OperationFailed(format!(
+            "{} exists and was not written by context_builder", output_path.display()
+        )));
+    }
+
+    let project_settings = ProjectSettings::load(&directory);
+    let mut ignore_patterns = settings.ignore_patterns.clone();
+    ignore_patterns.extend(project_settings.extension_ignore_patterns());
+    let handler = FileHandler::new(directory.clone())?
+        .with_generated_files_excluded(settings.exclude_generated_files)
+        .with_output_path(Some(output_path.clone()));
+    let root_node = progress.scan(&directory, handler, ignore_patterns)?;
+    let selected_files = matching_files(&directory, root_node.file_paths(), &project.include)?;
+    if selected_files.is_empty() {
+        return Err(AppError::OperationFailed("No files match the include patterns".to_string()));
+    }
+    if let Some(selected_output) = selected_files.iter().find(|path| is_output_document_path(path, &output_path)) {
+        return Err(AppError::OperationFailed(format!(
+            "{} is both included and the output; exclude it from the include patterns", selected_output.display()
+        )));
+    }
+    outcome.file_count = selected_files.len();
+    progress.emit(ProgressEvent::GenerationStarted { directory: &directory, files: outcome.file_count });
+
+    let max_tokens = project.max_tokens.or(settings.token_budget_enabled.then_some(settings.max_token_budget));
+    let document_index = settings.document_index_enabled.then(|| document_index_path(&output_path));
+    let generator = settings_generator(directory.clone(), selected_files, settings, &project_settings, max_tokens)
+        .with_document_index(document_index);
+
+    if let Some(output_dir) = output_path.parent() {
+        fs::create_dir_all(output_dir)
+            .map_err(|e| AppError::new_io_error(e, Some(output_dir.to_path_buf()), "Failed to create output directory".to_string()))?;
+    }
+    let chunk_token_limit = settings.split_output_enabled.then_some(settings.max_tokens_per_chunk);
+    generate_documents(&generator, &root_node, &[(output_path.clone(), format)], chunk_token_limit, settings.custom_template_path.as_deref())?;
+    let document_paths = match chunk_token_limit {
+        Some(_) => existing_chunk_paths(&output_path),
+        None => vec![output_path.clone()],
+    };
+    write_document_index(&generator, &document_paths, format)?;
+    if settings.compressed_copy_enabled {
+        write_compressed_copies(&document_paths)?;
+    }
+
+    outcome.size_bytes = document_paths.iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    progress.generation_done(&directory, &output_path, &document_paths);
+    info!("Batch generated {:?} ({} files)", output_path, outcome.file_count);
+    Ok(())
+}
+
+/// Generator configured by the saved settings and the project's settings, for generating
+/// without the window
+pub fn settings_generator(
+    directory: PathBuf,
+    selected_files: Vec<PathBuf>,
+    settings: &AppSettings,
+    project_settings: &ProjectSettings,
+    max_tokens: Option<usize>,
+) -> DocumentGenerator {
+    DocumentGenerator::new(directory, selected_files)
+        .with_token_budget(max_tokens.map(|max_tokens| TokenBudget { max_tokens, priorities: Default::default() }))
+        .with_file_size_limits(settings.file_size_limits)
+        .with_minified_extensions(settings.minified_extension_list())
+        .with_binary_files_excluded(settings.exclude_binary_files)
+        .with_notebook_markdown_cells(settings.notebook_markdown_cells)
+        .with_custom_header(&project_settings.custom_header)
+        .with_scratch_text(&project_settings.scratch_text)
+        .with_anchor_links(settings.anchor_links_enabled)
+        .with_section_footers(settings.section_footers_enabled)
+        .with_metadata_header(settings.metadata_header_enabled)
+        .with_language_summary(settings.language_summary_enabled)
+        .with_fence_languages(settings.fence_language_list())
+        .with_structure_section(settings.structure_section_enabled)
+        .with_full_tree_structure(settings.full_tree_structure_enabled)
+        .with_structure_limits(settings.structure_max_depth, settings.structure_max_entries)
+        .with_structure_annotations(settings.structure_annotations_enabled)
+        .with_files_section(settings.files_section_enabled)
+        .with_dependency_sources(settings.dependency_sources_enabled)
+}
+
+/// The files matching one of the globs, or all of them when there are none
+fn matching_files(directory: &Path, files: Vec<PathBuf>, patterns: &[String]) -> Result<Vec<PathBuf>> {
+    if patterns.is_empty() {
+        return Ok(files);
+    }
+    let mut builder = OverrideBuilder::new(directory);
+    for pattern in patterns {
+        builder.add(pattern)?;
+    }
+    let overrides = builder.build()?;
+    Ok(files.into_iter()
+        .filter(|path| overrides.matched(path, false).is_whitelist())
+        .collect())
+}
+
+/// Aligned table of the outcomes, one project per row
+pub fn format_summary(outcomes: &[BatchOutcome]) -> String {
+    let rows: Vec<[String; 5]> = outcomes.iter()
+        .map(|outcome| [
+            outcome.directory.display().to_string(),
+            outcome.file_count.to_string(),
+            format_byte_size(outcome.size_bytes),
+            format!("{:.1}s", outcome.elapsed.as_secs_f64()),
+            match (&outcome.error, &outcome.output_path) {
+                (Some(e), _) => format!("FAILED: {}", e),
+                (None, Some(output_path)) => format!("ok -> {}", output_path.display()),
+                (None, None) => "ok".to_string(),
+            },
+        ])
+        .collect();
+
+    let header = ["Project", "Files", "Size", "Time", "Result"].map(str::to_string);
+    let mut widths = header.clone().map(|cell| cell.len());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut table = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells: Vec<String> = row.iter().zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    let failed = outcomes.iter().filter(|outcome| outcome.error.is_some()).count();
+    table.push_str(&format!("{} projects, {} failed", outcomes.len(), failed));
+    table
+}
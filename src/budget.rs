@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{
+    ESTIMATED_CHARS_PER_TOKEN, BUDGET_SECTION_OVERHEAD_TOKENS, MIN_TRUNCATED_SECTION_TOKENS
+};
+
+/// Relative importance of a selected file when the output has to fit a token budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum FilePriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl FilePriority {
+    pub const ALL: [FilePriority; 3] = [FilePriority::High, FilePriority::Normal, FilePriority::Low];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FilePriority::Low => "Low",
+            FilePriority::Normal => "Normal",
+            FilePriority::High => "High",
+        }
+    }
+}
+
+/// What happens to a single file once the budget has been applied
+#[derive(Debug, Clone, PartialEq)]
+pub enum BudgetDecision {
+    Include,
+    /// Only the first `max_chars` characters of the content are kept
+    Truncate { max_chars: usize },
+    Omit,
+}
+
+#[derive(Debug, Clone)]
+pub struct TokenBudget {
+    pub max_tokens: usize,
+    pub priorities: HashMap<PathBuf, FilePriority>,
+}
+
+impl TokenBudget {
+    pub fn priority_of(&self, path: &Path) -> FilePriority {
+        self.priorities.get(path).copied().unwrap_or_default()
+    }
+
+    /// Decides which files fit into the budget.
+    /// `file_tokens` holds the estimated token count of each file section, while
+    /// `reserved_tokens` covers the headers and structure that are always emitted.
+    pub fn plan(&self, file_tokens: &[(PathBuf, usize)], reserved_tokens: usize) -> HashMap<PathBuf, BudgetDecision> {
+        let mut ranked: Vec<&(PathBuf, usize)> = file_tokens.iter().collect();
+        // Highest priority first; within a priority smaller files first so that more files survive
+        ranked.sort_by(|(a_path, a_tokens), (b_path, b_tokens)| {
+            self.priority_of(b_path).cmp(&self.priority_of(a_path))
+                .then(a_tokens.cmp(b_tokens))
+                .then(a_path.cmp(b_path))
+        });
+
+        let mut remaining = self.max_tokens.saturating_sub(reserved_tokens);
+        let mut decisions = HashMap::new();
+
+        for (path, tokens) in ranked {
+            let section_tokens = tokens + BUDGET_SECTION_OVERHEAD_TOKENS;
+            let decision = if section_tokens <= remaining {
+                remaining -= section_tokens;
+                BudgetDecision::Include
+            } else if remaining >= MIN_TRUNCATED_SECTION_TOKENS {
+                let max_chars = (remaining - BUDGET_SECTION_OVERHEAD_TOKENS) * chars_per_token();
+                remaining = 0;
+                BudgetDecision::Truncate { max_chars }
+            } else {
+                BudgetDecision::Omit
+            };
+            decisions.insert(path.clone(), decision);
+        }
+
+        decisions
+    }
+}
+
+/// Per-file size limits. Oversized files keep their head and tail around an omission marker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct FileSizeLimits {
+    pub max_lines: Option<usize>,
+    pub max_bytes: Option<usize>,
+}
+
+impl FileSizeLimits {
+    pub fn is_unlimited(&self) -> bool {
+        self.max_lines.is_none() && self.max_bytes.is_none()
+    }
+
+    pub fn apply(&self, content: &str) -> String {
+        if self.is_unlimited() {
+            return content.to_string();
+        }
+
+        let lines: Vec<&str> = content.lines().collect();
+        // Split each limit evenly between head and tail, giving the head the extra line
+        let head_lines = self.max_lines.map(|m| m.div_ceil(2)).unwrap_or(lines.len());
+        let tail_lines = self.max_lines.map(|m| m / 2).unwrap_or(lines.len());
+        let head_bytes = self.max_bytes.map(|m| m.div_ceil(2)).unwrap_or(usize::MAX);
+        let tail_bytes = self.max_bytes.map(|m| m / 2).unwrap_or(usize::MAX);
+
+        let head_count = Self::count_fitting(lines.iter(), head_lines, head_bytes);
+        if head_count == lines.len() && content.len() <= self.max_bytes.unwrap_or(usize::MAX) {
+            return content.to_string();
+        }
+        let tail_count = Self::count_fitting(lines[head_count..].iter().rev(), tail_lines, tail_bytes);
+
+        let omitted = lines.len() - head_count - tail_count;
+        if omitted == 0 {
+            return content.to_string();
+        }
+
+        let mut truncated = String::new();
+        for line in &lines[..head_count] {
+            truncated.push_str(line);
+            truncated.push('\n');
+        }
+        truncated.push_str(&format!("[... {} lines omitted ...]", omitted));
+        for line in &lines[lines.len() - tail_count..] {
+            truncated.push('\n');
+            truncated.push_str(line);
+        }
+        truncated
+    }
+
+    /// Number of leading items that fit within both the line and the byte limit
+    fn count_fitting<'a>(lines: impl Iterator<Item = &'a &'a str>, max_lines: usize, max_bytes: usize) -> usize {
+        let mut used_bytes = 0;
+        let mut count = 0;
+        for line in lines.take(max_lines) {
+            used_bytes += line.len() + 1; // Account for the newline
+            if used_bytes > max_bytes {
+                break;
+            }
+            count += 1;
+        }
+        count
+    }
+}
+
+/// Lines of a file to include instead of all of it, 1-based and inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl LineRange {
+    /// The lines of `content` within the range, with the range clamped to the file's lines and
+    /// the file's total line count
+    pub fn apply<'a>(&self, content: &'a str) -> (Vec<&'a str>, LineRange, usize) {
+        let lines: Vec<&str> = content.lines().collect();
+        let total = lines.len();
+        let start = self.start.clamp(1, total.max(1));
+        let end = self.end.clamp(start, total.max(start));
+        let excerpt = lines.get(start - 1..end).map(<[&str]>::to_vec).unwrap_or_default();
+        (excerpt, LineRange { start, end }, total)
+    }
+}
+
+// Set from the settings at startup and when the estimator changes
+static CHARS_PER_TOKEN: AtomicUsize = AtomicUsize::new(ESTIMATED_CHARS_PER_TOKEN);
+
+/// Characters per token used by every estimate in the process
+pub fn chars_per_token() -> usize {
+    CHARS_PER_TOKEN.load(Ordering::Relaxed)
+}
+
+pub fn set_chars_per_token(chars_per_token: usize) {
+    CHARS_PER_TOKEN.store(chars_per_token.max(1), Ordering::Relaxed);
+}
+
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(chars_per_token())
+}
+
+/// Cuts `content` to at most `max_chars` characters, preferring a line boundary,
+/// and appends a marker so the reader knows the section is incomplete.
+pub fn truncate_content(content: &str, max_chars: usize) -> String {
+    let cut = content.char_indices()
+        .nth(max_chars)
+        .map(|(byte_index, _)| byte_index)
+        .unwrap_or(content.len());
+    let head = &content[..cut];
+    let head = head.rfind('\n').map(|pos| &head[..pos]).unwrap_or(head);
+    format!("{}\n[... truncated to fit token budget ...]", head)
+}
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use log::debug;
+
+use crate::constants::MAX_DEPENDENCY_SOURCE_FILES;
+use crate::utils::version_key;
+
+const CARGO_LOCK_FILE_NAME: &str = "Cargo.lock";
+// Paths that never name a registry crate
+const NON_DEPENDENCY_ROOTS: &[&str] = &["std", "core", "alloc", "proc_macro", "test", "crate", "self", "super", "Self"];
+
+/// A file of an external crate, read from the cargo registry
+#[derive(Debug, Clone, PartialEq)]
+pub struct DependencySource {
+    pub path: PathBuf,
+    pub display_path: String, // e.g. `serde-1.0.200/src/de/mod.rs`
+}
+
+/// Registry sources of the crates used by the given Rust files. Each `use dep::a::b` resolves to
+/// the most specific module file of `dep` (`src/a/b.rs`, `src/a.rs`, ... down to `src/lib.rs`),
+/// at the version pinned in the nearest `Cargo.lock` or else the newest one downloaded.
+/// Crates that were never fetched (no `cargo build` yet) are skipped.
+pub fn dependency_sources(files: &[PathBuf], root: &Path) -> Vec<DependencySource> {
+    let registry_dirs = registry_source_dirs();
+    if registry_dirs.is_empty() {
+        debug!("No cargo registry sources found, skipping dependency sources");
+        return Vec::new();
+    }
+
+    let no_lock_file = HashMap::new();
+    let mut lock_files: HashMap<PathBuf, HashMap<String, String>> = HashMap::new();
+    let mut sources: Vec<DependencySource> = Vec::new();
+    for file_path in files.iter().filter(|path| path.extension().is_some_and(|ext| ext == "rs")) {
+        let Ok(content) = fs::read_to_string(file_path) else { continue };
+        let pinned_versions = match nearest_cargo_lock(file_path, root) {
+            Some(lock_path) => lock_files.entry(lock_path.clone()).or_insert_with(|| read_cargo_lock(&lock_path)),
+            None => &no_lock_file,
+        };
+
+        for segments in content.lines().filter_map(use_path_segments) {
+            let Some(crate_dir) = find_crate_dir(&registry_dirs, &segments[0], pinned_versions.get(&segments[0])) else {
+                continue;
+            };
+            let Some(path) = module_file(&crate_dir, &segments[1..]) else { continue };
+            if sources.iter().any(|source| source.path == path) {
+                continue;
+            }
+            if sources.len() == MAX_DEPENDENCY_SOURCE_FILES {
+                debug!("Reached the limit of {} dependency source files", MAX_DEPENDENCY_SOURCE_FILES);
+                return sources;
+            }
+
+            let display_path = crate_dir.parent()
+                .and_then(|registry_dir| path.strip_prefix(registry_dir).ok())
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            sources.push(DependencySource { path, display_path });
+        }
+    }
+
+    debug!("Resolved {} dependency source files", sources.len());
+    sources
+}
+
+/// `~/.cargo/registry/src/<index>` directories, which hold the unpacked `<name>-<version>` crates
+fn registry_source_dirs() -> Vec<PathBuf> {
+    let Some(cargo_home) = env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".cargo")))
+    else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(cargo_home.join("registry").join("src")) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// The crate path segments of `use dep::a::b;`, `pub use ::dep::a;` or `extern crate dep;`,
+/// with the crate name first. `None` for std and crate-local paths.
+fn use_path_segments(line: &str) -> Option<Vec<String>> {
+    let line = line.trim();
+    let line = line.strip_prefix("pub ").unwrap_or(line);
+    let path = line.strip_prefix("use ")
+        .or_else(|| line.strip_prefix("extern crate "))?
+        .trim_start_matches("::");
+
+    let segments: Vec<String> = path
+        .split("::")
+        .map(|segment| segment.trim_end_matches(';').trim())
+        .take_while(|segment| !segment.is_empty() && segment.chars().all(|c| c.is_alphanumeric() || c == '_'))
+        .map(str::to_string)
+        .collect();
+    let crate_name = segments.first()?;
+    (!NON_DEPENDENCY_ROOTS.contains(&crate_name.as_str())).then_some(segments)
+}
+
+fn nearest_cargo_lock(file_path: &Path, root: &Path) -> Option<PathBuf> {
+    file_path.ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(root))
+        .map(|dir| dir.join(CARGO_LOCK_FILE_NAME))
+        .find(|lock_path| lock_path.is_file())
+}
+
+/// Versions of the registry packages in a `Cargo.lock`, keyed by the name used in code
+/// (`serde-json` becomes `serde_json`). With several versions of a package, the newest wins.
+fn read_cargo_lock(lock_path: &Path) -> HashMap<String, String> {
+    let Ok(content) = fs::read_to_string(lock_path) else {
+        return HashMap::new();
+    };
+
+    let mut versions: HashMap<String, String> = HashMap::new();
+    for package in content.split("[[package]]").skip(1) {
+        let field = |key: &str| package.lines()
+            .filter_map(|line| line.split_once('='))
+            .find(|(name, _)| name.trim() == key)
+            .map(|(_, value)| value.trim().trim_matches('"').to_string());
+        // Workspace members have no source
+        if !field("source").is_some_and(|source| source.starts_with("registry+")) {
+            continue;
+        }
+        let (Some(name), Some(version)) = (field("name"), field("version")) else { continue };
+
+        let name = name.replace('-', "_");
+        let older = versions.get(&name).is_some_and(|existing| version_key(&version) <= version_key(existing));
+        if !older {
+            versions.insert(name, version);
+        }
+    }
+    debug!("Read {} registry package versions from {:?}", versions.len(), lock_path);
+    versions
+}
+
+/// The unpacked crate directory, at `version` if given, else the newest one found
+fn find_crate_dir(registry_dirs: &[PathBuf], crate_name: &str, version: Option<&String>) -> Option<PathBuf> {
+    let mut newest: Option<(Vec<u64>, PathBuf)> = None;
+    for registry_dir in registry_dirs {
+        let Ok(entries) = fs::read_dir(registry_dir) else { continue };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let dir_name = entry.file_name().to_string_lossy().to_string();
+            // `serde-1.0.200`, but not `serde_json-1.0.100` or `tokio-macros-2.2.0` for `serde`/`tokio`
+            let Some((name, dir_version)) = dir_name.rsplit_once('-')
+                .filter(|(_, dir_version)| dir_version.starts_with(|c: char| c.is_ascii_digit()))
+                .or_else(|| split_prerelease_dir_name(&dir_name))
+            else {
+                continue;
+            };
+            if name.replace('-', "_") != crate_name {
+                continue;
+            }
+
+            match version {
+                Some(version) if dir_version == version => return Some(entry.path()),
+                Some(_) => {}
+                None => {
+                    let key = version_key(dir_version);
+                    let is_newer = match &newest {
+                        Some((newest_key, _)) => key > *newest_key,
+                        None => true,
+                    };
+                    if is_newer {
+                        newest = Some((key, entry.path()));
+                    }
+                }
+            }
+        }
+    }
+    newest.map(|(_, path)| path)
+}
+
+/// `foo-1.0.0-beta.1` splits at the first `-` followed by a digit
+fn split_prerelease_dir_name(dir_name: &str) -> Option<(&str, &str)> {
+    let index = dir_name.char_indices()
+        .find(|(i, c)| *c == '-' && dir_name[i + 1..].starts_with(|c: char| c.is_ascii_digit()))
+        .map(|(i, _)| i)?;
+    Some((&dir_name[..index], &dir_name[index + 1..]))
+}
+
+/// The most specific existing module file for the path after the crate name.
+/// The last segments are often items (`de::Deserialize`), so shorter paths are tried too.
+fn module_file(crate_dir: &Path, segments: &[String]) -> Option<PathBuf> {
+    let src_dir = crate_dir.join("src");
+    for len in (1..=segments.len()).rev() {
+        let module_path = segments[..len].join("/");
+        for candidate in [src_dir.join(format!("{}.rs", module_path)), src_dir.join(&module_path).join("mod.rs")] {
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+        }
+    }
+    Some(src_dir.join("lib.rs")).filter(|lib| lib.is_file())
+}
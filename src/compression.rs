@@ -0,0 +1,47 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::error::{AppError, Result};
+use crate::utils::write_file_atomically;
+
+pub const COMPRESSED_EXTENSION: &str = "gz";
+
+/// `context.md` -> `context.md.gz`
+pub fn compressed_path(path: &Path) -> PathBuf {
+    let mut file_name = path.as_os_str().to_os_string();
+    file_name.push(format!(".{}", COMPRESSED_EXTENSION));
+    PathBuf::from(file_name)
+}
+
+/// Writes a gzip copy of each document next to it, replacing older copies
+pub fn write_compressed_copies(document_paths: &[PathBuf]) -> Result<()> {
+    for document_path in document_paths {
+        let content = fs::read(document_path)
+            .map_err(|e| AppError::new_io_error(e, Some(document_path.clone()), "Failed to read document to compress".to_string()))?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        let compressed = encoder.write_all(&content)
+            .and_then(|()| encoder.finish())
+            .map_err(|e| AppError::new_io_error(e, Some(document_path.clone()), "Failed to compress document".to_string()))?;
+        write_file_atomically(&compressed_path(document_path), &compressed)?;
+    }
+    Ok(())
+}
+
+/// The text of a document, decompressed when `path` is a `.gz` file or when only the
+/// compressed copy of the document is left
+pub fn read_document(path: &Path) -> io::Result<String> {
+    let compressed = if path.extension().is_some_and(|extension| extension == COMPRESSED_EXTENSION) {
+        path.to_path_buf()
+    } else if !path.exists() && compressed_path(path).is_file() {
+        compressed_path(path)
+    } else {
+        return fs::read_to_string(path);
+    };
+    let mut content = String::new();
+    GzDecoder::new(fs::File::open(compressed)?).read_to_string(&mut content)?;
+    Ok(content)
+}
@@ -1,25 +1,180 @@
 use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::output_formatter::{AdocFormatter, MarkdownFormatter, OrgFormatter, OutputFormatter, RstFormatter};
 
-pub const MARKDOWN_HEADER_CONTEXT: &str = "# Context";
 pub const MARKDOWN_HEADER_STRUCTURE: &str = "## Project Structure";
-pub const MARKDOWN_HEADER_FILES: &str = "## Files";
+pub const MARKDOWN_HEADER_TOC: &str = "## Table of Contents";
+pub const MARKDOWN_HEADER_SUMMARY: &str = "## Summary";
 pub const MARKDOWN_CODE_BLOCK: &str = "```";
 
-pub const DEBOUNCE_DURATION: Duration = Duration::from_millis(750); // Slightly longer debounce
+pub const APP_DATA_DIR_NAME: &str = "context_builder"; // Subdirectory of the OS cache/config dirs
+pub const GENERATOR_MARKER: &str = "Generated by context_builder"; // Commented near the top of every document, so it may be replaced
+pub const GENERATOR_MARKER_SEARCH_BYTES: u64 = 1024; // Head of an existing output file searched for the marker
+pub const TEMP_FILE_PREFIX: &str = ".context_builder_tmp"; // Lets us recognize our own temp files
+pub const WORKSPACE_CONFIG_FILE_NAME: &str = ".contextbuilder.toml"; // Checked-in project configuration, used once the directory is trusted
+pub const ORPHANED_TEMP_FILE_AGE: Duration = Duration::from_secs(10 * 60); // Older temp files are crash leftovers
+pub const SHUTDOWN_WORKER_TIMEOUT: Duration = Duration::from_secs(5); // Closing the window waits this long for running writes
+pub const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(20); // Between checks whether the writes finished
+pub const SCAN_CACHE_FORMAT_VERSION: u32 = 4; // Bump when FileNode's serialized shape changes
+
+pub const SCAN_PROGRESS_INTERVAL: usize = 500; // Entries walked between ScanProgress events
+pub const LAZY_SCAN_DEPTH: usize = 1; // Levels scanned at once when directories load on expansion
+pub const LINE_COUNT_MAX_FILE_BYTES: u64 = 4 * 1024 * 1024; // Larger files are shown without a line count
+pub const SHEBANG_MAX_BYTES: u64 = 256; // Read from files without an extension to pick their code fence language
+pub const BINARY_DETECTION_BYTES: usize = 8 * 1024; // Leading bytes checked for NUL to tell binary files apart
+pub const FILE_READ_RETRY_DELAY: Duration = Duration::from_millis(50); // Before re-reading a file that changed mid-read
+pub const TOKEN_COUNT_PROGRESS_INTERVAL: Duration = Duration::from_millis(100); // Between partial results of the background token count
+pub const FILE_PREVIEW_MAX_BYTES: usize = 256 * 1024; // Longer previews are cut; the panel lays out all of its text every frame
+pub const LARGE_FILE_WARNING_BYTES: u64 = 1024 * 1024; // Sizes above this are highlighted in the tree
+
+// Initial default ignore patterns
+pub const DEFAULT_IGNORE_PATTERNS: &[&str] = &[
+    // Common VCS and build artifacts
+    ".git/", ".hg/", ".svn/",
+    "target/", "build/", "dist/", "pkg/", "node_modules/",
+    // Python specific
+    "__pycache__/", "*.pyc", "*.pyo", "*.pyd",
+    ".env", ".venv", "venv/", "env/",
+    // "requirements.txt", // Often useful to see, but can be configured if user wants it ignored
+    // Node specific
+    "package-lock.json", "yarn.lock",
+    // Common OS files
+    ".DS_Store", "Thumbs.db",
+    // Log files
+    "*.log",
+    // Temporary files
+    "*.tmp", "*.swp", "*.swo",
+    // Compiled outputs & binaries from various languages/tools
+    "*.o", "*.so", "*.a", "*.dylib",
+    "*.exe", "*.dll", "*.lib", "*.exp", "*.obj", "*.def",
+    // Archives & compressed files
+    "*.zip", "*.tar", "*.gz", "*.rar",
+    // Image/Media (usually not context for code)
+    "*.ico", "*.png", "*.jpg", "*.jpeg", "*.gif", "*.bmp", "*.tiff", "*.svg",
+    "*.mp3", "*.mp4", "*.avi",
+    // Database files
+    "*.db", "*.sqlite", "*.sqlite3",
+    // IDE specific
+    ".idea/", ".vscode/", "*.sublime-project", "*.sublime-workspace",
+];
+
+pub const DEFAULT_DEBOUNCE_MS: u64 = 750; // Quiet time after the last event on a path before it is reported
+pub const DEFAULT_DEBOUNCE_CHECK_INTERVAL_MS: u64 = 100; // How often settled paths are collected and reported
+pub const DEFAULT_SCHEDULED_GENERATION_TIMES: &str = "09:00";
+pub const DEFAULT_POLL_INTERVAL_SECS: u64 = 2; // Polling watcher, used where native events don't arrive
+// Restarts of a failed watcher back off exponentially between these bounds
+pub const WATCHER_RESTART_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+pub const WATCHER_RESTART_MAX_BACKOFF: Duration = Duration::from_secs(60);
+pub const WATCHER_POLLING_FALLBACK_ATTEMPTS: u32 = 2; // Restarts after which polling is used instead of native events
+// File systems whose changes don't reach inotify (made by other hosts or a FUSE daemon)
+pub const NETWORK_FILESYSTEM_TYPES: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smb3", "smbfs", "9p", "afs", "davfs",
+    "fuse.sshfs", "fuse.rclone", "fuse.gvfsd-fuse",
+];
 pub const UI_STATUS_MESSAGE_DURATION: Duration = Duration::from_secs(5); 
 
+// Token budget
+pub const ESTIMATED_CHARS_PER_TOKEN: usize = 4; // Rough heuristic for source code and English text
+pub const CONSERVATIVE_CHARS_PER_TOKEN: usize = 3; // Overestimates rather than overflowing a context window
+pub const DEFAULT_TOKEN_BUDGET: usize = 128_000;
+pub const BUDGET_SECTION_OVERHEAD_TOKENS: usize = 32; // Header, fences and spacing around a file section
+pub const MIN_TRUNCATED_SECTION_TOKENS: usize = 256; // Smaller leftovers are not worth a truncated section
+pub const DEFAULT_CHUNK_TOKEN_LIMIT: usize = 32_000;
+pub const DEFAULT_MINIFIED_EXTENSIONS: &str = "rs, py, js, ts, go, java, c, cpp, h";
+pub const OUTLINE_CONTENT_NOTICE: &str = "[NOTE: Signatures only, function bodies omitted]";
+pub const SUMMARY_CONTENT_NOTICE: &str = "[NOTE: LLM-generated summary, content omitted]";
+pub const MAX_LOG_EXCERPTS_PER_FILE: usize = 5; // Log excerpts shown with a file selected from a pasted log
+pub const NOTEBOOK_CONTENT_NOTICE: &str = "[NOTE: Jupyter notebook shown as its cells, outputs omitted]";
+pub const MINIFIED_CONTENT_NOTICE: &str = "[NOTE: Comments stripped and blank lines collapsed to save tokens]";
+
+// Watch mode: above this document size, selection changes no longer trigger a full regeneration
+pub const DEFAULT_AUTO_REGENERATION_LIMIT_BYTES: usize = 4 * 1024 * 1024;
+pub const DEFAULT_GROWTH_ALERT_PERCENT: u32 = 50; // Growth of the document since monitoring started that raises an alert
+pub const SECTION_CACHE_MAX_BYTES: usize = 64 * 1024 * 1024; // Rendered sections kept for reuse across regenerations
+
+// Per-file limits (used when the user enables them)
+pub const DEFAULT_MAX_FILE_LINES: usize = 500;
+pub const DEFAULT_MAX_FILE_BYTES: usize = 64 * 1024;
+
+// Structure section limits (used when the user enables them)
+pub const DEFAULT_STRUCTURE_MAX_DEPTH: usize = 4;
+pub const DEFAULT_STRUCTURE_MAX_ENTRIES: usize = 50;
+
+// Manifests suggested alongside a focal file in the minimal context, nearest first
+pub const MANIFEST_FILE_NAMES: &[&str] = &[
+    "Cargo.toml", "package.json", "tsconfig.json", "pyproject.toml", "setup.py", "requirements.txt",
+    "go.mod", "CMakeLists.txt", "Makefile", "pom.xml", "build.gradle",
+];
+
+// Quick-select entries shown above the user's own presets: name and comma-separated globs
+pub const BUILTIN_SELECTION_PRESETS: &[(&str, &str)] = &[
+    ("Source files", "*.rs, *.py, *.js, *.jsx, *.ts, *.tsx, *.go, *.java, *.kt, *.c, *.h, *.cc, *.cpp, *.hpp, *.cs, *.rb, *.swift"),
+    ("Docs", "*.md, *.adoc, *.rst, *.txt"),
+    ("Config & manifests", "*.toml, *.json, *.yaml, *.yml, *.ini, Makefile, Dockerfile"),
+];
+
+// Per-project scratch text, included like a file section
+pub const SCRATCH_SECTION_TITLE: &str = "Scratch";
+pub const SCRATCH_LANGUAGE: &str = "text";
+
+// Rust dependency sources resolved from the cargo registry
+pub const MAX_DEPENDENCY_SOURCE_FILES: usize = 20; // Keeps a few broad `use` lines from swamping the document
+
+// Local usage insights
+pub const USAGE_MAX_GENERATION_RECORDS: usize = 1000;
+pub const USAGE_MIN_GENERATIONS_FOR_HABITS: usize = 5;
+pub const USAGE_FREQUENT_FILE_RATIO: f64 = 0.8; // Files in at least this share of generations count as habitual
+pub const USAGE_TOP_FILES: usize = 10;
+pub const USAGE_HABIT_TAG: &str = "usual";
+pub const DEFAULT_HISTORY_LENGTH: usize = 20; // Full generations kept in the generation history
+
+// Update check (opt-in); only this request is made, with no identifying data beyond the app version
+pub const UPDATE_CHECK_URL: &str = "https://api.github.com/repos/NikolayBobovnikov/context_manager/releases/latest";
+pub const UPDATE_CHECK_TIMEOUT_SECS: u64 = 10;
+pub const REMOTE_CONNECT_TIMEOUT_SECS: u64 = 10; // ssh ConnectTimeout when fetching a remote directory
+
+// Local HTTP API (opt-in), on 127.0.0.1 only
+pub const DEFAULT_HTTP_API_PORT: u16 = 7350;
+pub const HTTP_API_ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100); // Also how long stopping the server takes at most
+pub const HTTP_API_IO_TIMEOUT: Duration = Duration::from_secs(5); // For reading a request and writing its response
+pub const HTTP_API_REPLY_TIMEOUT: Duration = Duration::from_secs(5); // For the UI thread to answer a request
+
+// LLM chat (opt-in); requests go through the system curl like the update check
+pub const LLM_CONNECT_TIMEOUT_SECS: u64 = 10; // No overall timeout: answers of local models can take minutes
+pub const LLM_STREAM_REPAINT_INTERVAL: Duration = Duration::from_millis(100); // Polls for answer chunks while streaming
+pub const FILE_SUMMARY_TIMEOUT_SECS: u64 = 300; // Per file; generation waits for each summary
+pub const FILE_SUMMARY_MAX_WORDS: usize = 150;
+pub const FILE_SUMMARY_MAX_INPUT_CHARS: usize = 100_000; // Longer files are summarized from their start
+
 // Output Formats
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum OutputFormat {
     Markdown,
     Adoc,
+    Org,
+    Rst,
 }
 
 impl OutputFormat {
+    pub const ALL: [OutputFormat; 4] = [OutputFormat::Markdown, OutputFormat::Adoc, OutputFormat::Org, OutputFormat::Rst];
+
     pub fn extension(&self) -> &'static str {
         match self {
             OutputFormat::Markdown => "md",
             OutputFormat::Adoc => "adoc",
+            OutputFormat::Org => "org",
+            OutputFormat::Rst => "rst",
+        }
+    }
+
+    /// Writes and parses documents of this format
+    pub fn formatter(&self) -> &'static dyn OutputFormatter {
+        match self {
+            OutputFormat::Markdown => &MarkdownFormatter,
+            OutputFormat::Adoc => &AdocFormatter,
+            OutputFormat::Org => &OrgFormatter,
+            OutputFormat::Rst => &RstFormatter,
         }
     }
 
@@ -27,15 +182,27 @@ impl OutputFormat {
         match self {
             OutputFormat::Markdown => "Markdown",
             OutputFormat::Adoc => "AsciiDoc",
+            OutputFormat::Org => "Org",
+            OutputFormat::Rst => "reStructuredText",
         }
     }
 }
 
+pub const DOCUMENT_INDEX_EXTENSION: &str = "index.json"; // Sidecar section index, e.g. `context.index.json`
 pub const DEFAULT_OUTPUT_FILENAME_BASE: &str = "project_structure"; // Use base name
 pub const DEFAULT_OUTPUT_FORMAT: OutputFormat = OutputFormat::Markdown; // Default format
 
 // AsciiDoc specific constants
 pub const ADOC_SECTION_LEVEL_1: &str = "=";
 pub const ADOC_SECTION_LEVEL_2: &str = "==";
-pub const ADOC_SECTION_LEVEL_3: &str = "==="; // Corrected from "===" to "====" for file sections
-pub const ADOC_SOURCE_BLOCK_DELIMITER: &str = "----"; // Typically four hyphens 
\ No newline at end of file
+pub const ADOC_SOURCE_BLOCK_DELIMITER: &str = "----"; // Typically four hyphens 
+
+// Org-mode specific constants
+pub const ORG_HEADING_CHAR: char = '*'; // Repeated once per level
+pub const ORG_SOURCE_BLOCK_BEGIN: &str = "#+BEGIN_SRC";
+pub const ORG_SOURCE_BLOCK_END: &str = "#+END_SRC";
+
+// reStructuredText specific constants
+pub const RST_HEADING_CHARS: [char; 3] = ['=', '-', '~']; // Underlines of levels 1 to 3; level 1 is overlined as well
+pub const RST_CODE_BLOCK_DIRECTIVE: &str = ".. code-block::";
+pub const RST_INDENT: &str = "   "; // Directive content
@@ -0,0 +1,98 @@
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::fs;
+use std::panic::{self, Location};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use chrono::Local;
+
+use crate::constants::APP_DATA_DIR_NAME;
+use crate::update_check::CURRENT_VERSION;
+
+// What the app was doing, kept up to date by the UI for the report
+static STATE_SUMMARY: Mutex<String> = Mutex::new(String::new());
+// Report of a background thread panic in this session, not yet shown
+static UNSEEN_REPORT: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+fn reports_dir() -> Option<PathBuf> {
+    Some(dirs::data_local_dir()?.join(APP_DATA_DIR_NAME).join("crash_reports"))
+}
+
+/// Names the report not yet shown, so the next launch can offer it after a crash
+fn unseen_marker_path() -> Option<PathBuf> {
+    Some(reports_dir()?.join("unseen"))
+}
+
+/// Writes a crash report for every panic, on any thread, after the default hook printed it.
+/// Panics of background threads otherwise go unnoticed: their work just stops.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        let Some(report_path) = write_report(info.payload(), info.location()) else { return };
+        // Not logged: the panic may have happened inside the logger
+        eprintln!("Crash report written to {}", report_path.display());
+        if let Ok(mut unseen_report) = UNSEEN_REPORT.try_lock() {
+            *unseen_report = Some(report_path);
+        }
+    }));
+}
+
+fn write_report(payload: &(dyn Any + Send), location: Option<&Location>) -> Option<PathBuf> {
+    let message = payload.downcast_ref::<&str>().map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "(no message)".to_string());
+    let location = location
+        .map(|location| format!("{}:{}:{}", location.file(), location.line(), location.column()))
+        .unwrap_or_else(|| "unknown".to_string());
+    let state_summary = STATE_SUMMARY.try_lock()
+        .map(|summary| summary.clone())
+        .unwrap_or_else(|_| "(unavailable)".to_string());
+    let now = Local::now();
+    let report = format!(
+        "Context Builder {} crashed on {}\n\nThread: {}\nPanic: {}\nLocation: {}\nOS: {} {}\n\nApp state:\n{}\n\nBacktrace:\n{}\n",
+        CURRENT_VERSION,
+        now.format("%Y-%m-%d %H:%M:%S"),
+        thread::current().name().unwrap_or("unnamed"),
+        message,
+        location,
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        if state_summary.is_empty() { "(none)" } else { &state_summary },
+        Backtrace::force_capture(),
+    );
+
+    let reports_dir = reports_dir()?;
+    fs::create_dir_all(&reports_dir).ok()?;
+    let report_path = reports_dir.join(format!("crash-{}.txt", now.format("%Y%m%d-%H%M%S%.3f")));
+    fs::write(&report_path, report).ok()?;
+    if let Some(marker_path) = unseen_marker_path() {
+        let _ = fs::write(marker_path, report_path.to_string_lossy().as_bytes());
+    }
+    Some(report_path)
+}
+
+pub fn set_state_summary(summary: String) {
+    if let Ok(mut state_summary) = STATE_SUMMARY.lock() {
+        *state_summary = summary;
+    }
+}
+
+/// Report of the run that crashed, once at startup
+pub fn take_last_run_report() -> Option<PathBuf> {
+    let marker_path = unseen_marker_path()?;
+    let report_path = PathBuf::from(fs::read_to_string(&marker_path).ok()?);
+    let _ = fs::remove_file(&marker_path);
+    report_path.is_file().then_some(report_path)
+}
+
+/// Report of a background thread that panicked in this session, once. Cheap enough to poll
+/// every frame.
+pub fn take_session_report() -> Option<PathBuf> {
+    let report_path = UNSEEN_REPORT.lock().ok()?.take()?;
+    if let Some(marker_path) = unseen_marker_path() {
+        let _ = fs::remove_file(marker_path); // Shown now, not again at the next launch
+    }
+    Some(report_path)
+}
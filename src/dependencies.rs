@@ -0,0 +1,265 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use log::debug;
+
+use crate::constants::MANIFEST_FILE_NAMES;
+
+const JS_EXTENSIONS: &[&str] = &["ts", "tsx", "js", "jsx", "mjs", "cjs"];
+const C_EXTENSIONS: &[&str] = &["c", "h", "cc", "cpp", "cxx", "hh", "hpp", "hxx"];
+
+/// Why a file is part of a suggested context
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextReason {
+    Focal,
+    Import,
+    Manifest,
+    Readme,
+}
+
+impl ContextReason {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContextReason::Focal => "focal file",
+            ContextReason::Import => "imported",
+            ContextReason::Manifest => "manifest",
+            ContextReason::Readme => "readme",
+        }
+    }
+}
+
+/// The smallest useful context around a focal file: the file itself, the project files it
+/// imports directly, the manifests of the packages it belongs to and the nearest README.
+pub fn minimal_context(focal_file: &Path, root: &Path) -> Vec<(PathBuf, ContextReason)> {
+    let mut context = vec![(focal_file.to_path_buf(), ContextReason::Focal)];
+    let mut push = |path: PathBuf, reason: ContextReason| {
+        if !context.iter().any(|(existing, _)| *existing == path) {
+            context.push((path, reason));
+        }
+    };
+
+    for import in direct_local_imports(focal_file, root) {
+        push(import, ContextReason::Import);
+    }
+
+    // Walk up to the project root; the manifests of the innermost package win
+    let mut found_manifests = false;
+    let mut readme = None;
+    for dir in focal_file.ancestors().skip(1).take_while(|dir| dir.starts_with(root)) {
+        if !found_manifests {
+            for name in MANIFEST_FILE_NAMES {
+                let candidate = dir.join(name);
+                if candidate.is_file() {
+                    found_manifests = true;
+                    push(candidate, ContextReason::Manifest);
+                }
+            }
+        }
+        if readme.is_none() {
+            readme = find_readme(dir);
+        }
+    }
+    if let Some(readme) = readme {
+        push(readme, ContextReason::Readme);
+    }
+
+    debug!("Minimal context for {:?} has {} files", focal_file, context.len());
+    context
+}
+
+/// Project files imported by `file_path`, resolved to existing paths inside `root`.
+/// Only relative or crate-local imports are followed; packages are skipped.
+pub fn direct_local_imports(file_path: &Path, root: &Path) -> Vec<PathBuf> {
+    let Ok(content) = fs::read_to_string(file_path) else {
+        return Vec::new();
+    };
+    let extension = file_path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+
+    let mut imports: Vec<PathBuf> = Vec::new();
+    for line in content.lines().map(str::trim) {
+        let candidates = match extension {
+            "rs" => rust_import_candidates(line, file_path, root),
+            "py" => python_import_candidates(line, file_path, root),
+            ext if JS_EXTENSIONS.contains(&ext) => js_import_candidates(line, file_path),
+            ext if C_EXTENSIONS.contains(&ext) => c_include_candidates(line, file_path, root),
+            _ => Vec::new(),
+        };
+
+        let resolved = candidates.into_iter()
+            .map(|candidate| normalize_path(&candidate))
+            .find(|candidate| candidate.is_file() && candidate.starts_with(root) && candidate != file_path);
+        if let Some(path) = resolved {
+            if !imports.contains(&path) {
+                imports.push(path);
+            }
+        }
+    }
+    imports
+}
+
+/// Resolves `.` and `..` components without touching the file system
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+fn find_readme(dir: &Path) -> Option<PathBuf> {
+    let mut readmes: Vec<PathBuf> = fs::read_dir(dir).ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.file_stem()
+            .is_some_and(|stem| stem.to_string_lossy().eq_ignore_ascii_case("readme")))
+        .collect();
+    readmes.sort(); // README.md before README.txt etc., for stable suggestions
+    readmes.into_iter().next()
+}
+
+/// `mod name;` and `use crate::a::b` / `use super::a`
+fn rust_import_candidates(line: &str, file_path: &Path, root: &Path) -> Vec<PathBuf> {
+    let line = line.strip_prefix("pub ").unwrap_or(line);
+    let parent = file_path.parent().unwrap_or(root);
+    let stem = file_path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    // Child modules of `foo.rs` live in `foo/`, those of `main.rs`, `lib.rs` and `mod.rs` next to it
+    let module_dir = if matches!(stem.as_str(), "main" | "lib" | "mod") {
+        parent.to_path_buf()
+    } else {
+        parent.join(&stem)
+    };
+
+    if let Some(name) = line.strip_prefix("mod ").and_then(|rest| rest.strip_suffix(';')) {
+        let name = name.trim();
+        return vec![module_dir.join(format!("{}.rs", name)), module_dir.join(name).join("mod.rs")];
+    }
+
+    let Some(use_path) = line.strip_prefix("use ") else {
+        return Vec::new();
+    };
+    let (base_dir, rest) = if let Some(rest) = use_path.strip_prefix("crate::") {
+        // The crate root is the `src` directory of the nearest Cargo package
+        let Some(src_dir) = file_path.ancestors()
+            .take_while(|dir| dir.starts_with(root))
+            .find(|dir| dir.join("Cargo.toml").is_file())
+            .map(|package_dir| package_dir.join("src"))
+        else {
+            return Vec::new();
+        };
+        (src_dir, rest)
+    } else if let Some(rest) = use_path.strip_prefix("super::") {
+        let parent_module_dir = if matches!(stem.as_str(), "mod") {
+            parent.parent().unwrap_or(root).to_path_buf()
+        } else {
+            parent.to_path_buf()
+        };
+        (parent_module_dir, rest)
+    } else {
+        return Vec::new();
+    };
+
+    // Try the longest module path first: `a::b::C` may be `a/b.rs` or `a.rs`
+    let segments: Vec<&str> = rest
+        .split("::")
+        .map(|segment| segment.trim_end_matches(';'))
+        .take_while(|segment| !segment.is_empty() && segment.chars().all(|c| c.is_alphanumeric() || c == '_'))
+        .collect();
+    let mut candidates = Vec::new();
+    for len in (1..=segments.len()).rev() {
+        let module_path = segments[..len].join("/");
+        candidates.push(base_dir.join(format!("{}.rs", module_path)));
+        candidates.push(base_dir.join(&module_path).join("mod.rs"));
+    }
+    candidates
+}
+
+/// `import a.b`, `from a.b import c`, `from .a import b`, `from . import a`
+fn python_import_candidates(line: &str, file_path: &Path, root: &Path) -> Vec<PathBuf> {
+    let module = if let Some(rest) = line.strip_prefix("from ") {
+        let Some((module, imported)) = rest.split_once(" import ") else {
+            return Vec::new();
+        };
+        // `from . import a` refers to the module `.a`
+        if module.chars().all(|c| c == '.') {
+            format!("{}{}", module, imported.split(',').next().unwrap_or("").trim())
+        } else {
+            module.trim().to_string()
+        }
+    } else if let Some(rest) = line.strip_prefix("import ") {
+        rest.split([',', ' ']).next().unwrap_or("").to_string()
+    } else {
+        return Vec::new();
+    };
+
+    let level = module.chars().take_while(|c| *c == '.').count();
+    let module_path = module[level..].replace('.', "/");
+    if module_path.is_empty() {
+        return Vec::new();
+    }
+
+    // Relative imports start at the file's package; absolute ones at the file's directory or the root
+    let base_dirs: Vec<PathBuf> = if level > 0 {
+        file_path.ancestors().nth(level).map(Path::to_path_buf).into_iter().collect()
+    } else {
+        file_path.parent().map(Path::to_path_buf).into_iter().chain([root.to_path_buf()]).collect()
+    };
+    base_dirs.into_iter()
+        .flat_map(|base_dir| [
+            base_dir.join(format!("{}.py", module_path)),
+            base_dir.join(&module_path).join("__init__.py"),
+        ])
+        .collect()
+}
+
+/// Relative specifiers of `import ... from './x'`, `import './x'`, `require('./x')`, `import('./x')`
+fn js_import_candidates(line: &str, file_path: &Path) -> Vec<PathBuf> {
+    if !(line.starts_with("import") || line.starts_with("export") || line.contains("require(")) {
+        return Vec::new();
+    }
+    let Some(specifier) = quoted_strings(line).into_iter().find(|s| s.starts_with("./") || s.starts_with("../")) else {
+        return Vec::new();
+    };
+
+    let base = file_path.parent().map(|dir| dir.join(specifier)).unwrap_or_default();
+    let mut candidates = vec![base.clone()];
+    for extension in JS_EXTENSIONS {
+        candidates.push(PathBuf::from(format!("{}.{}", base.display(), extension)));
+    }
+    for extension in JS_EXTENSIONS {
+        candidates.push(base.join(format!("index.{}", extension)));
+    }
+    candidates
+}
+
+/// `#include "x.h"`, looked up next to the file and then from the project root
+fn c_include_candidates(line: &str, file_path: &Path, root: &Path) -> Vec<PathBuf> {
+    let Some(rest) = line.strip_prefix('#').map(str::trim_start).and_then(|rest| rest.strip_prefix("include")) else {
+        return Vec::new();
+    };
+    let Some(header) = quoted_strings(rest).into_iter().next() else {
+        return Vec::new(); // `<...>` includes are system headers
+    };
+
+    let mut candidates: Vec<PathBuf> = file_path.parent().map(|dir| dir.join(header)).into_iter().collect();
+    candidates.push(root.join(header));
+    candidates.push(root.join("include").join(header));
+    candidates
+}
+
+/// Contents of the single- or double-quoted strings on a line
+fn quoted_strings(line: &str) -> Vec<&str> {
+    let mut strings = Vec::new();
+    let mut rest = line;
+    while let Some(start) = rest.find(['"', '\'', '`']) {
+        let quote = rest[start..].chars().next().unwrap_or('"');
+        let after_quote = &rest[start + 1..];
+        let Some(end) = after_quote.find(quote) else { break };
+        strings.push(&after_quote[..end]);
+        rest = &after_quote[end + 1..];
+    }
+    strings
+}
@@ -0,0 +1,154 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use log::{debug, info};
+use similar::TextDiff;
+
+use crate::constants::OutputFormat;
+use crate::error::{AppError, Result};
+use crate::file_handler::{FileHandler, FileNode};
+use crate::utils::write_file_atomically;
+
+const DIFF_CONTEXT_LINES: usize = 3;
+
+/// How two directories differ, by path relative to their roots
+#[derive(Debug, Default)]
+pub struct DirectoryComparison {
+    pub only_in_a: Vec<PathBuf>,
+    pub only_in_b: Vec<PathBuf>,
+    pub changed: Vec<PathBuf>,
+    pub unchanged_count: usize,
+}
+
+/// Compares two directory trees (e.g. `v1/` and `v2/` of a service) for migration reviews.
+/// Files are aligned by relative path; both sides are scanned with the same ignore patterns.
+pub struct DirectoryComparer {
+    root_a: PathBuf,
+    root_b: PathBuf,
+    ignore_patterns: Vec<String>,
+}
+
+impl DirectoryComparer {
+    pub fn new(root_a: PathBuf, root_b: PathBuf) -> Self {
+        Self {
+            root_a,
+            root_b,
+            ignore_patterns: Vec::new(),
+        }
+    }
+
+    pub fn with_ignore_patterns(mut self, ignore_patterns: Vec<String>) -> Self {
+        self.ignore_patterns = ignore_patterns;
+        self
+    }
+
+    /// Writes a document with the only-in-A/only-in-B listings and a unified diff per changed file
+    pub fn generate_comparison_document(&self, output_path: &Path, format: OutputFormat) -> Result<DirectoryComparison> {
+        let root_a = self.scan(&self.root_a)?;
+        let root_b = self.scan(&self.root_b)?;
+        let files_a = relative_file_paths(&root_a);
+        let files_b = relative_file_paths(&root_b);
+
+        let mut comparison = DirectoryComparison {
+            only_in_a: files_a.difference(&files_b).cloned().collect(),
+            only_in_b: files_b.difference(&files_a).cloned().collect(),
+            ..Default::default()
+        };
+
+        let mut diffs = String::new();
+        for relative_path in files_a.intersection(&files_b) {
+            let path_a = root_a.path.join(relative_path);
+            let path_b = root_b.path.join(relative_path);
+            let bytes_a = read_file(&path_a)?;
+            let bytes_b = read_file(&path_b)?;
+            if bytes_a == bytes_b {
+                comparison.unchanged_count += 1;
+                continue;
+            }
+
+            let display_path = relative_path.to_string_lossy().replace('\\', "/");
+            let diff = match (String::from_utf8(bytes_a), String::from_utf8(bytes_b)) {
+                (Ok(text_a), Ok(text_b)) => TextDiff::from_lines(&text_a, &text_b)
+                    .unified_diff()
+                    .context_radius(DIFF_CONTEXT_LINES)
+                    .header(&format!("a/{}", display_path), &format!("b/{}", display_path))
+                    .to_string(),
+                _ => format!("Binary files a/{0} and b/{0} differ\n", display_path),
+            };
+            diffs.push_str(&render_diff_section(&display_path, diff.trim_end(), format));
+            diffs.push_str("\n\n");
+            comparison.changed.push(relative_path.clone());
+        }
+
+        let mut document = self.render_summary(&comparison, format);
+        document.push_str(&diffs);
+        write_file_atomically(output_path, document.trim_end().as_bytes())?;
+
+        info!(
+            "Compared {:?} with {:?}: {} changed, {} only in A, {} only in B, {} unchanged",
+            self.root_a, self.root_b,
+            comparison.changed.len(), comparison.only_in_a.len(), comparison.only_in_b.len(), comparison.unchanged_count
+        );
+        Ok(comparison)
+    }
+
+    fn scan(&self, root: &Path) -> Result<FileNode> {
+        debug!("Scanning {:?} for comparison", root);
+        FileHandler::new(root.to_path_buf())?.scan_directory(self.ignore_patterns.clone())
+    }
+
+    fn render_summary(&self, comparison: &DirectoryComparison, format: OutputFormat) -> String {
+        let formatter = format.formatter();
+        let bullet = formatter.list_bullet();
+        let code = |text: &str| formatter.inline_code(text);
+
+        let mut summary = format!("{}\n\n", formatter.heading("Directory Comparison", 1));
+        summary.push_str(&format!("{} A: {}\n", bullet, code(&self.root_a.display().to_string())));
+        summary.push_str(&format!("{} B: {}\n\n", bullet, code(&self.root_b.display().to_string())));
+        summary.push_str(&format!(
+            "{} changed, {} only in A, {} only in B, {} unchanged.\n\n",
+            comparison.changed.len(), comparison.only_in_a.len(), comparison.only_in_b.len(), comparison.unchanged_count
+        ));
+
+        for (name, paths) in [("Only in A", &comparison.only_in_a), ("Only in B", &comparison.only_in_b)] {
+            summary.push_str(&format!("{}\n\n", formatter.heading(name, 2)));
+            if paths.is_empty() {
+                summary.push_str("None.\n\n");
+                continue;
+            }
+            for path in paths {
+                summary.push_str(&format!("{} {}\n", bullet, code(&path.to_string_lossy().replace('\\', "/"))));
+            }
+            summary.push('\n');
+        }
+
+        summary.push_str(&format!("{}\n\n", formatter.heading("Changed Files", 2)));
+        if comparison.changed.is_empty() {
+            summary.push_str("None.\n\n");
+        }
+        summary
+    }
+}
+
+fn render_diff_section(display_path: &str, diff: &str, format: OutputFormat) -> String {
+    format.formatter().file_section(display_path, "diff", diff)
+}
+
+/// Files in the tree, relative to its root, sorted
+fn relative_file_paths(root_node: &FileNode) -> BTreeSet<PathBuf> {
+    let mut paths = BTreeSet::new();
+    let mut stack = vec![root_node];
+    while let Some(node) = stack.pop() {
+        if !node.is_dir {
+            if let Ok(relative_path) = node.path.strip_prefix(&root_node.path) {
+                paths.insert(relative_path.to_path_buf());
+            }
+        }
+        stack.extend(&node.children);
+    }
+    paths
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+    fs::read(path).map_err(|e| AppError::new_io_error(e, Some(path.to_path_buf()), "Failed to read file for comparison".to_string()))
+}
@@ -1,21 +1,96 @@
-use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::Write;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-use tempfile::NamedTempFile;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 use log::{debug, warn};
 
 use crate::constants::{
-    MARKDOWN_HEADER_CONTEXT, MARKDOWN_HEADER_STRUCTURE, MARKDOWN_HEADER_FILES, MARKDOWN_CODE_BLOCK,
-    ADOC_SECTION_LEVEL_1, ADOC_SECTION_LEVEL_2, ADOC_SECTION_LEVEL_3, ADOC_SOURCE_BLOCK_DELIMITER,
+    MARKDOWN_HEADER_STRUCTURE, RST_HEADING_CHARS, SHEBANG_MAX_BYTES, GENERATOR_MARKER, GENERATOR_MARKER_SEARCH_BYTES,
+    DOCUMENT_INDEX_EXTENSION, MARKDOWN_HEADER_TOC, MINIFIED_CONTENT_NOTICE, NOTEBOOK_CONTENT_NOTICE, OUTLINE_CONTENT_NOTICE, SCRATCH_LANGUAGE, SCRATCH_SECTION_TITLE, SUMMARY_CONTENT_NOTICE,
     OutputFormat
 };
+use crate::budget::{BudgetDecision, FileSizeLimits, LineRange, TokenBudget, chars_per_token, estimate_tokens, truncate_content};
+use crate::cargo_sources::dependency_sources;
+use crate::compression::{read_document, COMPRESSED_EXTENSION};
+use crate::document_model::Document;
+use crate::document_metadata::{DocumentStats, generated_time, metadata_block_range, metadata_time_now, render_metadata_block};
+use crate::document_template::{TemplateContext, TemplateFile, render_template};
 use crate::error::{AppError, Result};
+use crate::fence_language::fence_language;
 use crate::file_handler::FileNode;
+use crate::file_summaries::FileSummarizer;
+use crate::language_stats::{language_stats, render_summary};
+use crate::lifecycle::{LifecycleEvent, LifecycleObserver};
+use crate::minify::{CommentSyntax, minify};
+use crate::notebook::{is_notebook, notebook_cells};
+use crate::outline::outline;
+use crate::section_cache::{SectionCache, SectionOptions};
+use crate::section_merge::merge_sections;
+use crate::utils::{ensure_text_file, format_age, format_byte_size, format_token_count, looks_binary, read_file_consistently, relative_path};
+use crate::temp_files::create_temp_file_in;
+
+/// A heading in a generated document, as shown in the outline panel
+#[derive(Debug, Clone)]
+pub struct OutlineEntry {
+    pub title: String,
+    pub level: usize,
+    pub file_path: Option<PathBuf>, // Set for file sections
+    pub byte_offset: usize,         // Of the heading, within its document
+    pub line: usize,                // 1-based line of the heading
+    pub size_bytes: usize,          // From this heading up to the next one
+    pub line_count: usize,
+}
+
+/// File sections (rendered or raw content) keyed by file path, in output order
+type FileSections = Vec<(PathBuf, String)>;
+
+/// Files that get a section, with the character limit imposed by the token budget, if any
+type PlannedFiles = Vec<(PathBuf, Option<usize>)>;
+
+/// File contents captured in memory, used instead of reading the files at generation time
+pub type FileSnapshot = HashMap<PathBuf, Vec<u8>>;
 
 pub struct DocumentGenerator {
     directory: PathBuf,
     selected_files: HashSet<PathBuf>,
+    token_budget: Option<TokenBudget>,
+    file_size_limits: FileSizeLimits,
+    custom_header: Option<String>,
+    anchor_links: bool,
+    section_footers: bool,
+    snapshot: Option<Arc<FileSnapshot>>,
+    section_cache: Option<Arc<Mutex<SectionCache>>>,
+    dependency_sources: bool,
+    scratch_text: Option<String>,
+    document_index_path: Option<PathBuf>,
+    adoc_include_dir: Option<PathBuf>, // AsciiDoc: reference files relative to this directory instead of embedding them
+    markdown_link_dir: Option<PathBuf>, // Markdown: link to files relative to this directory instead of embedding them
+    line_anchors: bool,                 // Markdown links end in `#L1-L<lines>`
+    minified_extensions: Vec<String>,
+    outline_files: HashSet<PathBuf>, // Rendered as signatures only
+    summary_files: HashSet<PathBuf>, // Rendered as an LLM-written summary
+    file_summarizer: Option<FileSummarizer>,
+    line_ranges: HashMap<PathBuf, LineRange>, // Files included only in part
+    exclude_binary_files: bool,               // Otherwise they are listed with their size
+    notebook_markdown_cells: bool,            // Notebooks show Markdown cells as well as code
+    log_excerpts: HashMap<PathBuf, Vec<String>>, // Log lines shown below a file's content
+    external_files: HashSet<PathBuf>,             // Selected files outside `directory`, shown by full path
+    metadata_header: bool,                        // Metadata comment block below the title
+    language_summary: bool,                       // Summary section with the languages of the selection
+    fence_languages: Vec<(String, String)>,       // User patterns for code fence languages, e.g. `*.tpl` -> `html`
+    structure_section: bool,                      // Project Structure section
+    full_tree_structure: bool,                    // Structure shows every scanned entry, not just the selection
+    structure_max_depth: Option<usize>,           // Entries below this depth are summarized in an ellipsis row
+    structure_max_entries: Option<usize>,         // Entries shown per directory before an ellipsis row
+    structure_annotations: bool,                  // Structure entries show their size and age, e.g. `(14.2 KB, 2d ago)`
+    files_section: bool,                          // Files section with the file contents
+    read_outcomes: Arc<Mutex<HashMap<PathBuf, Option<String>>>>, // Files read from disk, with the error if reading failed
+    lifecycle: LifecycleObserver,
 }
 
 impl DocumentGenerator {
@@ -23,139 +98,859 @@ impl DocumentGenerator {
         Self {
             directory,
             selected_files: selected_files.into_iter().collect(),
+            token_budget: None,
+            file_size_limits: FileSizeLimits::default(),
+            custom_header: None,
+            anchor_links: false,
+            section_footers: false,
+            snapshot: None,
+            section_cache: None,
+            dependency_sources: false,
+            scratch_text: None,
+            document_index_path: None,
+            adoc_include_dir: None,
+            markdown_link_dir: None,
+            line_anchors: false,
+            minified_extensions: Vec::new(),
+            outline_files: HashSet::new(),
+            summary_files: HashSet::new(),
+            file_summarizer: None,
+            line_ranges: HashMap::new(),
+            exclude_binary_files: false,
+            notebook_markdown_cells: false,
+            log_excerpts: HashMap::new(),
+            external_files: HashSet::new(),
+            metadata_header: false,
+            language_summary: false,
+            fence_languages: Vec::new(),
+            structure_section: true,
+            full_tree_structure: false,
+            structure_max_depth: None,
+            structure_max_entries: None,
+            structure_annotations: false,
+            files_section: true,
+            read_outcomes: Arc::default(),
+            lifecycle: LifecycleObserver::default(),
         }
     }
 
+    /// Limits full generation to the given budget; files that don't fit are truncated or omitted
+    pub fn with_token_budget(mut self, token_budget: Option<TokenBudget>) -> Self {
+        self.token_budget = token_budget;
+        self
+    }
+
+    /// Truncates each file that exceeds the given line/byte limits, keeping its head and tail
+    pub fn with_file_size_limits(mut self, file_size_limits: FileSizeLimits) -> Self {
+        self.file_size_limits = file_size_limits;
+        self
+    }
+
+    /// Strips comments and collapses blank lines of files with these extensions (lowercase, no
+    /// dot), where the language is known. Such sections start with a notice saying so.
+    pub fn with_minified_extensions(mut self, minified_extensions: Vec<String>) -> Self {
+        self.minified_extensions = minified_extensions;
+        self
+    }
+
+    /// Renders these files as their declarations and signatures without function bodies, where
+    /// the language is supported and the file parses. Such sections start with a notice saying so.
+    pub fn with_outline_files(mut self, outline_files: HashSet<PathBuf>) -> Self {
+        self.outline_files = outline_files;
+        self
+    }
+
+    /// Replaces the content of these files with a short summary from the summarizer. If it
+    /// fails, such sections say why above the full content.
+    pub fn with_file_summaries(mut self, summary_files: HashSet<PathBuf>, file_summarizer: FileSummarizer) -> Self {
+        self.summary_files = summary_files;
+        self.file_summarizer = Some(file_summarizer);
+        self
+    }
+
+    /// Includes only the given lines of these files, noting the range at the top of the section
+    pub fn with_line_ranges(mut self, line_ranges: HashMap<PathBuf, LineRange>) -> Self {
+        self.line_ranges = line_ranges;
+        self
+    }
+
+    /// Leaves binary files out of the document instead of listing them as `binary, 1.2 MB, skipped`
+    pub fn with_binary_files_excluded(mut self, exclude_binary_files: bool) -> Self {
+        self.exclude_binary_files = exclude_binary_files;
+        self
+    }
+
+    /// Jupyter notebooks are always rendered as their cells instead of the notebook JSON; this
+    /// adds the Markdown cells to the code cells
+    pub fn with_notebook_markdown_cells(mut self, notebook_markdown_cells: bool) -> Self {
+        self.notebook_markdown_cells = notebook_markdown_cells;
+        self
+    }
+
+    /// Shows these compiler or test log excerpts below the content of their files
+    pub fn with_log_excerpts(mut self, log_excerpts: HashMap<PathBuf, Vec<String>>) -> Self {
+        self.log_excerpts = log_excerpts;
+        self
+    }
+
+    /// Allows these selected files from outside the project directory; their sections are
+    /// headed by the full path
+    pub fn with_external_files(mut self, external_files: HashSet<PathBuf>) -> Self {
+        self.external_files = external_files;
+        self
+    }
+
+    /// Text placed right below the document title; blank text is ignored
+    pub fn with_custom_header(mut self, custom_header: &str) -> Self {
+        let custom_header = custom_header.trim();
+        self.custom_header = (!custom_header.is_empty()).then(|| custom_header.to_string());
+        self
+    }
+
+    /// Adds a comment block below the title with the generation time, project path, git branch and
+    /// commit, file, line and token counts and the tool version. Not applied to custom templates.
+    pub fn with_metadata_header(mut self, metadata_header: bool) -> Self {
+        self.metadata_header = metadata_header;
+        self
+    }
+
+    /// Adds a Summary section after the project structure with the file count, line count and share
+    /// of the content of each language in the selection. Not applied to custom templates.
+    pub fn with_language_summary(mut self, language_summary: bool) -> Self {
+        self.language_summary = language_summary;
+        self
+    }
+
+    /// File name patterns (`*` matches any text) with the code fence language of matching files,
+    /// checked before the built-in names such as `Dockerfile`, extensions and shebangs
+    pub fn with_fence_languages(mut self, fence_languages: Vec<(String, String)>) -> Self {
+        self.fence_languages = fence_languages;
+        self
+    }
+
+    /// Without it the document skips the Project Structure section, e.g. for a files-only context
+    pub fn with_structure_section(mut self, structure_section: bool) -> Self {
+        self.structure_section = structure_section;
+        self
+    }
+
+    /// The structure section shows the whole scanned tree (minus gitignored entries) instead of
+    /// only the selected files and their directories; contents are still embedded for the
+    /// selection only. Directories a shallow scan left unloaded appear without their entries.
+    pub fn with_full_tree_structure(mut self, full_tree_structure: bool) -> Self {
+        self.full_tree_structure = full_tree_structure;
+        self
+    }
+
+    /// Caps the structure section for large trees: top-level entries are at depth 1, and
+    /// entries past either limit are summarized in a row such as `… and 42 more`. Limits apply
+    /// in tree order, so they may hide selected files whose sections are still included.
+    pub fn with_structure_limits(mut self, max_depth: Option<usize>, max_entries: Option<usize>) -> Self {
+        self.structure_max_depth = max_depth;
+        self.structure_max_entries = max_entries;
+        self
+    }
+
+    /// Annotates each structure entry with its size and the age of its last modification, as
+    /// scanned; directories show the total size and latest modification below them
+    pub fn with_structure_annotations(mut self, structure_annotations: bool) -> Self {
+        self.structure_annotations = structure_annotations;
+        self
+    }
+
+    /// Without it the document holds no file sections (nor dependency sources or omitted
+    /// files), e.g. for a structure-only context to discuss architecture
+    pub fn with_files_section(mut self, files_section: bool) -> Self {
+        self.files_section = files_section;
+        self
+    }
+
+    /// Markdown only: links structure entries to their file sections and adds a table of contents
+    pub fn with_anchor_links(mut self, anchor_links: bool) -> Self {
+        self.anchor_links = anchor_links;
+        self
+    }
+
+    /// Appends the line count and token estimate of the included content to each file section
+    pub fn with_section_footers(mut self, section_footers: bool) -> Self {
+        self.section_footers = section_footers;
+        self
+    }
+
+    /// Reads file contents from the snapshot where available, so files rewritten mid-generation
+    /// (e.g. by a running build) can't end up half-written in the document
+    pub fn with_snapshot(mut self, snapshot: Option<Arc<FileSnapshot>>) -> Self {
+        self.snapshot = snapshot;
+        self
+    }
+
+    /// Reuses sections rendered by earlier generations for files whose content hash is unchanged
+    pub fn with_section_cache(mut self, section_cache: Option<Arc<Mutex<SectionCache>>>) -> Self {
+        self.section_cache = section_cache;
+        if let Some(mut cache) = self.section_cache.as_ref().and_then(|cache| cache.lock().ok()) {
+            cache.use_directory(&self.directory);
+        }
+        self
+    }
+
+    /// Appends the cargo registry sources of the crates the selected Rust files `use`
+    pub fn with_dependency_sources(mut self, dependency_sources: bool) -> Self {
+        self.dependency_sources = dependency_sources;
+        self
+    }
+
+    /// Free text (e.g. an error log or stack trace) included as a pseudo-file section after the
+    /// selected files; blank text is ignored
+    pub fn with_scratch_text(mut self, scratch_text: &str) -> Self {
+        let scratch_text = scratch_text.trim();
+        self.scratch_text = (!scratch_text.is_empty()).then(|| scratch_text.to_string());
+        self
+    }
+
+    /// Writes a JSON index of the file sections' offsets here after each write of the document
+    pub fn with_document_index(mut self, document_index_path: Option<PathBuf>) -> Self {
+        self.document_index_path = document_index_path;
+        self
+    }
+
+    /// AsciiDoc only: file sections become `include::path[]` directives relative to the document's
+    /// directory, so Asciidoctor pulls in the current file contents at render time
+    pub fn with_adoc_includes(mut self, document_dir: Option<PathBuf>) -> Self {
+        self.adoc_include_dir = document_dir;
+        self
+    }
+
+    /// Markdown only: file sections become links relative to the document's directory, for wikis
+    /// that shouldn't embed whole files
+    pub fn with_markdown_links(mut self, document_dir: Option<PathBuf>) -> Self {
+        self.markdown_link_dir = document_dir;
+        self
+    }
+
+    /// Appends GitHub-style `#L1-L<lines>` line anchors to Markdown file links
+    pub fn with_line_anchors(mut self, line_anchors: bool) -> Self {
+        self.line_anchors = line_anchors;
+        self
+    }
+
+    /// Reports each generation's start, written file sections and outcome on this channel
+    pub fn with_lifecycle_sender(mut self, lifecycle_sender: Sender<LifecycleEvent>) -> Self {
+        self.lifecycle = LifecycleObserver::new(lifecycle_sender);
+        self
+    }
+
+    pub fn document_index_path(&self) -> Option<&Path> {
+        self.document_index_path.as_deref()
+    }
+
     pub fn generate_full_document(&self, root_node: &FileNode, output_path: &Path, format: OutputFormat) -> Result<()> {
+        self.lifecycle.observe_generation(output_path, |section_written| {
+            self.write_full_document(root_node, output_path, format, section_written)
+        })
+    }
+
+    fn write_full_document(&self, root_node: &FileNode, output_path: &Path, format: OutputFormat, section_written: &dyn Fn(&Path)) -> Result<()> {
         debug!("Generating full document ({:?}) for {} selected files to {:?}", format, self.selected_files.len(), output_path);
-        
+
+        // Reserve room for links to every selected file; only the files that get a section are linked
+        let linked_files = (self.anchor_links && self.files_section && format == OutputFormat::Markdown).then(|| self.sorted_selected_files());
+        let scratch_section = self.generate_scratch_section(format);
+        let summary = self.generate_summary_string(format);
+        let reserved_tokens = estimate_tokens(&self.generate_document_preamble(root_node, format, None, linked_files.as_deref(), summary.as_deref())?)
+            + scratch_section.as_deref().map_or(0, estimate_tokens);
+        let (planned_files, omitted_files) = self.plan_files(format, reserved_tokens)?;
+        let linked_files = linked_files.map(|_| planned_files.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>());
+        let preamble = self.generate_document_preamble(root_node, format, None, linked_files.as_deref(), summary.as_deref())?;
+
+        // Sections are read, rendered and written one at a time so memory use stays flat
+        // no matter how large the document gets
+        self.write_document_streamed(output_path, |writer| {
+            writer.write(&preamble)?;
+            for (i, (file_path, max_chars)) in planned_files.iter().enumerate() {
+                if i > 0 {
+                    writer.write("\n\n")?;
+                }
+                writer.write(&self.planned_file_section(file_path, *max_chars, format)?)?;
+                section_written(file_path);
+            }
+
+            if let Some(scratch_section) = &scratch_section {
+                if !planned_files.is_empty() {
+                    writer.write("\n\n")?;
+                }
+                writer.write(scratch_section)?;
+            }
+
+            if let Some(dependency_sources) = self.generate_dependency_sources_string(format)? {
+                writer.write("\n\n")?;
+                writer.write(&dependency_sources)?;
+            }
+
+            if !omitted_files.is_empty() {
+                writer.write("\n\n")?;
+                writer.write(&self.generate_omitted_files_string(&omitted_files, format)?)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Renders the whole document through a user-supplied Tera template instead of the
+    /// built-in layout. See `document_template::TemplateContext` for the available variables.
+    pub fn generate_templated_document(
+        &self,
+        root_node: &FileNode,
+        output_path: &Path,
+        format: OutputFormat,
+        template_path: &Path,
+    ) -> Result<()> {
+        self.lifecycle.observe_generation(output_path, |section_written| {
+            self.write_templated_document(root_node, output_path, format, template_path, section_written)
+        })
+    }
+
+    fn write_templated_document(
+        &self,
+        root_node: &FileNode,
+        output_path: &Path,
+        format: OutputFormat,
+        template_path: &Path,
+        section_written: &dyn Fn(&Path),
+    ) -> Result<()> {
+        debug!("Generating document ({:?}) from template {:?}", format, template_path);
+
+        let template_source = fs::read_to_string(template_path)
+            .map_err(|e| AppError::new_io_error(
+                e,
+                Some(template_path.to_path_buf()),
+                "Failed to read document template".to_string(),
+            ))?;
+
+        let structure = match self.structure_section {
+            true => self.generate_structure_lines(root_node, None)?,
+            false => String::new(),
+        };
+        // Reserve what the built-in layout reserves so both budget the same way
+        let preamble_tokens = estimate_tokens(&self.generate_document_preamble(root_node, format, None, None, None)?)
+            + self.scratch_text.as_deref().map_or(0, estimate_tokens);
+        let (file_contents, omitted_files) = self.collect_file_contents(format, preamble_tokens)?;
+
+        let file_paths: Vec<PathBuf> = file_contents.iter().map(|(path, _)| path.clone()).collect();
+        let mut files = Vec::with_capacity(file_contents.len());
+        for (file_path, content) in file_contents {
+            files.push(TemplateFile {
+                path: self.relative_display_path(&file_path)?,
+                language: self.fence_language(&file_path),
+                fence: format.formatter().code_fence(&content),
+                content: format.formatter().block_content(&content),
+            });
+        }
+        if let Some(content) = self.scratch_text.clone() {
+            files.push(TemplateFile {
+                path: SCRATCH_SECTION_TITLE.to_string(),
+                language: SCRATCH_LANGUAGE.to_string(),
+                fence: format.formatter().code_fence(&content),
+                content: format.formatter().block_content(&content),
+            });
+        }
+        let omitted_files = omitted_files.iter()
+            .map(|path| self.relative_display_path(path))
+            .collect::<Result<Vec<_>>>()?;
+
+        let template_context = TemplateContext {
+            project_name: self.directory.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            format: format.name().to_string(),
+            custom_header: self.custom_header.clone().unwrap_or_default(),
+            structure: format.formatter().block_content(&structure),
+            files,
+            omitted_files,
+            token_budget: self.token_budget.as_ref().map(|budget| budget.max_tokens),
+        };
+
+        // On top, as the template may start with anything
+        let content = format!("{}\n\n{}", format.formatter().comment(GENERATOR_MARKER), render_template(&template_source, &template_context)?);
+        self.atomic_write_document(output_path, &content)?;
+        file_paths.iter().for_each(|file_path| section_written(file_path));
+        Ok(())
+    }
+
+    /// Splits the document into numbered parts (`name_part1.md`, `name_part2.md`, ...) that each
+    /// stay under `max_tokens_per_chunk`. Every part repeats the project structure so it is
+    /// self-contained. Returns the written paths.
+    pub fn generate_chunked_documents(
+        &self,
+        root_node: &FileNode,
+        output_path: &Path,
+        format: OutputFormat,
+        max_tokens_per_chunk: usize,
+    ) -> Result<Vec<PathBuf>> {
+        self.lifecycle.observe_generation(output_path, |section_written| {
+            self.write_chunked_documents(root_node, output_path, format, max_tokens_per_chunk, section_written)
+        })
+    }
+
+    fn write_chunked_documents(
+        &self,
+        root_node: &FileNode,
+        output_path: &Path,
+        format: OutputFormat,
+        max_tokens_per_chunk: usize,
+        section_written: &dyn Fn(&Path),
+    ) -> Result<Vec<PathBuf>> {
+        debug!("Generating chunked document ({:?}) with at most {} tokens per part", format, max_tokens_per_chunk);
+
+        // The preamble length barely depends on the part numbers, so measure it once
+        let summary = self.generate_summary_string(format);
+        let preamble_tokens = estimate_tokens(&self.generate_document_preamble(root_node, format, None, None, summary.as_deref())?);
+        let (mut sections, omitted_files) = self.generate_file_sections(format, preamble_tokens)?;
+        if let Some(scratch_section) = self.generate_scratch_section(format) {
+            sections.push((PathBuf::from(SCRATCH_SECTION_TITLE), scratch_section));
+        }
+
+        // Greedily pack sections; a section larger than the limit gets a part of its own
+        let mut chunks: Vec<Vec<&(PathBuf, String)>> = vec![Vec::new()];
+        let mut chunk_tokens = preamble_tokens;
+        for entry in &sections {
+            let (_, section) = entry;
+            let section_tokens = estimate_tokens(section);
+            let current_chunk_used = chunks.last().is_some_and(|chunk| !chunk.is_empty());
+            if current_chunk_used && chunk_tokens + section_tokens > max_tokens_per_chunk {
+                chunks.push(Vec::new());
+                chunk_tokens = preamble_tokens;
+            }
+            chunk_tokens += section_tokens;
+            if let Some(chunk) = chunks.last_mut() {
+                chunk.push(entry);
+            }
+        }
+
+        let dependency_sources = self.generate_dependency_sources_string(format)?;
+        let total_parts = chunks.len();
+        let mut written_paths = Vec::with_capacity(total_parts);
+        for (i, chunk_sections) in chunks.iter().enumerate() {
+            let mut content = self.generate_document_preamble(root_node, format, Some((i + 1, total_parts)), None, summary.as_deref())?;
+            content.push_str(&chunk_sections.iter().map(|(_, section)| section.as_str()).collect::<Vec<_>>().join("\n\n"));
+
+            if let Some(dependency_sources) = dependency_sources.as_ref().filter(|_| i + 1 == total_parts) {
+                content.push_str("\n\n");
+                content.push_str(dependency_sources);
+            }
+            if i + 1 == total_parts && !omitted_files.is_empty() {
+                content.push_str("\n\n");
+                content.push_str(&self.generate_omitted_files_string(&omitted_files, format)?);
+            }
+
+            let part_path = chunk_output_path(output_path, i + 1);
+            self.atomic_write_document(&part_path, &content)?;
+            chunk_sections.iter().for_each(|(file_path, _)| section_written(file_path));
+            written_paths.push(part_path);
+        }
+
+        // Remove leftover parts from an earlier run that produced more chunks
+        for stale_path in (total_parts + 1..).map(|part| chunk_output_path(output_path, part)).take_while(|p| p.exists()) {
+            if let Err(e) = fs::remove_file(&stale_path) {
+                warn!("Failed to remove stale document part {:?}: {}", stale_path, e);
+            }
+        }
+
+        Ok(written_paths)
+    }
+
+    /// Context header, project structure, summary and the files header. `part` is `(index, total)` for chunked output.
+    /// With `linked_files` (Markdown only), those files are linked from the structure and listed in a table of contents.
+    fn generate_document_preamble(
+        &self,
+        root_node: &FileNode,
+        format: OutputFormat,
+        part: Option<(usize, usize)>,
+        linked_files: Option<&[PathBuf]>,
+        summary: Option<&str>,
+    ) -> Result<String> {
         let mut content = String::new();
-        
+
         // Context header
-        match format {
-            OutputFormat::Markdown => content.push_str(&format!("{}\n\n", MARKDOWN_HEADER_CONTEXT)),
-            OutputFormat::Adoc => content.push_str(&format!("{} {}\n\n", ADOC_SECTION_LEVEL_1, "Context")),
+        let part_suffix = part.map(|(index, total)| format!(" (part {} of {})", index, total)).unwrap_or_default();
+        let anchors = match linked_files {
+            Some(files) if format == OutputFormat::Markdown => Some(self.file_anchors(files, &part_suffix)?),
+            _ => None,
+        };
+        content.push_str(&format!("{}\n\n", format.formatter().heading(&format!("Context{}", part_suffix), 1)));
+        content.push_str(&format!("{}\n\n", format.formatter().comment(GENERATOR_MARKER)));
+
+        // Counts are filled in by `refresh_metadata_blocks` once the document is written
+        if self.metadata_header {
+            content.push_str(&render_metadata_block(&self.directory, format, &metadata_time_now(), None, &DocumentStats::default()));
+            content.push_str("\n\n");
+        }
+
+        // User-provided instructions, kept above everything generated from the project
+        if let Some(custom_header) = &self.custom_header {
+            content.push_str(custom_header);
+            content.push_str("\n\n");
         }
         
         // Project structure section
-        content.push_str(&self.generate_structure_string(root_node, format)?);
-        content.push_str("\n\n");
+        if self.structure_section {
+            content.push_str(&self.generate_structure_string(root_node, format, anchors.as_ref())?);
+            content.push_str("\n\n");
+        }
+
+        if let Some(summary) = summary {
+            content.push_str(summary);
+            content.push_str("\n\n");
+        }
+
+        // Table of contents, in section order
+        if let (Some(anchors), Some(files)) = (&anchors, linked_files) {
+            content.push_str(&format!("{}\n\n", MARKDOWN_HEADER_TOC));
+            for file_path in files {
+                content.push_str(&format!("- [{}](#{})\n", escape_html(&self.relative_display_path(file_path)?), anchors[file_path]));
+            }
+            content.push('\n');
+        }
         
         // Files section
-        match format {
-            OutputFormat::Markdown => content.push_str(&format!("{}\n\n", MARKDOWN_HEADER_FILES)),
-            OutputFormat::Adoc => content.push_str(&format!("{} {}\n\n", ADOC_SECTION_LEVEL_2, "Files")),
+        if self.files_section {
+            content.push_str(&format!("{}\n\n", format.formatter().heading("Files", 2)));
         }
-        content.push_str(&self.generate_files_string(format)?);
-        
-        self.atomic_write_document(output_path, &content)?;
 
-        Ok(())
+        Ok(content)
     }
 
-    pub fn generate_structure_string(&self, root_node: &FileNode, format: OutputFormat) -> Result<String> {
-        let mut structure_content = String::new();
+    /// With `anchors`, file entries link to their sections; the tree then goes into a `<pre>`
+    /// block because Markdown code blocks can't contain links
+    pub fn generate_structure_string(
+        &self,
+        root_node: &FileNode,
+        format: OutputFormat,
+        anchors: Option<&HashMap<PathBuf, String>>,
+    ) -> Result<String> {
+        let structure_lines = self.generate_structure_lines(root_node, anchors)?;
+        let structure_content = match anchors {
+            Some(_) if format == OutputFormat::Markdown => {
+                format!("{}\n<pre>\n{}</pre>", MARKDOWN_HEADER_STRUCTURE, structure_lines)
+            }
+            _ => format.formatter().structure_block(&structure_lines),
+        };
 
-        match format {
-            OutputFormat::Markdown => {
-                structure_content.push_str(&format!("{}\n", MARKDOWN_HEADER_STRUCTURE));
-                structure_content.push_str(&format!("{}\n", MARKDOWN_CODE_BLOCK));
-                
-                let mut structure_lines = String::new();
-                let mut is_last_child_stack = Vec::new();
-                
-                self.build_structure_string_recursive(
-                    root_node,
-                    &self.directory,
-                    &Path::new(""),
-                    0,
-                    &mut is_last_child_stack,
-                    &mut structure_lines,
-                    format
-                )?;
-                
-                structure_content.push_str(&structure_lines);
-                structure_content.push_str(&format!("{}", MARKDOWN_CODE_BLOCK));
-            },
-            OutputFormat::Adoc => {
-                structure_content.push_str(&format!("{} {}\n", ADOC_SECTION_LEVEL_2, "Project Structure"));
-                structure_content.push_str("[source, text]\n");
-                structure_content.push_str(&format!("{}\n", ADOC_SOURCE_BLOCK_DELIMITER));
-                
-                let mut structure_lines = String::new();
-                let mut is_last_child_stack = Vec::new();
-                
-                self.build_structure_string_recursive(
-                    root_node,
-                    &self.directory,
-                    &Path::new(""),
-                    0,
-                    &mut is_last_child_stack,
-                    &mut structure_lines,
-                    format
-                )?;
-                structure_content.push_str(&structure_lines);
-                
-                structure_content.push_str(&format!("{}", ADOC_SOURCE_BLOCK_DELIMITER));
+        Ok(structure_content)
+    }
+
+    /// The bare tree lines of the structure section, each ending with a newline.
+    /// With `anchors`, names are HTML-escaped and linked files become `<a>` elements.
+    fn generate_structure_lines(&self, root_node: &FileNode, anchors: Option<&HashMap<PathBuf, String>>) -> Result<String> {
+        let mut structure_lines = String::new();
+        let mut is_last_child_stack = Vec::new();
+
+        self.build_structure_string_recursive(
+            root_node,
+            &self.directory,
+            Path::new(""),
+            0,
+            &mut is_last_child_stack,
+            &mut structure_lines,
+            anchors
+        )?;
+
+        Ok(structure_lines)
+    }
+
+    /// GitHub-style anchors of the given file sections. Every heading before them is run
+    /// through the same slugger so that repeated slugs get the same `-1`, `-2` suffixes as on GitHub.
+    fn file_anchors(&self, files: &[PathBuf], part_suffix: &str) -> Result<HashMap<PathBuf, String>> {
+        let mut slugger = AnchorSlugger::default();
+        slugger.slug(&format!("Context{}", part_suffix));
+        if let Some(custom_header) = &self.custom_header {
+            for line in custom_header.lines() {
+                let level = line.chars().take_while(|c| *c == '#').count();
+                if let Some(title) = line[level..].strip_prefix(' ').filter(|_| level > 0) {
+                    slugger.slug(title);
+                }
             }
         }
+        slugger.slug("Project Structure");
+        if self.language_summary {
+            slugger.slug("Summary");
+        }
+        for heading in ["Table of Contents", "Files"] {
+            slugger.slug(heading);
+        }
 
-        Ok(structure_content)
+        let mut anchors = HashMap::with_capacity(files.len());
+        for file_path in files {
+            anchors.insert(file_path.clone(), slugger.slug(&self.relative_display_path(file_path)?));
+        }
+        Ok(anchors)
     }
 
-    pub fn generate_files_string(&self, format: OutputFormat) -> Result<String> {
-        let mut content = String::new();
-        
-        // Sort selected files for consistent output
-        let mut sorted_files: Vec<_> = self.selected_files.iter().collect();
+    /// The Summary section over the selected text files, each read as its section shows it
+    fn generate_summary_string(&self, format: OutputFormat) -> Option<String> {
+        if !self.language_summary {
+            return None;
+        }
+        let contents: Vec<(PathBuf, String)> = self.sorted_selected_files().into_iter()
+            .filter(|path| self.check_text_file(path).is_ok())
+            .map(|path| {
+                let content = self.read_file_content(&path);
+                (path, content)
+            })
+            .collect();
+        let stats = language_stats(contents.iter().map(|(path, content)| (path.as_path(), content.as_str())));
+        Some(render_summary(&stats, format))
+    }
+
+    /// Selected files in output order
+    fn sorted_selected_files(&self) -> Vec<PathBuf> {
+        let mut sorted_files: Vec<PathBuf> = self.selected_files.iter()
+            .filter(|path| !self.exclude_binary_files || self.check_text_file(path).is_ok())
+            .cloned()
+            .collect();
         sorted_files.sort();
-        
-        for (i, file_path) in sorted_files.iter().enumerate() {
-            if i > 0 {
-                content.push_str("\n\n");
+        sorted_files
+    }
+
+    /// Rendered section per selected file, sorted by path, plus the files omitted by the token budget.
+    /// When a token budget is set, `reserved_tokens` is subtracted from it first.
+    fn generate_file_sections(&self, format: OutputFormat, reserved_tokens: usize) -> Result<(FileSections, Vec<PathBuf>)> {
+        let (planned_files, omitted_files) = self.plan_files(format, reserved_tokens)?;
+
+        let mut sections = Vec::with_capacity(planned_files.len());
+        for (file_path, max_chars) in planned_files {
+            let section = self.planned_file_section(&file_path, max_chars, format)?;
+            sections.push((file_path, section));
+        }
+
+        Ok((sections, omitted_files))
+    }
+
+    /// Content per selected file, sorted by path, after limits and the token budget are applied
+    fn collect_file_contents(&self, format: OutputFormat, reserved_tokens: usize) -> Result<(FileSections, Vec<PathBuf>)> {
+        let (planned_files, omitted_files) = self.plan_files(format, reserved_tokens)?;
+
+        let mut file_contents = Vec::with_capacity(planned_files.len());
+        for (file_path, max_chars) in planned_files {
+            let content = self.planned_file_content(&file_path, max_chars)?;
+            file_contents.push((file_path, content));
+        }
+
+        Ok((file_contents, omitted_files))
+    }
+
+    /// Selected files that get a section, sorted by path, plus the files omitted by the token budget.
+    /// Only token estimates are kept while planning, so with a budget every file is read twice
+    /// (the section cache saves re-rendering unchanged files).
+    fn plan_files(&self, format: OutputFormat, reserved_tokens: usize) -> Result<(PlannedFiles, Vec<PathBuf>)> {
+        if !self.files_section {
+            return Ok((Vec::new(), Vec::new()));
+        }
+        // Sort selected files for consistent output
+        let sorted_files = self.sorted_selected_files();
+
+        let Some(budget) = &self.token_budget else {
+            return Ok((sorted_files.into_iter().map(|path| (path, None)).collect(), Vec::new()));
+        };
+
+        let mut file_tokens = Vec::with_capacity(sorted_files.len());
+        for file_path in sorted_files {
+            let (_, tokens) = self.render_file_cached(&file_path, format)?;
+            file_tokens.push((file_path, tokens));
+        }
+        let decisions = budget.plan(&file_tokens, reserved_tokens);
+
+        let mut planned_files = Vec::with_capacity(file_tokens.len());
+        let mut omitted_files = Vec::new();
+        for (path, _) in file_tokens {
+            match decisions.get(&path) {
+                Some(BudgetDecision::Omit) => omitted_files.push(path),
+                Some(BudgetDecision::Truncate { max_chars }) => planned_files.push((path, Some(*max_chars))),
+                _ => planned_files.push((path, None)),
             }
-            content.push_str(&self.generate_file_string(file_path, format)?);
         }
-        
-        Ok(content)
+        debug!("Token budget of {} tokens omitted {} files", budget.max_tokens, omitted_files.len());
+
+        Ok((planned_files, omitted_files))
+    }
+
+    fn planned_file_section(&self, file_path: &Path, max_chars: Option<usize>, format: OutputFormat) -> Result<String> {
+        match max_chars {
+            Some(_) => {
+                let content = self.planned_file_content(file_path, max_chars)?;
+                self.render_file_section(file_path, &content, format)
+            }
+            None => Ok(self.render_file_cached(file_path, format)?.0),
+        }
+    }
+
+    fn planned_file_content(&self, file_path: &Path, max_chars: Option<usize>) -> Result<String> {
+        let content = self.read_file_content(file_path);
+        Ok(match max_chars {
+            Some(max_chars) => truncate_content(&content, max_chars),
+            None => content,
+        })
     }
 
     pub fn generate_file_string(&self, file_path: &Path, format: OutputFormat) -> Result<String> {
-        let relative_path = file_path.strip_prefix(&self.directory)
-            .map_err(|_| AppError::StripPrefixError {
-                prefix: self.directory.clone(),
-                path: file_path.to_path_buf(),
-            })?;
-        
-        // Use forward slashes for cross-platform consistency
-        let display_path = relative_path.to_string_lossy().replace('\\', "/");
-        let extension = self.get_file_extension(file_path);
-        let content = self.read_file_content(file_path, format)?;
-        
-        match format {
-            OutputFormat::Markdown => {
-                Ok(format!(
-                    "### {}\n\n{}{}\n{}\n{}",
-                    display_path,
-                    MARKDOWN_CODE_BLOCK,
-                    extension,
-                    content,
-                    MARKDOWN_CODE_BLOCK
-                ))
-            },
-            OutputFormat::Adoc => {
-                Ok(format!(
-                    "{} {}\n\n{}[source, {}]\n{}\n{}\n{}",
-                    ADOC_SECTION_LEVEL_3,
-                    display_path,
-                    "",
-                    extension,
-                    ADOC_SOURCE_BLOCK_DELIMITER,
-                    content,
-                    ADOC_SOURCE_BLOCK_DELIMITER
-                ))
+        Ok(self.render_file_cached(file_path, format)?.0)
+    }
+
+    /// Rendered section of a file plus the token estimate of its content. Taken from the section
+    /// cache when the file's content hash is unchanged; the file is read either way.
+    fn render_file_cached(&self, file_path: &Path, format: OutputFormat) -> Result<(String, usize)> {
+        if let Some(section) = self.render_file_reference(file_path, format)? {
+            let tokens = estimate_tokens(&section);
+            return Ok((section, tokens));
+        }
+        if let Err(AppError::BinaryFile { size_bytes, .. }) = self.check_text_file(file_path) {
+            let display_path = self.relative_display_path(file_path)?;
+            let section = render_listing_entry(&display_path, &binary_file_note(size_bytes), format);
+            return Ok((section, 0));
+        }
+
+        let bytes = self.read_file_bytes(file_path);
+        // Annotated sections are few and change with every pasted log, so they are not cached.
+        // Summaries have their own cache, and a failed one must not be kept.
+        let section_cache = self.section_cache.as_ref()
+            .filter(|_| !self.log_excerpts.contains_key(file_path) && !self.summary_files.contains(file_path));
+        let Some(section_cache) = section_cache else {
+            let content = self.decode_file_content(file_path, bytes);
+            return Ok((self.render_file_section(file_path, &content, format)?, estimate_tokens(&content)));
+        };
+
+        let content_hash = blake3::hash(&bytes);
+        let options = SectionOptions {
+            format,
+            file_size_limits: self.file_size_limits,
+            footer: self.section_footers,
+            minified: self.comment_syntax(file_path).is_some(),
+            outline: self.outline_files.contains(file_path),
+            line_range: self.line_ranges.get(file_path).copied(),
+            notebook_markdown: is_notebook(file_path) && self.notebook_markdown_cells,
+            fence_languages: self.fence_languages_hash(),
+            chars_per_token: chars_per_token(),
+        };
+        let cached = section_cache.lock().ok()
+            .and_then(|cache| cache.get(file_path, &content_hash, &options));
+        if let Some(cached) = cached {
+            return Ok(cached);
+        }
+
+        let content = self.decode_file_content(file_path, bytes);
+        let content_tokens = estimate_tokens(&content);
+        let section = self.render_file_section(file_path, &content, format)?;
+        if let Ok(mut cache) = section_cache.lock() {
+            cache.insert(file_path, content_hash, options, &section, content_tokens);
+        }
+        Ok((section, content_tokens))
+    }
+
+    /// A section that references the file instead of embedding it: an AsciiDoc `include::`
+    /// directive or a Markdown link, relative to the document. `None` when embedding.
+    fn render_file_reference(&self, file_path: &Path, format: OutputFormat) -> Result<Option<String>> {
+        match (format, &self.adoc_include_dir, &self.markdown_link_dir) {
+            (OutputFormat::Adoc, Some(include_dir), _) => {
+                // Nothing to read: the directive stays the same whatever the file contains
+                let directive = format!("include::{}[]", relative_path(include_dir, file_path));
+                Ok(Some(self.render_file_block(file_path, &directive, format)?))
             }
+            (OutputFormat::Markdown, _, Some(link_dir)) => {
+                let display_path = self.relative_display_path(file_path)?;
+                let mut target = relative_path(link_dir, file_path)
+                    .replace(' ', "%20")
+                    .replace('(', "%28")
+                    .replace(')', "%29");
+                if let Some(line_range) = self.line_ranges.get(file_path) {
+                    target.push_str(&format!("#L{}-L{}", line_range.start, line_range.end));
+                } else if self.line_anchors {
+                    let bytes = self.read_file_bytes(file_path);
+                    let line_count = String::from_utf8_lossy(&bytes).lines().count();
+                    target.push_str(&format!("#L1-L{}", line_count.max(1)));
+                }
+                Ok(Some(format!("### {}\n\n[{}]({})", display_path, display_path, target)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn render_file_section(&self, file_path: &Path, content: &str, format: OutputFormat) -> Result<String> {
+        let mut section = self.render_file_block(file_path, content, format)?;
+        if let Some(excerpts) = self.log_excerpts.get(file_path).filter(|excerpts| !excerpts.is_empty()) {
+            section.push_str("\n\n");
+            section.push_str(&render_log_excerpts(excerpts, format));
+        }
+        if self.section_footers {
+            let line_count = content.lines().count();
+            let footer = format!(
+                "{} {}, {} tokens",
+                line_count,
+                if line_count == 1 { "line" } else { "lines" },
+                format_token_count(estimate_tokens(content))
+            );
+            section.push_str(&format!("\n\n{}", format.formatter().emphasis(&footer)));
+        }
+        Ok(section)
+    }
+
+    fn render_file_block(&self, file_path: &Path, content: &str, format: OutputFormat) -> Result<String> {
+        let display_path = self.relative_display_path(file_path)?;
+        Ok(format.formatter().file_section(&display_path, &self.fence_language(file_path), content))
+    }
+
+    fn generate_scratch_section(&self, format: OutputFormat) -> Option<String> {
+        let scratch_text = self.scratch_text.as_deref()?;
+        Some(format.formatter().file_section(SCRATCH_SECTION_TITLE, SCRATCH_LANGUAGE, scratch_text))
+    }
+
+    /// Read-only sources of the external crates the selected Rust files use, or `None` if
+    /// disabled or nothing resolved. They sit outside the budget and are not kept up to date
+    /// by partial updates; registry sources never change for a given version anyway.
+    fn generate_dependency_sources_string(&self, format: OutputFormat) -> Result<Option<String>> {
+        if !self.dependency_sources || !self.files_section {
+            return Ok(None);
+        }
+        let sources = dependency_sources(&self.sorted_selected_files(), &self.directory);
+        if sources.is_empty() {
+            return Ok(None);
         }
+
+        let mut content = format!("{}\n\n", format.formatter().heading("Dependency Sources", 2));
+        content.push_str("Read-only sources of external crates used by the selected files, from the cargo registry.");
+        for source in sources {
+            let file_content = self.read_file_content(&source.path);
+            content.push_str("\n\n");
+            content.push_str(&format.formatter().file_section(&source.display_path, &self.fence_language(&source.path), &file_content));
+        }
+        Ok(Some(content))
+    }
+
+    fn generate_omitted_files_string(&self, omitted_files: &[PathBuf], format: OutputFormat) -> Result<String> {
+        let max_tokens = self.token_budget.as_ref().map(|b| b.max_tokens).unwrap_or_default();
+        let mut content = format!("{}\n\n", format.formatter().heading("Omitted Files", 2));
+        content.push_str(&format!(
+            "The following selected files were omitted to stay within the token budget of {} tokens:\n\n",
+            max_tokens
+        ));
+
+        let bullet = format.formatter().list_bullet();
+        for file_path in omitted_files {
+            content.push_str(&format!("{} {}\n", bullet, self.relative_display_path(file_path)?));
+        }
+
+        Ok(content.trim_end().to_string())
+    }
+
+    /// Path relative to the project directory, with forward slashes for cross-platform consistency
+    fn relative_display_path(&self, file_path: &Path) -> Result<String> {
+        let relative_path = match file_path.strip_prefix(&self.directory) {
+            Ok(relative_path) => relative_path,
+            Err(_) if self.external_files.contains(file_path) => file_path,
+            Err(_) => return Err(AppError::StripPrefixError {
+                prefix: self.directory.clone(),
+                path: file_path.to_path_buf(),
+            }),
+        };
+
+        Ok(relative_path.to_string_lossy().replace('\\', "/"))
     }
 
     fn build_structure_string_recursive(
@@ -166,7 +961,7 @@ impl DocumentGenerator {
         depth: usize,
         is_last_child_stack: &mut Vec<bool>,
         output: &mut String,
-        _format: OutputFormat
+        anchors: Option<&HashMap<PathBuf, String>>
     ) -> Result<()> {
         if depth == 0 {
             // Root directory
@@ -174,31 +969,49 @@ impl DocumentGenerator {
                 .map(|n| n.to_string_lossy().to_string())
                 .unwrap_or_else(|| "root".to_string());
 
+            let root_name = if anchors.is_some() { escape_html(&root_name) } else { root_name };
             output.push_str(&format!("{}\n", root_name));
         } else {
             let prefix = self.get_branch_prefix(depth, is_last_child_stack);
             let is_last = is_last_child_stack.last().copied().unwrap_or(false);
             let connector = if is_last { "└── " } else { "├── " };
-            
-            output.push_str(&format!("{}{}{}", prefix, connector, node.name));
+
+            let name = match anchors {
+                Some(anchors) => match anchors.get(&node.path) {
+                    Some(anchor) => format!("<a href=\"#{}\">{}</a>", anchor, escape_html(&node.name)),
+                    None => escape_html(&node.name),
+                },
+                None => node.name.clone(),
+            };
+            output.push_str(&format!("{}{}{}", prefix, connector, name));
             if node.is_dir {
                 output.push('/');
             }
+            if self.structure_annotations {
+                output.push_str(&format!(" ({})", structure_annotation(node)));
+            }
             output.push('\n');
         }
 
         if node.is_dir {
-            // Filter children: only include directories that contain selected files, or selected files themselves
+            // Filter children: only include directories that contain selected files, or selected files themselves,
+            // unless the whole tree is shown
             let children_to_render: Vec<&FileNode> = node.children.iter()
                 .filter(|child_node| {
+                    (self.full_tree_structure && !child_node.is_gitignored) ||
                     self.selected_files.contains(&child_node.path) ||
                     (child_node.is_dir && self.directory_contains_selected_file(child_node))
                 })
                 .collect();
 
-            let num_children_to_render = children_to_render.len();
-            for (i, child) in children_to_render.iter().enumerate() {
-                is_last_child_stack.push(i == num_children_to_render - 1);
+            let limit = match self.structure_max_depth {
+                Some(max_depth) if depth >= max_depth => 0,
+                _ => self.structure_max_entries.unwrap_or(usize::MAX),
+            };
+            let num_children_to_render = children_to_render.len().min(limit);
+            let num_hidden = children_to_render.len() - num_children_to_render;
+            for (i, child) in children_to_render.iter().take(num_children_to_render).enumerate() {
+                is_last_child_stack.push(i == num_children_to_render - 1 && num_hidden == 0);
                 let child_relative_path = _current_relative_path.join(&child.name);
                 self.build_structure_string_recursive(
                     child,
@@ -207,10 +1020,17 @@ impl DocumentGenerator {
                     depth + 1,
                     is_last_child_stack,
                     output,
-                    _format
+                    anchors
                 )?;
                 is_last_child_stack.pop();
             }
+            if num_hidden > 0 {
+                let prefix = self.get_branch_prefix(depth + 1, is_last_child_stack);
+                match num_children_to_render {
+                    0 => output.push_str(&format!("{}└── … {} {}\n", prefix, num_hidden, if num_hidden == 1 { "entry" } else { "entries" })),
+                    _ => output.push_str(&format!("{}└── … and {} more\n", prefix, num_hidden)),
+                }
+            }
         }
 
         Ok(())
@@ -247,65 +1067,174 @@ impl DocumentGenerator {
         false
     }
 
-    fn read_file_content(&self, file_path: &Path, format: OutputFormat) -> Result<String> {
-        let bytes = fs::read(file_path)
-            .map_err(|e| AppError::new_io_error(
-                e,
-                Some(file_path.to_path_buf()),
-                "Failed to read file".to_string(),
-            ))?;
+    /// The file's content as its section would show it: binary files as a note, text decoded,
+    /// cut, minified or outlined as configured
+    pub fn read_file_content(&self, file_path: &Path) -> String {
+        if let Err(AppError::BinaryFile { size_bytes, .. }) = self.check_text_file(file_path) {
+            return format!("[{}]", binary_file_note(size_bytes));
+        }
+        let bytes = self.read_file_bytes(file_path);
+        self.decode_file_content(file_path, bytes)
+    }
+
+    /// `AppError::BinaryFile` for binary files, judged by their snapshot if there is one and
+    /// otherwise by the start of the file, before it is read
+    fn check_text_file(&self, file_path: &Path) -> Result<()> {
+        match self.snapshot.as_ref().and_then(|snapshot| snapshot.get(file_path)) {
+            Some(bytes) if looks_binary(bytes) => Err(AppError::BinaryFile {
+                path: file_path.to_path_buf(),
+                size_bytes: bytes.len() as u64,
+            }),
+            Some(_) => Ok(()),
+            None => ensure_text_file(file_path),
+        }
+    }
 
+    /// Bytes of the file from the snapshot or disk. A file that can't be read consistently (it
+    /// vanished, or keeps changing) yields a placeholder naming the error, so one file being
+    /// rewritten doesn't abort the whole document.
+    fn read_file_bytes(&self, file_path: &Path) -> Vec<u8> {
+        if let Some(bytes) = self.snapshot.as_ref().and_then(|snapshot| snapshot.get(file_path)) {
+            return bytes.clone();
+        }
+        let result = read_file_consistently(file_path);
+        if let Ok(mut read_outcomes) = self.read_outcomes.lock() {
+            read_outcomes.insert(file_path.to_path_buf(), result.as_ref().err().map(ToString::to_string));
+        }
+        result.unwrap_or_else(|e| {
+            warn!("Emitting a placeholder for {:?}: {}", file_path, e);
+            format!("[ERROR: This file could not be read: {}]", e).into_bytes()
+        })
+    }
+
+    /// Files read from disk since the last call, each with its read error if it had one
+    pub fn take_read_outcomes(&self) -> HashMap<PathBuf, Option<String>> {
+        self.read_outcomes.lock().map(|mut read_outcomes| std::mem::take(&mut *read_outcomes)).unwrap_or_default()
+    }
+
+    /// Sanitized, size-limited text of a file's bytes
+    fn decode_file_content(&self, file_path: &Path, bytes: Vec<u8>) -> String {
         match String::from_utf8(bytes) {
-            Ok(content) => {
-                // Sanitize content to prevent markdown issues
-                let sanitized = match format {
-                    OutputFormat::Markdown => content.replace("```", r"\`\`\`"),
-                    OutputFormat::Adoc => content.replace("----", "\\----"),
-                };
-                Ok(sanitized.trim().to_string())
-            }
+            Ok(content) => self.prepare_content(file_path, &content),
             Err(e) => {
                 warn!("File {:?} contains non-UTF8 content, using lossy conversion", file_path);
                 let bytes = e.into_bytes();
-                let content = String::from_utf8_lossy(&bytes);
-                let sanitized = match format {
-                    OutputFormat::Markdown => content.replace("```", r"\`\`\`"),
-                    OutputFormat::Adoc => content.replace("----", "\\----"),
-                };
-                Ok(format!(
+                format!(
                     "[WARNING: This file contained non-UTF8 content and was converted with potential data loss]\n\n{}",
-                    sanitized.trim()
-                ))
+                    self.prepare_content(file_path, &String::from_utf8_lossy(&bytes))
+                )
+            }
+        }
+    }
+
+    /// Notebooks as their cells; then summarized, cut to the line range, outlined or minified where
+    /// configured, and size-limited
+    fn prepare_content(&self, file_path: &Path, content: &str) -> String {
+        if is_notebook(file_path) {
+            if let Some(cells) = notebook_cells(content, self.notebook_markdown_cells) {
+                return format!("{}\n\n{}", NOTEBOOK_CONTENT_NOTICE, self.prepare_text(file_path, &cells));
+            }
+        }
+        self.prepare_text(file_path, content)
+    }
+
+    fn prepare_text(&self, file_path: &Path, content: &str) -> String {
+        let summarizer = self.file_summarizer.as_ref().filter(|_| self.summary_files.contains(file_path));
+        let Some(summarizer) = summarizer else {
+            return self.prepare_full_text(file_path, content);
+        };
+        match summarizer.summarize(file_path, content) {
+            Ok(Some(summary)) => format!("{}\n\n{}", SUMMARY_CONTENT_NOTICE, summary),
+            Ok(None) => self.prepare_full_text(file_path, content),
+            Err(e) => {
+                warn!("No summary of {:?}: {}", file_path, e);
+                format!("[NOTE: Summary unavailable ({}), full content follows]\n\n{}", e, self.prepare_full_text(file_path, content))
+            }
+        }
+    }
+
+    fn prepare_full_text(&self, file_path: &Path, content: &str) -> String {
+        if let Some(line_range) = self.line_ranges.get(file_path) {
+            let (lines, included, total) = line_range.apply(content);
+            let excerpt = lines.join("\n");
+            return format!(
+                "[NOTE: Lines {}–{} of {}]\n\n{}",
+                included.start, included.end, total,
+                self.file_size_limits.apply(excerpt.trim_start_matches('\n').trim_end())
+            );
+        }
+        if let Some(outline) = self.outline_files.contains(file_path).then(|| outline(file_path, content)).flatten() {
+            return format!("{}\n\n{}", OUTLINE_CONTENT_NOTICE, self.file_size_limits.apply(outline.trim()));
+        }
+        let Some(syntax) = self.comment_syntax(file_path) else {
+            return self.file_size_limits.apply(content.trim());
+        };
+        let minified = minify(content, syntax);
+        format!("{}\n\n{}", MINIFIED_CONTENT_NOTICE, self.file_size_limits.apply(minified.trim()))
+    }
+
+    fn comment_syntax(&self, file_path: &Path) -> Option<CommentSyntax> {
+        let extension = file_path.extension()?.to_str()?.to_lowercase();
+        if !self.minified_extensions.contains(&extension) {
+            return None;
+        }
+        CommentSyntax::for_path(file_path)
+    }
+
+    fn fence_language(&self, file_path: &Path) -> String {
+        if is_notebook(file_path) {
+            return "python".to_string(); // Code cells, in `# %%` form
+        }
+        fence_language(file_path, || self.first_line(file_path), &self.fence_languages)
+    }
+
+    /// The start of the file's first line, from the snapshot or a bounded read of its head; a file
+    /// that can't be read has none here, its section reports the error
+    fn first_line(&self, file_path: &Path) -> Option<String> {
+        let mut line = Vec::new();
+        match self.snapshot.as_ref().and_then(|snapshot| snapshot.get(file_path)) {
+            Some(bytes) => line.extend(bytes.iter().take(SHEBANG_MAX_BYTES as usize).take_while(|&&byte| byte != b'\n')),
+            None => {
+                let file = fs::File::open(file_path).ok()?;
+                BufReader::new(file.take(SHEBANG_MAX_BYTES)).read_until(b'\n', &mut line).ok()?;
             }
         }
+        String::from_utf8_lossy(&line).lines().next().map(str::to_string)
     }
 
-    fn get_file_extension(&self, file_path: &Path) -> String {
-        file_path.extension()
-            .and_then(|ext| ext.to_str())
-            .unwrap_or("")
-            .to_string()
+    /// Identifies the user's fence language patterns in cached sections
+    fn fence_languages_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.fence_languages.hash(&mut hasher);
+        hasher.finish()
     }
 
     pub fn atomic_write_document(&self, output_path: &Path, content: &str) -> Result<()> {
+        self.write_document_streamed(output_path, |writer| writer.write(content))
+    }
+
+    /// Writes the document through a buffered temp file that replaces `output_path` only once
+    /// `write_content` succeeded; on error the temp file is removed and the old document kept
+    fn write_document_streamed(
+        &self,
+        output_path: &Path,
+        write_content: impl FnOnce(&mut DocumentWriter) -> Result<()>,
+    ) -> Result<()> {
         let parent_dir = output_path.parent().ok_or_else(|| AppError::AtomicWriteError {
             path: output_path.to_path_buf(),
             details: "Could not get parent directory for temp file.".to_string(),
         })?;
 
-        let mut temp_file = NamedTempFile::new_in(parent_dir)
-            .map_err(|e| AppError::new_io_error(
-                e,
-                None,
-                "Failed to create temp file for atomic write.".to_string(),
-            ))?;
-
-        temp_file.write_all(content.as_bytes())
-            .map_err(|e| AppError::new_io_error(
-                e,
-                Some(temp_file.path().to_path_buf()),
-                "Failed to write to temp file.".to_string(),
-            ))?;
+        let mut temp_file = create_temp_file_in(parent_dir)?;
+        let temp_path = temp_file.path().to_path_buf();
+        {
+            let mut writer = DocumentWriter {
+                writer: BufWriter::new(temp_file.as_file_mut()),
+                temp_path: &temp_path,
+            };
+            write_content(&mut writer)?;
+            writer.flush()?;
+        }
 
         temp_file.persist(output_path)
             .map_err(|e| AppError::AtomicWriteError {
@@ -317,6 +1246,219 @@ impl DocumentGenerator {
         Ok(())
     }
 
+    /// Fills in the metadata block of each document with the counts of what was written. After a
+    /// full generation (`generated`) the block gets the current time as generation time; partial
+    /// updates keep it and add the update time. The block is patched in place when its length is
+    /// unchanged. Documents without a block are left alone.
+    pub fn refresh_metadata_blocks(&self, document_paths: &[PathBuf], format: OutputFormat, generated: bool) -> Result<()> {
+        if !self.metadata_header {
+            return Ok(());
+        }
+        for document_path in document_paths {
+            let read_modified = fs::metadata(document_path).and_then(|metadata| metadata.modified()).ok();
+            let content = fs::read_to_string(document_path)
+                .map_err(|e| AppError::new_io_error(
+                    e,
+                    Some(document_path.to_path_buf()),
+                    "Failed to read document for its metadata".to_string(),
+                ))?;
+            let Some((start, end)) = metadata_block_range(&content, format) else {
+                continue;
+            };
+
+            let old_block = &content[start..end];
+            let now = metadata_time_now();
+            let (generated_at, updated_at) = match generated_time(old_block).filter(|_| !generated) {
+                Some(generated_at) => (generated_at, Some(now)),
+                None => (now, None),
+            };
+            let stats = DocumentStats {
+                files: self.text_outline(&content, format).iter()
+                    .filter(|entry| entry.file_path.is_some() && entry.title != SCRATCH_SECTION_TITLE)
+                    .count(),
+                lines: content.lines().count(),
+                tokens: estimate_tokens(&content),
+            };
+            let block = render_metadata_block(&self.directory, format, &generated_at, updated_at.as_deref(), &stats);
+
+            if block.len() == old_block.len() {
+                if block != old_block && !self.write_in_place(document_path, &content, read_modified, start, &block)? {
+                    debug!("{:?} changed since it was read, its metadata is refreshed by the next update", document_path);
+                }
+            } else {
+                self.atomic_write_document(document_path, &format!("{}{}{}", &content[..start], block, &content[end..]))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Overwrites the bytes at `offset` of `expected` (the content the document was read with,
+    /// when it was last modified at `read_modified`) with `replacement` of the same length.
+    /// Returns `false` without writing when the document changed since it was read.
+    fn write_in_place(
+        &self,
+        document_path: &Path,
+        expected: &str,
+        read_modified: Option<SystemTime>,
+        offset: usize,
+        replacement: &str,
+    ) -> Result<bool> {
+        let io_error = |e: io::Error| AppError::new_io_error(
+            e,
+            Some(document_path.to_path_buf()),
+            "Failed to write in-place update".to_string(),
+        );
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(document_path)
+            .map_err(io_error)?;
+
+        let metadata = file.metadata().map_err(io_error)?;
+        let mut current = vec![0; replacement.len()];
+        file.seek(SeekFrom::Start(offset as u64))
+            .and_then(|_| file.read_exact(&mut current))
+            .map_err(io_error)?;
+        let unchanged = metadata.len() == expected.len() as u64
+            && metadata.modified().ok() == read_modified
+            && current == expected.as_bytes()[offset..offset + replacement.len()];
+        if !unchanged {
+            return Ok(false);
+        }
+
+        file.seek(SeekFrom::Start(offset as u64))
+            .and_then(|_| file.write_all(replacement.as_bytes()))
+            .map_err(io_error)?;
+        Ok(true)
+    }
+
+    /// Headings of the given documents with the size of each section, found outside of code
+    /// blocks. Level-3 headings are file sections and carry the file's path.
+    pub fn document_outline(&self, document_paths: &[PathBuf], format: OutputFormat) -> Result<Vec<OutlineEntry>> {
+        let mut outline: Vec<OutlineEntry> = Vec::new();
+        for document_path in document_paths {
+            let content = read_document(document_path)
+                .map_err(|e| AppError::new_io_error(
+                    e,
+                    Some(document_path.clone()),
+                    "Failed to read document for outline".to_string(),
+                ))?;
+            outline.extend(self.text_outline(&content, format));
+        }
+
+        Ok(outline)
+    }
+
+    /// Outline of one document's text; byte offsets are within `content`
+    pub fn text_outline(&self, content: &str, format: OutputFormat) -> Vec<OutlineEntry> {
+        let lines: Vec<&str> = content.split_inclusive('\n').collect();
+        let headings = format.formatter().headings(&lines);
+
+        let mut outline: Vec<OutlineEntry> = Vec::new();
+        let mut byte_offset = 0;
+        for (line_index, (line, heading)) in lines.iter().zip(headings).enumerate() {
+            if let Some((level, title)) = heading {
+                let title = title.trim().to_string();
+                let file_path = (level == 3).then(|| self.directory.join(&title));
+                outline.push(OutlineEntry { title, level, file_path, byte_offset, line: line_index + 1, size_bytes: 0, line_count: 0 });
+            }
+
+            if let Some(entry) = outline.last_mut() {
+                entry.size_bytes += line.len();
+                entry.line_count += 1;
+            }
+            byte_offset += line.len();
+        }
+        outline
+    }
+
+    /// Applies a partial update to whichever of `document_paths` contains the file's section
+    /// (used for chunked output, where each file lives in exactly one part)
+    pub fn update_file_section_in_documents(
+        &self,
+        document_paths: &[PathBuf],
+        updated_file_path: &Path,
+        format: OutputFormat
+    ) -> Result<()> {
+        let display_path = self.relative_display_path(updated_file_path)?;
+        for document_path in document_paths {
+            let contains_section = fs::read_to_string(document_path)
+                .is_ok_and(|content| Document::parse(&content, format).section(&display_path).is_some());
+            if contains_section {
+                return self.update_file_section_in_document(document_path, updated_file_path, format);
+            }
+        }
+
+        Err(AppError::DocumentGenerationError(
+            format!("Could not find section for file {} in any document part. Consider regenerating the full document.", display_path)
+        ))
+    }
+
+    /// Updates the sections of several modified files in one pass. Every file is attempted;
+    /// the first failure is returned.
+    pub fn update_file_sections_in_documents(
+        &self,
+        document_paths: &[PathBuf],
+        updated_file_paths: &[PathBuf],
+        format: OutputFormat
+    ) -> Result<()> {
+        let mut first_error = None;
+        for updated_file_path in updated_file_paths {
+            if let Err(e) = self.update_file_section_in_documents(document_paths, updated_file_path, format) {
+                warn!("Failed to update section for {:?}: {}", updated_file_path, e);
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Follows deleted (`to` is `None`) or renamed selected files: their sections are removed or
+    /// re-rendered under the new path, and the project structure of every part is rebuilt from
+    /// `root_node`. The generator's selection must already reflect the changes.
+    pub fn move_file_sections_in_documents(
+        &self,
+        document_paths: &[PathBuf],
+        moves: &[(PathBuf, Option<PathBuf>)],
+        root_node: &FileNode,
+        format: OutputFormat,
+    ) -> Result<()> {
+        let structure = self.generate_structure_string(root_node, format, None)?;
+        let mut moved_sections = 0;
+
+        for document_path in document_paths {
+            let original_content = fs::read_to_string(document_path)
+                .map_err(|e| AppError::new_io_error(
+                    e,
+                    Some(document_path.to_path_buf()),
+                    "Failed to read existing document file".to_string(),
+                ))?;
+            let mut document = Document::parse(&original_content, format);
+            document.replace_block(2, "Project Structure", &structure);
+
+            for (from, to) in moves {
+                let from = self.relative_display_path(from)?;
+                let moved = match to {
+                    Some(to) => document.replace_section(&from, &self.generate_file_string(to, format)?),
+                    None => document.remove_section(&from),
+                };
+                if moved {
+                    moved_sections += 1;
+                }
+            }
+
+            let content = document.render();
+            if content != original_content {
+                self.atomic_write_document(document_path, &content)?;
+            }
+        }
+
+        debug!("Moved {} of {} file sections and updated the structure", moved_sections, moves.len());
+        Ok(())
+    }
+
+    /// Replaces the file's section with a freshly rendered one. A changed hunk of the same byte
+    /// length is overwritten in place; anything else, or a document that changed meanwhile, is
+    /// rewritten atomically.
     pub fn update_file_section_in_document(
         &self,
         document_path: &Path,
@@ -324,81 +1466,391 @@ impl DocumentGenerator {
         format: OutputFormat
     ) -> Result<()> {
         debug!("Updating document section ({:?}) for file: {:?}", format, updated_file_path);
-        
-        // Read current document content
-        let current_content = fs::read_to_string(document_path)
-            .map_err(|e| AppError::new_io_error(
-                e,
-                Some(document_path.to_path_buf()),
-                "Failed to read existing document file".to_string(),
-            ))?;
+        let display_path = self.relative_display_path(updated_file_path)?;
 
-        let relative_path = updated_file_path.strip_prefix(&self.directory)
-            .map_err(|_| AppError::StripPrefixError {
-                prefix: self.directory.clone(),
-                path: updated_file_path.to_path_buf(),
-            })?;
+        for attempt in 0..2 {
+            // Read current document content, noting its modification time for the in-place guard
+            let read_modified = fs::metadata(document_path).and_then(|metadata| metadata.modified()).ok();
+            let current_content = fs::read_to_string(document_path)
+                .map_err(|e| AppError::new_io_error(
+                    e,
+                    Some(document_path.to_path_buf()),
+                    "Failed to read existing document file".to_string(),
+                ))?;
 
-        let display_path = relative_path.to_string_lossy().replace('\\', "/");
+            // Find the section to replace
+            let mut document = Document::parse(&current_content, format);
+            let Some(range) = document.section_range(&display_path) else {
+                warn!("Could not find section for file {} in document", display_path);
+                // This might happen if the document was edited manually and the header changed.
+                return Err(AppError::DocumentGenerationError(
+                    format!("Could not find section for file {} in document. Consider regenerating the full document.", display_path)
+                ));
+            };
 
-        // Determine the section header based on format
-        let section_header_prefix = match format {
-            OutputFormat::Markdown => format!("### {}", display_path),
-            OutputFormat::Adoc => format!("{} {}", ADOC_SECTION_LEVEL_3, display_path),
-        };
+            // What was generated last time, before rendering replaces it in the cache
+            let previous_section = self.section_cache.as_ref()
+                .and_then(|section_cache| section_cache.lock().ok()?.last_section(updated_file_path, format));
+            // Generate new section for this file
+            let mut new_section = self.generate_file_string(updated_file_path, format)?;
+            let old_section = &current_content[range.clone()];
 
-        // Find the section to replace
-        if let Some(start_index) = current_content.find(&section_header_prefix) {
-            // Find the end of this section (next header of same or higher level, or end of file)
-            let search_start = start_index + section_header_prefix.len();
-            let end_index = current_content[search_start..]
-                .find("\n### ")
-                .or_else(|| {
-                    if format == OutputFormat::Adoc {
-                        current_content[search_start..].find(&format!("\n{} ", ADOC_SECTION_LEVEL_3))
-                    } else {
-                        None
-                    }
-                })
-                .or_else(|| {
-                    if format == OutputFormat::Adoc {
-                        current_content[search_start..].find(&format!("\n{} ", ADOC_SECTION_LEVEL_2))
-                    } else {
-                        None
-                    }
-                })
-                .or_else(|| {
-                    if format == OutputFormat::Adoc {
-                        current_content[search_start..].find(&format!("\n{} ", ADOC_SECTION_LEVEL_1))
-                    } else {
-                        None
-                    }
-                })
-                .map(|pos| search_start + pos)
-                .unwrap_or(current_content.len());
+            // Text added to the section by hand since then is merged in instead of overwritten
+            if let Some(previous_section) = previous_section.filter(|previous| previous.trim_end() != old_section) {
+                let merged = merge_sections(&previous_section, old_section, &new_section);
+                if merged.conflicts > 0 {
+                    warn!("{} manually edited region(s) of {} conflicted with the update; kept the generated lines", merged.conflicts, display_path);
+                }
+                debug!("Merged manual edits into the updated section for: {}", display_path);
+                new_section = merged.text;
+            }
 
-            // Generate new section for this file
-            let new_section = self.generate_file_string(updated_file_path, format)?;
-            
-            // Replace the section
-            let updated_content = format!(
-                "{}{}{}",
-                &current_content[..start_index],
-                new_section,
-                &current_content[end_index..]
-            );
-            
-            self.atomic_write_document(document_path, &updated_content)?;
-            debug!("Successfully updated document section for: {}", display_path);
-        } else {
-            warn!("Could not find section for file {} in document", display_path);
-            // This might happen if the document was edited manually and the header changed.
-            // Re-generating the full document might be a fallback, or just log a warning.
-            return Err(AppError::DocumentGenerationError(
-                format!("Could not find section for file {} in document. Consider regenerating the full document.", display_path)
-            ));
+            match changed_hunk(old_section, &new_section) {
+                None => {
+                    debug!("Section for {} is unchanged, skipping write", display_path);
+                }
+                Some((hunk_start, old_hunk_end, new_hunk_end)) if attempt == 0 && old_hunk_end == new_hunk_end => {
+                    // E.g. a changed counter or timestamp: no byte moves, so overwriting the hunk
+                    // never leaves a torn document behind
+                    let patched = self.write_in_place(
+                        document_path,
+                        &current_content,
+                        read_modified,
+                        range.start + hunk_start,
+                        &new_section[hunk_start..new_hunk_end],
+                    )?;
+                    if !patched {
+                        debug!("{:?} changed while updating {}, updating it again", document_path, display_path);
+                        continue;
+                    }
+                    debug!("Patched {} bytes in place for: {}", new_hunk_end - hunk_start, display_path);
+                }
+                Some(_) => {
+                    document.replace_section(&display_path, &new_section);
+                    self.atomic_write_document(document_path, &document.render())?;
+                    debug!("Successfully updated document section for: {}", display_path);
+                }
+            }
+            break;
         }
 
         Ok(())
     }
-} 
\ No newline at end of file
+}
+
+/// Buffered writer over the temp file of a document being written
+struct DocumentWriter<'a> {
+    writer: BufWriter<&'a mut fs::File>,
+    temp_path: &'a Path,
+}
+
+impl DocumentWriter<'_> {
+    fn write(&mut self, text: &str) -> Result<()> {
+        self.writer.write_all(text.as_bytes()).map_err(|e| self.write_error(e))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush().map_err(|e| self.write_error(e))
+    }
+
+    fn write_error(&self, e: io::Error) -> AppError {
+        AppError::new_io_error(e, Some(self.temp_path.to_path_buf()), "Failed to write to temp file.".to_string())
+    }
+}
+
+/// The line range that differs between two versions of a section, as byte offsets
+/// `(start, old_end, new_end)`, or `None` if they are identical. Common leading and
+/// trailing lines are excluded.
+fn changed_hunk(old: &str, new: &str) -> Option<(usize, usize, usize)> {
+    if old == new {
+        return None;
+    }
+
+    let prefix_len: usize = old.split_inclusive('\n')
+        .zip(new.split_inclusive('\n'))
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .map(|(line, _)| line.len())
+        .sum();
+
+    // Trailing lines, not reaching into the common prefix of either version
+    let suffix_len: usize = old[prefix_len..].split_inclusive('\n').rev()
+        .zip(new[prefix_len..].split_inclusive('\n').rev())
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .map(|(line, _)| line.len())
+        .sum();
+
+    Some((prefix_len, old.len() - suffix_len, new.len() - suffix_len))
+}
+
+/// GitHub's heading anchor rules: lowercase, spaces become hyphens, punctuation is dropped,
+/// and repeated slugs get a numeric suffix
+#[derive(Default)]
+struct AnchorSlugger {
+    used: HashSet<String>,
+}
+
+impl AnchorSlugger {
+    fn slug(&mut self, heading: &str) -> String {
+        let base: String = heading.trim()
+            .to_lowercase()
+            .chars()
+            .filter_map(|c| match c {
+                ' ' => Some('-'),
+                c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+                _ => None,
+            })
+            .collect();
+
+        let mut slug = base.clone();
+        let mut suffix = 0;
+        while self.used.contains(&slug) {
+            suffix += 1;
+            slug = format!("{}-{}", base, suffix);
+        }
+        self.used.insert(slug.clone());
+        slug
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// `binary, 1.2 MB, skipped`
+fn binary_file_note(size_bytes: u64) -> String {
+    format!("binary, {}, skipped", format_byte_size(size_bytes))
+}
+
+/// Labeled text block of the log lines that mention a file
+fn render_log_excerpts(excerpts: &[String], format: OutputFormat) -> String {
+    let formatter = format.formatter();
+    format!("{}\n\n{}", formatter.strong("From the log:"), formatter.code_block("text", &excerpts.join("\n\n")))
+}
+
+/// A file section holding a one-line note instead of the file's contents
+fn render_listing_entry(display_path: &str, note: &str, format: OutputFormat) -> String {
+    let formatter = format.formatter();
+    format!("{}\n\n{}", formatter.heading(display_path, 3), formatter.emphasis(note))
+}
+
+/// `project_structure.md` -> `project_structure_part2.md`
+pub fn chunk_output_path(output_path: &Path, part: usize) -> PathBuf {
+    let stem = output_path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let file_name = match output_path.extension() {
+        Some(extension) => format!("{}_part{}.{}", stem, part, extension.to_string_lossy()),
+        None => format!("{}_part{}", stem, part),
+    };
+    output_path.with_file_name(file_name)
+}
+
+/// Whether writing the document to `output_path` would overwrite the file at `path`, in any
+/// output format, split into parts or not, as its section index, or as their compressed copies
+pub fn is_output_document_path(path: &Path, output_path: &Path) -> bool {
+    let (Some(file_name), Some(stem)) = (
+        path.file_name().and_then(|name| name.to_str())
+            .map(|name| name.strip_suffix(&format!(".{}", COMPRESSED_EXTENSION)).unwrap_or(name)),
+        output_path.file_stem().and_then(|stem| stem.to_str()),
+    ) else {
+        return false;
+    };
+    let is_index = file_name == format!("{}.{}", stem, DOCUMENT_INDEX_EXTENSION);
+    let names_match = is_index || OutputFormat::ALL.into_iter().any(|format| {
+        let Some(name) = file_name.strip_suffix(&format!(".{}", format.extension())) else {
+            return false;
+        };
+        name == stem || name.strip_prefix(stem)
+            .and_then(|rest| rest.strip_prefix("_part"))
+            .is_some_and(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+    });
+    // Only then compare the folders, which may be spelled differently (symlinks, `..`)
+    names_match && match (path.parent(), output_path.parent()) {
+        (Some(folder), Some(output_folder)) => match (folder.canonicalize(), output_folder.canonicalize()) {
+            (Ok(folder), Ok(output_folder)) => folder == output_folder,
+            _ => folder == output_folder,
+        },
+        (folder, output_folder) => folder == output_folder,
+    }
+}
+
+/// Whether the file at `path` can be written without losing hand-written text: it is missing,
+/// empty, or has the generator marker comment near its top. Documents written before the marker
+/// are recognized by their `Context` title.
+pub fn is_replaceable_document(path: &Path) -> bool {
+    let Ok(file) = fs::File::open(path) else {
+        return true;
+    };
+    let mut head = Vec::new();
+    if file.take(GENERATOR_MARKER_SEARCH_BYTES).read_to_end(&mut head).is_err() {
+        return false;
+    }
+    if head.is_empty() {
+        return true;
+    }
+    let head = String::from_utf8_lossy(&head);
+    let markers = OutputFormat::ALL.map(|format| format.formatter().comment(GENERATOR_MARKER));
+    if head.lines().any(|line| markers.iter().any(|marker| marker == line.trim_end())) {
+        return true;
+    }
+
+    let mut lines = head.lines();
+    let mut first_line = lines.next().unwrap_or_default().trim_end();
+    let titles = if first_line.starts_with(RST_HEADING_CHARS[0]) && first_line.chars().all(|c| c == RST_HEADING_CHARS[0]) {
+        // A reStructuredText title, below its overline
+        first_line = lines.next().unwrap_or_default().trim_end();
+        vec!["Context".to_string()]
+    } else {
+        [OutputFormat::Markdown, OutputFormat::Adoc, OutputFormat::Org].map(|format| format.formatter().heading("Context", 1)).to_vec()
+    };
+    titles.iter().any(|title| {
+        first_line.strip_prefix(title.as_str())
+            .is_some_and(|rest| rest.trim().is_empty() || rest.starts_with(" (part"))
+    })
+}
+
+/// The first free `name(2).md`, `name(3).md`, ... next to `output_path`
+pub fn next_free_output_path(output_path: &Path) -> PathBuf {
+    let stem = output_path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // `name(2)` continues with `name(3)` rather than `name(2)(2)`
+    let base = match stem.rsplit_once('(') {
+        Some((base, counter)) if counter.strip_suffix(')').is_some_and(|n| n.parse::<usize>().is_ok()) => base.to_string(),
+        _ => stem,
+    };
+    let mut counter = 2;
+    loop {
+        let file_name = match output_path.extension() {
+            Some(extension) => format!("{}({}).{}", base, counter, extension.to_string_lossy()),
+            None => format!("{}({})", base, counter),
+        };
+        let candidate = output_path.with_file_name(file_name);
+        if !candidate.exists() {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+/// Parts written by the last chunked generation, in order
+pub fn existing_chunk_paths(output_path: &Path) -> Vec<PathBuf> {
+    (1..)
+        .map(|part| chunk_output_path(output_path, part))
+        .take_while(|path| path.exists())
+        .collect()
+}
+
+/// Size and age of a structure entry, e.g. `14.2 KB, 2d ago`
+fn structure_annotation(node: &FileNode) -> String {
+    let size = format_byte_size(node.size_bytes);
+    match node.modified {
+        Some(modified) => format!("{}, {}", size, format_age(modified, SystemTime::now())),
+        None => size,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_handler::FileHandler;
+
+    /// A project with one file, and the generator and scanned tree for it
+    fn project() -> (tempfile::TempDir, DocumentGenerator, FileNode) {
+        let project = tempfile::tempdir().unwrap();
+        fs::write(project.path().join("main.rs"), "fn main() {}\n").unwrap();
+        let root_node = FileHandler::new(project.path().to_path_buf()).unwrap().scan_directory(Vec::new()).unwrap();
+        let generator = DocumentGenerator::new(project.path().to_path_buf(), root_node.file_paths());
+        (project, generator, root_node)
+    }
+
+    #[test]
+    fn generated_documents_are_replaceable() {
+        let (_project, generator, root_node) = project();
+        let output = tempfile::tempdir().unwrap();
+        for format in OutputFormat::ALL {
+            let output_path = output.path().join(format!("context.{}", format.extension()));
+            generator.generate_full_document(&root_node, &output_path, format).unwrap();
+            assert!(is_replaceable_document(&output_path), "{:?}", format);
+
+            let part_paths = generator.generate_chunked_documents(&root_node, &output_path, format, 1000).unwrap();
+            assert!(part_paths.iter().all(|path| is_replaceable_document(path)), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn template_output_is_replaceable() {
+        let (project, generator, root_node) = project();
+        let template_path = project.path().join("layout.tera");
+        // Nothing like the built-in layout, not even a `Context` title
+        fs::write(&template_path, "Files of {{ project_name }}:\n{% for file in files %}- {{ file.path }}\n{% endfor %}").unwrap();
+        let output = tempfile::tempdir().unwrap();
+        for format in OutputFormat::ALL {
+            let output_path = output.path().join(format!("context.{}", format.extension()));
+            generator.generate_templated_document(&root_node, &output_path, format, &template_path).unwrap();
+            assert!(fs::read_to_string(&output_path).unwrap().contains("- main.rs"));
+            assert!(is_replaceable_document(&output_path), "{:?}", format);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_same_length_hunks_are_patched_in_place() {
+        use std::os::unix::fs::MetadataExt;
+        let (project, generator, root_node) = project();
+        let file_path = project.path().join("main.rs");
+        let output = tempfile::tempdir().unwrap();
+        let output_path = output.path().join("context.md");
+        generator.generate_full_document(&root_node, &output_path, OutputFormat::Markdown).unwrap();
+        let inode = fs::metadata(&output_path).unwrap().ino();
+
+        fs::write(&file_path, "fn mine() {}\n").unwrap();
+        generator.update_file_section_in_document(&output_path, &file_path, OutputFormat::Markdown).unwrap();
+        assert!(fs::read_to_string(&output_path).unwrap().contains("fn mine() {}"));
+        assert_eq!(fs::metadata(&output_path).unwrap().ino(), inode, "patched in place");
+
+        // A longer hunk replaces the document through a temp file instead
+        fs::write(&file_path, "fn main() { println!(); }\n").unwrap();
+        generator.update_file_section_in_document(&output_path, &file_path, OutputFormat::Markdown).unwrap();
+        let content = fs::read_to_string(&output_path).unwrap();
+        assert!(content.contains("fn main() { println!(); }") && !content.contains("fn mine"));
+        assert_ne!(fs::metadata(&output_path).unwrap().ino(), inode, "rewritten atomically");
+    }
+
+    #[test]
+    fn section_footers_use_the_format_emphasis() {
+        let (project, generator, _root_node) = project();
+        let generator = generator.with_section_footers(true);
+        let file_path = project.path().join("main.rs");
+        for (format, footer) in [
+            (OutputFormat::Markdown, "_2 lines, ~4 tokens_"),
+            (OutputFormat::Adoc, "_2 lines, ~4 tokens_"),
+            (OutputFormat::Org, "/2 lines, ~4 tokens/"),
+            (OutputFormat::Rst, "*2 lines, ~4 tokens*"),
+        ] {
+            let section = generator.render_file_section(&file_path, "fn main() {\n}", format).unwrap();
+            assert!(section.ends_with(&format!("\n\n{}", footer)), "{:?}: {}", format, section);
+        }
+    }
+
+    #[test]
+    fn hand_written_files_are_not_replaceable() {
+        let output = tempfile::tempdir().unwrap();
+        let path = output.path().join("notes.md");
+        assert!(is_replaceable_document(&path)); // Missing
+        fs::write(&path, "").unwrap();
+        assert!(is_replaceable_document(&path));
+
+        fs::write(&path, "# Notes\n\nMention context_builder here.\n").unwrap();
+        assert!(!is_replaceable_document(&path));
+        // The marker counts only on a line of its own, near the top
+        fs::write(&path, format!("# Notes\n\nSee `{}`\n", OutputFormat::Markdown.formatter().comment(GENERATOR_MARKER))).unwrap();
+        assert!(!is_replaceable_document(&path));
+        fs::write(&path, format!("# Notes\n\n{}\n{}\n", "text ".repeat(400), OutputFormat::Markdown.formatter().comment(GENERATOR_MARKER))).unwrap();
+        assert!(!is_replaceable_document(&path));
+
+        // Written before the marker existed
+        fs::write(&path, "# Context\n\n## Project Structure\n").unwrap();
+        assert!(is_replaceable_document(&path));
+    }
+}
@@ -0,0 +1,64 @@
+use std::path::{Path, PathBuf};
+use log::debug;
+use serde::Serialize;
+
+use crate::constants::{OutputFormat, DOCUMENT_INDEX_EXTENSION};
+use crate::document_generator::DocumentGenerator;
+use crate::error::{AppError, Result};
+use crate::utils::write_file_atomically;
+
+/// Sidecar written next to the document, so tools can seek to a file's section without
+/// parsing the Markdown or AsciiDoc themselves
+#[derive(Debug, Serialize)]
+struct DocumentIndex {
+    format: OutputFormat,
+    sections: Vec<IndexedSection>,
+}
+
+#[derive(Debug, Serialize)]
+struct IndexedSection {
+    path: String,     // As in the section heading, relative to the project directory
+    document: String, // File name of the document (or part) holding the section
+    byte_offset: usize,
+    byte_length: usize,
+    line_start: usize, // 1-based, inclusive
+    line_end: usize,
+}
+
+/// `context.md` is indexed in `context.index.json`
+pub fn document_index_path(output_path: &Path) -> PathBuf {
+    output_path.with_extension(DOCUMENT_INDEX_EXTENSION)
+}
+
+/// Rewrites the index of the file sections in `document_paths`, if the generator has an index
+/// path. Offsets are those of the documents as they are on disk now.
+pub fn write_document_index(generator: &DocumentGenerator, document_paths: &[PathBuf], format: OutputFormat) -> Result<()> {
+    let Some(index_path) = generator.document_index_path() else {
+        return Ok(());
+    };
+
+    let mut sections = Vec::new();
+    for document_path in document_paths {
+        let document = document_path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let outline = generator.document_outline(std::slice::from_ref(document_path), format)?;
+        sections.extend(outline.into_iter()
+            .filter(|entry| entry.file_path.is_some())
+            .map(|entry| IndexedSection {
+                path: entry.title,
+                document: document.clone(),
+                byte_offset: entry.byte_offset,
+                byte_length: entry.size_bytes,
+                line_start: entry.line,
+                line_end: entry.line + entry.line_count - 1,
+            }));
+    }
+
+    let bytes = serde_json::to_vec_pretty(&DocumentIndex { format, sections }).map_err(|e| AppError::OperationFailed(
+        format!("Failed to serialize document index: {}", e)
+    ))?;
+    write_file_atomically(index_path, &bytes)?;
+    debug!("Wrote document index {:?} for {} documents", index_path, document_paths.len());
+    Ok(())
+}
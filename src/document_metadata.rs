@@ -0,0 +1,130 @@
+use std::fs;
+use std::path::Path;
+use chrono::Local;
+
+use crate::constants::{OutputFormat, RST_INDENT};
+use crate::update_check::CURRENT_VERSION;
+use crate::utils::format_token_count;
+
+const METADATA_TITLE: &str = "Document metadata";
+const GENERATED_LABEL: &str = "Generated: ";
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S %z"; // Fixed width, so refreshes can patch the block in place
+const SHORT_COMMIT_LENGTH: usize = 12;
+
+/// Counts over one written document (or part)
+#[derive(Debug, Default)]
+pub struct DocumentStats {
+    pub files: usize,
+    pub lines: usize,
+    pub tokens: usize,
+}
+
+/// The current local time as written in the block
+pub fn metadata_time_now() -> String {
+    Local::now().format(TIME_FORMAT).to_string()
+}
+
+/// A comment block, so renderers hide it while tools and models still read it. `updated` is
+/// the time of the last partial update since the generation.
+pub fn render_metadata_block(
+    directory: &Path,
+    format: OutputFormat,
+    generated: &str,
+    updated: Option<&str>,
+    stats: &DocumentStats,
+) -> String {
+    let mut lines = vec![format!("{}{}", GENERATED_LABEL, generated)];
+    if let Some(updated) = updated {
+        lines.push(format!("Updated: {}", updated));
+    }
+    lines.push(format!("Project: {}", directory.display()));
+    if let Some(revision) = git_revision(directory) {
+        lines.push(format!("Git: {}", revision));
+    }
+    lines.push(format!("Files: {}", stats.files));
+    lines.push(format!("Lines: {}", stats.lines));
+    lines.push(format!("Tokens: {}", format_token_count(stats.tokens)));
+    lines.push(format!("Tool: context_builder {}", CURRENT_VERSION));
+
+    let (start, end) = delimiters(format);
+    match format {
+        // Comments there are the indented lines below `..`
+        OutputFormat::Rst => format!("{}\n{}{}", start, RST_INDENT, lines.join(&format!("\n{}", RST_INDENT))),
+        _ => format!("{}\n{}\n{}", start, lines.join("\n"), end),
+    }
+}
+
+/// Byte range of the metadata block, looked for only above the first section heading so that
+/// a block quoted in a file's content is never mistaken for it
+pub fn metadata_block_range(content: &str, format: OutputFormat) -> Option<(usize, usize)> {
+    let (start_marker, end_marker) = delimiters(format);
+    let header = &content[..format.formatter().next_heading(content, 2).unwrap_or(content.len())];
+    let start = header.find(&format!("{}\n", start_marker))?;
+    if format == OutputFormat::Rst {
+        let end = header[start..].find("\n\n").map_or(header.trim_end().len(), |pos| start + pos);
+        return Some((start, end));
+    }
+    let end_line = format!("\n{}", end_marker);
+    let end = start + header[start..].find(&end_line)? + end_line.len();
+    Some((start, end))
+}
+
+/// The generation time written in a block
+pub fn generated_time(block: &str) -> Option<String> {
+    block.lines().find_map(|line| line.trim_start().strip_prefix(GENERATED_LABEL)).map(str::to_string)
+}
+
+fn delimiters(format: OutputFormat) -> (String, &'static str) {
+    match format {
+        OutputFormat::Markdown => (format!("<!-- {}", METADATA_TITLE), "-->"),
+        OutputFormat::Adoc => (format!("////\n{}", METADATA_TITLE), "////"),
+        OutputFormat::Org => (format!("#+BEGIN_COMMENT\n{}", METADATA_TITLE), "#+END_COMMENT"),
+        OutputFormat::Rst => (format!(".. {}", METADATA_TITLE), ""), // Ends at the first blank line
+    }
+}
+
+/// `branch @ commit` of the repository containing `directory`, read from `.git` without running
+/// git. `None` outside a repository.
+fn git_revision(directory: &Path) -> Option<String> {
+    let git_dir = directory.ancestors().find_map(|folder| {
+        let dot_git = folder.join(".git");
+        if dot_git.is_dir() {
+            return Some(dot_git);
+        }
+        // Worktrees and submodules have a `.git` file pointing to the real directory
+        let link = fs::read_to_string(&dot_git).ok()?;
+        link.trim().strip_prefix("gitdir: ").map(|git_dir| folder.join(git_dir))
+    })?;
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+
+    Some(match head.strip_prefix("ref: ") {
+        Some(reference) => {
+            let branch = reference.strip_prefix("refs/heads/").unwrap_or(reference);
+            match resolve_reference(&git_dir, reference) {
+                Some(commit) => format!("{} @ {}", branch, short_commit(&commit)),
+                None => branch.to_string(), // No commit yet
+            }
+        }
+        None => format!("detached @ {}", short_commit(head)),
+    })
+}
+
+fn resolve_reference(git_dir: &Path, reference: &str) -> Option<String> {
+    // Worktrees keep their branches in the main repository's directory
+    let common_dir = fs::read_to_string(git_dir.join("commondir")).ok()
+        .map(|common_dir| git_dir.join(common_dir.trim()))
+        .unwrap_or_else(|| git_dir.to_path_buf());
+    if let Some(commit) = [git_dir, common_dir.as_path()].iter().find_map(|dir| fs::read_to_string(dir.join(reference)).ok()) {
+        return Some(commit.trim().to_string());
+    }
+    let packed_refs = fs::read_to_string(common_dir.join("packed-refs")).ok()?;
+    packed_refs.lines().find_map(|line| {
+        let (commit, name) = line.split_once(' ')?;
+        (name == reference).then(|| commit.to_string())
+    })
+}
+
+fn short_commit(commit: &str) -> &str {
+    commit.get(..SHORT_COMMIT_LENGTH).unwrap_or(commit)
+}
@@ -0,0 +1,264 @@
+use std::ops::Range;
+
+use crate::constants::OutputFormat;
+
+/// Level of the headings that start file sections
+pub const FILE_SECTION_LEVEL: usize = 3;
+
+/// A generated document as an ordered list of blocks, each starting at a heading of level 1 to
+/// 3 (text before the first heading is a block without one). File sections are the level 3
+/// blocks, keyed by their title, the file's display path. Blocks keep their text byte for byte,
+/// so an unchanged document renders back to the text it was parsed from, and partial updates
+/// replace whole sections instead of splicing at searched offsets.
+#[derive(Debug, Clone)]
+pub struct Document {
+    format: OutputFormat,
+    blocks: Vec<Block>,
+}
+
+#[derive(Debug, Clone)]
+struct Block {
+    heading: Option<(usize, String)>, // Level and title
+    text: String,                     // From the heading, without the blank lines after it
+    separator: String,                // Whitespace up to the next block
+}
+
+impl Document {
+    pub fn parse(content: &str, format: OutputFormat) -> Self {
+        let lines: Vec<&str> = content.split_inclusive('\n').collect();
+        let headings = format.formatter().headings(&lines);
+
+        let mut blocks = Vec::new();
+        let mut current = Block::new(None);
+        for (line, heading) in lines.iter().zip(headings) {
+            if let Some((level, title)) = heading.filter(|(level, _)| *level <= FILE_SECTION_LEVEL) {
+                if current.heading.is_some() || !current.text.is_empty() {
+                    blocks.push(current.finish());
+                }
+                current = Block::new(Some((level, title.trim().to_string())));
+            }
+            current.text.push_str(line);
+        }
+        blocks.push(current.finish());
+        Self { format, blocks }
+    }
+
+    pub fn render(&self) -> String {
+        self.blocks.iter().flat_map(|block| [block.text.as_str(), block.separator.as_str()]).collect()
+    }
+
+    /// Text of the file section titled `display_path`, without the blank lines after it
+    pub fn section(&self, display_path: &str) -> Option<&str> {
+        self.find(FILE_SECTION_LEVEL, display_path).map(|index| self.blocks[index].text.as_str())
+    }
+
+    /// Byte range of the file section in the rendered document
+    pub fn section_range(&self, display_path: &str) -> Option<Range<usize>> {
+        let index = self.find(FILE_SECTION_LEVEL, display_path)?;
+        let start: usize = self.blocks[..index].iter().map(|block| block.text.len() + block.separator.len()).sum();
+        Some(start..start + self.blocks[index].text.len())
+    }
+
+    /// Replaces the file section, which may then carry a different title (a renamed file)
+    pub fn replace_section(&mut self, display_path: &str, text: &str) -> bool {
+        self.replace_block(FILE_SECTION_LEVEL, display_path, text)
+    }
+
+    /// Replaces the block with the given heading, e.g. the level 2 Project Structure block
+    pub fn replace_block(&mut self, level: usize, title: &str, text: &str) -> bool {
+        let Some(index) = self.find(level, title) else {
+            return false;
+        };
+        let (text, trailing) = text.split_at(text.trim_end().len());
+        let heading = self.format.formatter().headings(&text.split_inclusive('\n').collect::<Vec<_>>())
+            .into_iter()
+            .flatten()
+            .next()
+            .map(|(level, title)| (level, title.trim().to_string()));
+        let block = &mut self.blocks[index];
+        block.heading = heading;
+        block.text = text.to_string();
+        block.separator.insert_str(0, trailing);
+        true
+    }
+
+    /// Removes the file section with the blank lines before the next block, or before this one
+    /// if it is the last
+    pub fn remove_section(&mut self, display_path: &str) -> bool {
+        let Some(index) = self.find(FILE_SECTION_LEVEL, display_path) else {
+            return false;
+        };
+        let removed = self.blocks.remove(index);
+        if index == self.blocks.len() && index > 0 {
+            self.blocks[index - 1].separator = removed.separator;
+        }
+        true
+    }
+
+    fn find(&self, level: usize, title: &str) -> Option<usize> {
+        self.blocks.iter().position(|block| block.heading.as_ref().is_some_and(|(l, t)| *l == level && t == title))
+    }
+}
+
+impl Block {
+    fn new(heading: Option<(usize, String)>) -> Self {
+        Self { heading, text: String::new(), separator: String::new() }
+    }
+
+    /// Moves the trailing whitespace of the text into the separator
+    fn finish(mut self) -> Self {
+        let end = self.text.trim_end().len();
+        self.separator = self.text.split_off(end);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reads like a heading in every format, and holds delimiters of every format's code blocks
+    const TRICKY_PATH: &str = "## Setup/* notes.md";
+    const TRICKY_CONTENT: &str = "# not a heading\n== nor this\n* nor this\n```\n----\nnor this\n-----\nend";
+
+    fn file_section(format: OutputFormat, display_path: &str, content: &str) -> String {
+        format.formatter().file_section(display_path, "text", content)
+    }
+
+    /// Title, Project Structure and three file sections, as the generator lays them out
+    struct Sample {
+        title: String,
+        structure: String,
+        a: String,
+        tricky: String,
+        c: String,
+    }
+
+    impl Sample {
+        fn new(format: OutputFormat) -> Self {
+            let formatter = format.formatter();
+            Self {
+                title: formatter.heading("Project Context", 1),
+                structure: formatter.structure_block("src/\n  a.rs\n"),
+                a: file_section(format, "src/a.rs", TRICKY_CONTENT),
+                tricky: file_section(format, TRICKY_PATH, "b"),
+                c: file_section(format, "src/c.rs", "c"),
+            }
+        }
+
+        fn render(parts: &[&str]) -> String {
+            format!("{}\n", parts.join("\n\n"))
+        }
+
+        fn document(&self) -> String {
+            Self::render(&[&self.title, &self.structure, &self.a, &self.tricky, &self.c])
+        }
+    }
+
+    #[test]
+    fn unchanged_documents_render_back_byte_for_byte() {
+        for format in OutputFormat::ALL {
+            let content = Sample::new(format).document();
+            assert_eq!(Document::parse(&content, format).render(), content, "{:?}", format);
+            let unterminated = content.trim_end();
+            assert_eq!(Document::parse(unterminated, format).render(), unterminated, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn sections_are_found_by_path_and_not_inside_code_blocks() {
+        for format in OutputFormat::ALL {
+            let sample = Sample::new(format);
+            let content = sample.document();
+            let document = Document::parse(&content, format);
+            for (display_path, section) in [("src/a.rs", &sample.a), (TRICKY_PATH, &sample.tricky), ("src/c.rs", &sample.c)] {
+                assert_eq!(document.section(display_path), Some(section.as_str()), "{:?} {}", format, display_path);
+                let range = document.section_range(display_path).unwrap();
+                assert_eq!(&content[range], section.as_str(), "{:?} {}", format, display_path);
+            }
+            for line in TRICKY_CONTENT.lines() {
+                assert!(document.section(line.trim_start_matches(['#', '=', '*', ' '])).is_none(), "{:?} {}", format, line);
+            }
+        }
+    }
+
+    #[test]
+    fn replace_section_changes_only_that_section() {
+        for format in OutputFormat::ALL {
+            let sample = Sample::new(format);
+            let mut document = Document::parse(&sample.document(), format);
+            let tricky = file_section(format, TRICKY_PATH, "b, edited\nover two lines");
+            assert!(document.replace_section(TRICKY_PATH, &tricky));
+            assert_eq!(document.render(), Sample::render(&[&sample.title, &sample.structure, &sample.a, &tricky, &sample.c]), "{:?}", format);
+
+            // A renamed file's section is found under its new path
+            let renamed = file_section(format, "src/d.rs", "c");
+            assert!(document.replace_section("src/c.rs", &renamed));
+            assert!(document.section("src/c.rs").is_none());
+            assert_eq!(document.section("src/d.rs"), Some(renamed.as_str()), "{:?}", format);
+            assert_eq!(document.render(), Sample::render(&[&sample.title, &sample.structure, &sample.a, &tricky, &renamed]), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn remove_section_takes_its_blank_lines_along() {
+        for format in OutputFormat::ALL {
+            let sample = Sample::new(format);
+            let mut document = Document::parse(&sample.document(), format);
+            assert!(document.remove_section(TRICKY_PATH));
+            assert_eq!(document.render(), Sample::render(&[&sample.title, &sample.structure, &sample.a, &sample.c]), "{:?}", format);
+            assert!(document.remove_section("src/c.rs"));
+            assert_eq!(document.render(), Sample::render(&[&sample.title, &sample.structure, &sample.a]), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn replace_block_replaces_the_project_structure() {
+        for format in OutputFormat::ALL {
+            let sample = Sample::new(format);
+            let mut document = Document::parse(&sample.document(), format);
+            let structure = format.formatter().structure_block("src/\n  a.rs\n  c.rs\n");
+            assert!(document.replace_block(2, "Project Structure", &structure));
+            assert_eq!(document.render(), Sample::render(&[&sample.title, &structure, &sample.a, &sample.tricky, &sample.c]), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn missing_sections_leave_the_document_alone() {
+        for format in OutputFormat::ALL {
+            let content = Sample::new(format).document();
+            let mut document = Document::parse(&content, format);
+            assert!(document.section_range("src/missing.rs").is_none());
+            assert!(document.section_range("Setup/* notes.md").is_none()); // Only whole titles match
+            assert!(!document.replace_section("src/missing.rs", &file_section(format, "src/missing.rs", "x")));
+            assert!(!document.remove_section("src/missing.rs"));
+            assert!(!document.replace_block(2, "src/a.rs", "x")); // File sections are level 3
+            assert!(!document.remove_section("Project Structure"));
+            assert_eq!(document.render(), content, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn hand_edits_outside_the_changed_section_are_kept() {
+        for format in OutputFormat::ALL {
+            let sample = Sample::new(format);
+            let notes = format!("{}\n\nCheck a.rs first.", format.formatter().heading("Review notes", 2));
+            // A note under the title, extra and whitespace-only blank lines, an added heading and
+            // no final newline
+            let content = format!(
+                "{}\n\nMy notes on this snapshot.\n\n\n\n{}\n\n{}\n  \n\t\n{}\n\n{}\n\n{}",
+                sample.title, sample.structure, sample.a, notes, sample.tricky, sample.c
+            );
+            let mut document = Document::parse(&content, format);
+            assert_eq!(document.render(), content, "{:?}", format);
+
+            let a = file_section(format, "src/a.rs", "fn a() {}");
+            assert!(document.replace_section("src/a.rs", &a));
+            let content = content.replace(&sample.a, &a);
+            assert_eq!(document.render(), content, "{:?}", format);
+
+            assert!(document.remove_section(TRICKY_PATH));
+            assert_eq!(document.render(), content.replace(&format!("{}\n\n", sample.tricky), ""), "{:?}", format);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use serde::Serialize;
+use tera::{Context, Tera};
+
+use crate::constants::OutputFormat;
+use crate::error::{AppError, Result};
+
+/// Built-in templates, equivalent to the hard-coded layout. Users can export them as a
+/// starting point for a custom template.
+const DEFAULT_MARKDOWN_TEMPLATE: &str = include_str!("../templates/default.md.tera");
+const DEFAULT_ADOC_TEMPLATE: &str = include_str!("../templates/default.adoc.tera");
+const DEFAULT_ORG_TEMPLATE: &str = include_str!("../templates/default.org.tera");
+const DEFAULT_RST_TEMPLATE: &str = include_str!("../templates/default.rst.tera");
+
+/// A selected file as seen by templates
+#[derive(Debug, Serialize)]
+pub struct TemplateFile {
+    pub path: String,     // Relative to the project directory, forward slashes
+    pub language: String, // Code block language
+    pub fence: String,    // Code block delimiter, longer than any run of it in the content; empty for Org and reStructuredText
+    pub content: String,  // Truncated content, verbatim otherwise; comma-escaped for Org, indented for reStructuredText
+}
+
+/// Everything a document template can reference
+#[derive(Debug, Serialize)]
+pub struct TemplateContext {
+    pub project_name: String,
+    pub format: String,
+    pub custom_header: String, // Trimmed, empty if not set
+    pub structure: String, // Tree lines of the project structure, each ending with a newline; indented for reStructuredText
+    pub files: Vec<TemplateFile>,
+    pub omitted_files: Vec<String>,
+    pub token_budget: Option<usize>,
+}
+
+pub fn default_template(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Markdown => DEFAULT_MARKDOWN_TEMPLATE,
+        OutputFormat::Adoc => DEFAULT_ADOC_TEMPLATE,
+        OutputFormat::Org => DEFAULT_ORG_TEMPLATE,
+        OutputFormat::Rst => DEFAULT_RST_TEMPLATE,
+    }
+}
+
+pub fn render_template(template_source: &str, template_context: &TemplateContext) -> Result<String> {
+    let context = Context::from_serialize(template_context)
+        .map_err(|e| AppError::TemplateError(format!("Failed to build template context: {}", e)))?;
+
+    // No autoescaping: the output is Markdown/AsciiDoc, not HTML
+    Tera::one_off(template_source, &context, false)
+        .map_err(|e| AppError::TemplateError(template_error_chain(&e)))
+}
+
+/// Tera reports the useful detail (line, unknown variable) in the error's sources
+fn template_error_chain(error: &tera::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    message
+}
@@ -0,0 +1,316 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+use log::{debug, error};
+
+use crate::constants::{OutputFormat, SHUTDOWN_POLL_INTERVAL};
+use crate::document_generator::{existing_chunk_paths, DocumentGenerator};
+use crate::document_index::write_document_index;
+use crate::error::{AppError, Result};
+use crate::events::AppEvent;
+use crate::file_handler::FileNode;
+
+/// A write to the output document, run by the `DocumentWorker`
+pub enum DocumentJob {
+    Generate {
+        generator: DocumentGenerator,
+        root_node: FileNode,
+        output_path: PathBuf,
+        format: OutputFormat,
+        chunk_token_limit: Option<usize>, // Split output into parts of this size
+        template_path: Option<PathBuf>,
+        companion_targets: Vec<(PathBuf, OutputFormat)>, // Same document in other formats, written concurrently
+    },
+    UpdateSections {
+        generator: DocumentGenerator,
+        document_paths: Vec<PathBuf>,
+        file_paths: Vec<PathBuf>,
+        format: OutputFormat,
+    },
+    MoveSections {
+        generator: DocumentGenerator,
+        document_paths: Vec<PathBuf>,
+        moves: Vec<(PathBuf, Option<PathBuf>)>,
+        root_node: FileNode,
+        format: OutputFormat,
+    },
+}
+
+impl DocumentJob {
+    fn name(&self) -> &'static str {
+        match self {
+            DocumentJob::Generate { .. } => "full generation",
+            DocumentJob::UpdateSections { .. } => "section update",
+            DocumentJob::MoveSections { .. } => "section move",
+        }
+    }
+
+    fn run(self, event_sender: &mpsc::Sender<AppEvent>) {
+        let (event, files_read) = match self {
+            DocumentJob::Generate { generator, root_node, output_path, format, chunk_token_limit, template_path, companion_targets } => {
+                let mut targets = vec![(output_path.clone(), format)];
+                targets.extend(companion_targets);
+                let result = generate_documents(&generator, &root_node, &targets, chunk_token_limit, template_path.as_deref())
+                    .and_then(|()| {
+                        // The parts just written; a split generation may add or delete some
+                        let document_paths = match chunk_token_limit {
+                            Some(_) => existing_chunk_paths(&output_path),
+                            None => vec![output_path],
+                        };
+                        write_document_index(&generator, &document_paths, format)
+                    });
+                let files_read = AppEvent::FilesRead { outcomes: generator.take_read_outcomes(), replace: true };
+                (AppEvent::DocumentGenerationComplete(result), files_read)
+            }
+            DocumentJob::UpdateSections { generator, document_paths, file_paths, format } => {
+                let result = generator.update_file_sections_in_documents(&document_paths, &file_paths, format)
+                    .and_then(|()| generator.refresh_metadata_blocks(&document_paths, format, false))
+                    .and_then(|()| write_document_index(&generator, &document_paths, format));
+                let files_read = AppEvent::FilesRead { outcomes: generator.take_read_outcomes(), replace: false };
+                (AppEvent::PartialDocumentUpdateComplete(result), files_read)
+            }
+            DocumentJob::MoveSections { generator, document_paths, moves, root_node, format } => {
+                let result = generator.move_file_sections_in_documents(&document_paths, &moves, &root_node, format)
+                    .and_then(|()| generator.refresh_metadata_blocks(&document_paths, format, false))
+                    .and_then(|()| write_document_index(&generator, &document_paths, format));
+                let files_read = AppEvent::FilesRead { outcomes: generator.take_read_outcomes(), replace: false };
+                (AppEvent::PartialDocumentUpdateComplete(result), files_read)
+            }
+        };
+
+        // Before the result, so the tree shows the read errors once the generation is reported
+        if let Err(e) = event_sender.send(files_read) {
+            error!("Failed to send file read outcomes: {}", e);
+        }
+        if let Err(e) = event_sender.send(event) {
+            error!("Failed to send document write result: {}", e);
+        }
+    }
+}
+
+/// Writes a whole document in the layout chosen by the settings: split into parts, rendered
+/// with a custom template, or the built-in layout
+pub fn generate_document(
+    generator: &DocumentGenerator,
+    root_node: &FileNode,
+    output_path: &Path,
+    format: OutputFormat,
+    chunk_token_limit: Option<usize>,
+    template_path: Option<&Path>,
+) -> Result<()> {
+    let document_paths = match (chunk_token_limit, template_path) {
+        (Some(max_tokens_per_chunk), _) => {
+            let part_paths = generator.generate_chunked_documents(root_node, output_path, format, max_tokens_per_chunk)?;
+            debug!("Wrote {} document parts", part_paths.len());
+            part_paths
+        }
+        (None, Some(template_path)) => {
+            generator.generate_templated_document(root_node, output_path, format, template_path)?;
+            vec![output_path.to_path_buf()]
+        }
+        (None, None) => {
+            generator.generate_full_document(root_node, output_path, format)?;
+            vec![output_path.to_path_buf()]
+        }
+    };
+    generator.refresh_metadata_blocks(&document_paths, format, true)
+}
+
+/// Writes the document to every target, one thread per format. Each target is replaced
+/// atomically on its own, so a failed format leaves the others written.
+pub fn generate_documents(
+    generator: &DocumentGenerator,
+    root_node: &FileNode,
+    targets: &[(PathBuf, OutputFormat)],
+    chunk_token_limit: Option<usize>,
+    template_path: Option<&Path>,
+) -> Result<()> {
+    if let [(output_path, format)] = targets {
+        return generate_document(generator, root_node, output_path, *format, chunk_token_limit, template_path);
+    }
+
+    let results: Vec<(&Path, Result<()>)> = thread::scope(|scope| {
+        let handles: Vec<_> = targets.iter()
+            .map(|(output_path, format)| (output_path, scope.spawn(move || {
+                generate_document(generator, root_node, output_path, *format, chunk_token_limit, template_path)
+            })))
+            .collect();
+        handles.into_iter()
+            .map(|(output_path, handle)| {
+                let result = handle.join().unwrap_or_else(|_| Err(AppError::OperationFailed("Document writer thread panicked".to_string())));
+                (output_path.as_path(), result)
+            })
+            .collect()
+    });
+
+    let failures: Vec<String> = results.into_iter()
+        .filter_map(|(output_path, result)| result.err().map(|e| format!("{}: {}", output_path.display(), e)))
+        .collect();
+    if failures.is_empty() {
+        debug!("Wrote the document in {} formats", targets.len());
+        return Ok(());
+    }
+    Err(AppError::OperationFailed(format!("{} of {} outputs failed: {}", failures.len(), targets.len(), failures.join("; "))))
+}
+
+/// The same document in every other format, next to `output_path` (e.g. `context.adoc` for `context.md`)
+pub fn companion_targets(output_path: &Path, format: OutputFormat) -> Vec<(PathBuf, OutputFormat)> {
+    OutputFormat::ALL.into_iter()
+        .filter(|other| *other != format)
+        .map(|other| (output_path.with_extension(other.extension()), other))
+        .collect()
+}
+
+/// Runs document writes one at a time on a background thread, so a partial update can't
+/// clobber a full generation that finishes after it (or the other way round)
+pub struct DocumentWorker {
+    job_sender: Option<mpsc::Sender<DocumentJob>>,
+    thread_handle: Option<thread::JoinHandle<()>>,
+}
+
+impl DocumentWorker {
+    pub fn new(event_sender: mpsc::Sender<AppEvent>) -> Self {
+        let (job_sender, job_receiver) = mpsc::channel();
+        let thread_handle = thread::spawn(move || {
+            Self::worker_thread(job_receiver, event_sender);
+        });
+
+        Self {
+            job_sender: Some(job_sender),
+            thread_handle: Some(thread_handle),
+        }
+    }
+
+    pub fn submit(&self, job: DocumentJob) {
+        let Some(job_sender) = &self.job_sender else { return };
+        if let Err(e) = job_sender.send(job) {
+            error!("Failed to queue document write: {}", e.0.name());
+        }
+    }
+
+    /// Lets the thread finish the queued writes until `deadline`; `false` when it didn't make it
+    /// and was left running
+    pub fn shutdown(&mut self, deadline: Instant) -> bool {
+        self.job_sender.take();
+        let Some(handle) = self.thread_handle.take() else { return true };
+        while !handle.is_finished() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(SHUTDOWN_POLL_INTERVAL);
+        }
+        let _ = handle.join();
+        true
+    }
+
+    fn worker_thread(job_receiver: mpsc::Receiver<DocumentJob>, event_sender: mpsc::Sender<AppEvent>) {
+        while let Ok(job) = job_receiver.recv() {
+            // Everything queued while the previous job ran is handled as one batch
+            let mut jobs = vec![job];
+            jobs.extend(job_receiver.try_iter());
+
+            for job in Self::coalesce(jobs) {
+                debug!("Running document {}", job.name());
+                job.run(&event_sender);
+            }
+        }
+        debug!("Document worker exiting");
+    }
+
+    /// Drops jobs made redundant by a later full generation, which rewrites everything from
+    /// the current selection, and merges consecutive section updates into one pass
+    fn coalesce(mut jobs: Vec<DocumentJob>) -> Vec<DocumentJob> {
+        if let Some(last_generation) = jobs.iter().rposition(|job| matches!(job, DocumentJob::Generate { .. })) {
+            if last_generation > 0 {
+                debug!("Skipping {} document writes superseded by a full generation", last_generation);
+            }
+            jobs.drain(..last_generation);
+        }
+
+        let mut coalesced: Vec<DocumentJob> = Vec::with_capacity(jobs.len());
+        for job in jobs {
+            match (coalesced.last_mut(), job) {
+                (
+                    Some(DocumentJob::UpdateSections { file_paths, .. }),
+                    DocumentJob::UpdateSections { generator, document_paths, file_paths: new_file_paths, format },
+                ) => {
+                    // The newer job's generator carries the current settings and selection
+                    let mut merged_file_paths = std::mem::take(file_paths);
+                    for path in new_file_paths {
+                        if !merged_file_paths.contains(&path) {
+                            merged_file_paths.push(path);
+                        }
+                    }
+                    coalesced.pop();
+                    coalesced.push(DocumentJob::UpdateSections { generator, document_paths, file_paths: merged_file_paths, format });
+                }
+                (_, job) => coalesced.push(job),
+            }
+        }
+        coalesced
+    }
+}
+
+impl Drop for DocumentWorker {
+    fn drop(&mut self) {
+        // Closing the channel lets the thread finish the queued writes and exit
+        self.job_sender.take();
+        if let Some(handle) = self.thread_handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document_generator::chunk_output_path;
+    use crate::document_index::document_index_path;
+    use crate::file_handler::FileHandler;
+
+    /// Runs a split generation of every file in `directory` and returns the index it wrote
+    fn generate_split(directory: &Path, output_path: &Path, chunk_token_limit: usize) -> serde_json::Value {
+        let root_node = FileHandler::new(directory.to_path_buf()).unwrap().scan_directory(Vec::new()).unwrap();
+        let generator = DocumentGenerator::new(directory.to_path_buf(), root_node.file_paths())
+            .with_document_index(Some(document_index_path(output_path)));
+        let job = DocumentJob::Generate {
+            generator,
+            root_node,
+            output_path: output_path.to_path_buf(),
+            format: OutputFormat::Markdown,
+            chunk_token_limit: Some(chunk_token_limit),
+            template_path: None,
+            companion_targets: Vec::new(),
+        };
+        let (event_sender, event_receiver) = mpsc::channel();
+        job.run(&event_sender);
+        let completed = event_receiver.try_iter().any(|event| matches!(event, AppEvent::DocumentGenerationComplete(Ok(()))));
+        assert!(completed, "the generation failed");
+        serde_json::from_slice(&std::fs::read(document_index_path(output_path)).unwrap()).unwrap()
+    }
+
+    fn indexed_documents(index: &serde_json::Value) -> Vec<&str> {
+        index["sections"].as_array().unwrap().iter().map(|section| section["document"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn split_generations_index_the_parts_they_wrote() {
+        let project = tempfile::tempdir().unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            std::fs::write(project.path().join(name), format!("{}\n", name.repeat(400))).unwrap();
+        }
+        let output = tempfile::tempdir().unwrap();
+        let output_path = output.path().join("context.md");
+
+        // First generation: no parts existed before it
+        let index = generate_split(project.path(), &output_path, 1000);
+        assert_eq!(indexed_documents(&index).len(), 3);
+        assert!(chunk_output_path(&output_path, 2).exists());
+
+        // Fewer parts than before: the deleted ones are neither read nor indexed
+        let index = generate_split(project.path(), &output_path, 100_000);
+        assert_eq!(indexed_documents(&index), ["context_part1.md"; 3]);
+        assert!(!chunk_output_path(&output_path, 2).exists());
+    }
+}
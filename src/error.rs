@@ -1,4 +1,5 @@
-use std::path::PathBuf;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -13,6 +14,8 @@ pub enum AppError {
     Notify(#[from] notify::Error),
     #[error("Error building ignore rules (ignore crate): {0}")]
     IgnoreBuild(#[from] ignore::Error),
+    #[error("Directory scan was cancelled")]
+    ScanCancelled,
     #[error("Invalid directory selected: {0}")]
     InvalidDirectory(String),
     #[error("File path not found: {0}")]
@@ -32,10 +35,24 @@ pub enum AppError {
     #[allow(dead_code)]
     #[error("Error handling non-UTF8 content for file {path:?}: {details}")]
     NonUtf8Content { path: PathBuf, details: String },
+    #[error("Binary file {path:?} ({size_bytes} bytes) was skipped")]
+    BinaryFile { path: PathBuf, size_bytes: u64 },
     #[error("Permissions error accessing {path:?}: {details}")]
     PermissionsError { path: PathBuf, details: String },
     #[error("Failed to create or persist temporary file for atomic write at {path:?}: {details}")]
     AtomicWriteError { path: PathBuf, details: String },
+    #[error("Template error: {0}")]
+    TemplateError(String),
+    #[error("Cache error for {path:?}: {details}")]
+    CacheError { path: PathBuf, details: String },
+    #[error("Settings error for {path:?}: {details}")]
+    SettingsError { path: PathBuf, details: String },
+    #[error("Update check failed: {0}")]
+    UpdateCheckError(String),
+    #[error("LLM request failed: {0}")]
+    LlmError(String),
+    #[error("Fetching the remote directory failed: {0}")]
+    RemoteSourceError(String),
     /// Symlink handling errors
     #[allow(dead_code)]
     #[error("Symlink error for {path:?}: {details}")]
@@ -47,6 +64,47 @@ impl AppError {
     pub fn new_io_error(source: std::io::Error, path: Option<PathBuf>, details: String) -> Self {
         AppError::Io { source, path, details }
     }
+
+    /// The path the error is about, if it has one
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            AppError::Io { path, .. } => path.as_deref(),
+            AppError::Notify(e) => e.paths.first().map(PathBuf::as_path),
+            AppError::PathNotFound(path)
+            | AppError::StripPrefixError { path, .. }
+            | AppError::NonUtf8Content { path, .. }
+            | AppError::BinaryFile { path, .. }
+            | AppError::PermissionsError { path, .. }
+            | AppError::AtomicWriteError { path, .. }
+            | AppError::CacheError { path, .. }
+            | AppError::SettingsError { path, .. }
+            | AppError::SymlinkError { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// What the user can do about the error, shown below its details
+    pub fn suggestion(&self) -> Option<&'static str> {
+        match self {
+            AppError::Io { source, .. } => match source.kind() {
+                ErrorKind::PermissionDenied => Some("Check that you can read (and for the output, write) this path."),
+                ErrorKind::NotFound => Some("The path was moved or deleted. Rescan the directory or choose another one."),
+                _ => None,
+            },
+            AppError::Notify(_) => Some("The system may be out of file watches (on Linux, raise fs.inotify.max_user_watches), or enable \"Always use the polling watcher\" under Settings → Monitoring."),
+            AppError::IgnoreBuild(_) => Some("Check the ignore patterns and the project's .gitignore files for invalid globs."),
+            AppError::InvalidDirectory(_) | AppError::PathNotFound(_) => Some("Choose an existing directory."),
+            AppError::PermissionsError { .. } => Some("Check that you can read (and for the output, write) this path."),
+            AppError::AtomicWriteError { .. } => Some("Check that the output directory exists, is writable and has free space, or choose another output path."),
+            AppError::TemplateError(_) => Some("Fix the custom template or switch back to the built-in one under Settings → Output."),
+            AppError::CacheError { .. } => Some("The cache is only an optimization; deleting the file is safe."),
+            AppError::SettingsError { .. } => Some("Fix or delete the file; the defaults are used until then."),
+            AppError::UpdateCheckError(_) => Some("Check the network connection. The check uses the system curl."),
+            AppError::LlmError(_) => Some("Check the endpoint, model and API key under Settings → LLM. Requests use the system curl."),
+            AppError::RemoteSourceError(_) => Some("Check that `ssh HOST` logs in without a password prompt (use an SSH agent or key) and that the path exists on the server."),
+            _ => None,
+        }
+    }
 }
 
 pub type Result<T, E = AppError> = std::result::Result<T, E>; 
\ No newline at end of file
@@ -1,22 +1,68 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
+use crate::directory_compare::DirectoryComparison;
+use crate::document_generator::{FileSnapshot, OutlineEntry};
 use crate::file_handler::FileNode;
 use crate::error::AppError;
+use crate::generation_history::HistoryEntry;
+use crate::remote_source::RemoteSource;
+use crate::update_check::ReleaseInfo;
 
 /// Events sent from background threads to the main UI thread
 #[derive(Debug)]
 pub enum AppEvent {
+    /// A previously cached tree was loaded; a validating scan is still running
+    CachedScanLoaded(FileNode),
+    /// Number of entries walked so far by the running directory scan
+    ScanProgress(usize),
     /// Directory scan completed
     DirectoryScanComplete(Result<FileNode, AppError>),
-    /// File modified and debounced
-    FileModifiedDebounced(PathBuf),
+    /// Background rescan after a structural change completed; merged into the current tree
+    StructureRescanComplete(Result<FileNode, AppError>),
+    /// Children of a directory of a shallow tree were scanned; `select_files` selects every file
+    /// in the loaded subtree (its directory checkbox was clicked before it was loaded)
+    DirectoryChildrenLoaded { directory: PathBuf, result: Result<FileNode, AppError>, select_files: bool },
+    /// Files modified and debounced, batched per debounce check
+    FilesModifiedDebounced(Vec<PathBuf>),
+    /// File deleted or moved out of the watched directory
+    FileRemoved(PathBuf),
+    /// File renamed or moved within the watched directory
+    FileRenamed { from: PathBuf, to: PathBuf },
     /// Document generation completed (renamed)
     DocumentGenerationComplete(Result<(), AppError>),
+    /// Files read by a document job, each with its read error if it had one; `replace` (full
+    /// generations) discards the errors of files not read this time
+    FilesRead { outcomes: HashMap<PathBuf, Option<String>>, replace: bool },
     /// Partial document update completed (renamed)
     PartialDocumentUpdateComplete(Result<(), AppError>),
-    /// Signals that the directory content has changed, requiring a full re-scan.
-    DirectoryContentChanged,
-    /// File watcher encountered an error
-    #[allow(dead_code)]
+    /// A full generation was read into an entry of the generation history
+    GenerationRecorded(Result<HistoryEntry, AppError>),
+    /// Outline of the current output document (headings and section sizes)
+    DocumentOutlineParsed(Result<Vec<OutlineEntry>, AppError>),
+    /// Progress of the background token count `id` over the selected files; `done` once all are counted
+    TokenCountProgress { id: u64, counted_files: usize, tokens: usize, done: bool },
+    /// A file's content as the document would show it, for the preview panel
+    FilePreviewLoaded { path: PathBuf, content: String },
+    /// File contents read for snapshot mode; `replace` discards the previous snapshot
+    SnapshotTaken { files: FileSnapshot, replace: bool },
+    /// A directory comparison document was written to this path
+    ComparisonComplete(Result<(PathBuf, DirectoryComparison), AppError>),
+    /// The document was exported to this PDF
+    PdfExported(Result<PathBuf, AppError>),
+    /// The server directory was mirrored into this local directory
+    RemoteSourceFetched { source: RemoteSource, result: Result<PathBuf, AppError> },
+    /// A scheduled generation wrote this timestamped document
+    ScheduledGenerationComplete(Result<PathBuf, AppError>),
+    /// The latest release was fetched; `manual` checks also report when there is no update
+    UpdateCheckComplete { result: Result<ReleaseInfo, AppError>, manual: bool },
+    /// Text streamed for the answer of LLM request `id`
+    LlmAnswerChunk { id: u64, text: String },
+    /// LLM request `id` finished, was cancelled or failed
+    LlmAnswerDone { id: u64, result: Result<(), AppError> },
+    /// Signals that the directory content has changed, requiring a re-scan. Carries the paths
+    /// created since the last report.
+    DirectoryContentChanged(Vec<PathBuf>),
+    /// File watcher encountered an error; the app restarts it with backoff
     WatcherError(AppError),
     /// Status message to display to user
     #[allow(dead_code)]
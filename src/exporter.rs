@@ -0,0 +1,164 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use log::info;
+use printpdf::{BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, PdfPageIndex};
+
+use crate::compression::read_document;
+use crate::constants::OutputFormat;
+use crate::document_generator::DocumentGenerator;
+use crate::error::{AppError, Result};
+use crate::utils::write_file_atomically;
+
+const PAGE_WIDTH_MM: f32 = 210.0; // A4
+const PAGE_HEIGHT_MM: f32 = 297.0;
+const MARGIN_MM: f32 = 15.0;
+const FONT_SIZE_PT: f32 = 8.0;
+const LINE_HEIGHT_MM: f32 = 3.5;
+const FOOTER_FONT_SIZE_PT: f32 = 7.0;
+const TAB_WIDTH: usize = 4;
+
+/// Width of one Courier character, which is 0.6 em
+const CHARACTER_WIDTH_MM: f32 = FONT_SIZE_PT * 0.6 * 25.4 / 72.0;
+
+/// Renders the document, or all parts of a split document, to a PDF for attaching to review
+/// tickets: monospace text on A4 pages with bold headings, long lines wrapped, page numbers,
+/// and a bookmark per page for the first file section starting on it.
+/// PDF built-in fonts only cover Latin-1; tree characters are drawn as ASCII and other
+/// characters as `?`.
+pub fn export_pdf(directory: &Path, document_paths: &[PathBuf], format: OutputFormat, pdf_path: &Path) -> Result<()> {
+    let generator = DocumentGenerator::new(directory.to_path_buf(), Vec::new());
+    let title = pdf_path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("Context");
+    let mut writer = PdfWriter::new(title)?;
+
+    for document_path in document_paths {
+        let content = read_document(document_path)
+            .map_err(|e| AppError::new_io_error(e, Some(document_path.clone()), "Failed to read document for PDF export".to_string()))?;
+        let outline = generator.text_outline(&content, format);
+        let heading_lines: HashSet<usize> = outline.iter().map(|entry| entry.line).collect();
+
+        for (index, line) in content.lines().enumerate() {
+            let line_number = index + 1;
+            if let Some(entry) = outline.iter().find(|entry| entry.line == line_number && entry.file_path.is_some()) {
+                writer.bookmark(&entry.title);
+            }
+            writer.write_line(&pdf_text(line), heading_lines.contains(&line_number));
+        }
+    }
+
+    let bytes = writer.finish()?;
+    write_file_atomically(pdf_path, &bytes)?;
+    info!("Exported the document to {}", pdf_path.display());
+    Ok(())
+}
+
+struct PdfWriter {
+    document: PdfDocumentReference,
+    layer: PdfLayerReference,
+    page_index: PdfPageIndex,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    y: f32,
+    bookmarked_page: bool,
+    page_count: usize,
+    columns: usize,
+}
+
+impl PdfWriter {
+    fn new(title: &str) -> Result<Self> {
+        let (document, page_index, layer_index) = PdfDocument::new(title, Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), "Text");
+        let regular = document.add_builtin_font(BuiltinFont::Courier).map_err(pdf_error)?;
+        let bold = document.add_builtin_font(BuiltinFont::CourierBold).map_err(pdf_error)?;
+        let layer = document.get_page(page_index).get_layer(layer_index);
+        let writer = Self {
+            document,
+            layer,
+            page_index,
+            regular,
+            bold,
+            y: PAGE_HEIGHT_MM - MARGIN_MM,
+            bookmarked_page: false,
+            page_count: 1,
+            columns: ((PAGE_WIDTH_MM - 2.0 * MARGIN_MM) / CHARACTER_WIDTH_MM) as usize,
+        };
+        writer.write_page_number();
+        Ok(writer)
+    }
+
+    /// Writes one line, wrapped at the page width, starting a new page when this one is full
+    fn write_line(&mut self, line: &str, bold: bool) {
+        let characters: Vec<char> = line.chars().collect();
+        let mut rows: Vec<String> = characters.chunks(self.columns.max(1)).map(|row| row.iter().collect()).collect();
+        if rows.is_empty() {
+            rows.push(String::new()); // Blank lines still take their space
+        }
+
+        for row in rows {
+            if self.y - LINE_HEIGHT_MM < MARGIN_MM {
+                self.new_page();
+            }
+            self.y -= LINE_HEIGHT_MM;
+            if !row.is_empty() {
+                let font = if bold { &self.bold } else { &self.regular };
+                self.layer.use_text(row, FONT_SIZE_PT, Mm(MARGIN_MM), Mm(self.y), font);
+            }
+        }
+    }
+
+    /// Bookmarks the page of the next line; PDF outlines made by printpdf hold one entry per page
+    fn bookmark(&mut self, title: &str) {
+        if self.y - LINE_HEIGHT_MM < MARGIN_MM {
+            self.new_page();
+        }
+        if !self.bookmarked_page {
+            self.document.add_bookmark(pdf_text(title), self.page_index);
+            self.bookmarked_page = true;
+        }
+    }
+
+    fn new_page(&mut self) {
+        let (page_index, layer_index) = self.document.add_page(Mm(PAGE_WIDTH_MM), Mm(PAGE_HEIGHT_MM), "Text");
+        self.layer = self.document.get_page(page_index).get_layer(layer_index);
+        self.page_index = page_index;
+        self.y = PAGE_HEIGHT_MM - MARGIN_MM;
+        self.bookmarked_page = false;
+        self.page_count += 1;
+        self.write_page_number();
+    }
+
+    fn write_page_number(&self) {
+        let number = self.page_count.to_string();
+        let x = PAGE_WIDTH_MM - MARGIN_MM - number.len() as f32 * FOOTER_FONT_SIZE_PT * 0.6 * 25.4 / 72.0;
+        self.layer.use_text(number, FOOTER_FONT_SIZE_PT, Mm(x), Mm(MARGIN_MM / 2.0), &self.regular);
+    }
+
+    fn finish(self) -> Result<Vec<u8>> {
+        self.document.save_to_bytes().map_err(pdf_error)
+    }
+}
+
+fn pdf_error(error: printpdf::Error) -> AppError {
+    AppError::DocumentGenerationError(format!("Failed to write PDF: {}", error))
+}
+
+/// Text the built-in fonts can draw: tabs expanded, tree and typographic characters as ASCII,
+/// anything else outside Latin-1 as `?`
+fn pdf_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '\t' => {
+                let column = result.chars().count();
+                result.push_str(&" ".repeat(TAB_WIDTH - column % TAB_WIDTH));
+            }
+            '├' | '│' | '┃' | '┣' => result.push('|'),
+            '└' | '┗' => result.push('`'),
+            '─' | '━' | '–' | '—' => result.push('-'),
+            '‘' | '’' => result.push('\''),
+            '“' | '”' => result.push('"'),
+            '…' => result.push_str("..."),
+            ' '..='~' | '\u{a0}'..='\u{ff}' => result.push(character),
+            _ => result.push('?'),
+        }
+    }
+    result
+}
@@ -0,0 +1,103 @@
+use std::env;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use log::{info, warn};
+
+use crate::error::{AppError, Result};
+
+// Editors from $VISUAL/$EDITOR that need a terminal, which a desktop app doesn't have
+const TERMINAL_EDITORS: &[&str] = &["vi", "vim", "nvim", "nano", "pico", "micro", "hx", "helix", "kak", "joe", "ne", "ed", "emacsclient -t", "emacs -nw"];
+
+/// Opens `path` with `editor_command` (e.g. `code -g {path}`; the path is appended when there is
+/// no `{path}`), or when that is blank, with $VISUAL/$EDITOR unless it is a terminal editor, or
+/// else the OS default application
+pub fn open_in_editor(path: &Path, editor_command: &str) -> Result<()> {
+    let environment_editor = ["VISUAL", "EDITOR"].iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|editor| !editor.trim().is_empty() && !is_terminal_editor(editor));
+    match (editor_command.trim(), environment_editor) {
+        ("", None) => spawn(default_open_command(path), path),
+        ("", Some(editor)) => spawn(editor_command_line(&editor, path)?, path),
+        (editor_command, _) => spawn(editor_command_line(editor_command, path)?, path),
+    }
+}
+
+/// Shows `path` in the system file manager: selected in its folder on macOS and Windows, its
+/// containing folder elsewhere
+pub fn reveal_in_file_manager(path: &Path) -> Result<()> {
+    let command = if cfg!(target_os = "macos") {
+        command_with_args("open", [Path::new("-R"), path])
+    } else if cfg!(windows) {
+        let mut command = Command::new("explorer");
+        command.arg(format!("/select,{}", path.display()));
+        command
+    } else {
+        let folder = if path.is_dir() { path } else { path.parent().unwrap_or(path) };
+        command_with_args("xdg-open", [folder])
+    };
+    spawn(command, path)
+}
+
+fn is_terminal_editor(editor: &str) -> bool {
+    let editor = editor.trim();
+    let program = editor.split_whitespace().next().unwrap_or_default();
+    let program = Path::new(program).file_name().and_then(|name| name.to_str()).unwrap_or(program);
+    TERMINAL_EDITORS.iter().any(|terminal_editor| *terminal_editor == program || editor.ends_with(terminal_editor))
+}
+
+/// Splits the command on whitespace; there is no shell, so quoting is not supported
+fn editor_command_line(editor_command: &str, path: &Path) -> Result<Command> {
+    let path_text = path.display().to_string();
+    let mut parts: Vec<String> = editor_command.split_whitespace().map(str::to_string).collect();
+    if parts.iter().any(|part| part.contains("{path}")) {
+        for part in &mut parts {
+            *part = part.replace("{path}", &path_text);
+        }
+    } else {
+        parts.push(path_text);
+    }
+    let (program, args) = parts.split_first()
+        .ok_or_else(|| AppError::OperationFailed("The editor command is empty".to_string()))?;
+    let mut command = Command::new(program);
+    command.args(args);
+    Ok(command)
+}
+
+fn default_open_command(path: &Path) -> Command {
+    if cfg!(target_os = "macos") {
+        command_with_args("open", [path])
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]).arg(path);
+        command
+    } else {
+        command_with_args("xdg-open", [path])
+    }
+}
+
+fn command_with_args<'a>(program: &str, args: impl IntoIterator<Item = &'a Path>) -> Command {
+    let mut command = Command::new(program);
+    command.args(args);
+    command
+}
+
+/// Starts the command without waiting for it; a thread reaps it once it exits
+fn spawn(mut command: Command, path: &Path) -> Result<()> {
+    info!("Opening {:?} with {:?}", path, command);
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| {
+            let details = format!("Failed to run {:?}: {}", command.get_program(), e);
+            AppError::new_io_error(e, Some(path.to_path_buf()), details)
+        })?;
+    thread::spawn(move || match child.wait() {
+        Ok(status) if !status.success() => warn!("External command exited with {}", status),
+        Ok(_) => {}
+        Err(e) => warn!("Failed to wait for external command: {}", e),
+    });
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use std::path::Path;
+
+/// Languages of files recognized by name, for names without a telling extension
+const FILE_NAME_LANGUAGES: &[(&str, &str)] = &[
+    ("Dockerfile", "dockerfile"),
+    ("Dockerfile.*", "dockerfile"),
+    ("*.Dockerfile", "dockerfile"),
+    ("Containerfile", "dockerfile"),
+    ("Makefile", "makefile"),
+    ("GNUmakefile", "makefile"),
+    ("CMakeLists.txt", "cmake"),
+    ("Jenkinsfile", "groovy"),
+    ("Gemfile", "ruby"),
+    ("Rakefile", "ruby"),
+    ("Vagrantfile", "ruby"),
+    ("Podfile", "ruby"),
+    ("Brewfile", "ruby"),
+    (".bashrc", "bash"),
+    (".bash_profile", "bash"),
+    (".profile", "bash"),
+    (".zshrc", "zsh"),
+    ("BUILD", "python"), // Starlark
+    ("BUILD.bazel", "python"),
+    ("WORKSPACE", "python"),
+];
+
+/// Extensions whose fence language is not the extension itself
+const EXTENSION_LANGUAGES: &[(&str, &str)] = &[
+    ("mk", "makefile"),
+    ("dockerfile", "dockerfile"),
+    ("h", "c"),
+    ("hh", "cpp"),
+    ("hpp", "cpp"),
+    ("hxx", "cpp"),
+    ("cc", "cpp"),
+    ("cxx", "cpp"),
+    ("mjs", "javascript"),
+    ("cjs", "javascript"),
+    ("mts", "typescript"),
+    ("cts", "typescript"),
+    ("pyi", "python"),
+    ("kts", "kotlin"),
+    ("ps1", "powershell"),
+    ("psm1", "powershell"),
+    ("gradle", "groovy"),
+    ("tf", "hcl"),
+    ("csproj", "xml"),
+    ("svg", "xml"),
+];
+
+/// Interpreters named on a shebang line; version suffixes such as `python3.12` are ignored
+const SHEBANG_LANGUAGES: &[(&str, &str)] = &[
+    ("python", "python"),
+    ("bash", "bash"),
+    ("sh", "sh"),
+    ("dash", "sh"),
+    ("zsh", "zsh"),
+    ("fish", "fish"),
+    ("node", "javascript"),
+    ("deno", "typescript"),
+    ("bun", "javascript"),
+    ("ruby", "ruby"),
+    ("perl", "perl"),
+    ("php", "php"),
+    ("lua", "lua"),
+    ("Rscript", "r"),
+    ("pwsh", "powershell"),
+    ("awk", "awk"),
+    ("tclsh", "tcl"),
+];
+
+/// `pattern = language` lines from the settings, e.g. `*.tpl = html` or `Justfile = make`.
+/// Blank lines, lines starting with `#` and lines without `=` are skipped.
+pub fn parse_fence_languages(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (pattern, language) = line.split_once('=')?;
+            let (pattern, language) = (pattern.trim(), language.trim());
+            (!pattern.is_empty() && !language.is_empty()).then(|| (pattern.to_string(), language.to_string()))
+        })
+        .collect()
+}
+
+/// The code fence language of a file: from the user's patterns, then the built-in file names
+/// and extensions, then the shebang of files without an extension, and else the extension
+/// itself. `first_line` is only read for files without an extension.
+pub fn fence_language(path: &Path, first_line: impl FnOnce() -> Option<String>, custom: &[(String, String)]) -> String {
+    let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    let by_name = custom.iter()
+        .map(|(pattern, language)| (pattern.as_str(), language.as_str()))
+        .chain(FILE_NAME_LANGUAGES.iter().copied())
+        .find(|(pattern, _)| matches_file_name(pattern, file_name));
+    if let Some((_, language)) = by_name {
+        return language.to_string();
+    }
+
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => EXTENSION_LANGUAGES.iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(extension))
+            .map_or(extension, |(_, language)| language)
+            .to_string(),
+        None => first_line()
+            .and_then(|line| shebang_language(&line))
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+/// Case-insensitive match of a file name against a pattern where `*` stands for any text
+fn matches_file_name(pattern: &str, file_name: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let file_name = file_name.to_lowercase();
+    let mut parts = pattern.split('*');
+    let Some(first) = parts.next() else {
+        return false;
+    };
+    let Some(mut rest) = file_name.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty(); // No `*`
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+fn shebang_language(line: &str) -> Option<&'static str> {
+    let command = line.strip_prefix("#!")?.trim();
+    let mut words = command.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        // `env -S python3 -u`: the first word that isn't an option
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+    let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    SHEBANG_LANGUAGES.iter()
+        .find(|(interpreter, _)| *interpreter == program)
+        .map(|(_, language)| *language)
+}
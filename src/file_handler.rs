@@ -1,16 +1,43 @@
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::cmp::Ordering;
 use std::fs;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::mpsc::Sender;
+use std::time::SystemTime;
 use ignore::{WalkBuilder, DirEntry};
+use ignore::overrides::OverrideBuilder;
 use log::{debug, warn};
+use serde::{Deserialize, Serialize};
 
+use crate::constants::{LINE_COUNT_MAX_FILE_BYTES, SCAN_PROGRESS_INTERVAL};
+use crate::document_generator::is_output_document_path;
 use crate::error::{AppError, Result};
+use crate::events::AppEvent;
+use crate::generated_files::GeneratedFileDetector;
+use crate::gitignored_files::GitignoredFileDetector;
+use crate::lifecycle::{LifecycleEvent, LifecycleObserver};
+use crate::temp_files::is_temp_file;
+use crate::utils::looks_binary;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileNode {
     pub name: String,          // Base name of the file/directory
     pub path: PathBuf,         // Full, canonicalized path
     pub is_dir: bool,
+    #[serde(default)]
+    pub is_generated: bool,    // Marked `linguist-generated` in a .gitattributes file
+    #[serde(default)]
+    pub is_gitignored: bool,   // Matched by a .gitignore file, e.g. outside a git repository
+    #[serde(default)]
+    pub size_bytes: u64,       // File size, or the total size of the files below a directory
+    #[serde(default)]
+    pub modified: Option<SystemTime>, // Last modification, or the latest one below a directory
+    #[serde(default)]
+    pub line_count: Option<usize>, // Only counted for text files when enabled for the scan
+    #[serde(default)]
+    pub children_pending: bool, // Directory left unscanned by a shallow scan, loaded when expanded
     pub children: Vec<FileNode>, // Sorted: directories first, then files, then alphabetically case-insensitively
 }
 
@@ -41,8 +68,124 @@ impl Ord for FileNode {
     }
 }
 
+impl FileNode {
+    /// Paths of this node and all its descendants
+    pub fn all_paths(&self) -> HashSet<PathBuf> {
+        let mut paths = HashSet::new();
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            paths.insert(node.path.clone());
+            stack.extend(&node.children);
+        }
+        paths
+    }
+
+    pub fn find(&self, path: &Path) -> Option<&FileNode> {
+        if self.path == path {
+            return Some(self);
+        }
+        self.children.iter()
+            .filter(|child| path.starts_with(&child.path))
+            .find_map(|child| child.find(path))
+    }
+
+    /// Detaches the node at `path` from the tree
+    pub fn remove(&mut self, path: &Path) -> Option<FileNode> {
+        if let Some(index) = self.children.iter().position(|child| child.path == path) {
+            return Some(self.children.remove(index));
+        }
+        self.children.iter_mut()
+            .filter(|child| path.starts_with(&child.path))
+            .find_map(|child| child.remove(path))
+    }
+
+    /// Adds `node` to the directory containing its path, keeping children sorted. Returns the
+    /// node back if that directory is not in the tree.
+    pub fn insert(&mut self, node: FileNode) -> std::result::Result<(), FileNode> {
+        if node.path.parent() == Some(self.path.as_path()) && self.is_dir {
+            let index = self.children.binary_search(&node).unwrap_or_else(|index| index);
+            self.children.insert(index, node);
+            return Ok(());
+        }
+        match self.children.iter_mut().find(|child| child.is_dir && node.path.starts_with(&child.path)) {
+            Some(child) => child.insert(node),
+            None => Err(node),
+        }
+    }
+
+    /// Replaces the node at `node.path`, e.g. with a lazily loaded directory. Returns the node
+    /// back if its path is not in the tree.
+    pub fn replace(&mut self, node: FileNode) -> std::result::Result<(), FileNode> {
+        if self.path == node.path {
+            *self = node;
+            return Ok(());
+        }
+        match self.children.iter_mut().find(|child| child.is_dir && node.path.starts_with(&child.path)) {
+            Some(child) => child.replace(node),
+            None => Err(node),
+        }
+    }
+
+    /// Directories whose children were scanned, i.e. all directories of a full scan
+    pub fn loaded_directories(&self) -> HashSet<PathBuf> {
+        let mut directories = HashSet::new();
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            if node.is_dir && !node.children_pending {
+                directories.insert(node.path.clone());
+            }
+            stack.extend(&node.children);
+        }
+        directories
+    }
+
+    /// Fills directories still pending here with their loaded counterparts from `other`, so a
+    /// shallow rescan doesn't collapse directories loaded while it ran
+    pub fn fill_pending_from(&mut self, other: &FileNode) {
+        if self.children_pending {
+            if let Some(loaded) = other.find(&self.path).filter(|node| !node.children_pending) {
+                *self = loaded.clone();
+            }
+            return;
+        }
+        for child in &mut self.children {
+            child.fill_pending_from(other);
+        }
+    }
+
+    /// Paths of all files (not directories) in this subtree
+    pub fn file_paths(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            if !node.is_dir {
+                files.push(node.path.clone());
+            }
+            stack.extend(&node.children);
+        }
+        files
+    }
+
+    /// Recomputes the aggregate sizes and modification times of directories after nodes were
+    /// inserted or removed
+    pub fn update_directory_sizes(&mut self) -> u64 {
+        if self.is_dir {
+            self.size_bytes = self.children.iter_mut().map(|child| child.update_directory_sizes()).sum();
+            self.modified = self.children.iter().filter_map(|child| child.modified).max();
+        }
+        self.size_bytes
+    }
+}
+
 pub struct FileHandler {
     directory: PathBuf,
+    exclude_generated: bool,
+    count_lines: bool,
+    max_depth: Option<usize>,
+    progress_sender: Option<Sender<AppEvent>>,
+    cancel_flag: Option<Arc<AtomicBool>>,
+    output_path: Option<PathBuf>,
+    lifecycle: LifecycleObserver,
 }
 
 impl FileHandler {
@@ -71,13 +214,101 @@ impl FileHandler {
             }
         }
 
-        Ok(FileHandler { directory })
+        Ok(FileHandler { directory, exclude_generated: false, count_lines: false, max_depth: None, progress_sender: None, cancel_flag: None, output_path: None, lifecycle: LifecycleObserver::default() })
+    }
+
+    /// Leaves files marked `linguist-generated` out of the tree instead of just flagging them
+    pub fn with_generated_files_excluded(mut self, exclude_generated: bool) -> Self {
+        self.exclude_generated = exclude_generated;
+        self
+    }
+
+    /// Counts the lines of text files up to `LINE_COUNT_MAX_FILE_BYTES`, which reads their content
+    pub fn with_line_counts(mut self, count_lines: bool) -> Self {
+        self.count_lines = count_lines;
+        self
+    }
+
+    /// Shallow scans for huge trees: directories at this depth are not entered and are marked
+    /// `children_pending` instead
+    pub fn with_max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Reports the number of walked entries as `AppEvent::ScanProgress` every few hundred entries
+    pub fn with_progress_sender(mut self, progress_sender: Sender<AppEvent>) -> Self {
+        self.progress_sender = Some(progress_sender);
+        self
+    }
+
+    /// Reports the start and outcome of each whole-project scan on this channel
+    pub fn with_lifecycle_sender(mut self, lifecycle_sender: Sender<LifecycleEvent>) -> Self {
+        self.lifecycle = LifecycleObserver::new(lifecycle_sender);
+        self
+    }
+
+    /// Leaves the document written to this path out of the tree, in any format, its parts and
+    /// its index
+    pub fn with_output_path(mut self, output_path: Option<PathBuf>) -> Self {
+        self.output_path = output_path;
+        self
+    }
+
+    /// Aborts the scan with `AppError::ScanCancelled` once the flag is set
+    pub fn with_cancel_flag(mut self, cancel_flag: Arc<AtomicBool>) -> Self {
+        self.cancel_flag = Some(cancel_flag);
+        self
     }
 
     pub fn scan_directory(&self, ignore_patterns: Vec<String>) -> Result<FileNode> {
-        debug!("Starting directory scan for: {:?}", self.directory);
+        self.lifecycle.observe_scan(&self.directory, || self.scan_path(&self.directory, ignore_patterns), |root_node| root_node.file_paths().len())
+    }
+
+    /// Scans a directory below the project root, e.g. one expanded in a shallow tree. Ignore
+    /// patterns and `.gitignore` files apply as in a scan of the whole project.
+    pub fn scan_subdirectory(&self, subdirectory: &Path, ignore_patterns: Vec<String>) -> Result<FileNode> {
+        // Walk below `directory` as given, so paths line up with the ignore pattern root
+        let canonical_root = self.directory.canonicalize().unwrap_or_else(|_| self.directory.clone());
+        let scan_root = match subdirectory.strip_prefix(&canonical_root) {
+            Ok(relative_path) => self.directory.join(relative_path),
+            Err(_) => subdirectory.to_path_buf(),
+        };
+        self.scan_path(&scan_root, ignore_patterns)
+    }
+
+    /// A shallow scan that also rescans the directories in `loaded_directories`, so directories
+    /// the user already expanded stay expanded
+    pub fn scan_directory_keeping_loaded(&self, ignore_patterns: Vec<String>, loaded_directories: &HashSet<PathBuf>) -> Result<FileNode> {
+        let mut root_node = self.scan_directory(ignore_patterns.clone())?;
+        let mut queue: Vec<PathBuf> = loaded_directories.iter()
+            .filter(|path| root_node.find(path).is_some_and(|node| node.children_pending))
+            .cloned()
+            .collect();
+        while let Some(directory) = queue.pop() {
+            let directory_node = match self.scan_subdirectory(&directory, ignore_patterns.clone()) {
+                Ok(directory_node) => directory_node,
+                Err(AppError::ScanCancelled) => return Err(AppError::ScanCancelled),
+                Err(e) => {
+                    warn!("Failed to rescan {:?}: {}", directory, e);
+                    continue;
+                }
+            };
+            queue.extend(directory_node.children.iter()
+                .filter(|child| child.children_pending && loaded_directories.contains(&child.path))
+                .map(|child| child.path.clone()));
+            if let Err(directory_node) = root_node.replace(directory_node) {
+                debug!("Rescanned {:?} is no longer in the tree", directory_node.path);
+            }
+        }
+        root_node.update_directory_sizes();
+        Ok(root_node)
+    }
+
+    fn scan_path(&self, scan_root: &Path, ignore_patterns: Vec<String>) -> Result<FileNode> {
+        debug!("Starting directory scan for: {:?}", scan_root);
         
-        let mut builder = WalkBuilder::new(&self.directory);
+        let mut builder = WalkBuilder::new(scan_root);
         
         // Configure the walker according to the plan
         builder
@@ -86,10 +317,11 @@ impl FileHandler {
             .git_ignore(true)
             .git_exclude(true)
             .hidden(false)          // initially include hidden files, let ignore patterns filter them
-            .follow_links(false);   // crucial: do not follow symlinks
+            .follow_links(false)    // crucial: do not follow symlinks
+            .max_depth(self.max_depth);
 
         // Add additional ignore patterns
-        let mut overrides_builder = ignore::overrides::OverrideBuilder::new(&self.directory);
+        let mut overrides_builder = OverrideBuilder::new(&self.directory);
         for pattern_to_ignore in ignore_patterns {
             let blacklist_pattern = format!("!{}", pattern_to_ignore);
             if let Err(e) = overrides_builder.add(&blacklist_pattern) {
@@ -104,26 +336,42 @@ impl FileHandler {
         let walker = builder.build();
         
         // Build the tree structure
-        let root_node = self.build_file_tree(walker)?;
+        let root_node = self.build_file_tree(walker, scan_root)?;
         
         debug!("Directory scan completed");
         Ok(root_node)
     }
 
-    fn build_file_tree(&self, walker: ignore::Walk) -> Result<FileNode> {
+    fn build_file_tree(&self, walker: ignore::Walk, scan_root: &Path) -> Result<FileNode> {
         let mut path_to_node: std::collections::HashMap<PathBuf, FileNode> = std::collections::HashMap::new();
         let mut parent_child_map: std::collections::HashMap<PathBuf, Vec<PathBuf>> = std::collections::HashMap::new();
 
         let mut total_entries = 0;
         let mut processed_entries = 0;
 
+        let detector_root = self.directory.canonicalize().unwrap_or_else(|_| self.directory.clone());
+        let mut generated_detector = GeneratedFileDetector::new(detector_root.clone());
+        let mut gitignored_detector = GitignoredFileDetector::new(detector_root);
+
         // First pass: collect all entries and build node relationships
         for result in walker {
+            if self.cancel_flag.as_ref().is_some_and(|flag| flag.load(AtomicOrdering::Relaxed)) {
+                debug!("Directory scan cancelled after {} entries", total_entries);
+                return Err(AppError::ScanCancelled);
+            }
+
             total_entries += 1;
+            if total_entries % SCAN_PROGRESS_INTERVAL == 0 {
+                if let Some(sender) = &self.progress_sender {
+                    if let Err(e) = sender.send(AppEvent::ScanProgress(total_entries)) {
+                        debug!("Failed to send scan progress: {}", e);
+                    }
+                }
+            }
             match result {
                 Ok(entry) => {
                     debug!("Processing entry: {:?}", entry.path());
-                    if let Err(e) = self.process_dir_entry(entry, &mut path_to_node, &mut parent_child_map) {
+                    if let Err(e) = self.process_dir_entry(entry, &mut generated_detector, &mut gitignored_detector, &mut path_to_node, &mut parent_child_map) {
                         warn!("Error processing directory entry: {}", e);
                     } else {
                         processed_entries += 1;
@@ -139,12 +387,12 @@ impl FileHandler {
         debug!("Created {} nodes", path_to_node.len());
 
         // Second pass: build the tree structure
-        let root_path = match self.directory.canonicalize() {
+        let root_path = match scan_root.canonicalize() {
             Ok(path) => path,
             Err(e) => {
                 return Err(AppError::new_io_error(
                     e,
-                    Some(self.directory.clone()),
+                    Some(scan_root.to_path_buf()),
                     "Failed to canonicalize root directory".to_string(),
                 ));
             }
@@ -156,6 +404,8 @@ impl FileHandler {
     fn process_dir_entry(
         &self,
         entry: DirEntry,
+        generated_detector: &mut GeneratedFileDetector,
+        gitignored_detector: &mut GitignoredFileDetector,
         path_to_node: &mut std::collections::HashMap<PathBuf, FileNode>,
         parent_child_map: &mut std::collections::HashMap<PathBuf, Vec<PathBuf>>,
     ) -> Result<()> {
@@ -184,10 +434,41 @@ impl FileHandler {
             }
         };
 
+        // Our own in-flight (or crash-orphaned) temp files are never project content, nor is our output
+        if is_temp_file(&canonical_path) {
+            return Ok(());
+        }
+        if !is_dir && self.output_path.as_ref().is_some_and(|output_path| is_output_document_path(&canonical_path, output_path)) {
+            debug!("Excluding output document {:?}", canonical_path);
+            return Ok(());
+        }
+
+        let is_generated = generated_detector.is_generated(&canonical_path, is_dir);
+        if is_generated && self.exclude_generated {
+            debug!("Excluding generated path {:?}", canonical_path);
+            return Ok(());
+        }
+
+        // Directory sizes and modification times are aggregated once the tree is built
+        let metadata = if is_dir { None } else { entry.metadata().ok() };
+        let size_bytes = metadata.as_ref().map(|metadata| metadata.len()).unwrap_or(0);
+        let modified = metadata.and_then(|metadata| metadata.modified().ok());
+        let line_count = if self.count_lines && !is_dir && size_bytes <= LINE_COUNT_MAX_FILE_BYTES {
+            count_lines(&canonical_path)
+        } else {
+            None
+        };
+
         let node = FileNode {
             name,
             path: canonical_path.clone(),
             is_dir,
+            is_generated,
+            is_gitignored: !is_dir && gitignored_detector.is_gitignored(&canonical_path, is_dir),
+            size_bytes,
+            modified,
+            line_count,
+            children_pending: is_dir && self.max_depth.is_some_and(|max_depth| entry.depth() == max_depth),
             children: Vec::new(),
         };
 
@@ -225,7 +506,47 @@ impl FileHandler {
             children.sort();
             node.children = children;
         }
+        if node.is_dir {
+            node.size_bytes = node.children.iter().map(|child| child.size_bytes).sum();
+            node.modified = node.children.iter().filter_map(|child| child.modified).max();
+        }
 
         Ok(node)
     }
-} 
\ No newline at end of file
+}
+
+/// Number of lines in a text file; `None` if it can't be read or looks binary
+fn count_lines(path: &Path) -> Option<usize> {
+    let bytes = fs::read(path).ok()?;
+    if looks_binary(&bytes) {
+        return None;
+    }
+    let newlines = bytes.iter().filter(|byte| **byte == b'\n').count();
+    // A last line without a trailing newline still counts
+    Some(newlines + usize::from(bytes.last().is_some_and(|byte| *byte != b'\n')))
+} 
+
+/// Files whose rules decide what a scan includes: ignore files and `.gitattributes`, which marks
+/// generated files
+const IGNORE_RULE_FILE_NAMES: &[&str] = &[".gitignore", ".ignore", ".gitattributes"];
+
+/// Whether changing `path` can change what a scan includes, so the tree needs a rescan
+pub fn is_ignore_rule_file(path: &Path) -> bool {
+    path.file_name().and_then(|name| name.to_str()).is_some_and(|name| IGNORE_RULE_FILE_NAMES.contains(&name))
+        || path.ends_with(".git/info/exclude")
+}
+
+/// Whether the ignore patterns leave `path` or one of its parent directories out of a scan of
+/// `directory`. `.gitignore` rules are not consulted.
+pub fn matches_ignore_patterns(directory: &Path, path: &Path, ignore_patterns: &[String]) -> bool {
+    let mut builder = OverrideBuilder::new(directory);
+    for pattern in ignore_patterns {
+        if let Err(e) = builder.add(&format!("!{}", pattern)) {
+            debug!("Skipping invalid ignore pattern '{}': {}", pattern, e);
+        }
+    }
+    let Ok(overrides) = builder.build() else { return false };
+    path.ancestors()
+        .take_while(|ancestor| ancestor.starts_with(directory) && *ancestor != directory)
+        .any(|ancestor| overrides.matched(ancestor, ancestor != path || path.is_dir()).is_ignore())
+}
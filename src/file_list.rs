@@ -0,0 +1,119 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use log::{info, warn};
+
+use crate::batch::settings_generator;
+use crate::budget::{estimate_tokens, set_chars_per_token};
+use crate::constants::{OutputFormat, DEFAULT_OUTPUT_FILENAME_BASE, TEMP_FILE_PREFIX};
+use crate::document_generator::{is_output_document_path, is_replaceable_document};
+use crate::document_worker::generate_document;
+use crate::error::{AppError, Result};
+use crate::file_handler::{FileHandler, FileNode};
+use crate::progress::{ProgressEvent, ProgressReporter};
+use crate::project_settings::ProjectSettings;
+use crate::settings::AppSettings;
+
+/// `--stdin-files`: generates one document for exactly the files listed in `file_list`, one
+/// path per line relative to `directory`, e.g. from `git diff --name-only`, `fd` or `rg -l`.
+/// Writes it to `output_path`, or prints it when there is none. Returns the number of files.
+pub fn run_file_list(directory: &Path, file_list: &str, output_path: Option<&Path>, progress: ProgressReporter) -> Result<usize> {
+    let directory = directory.canonicalize()
+        .map_err(|e| AppError::new_io_error(e, Some(directory.to_path_buf()), "Failed to open project directory".to_string()))?;
+    let settings = AppSettings::load();
+    set_chars_per_token(settings.token_estimator.chars_per_token());
+    let format = output_path
+        .and_then(|output_path| output_path.extension())
+        .and_then(|extension| OutputFormat::ALL.into_iter().find(|format| extension == format.extension()))
+        .unwrap_or(settings.output_format);
+    if let Some(output_path) = output_path.filter(|output_path| !is_replaceable_document(output_path)) {
+        return Err(AppError::OperationFailed(format!(
+            "{} exists and was not written by context_builder", output_path.display()
+        )));
+    }
+
+    // The tree is only needed for the structure section, so ignored files may still be listed
+    let project_settings = ProjectSettings::load(&directory);
+    let mut ignore_patterns = settings.ignore_patterns.clone();
+    ignore_patterns.extend(project_settings.extension_ignore_patterns());
+    let handler = FileHandler::new(directory.clone())?
+        .with_generated_files_excluded(settings.exclude_generated_files)
+        .with_output_path(output_path.map(Path::to_path_buf));
+    let root_node = progress.scan(&directory, handler, ignore_patterns)?;
+
+    let selected_files = listed_files(&directory, file_list, &root_node);
+    if selected_files.is_empty() {
+        return Err(AppError::OperationFailed("None of the listed files exist in the directory".to_string()));
+    }
+    if let Some(selected_output) = output_path.and_then(|output_path| selected_files.iter().find(|path| is_output_document_path(path, output_path))) {
+        return Err(AppError::OperationFailed(format!(
+            "{} is both listed and the output; leave it out of the list", selected_output.display()
+        )));
+    }
+    let file_count = selected_files.len();
+    progress.emit(ProgressEvent::GenerationStarted { directory: &directory, files: file_count });
+
+    let max_tokens = settings.token_budget_enabled.then_some(settings.max_token_budget);
+    let generator = settings_generator(directory.clone(), selected_files, &settings, &project_settings, max_tokens);
+    match output_path {
+        Some(output_path) => {
+            if let Some(output_dir) = output_path.parent().filter(|output_dir| !output_dir.as_os_str().is_empty()) {
+                fs::create_dir_all(output_dir)
+                    .map_err(|e| AppError::new_io_error(e, Some(output_dir.to_path_buf()), "Failed to create output directory".to_string()))?;
+            }
+            generate_document(&generator, &root_node, output_path, format, None, settings.custom_template_path.as_deref())?;
+            progress.generation_done(&directory, output_path, &[output_path.to_path_buf()]);
+            info!("Generated {:?} from {} listed files", output_path, file_count);
+        }
+        None => {
+            // The generator writes files, so stdout gets the content of one in a temp directory
+            let temp_dir = tempfile::Builder::new()
+                .prefix(TEMP_FILE_PREFIX)
+                .tempdir()
+                .map_err(|e| AppError::new_io_error(e, None, "Failed to create temp directory".to_string()))?;
+            let temp_path = temp_dir.path().join(format!("{}.{}", DEFAULT_OUTPUT_FILENAME_BASE, format.extension()));
+            generate_document(&generator, &root_node, &temp_path, format, None, settings.custom_template_path.as_deref())?;
+            let content = fs::read_to_string(&temp_path)
+                .map_err(|e| AppError::new_io_error(e, Some(temp_path.clone()), "Failed to read the generated document".to_string()))?;
+            progress.emit(ProgressEvent::GenerationDone {
+                directory: &directory,
+                path: None,
+                bytes: content.len() as u64,
+                tokens: estimate_tokens(&content),
+            });
+            // `print!` would panic once a reader like `head` closed the pipe
+            let mut stdout = io::stdout().lock();
+            if let Err(e) = stdout.write_all(content.as_bytes()).and_then(|()| stdout.flush()) {
+                if e.kind() != io::ErrorKind::BrokenPipe {
+                    return Err(AppError::new_io_error(e, None, "Failed to write the document to stdout".to_string()));
+                }
+            }
+        }
+    }
+    Ok(file_count)
+}
+
+/// Existing files of the list inside `directory`, in list order without duplicates. A listed
+/// directory stands for its scanned files; missing paths (e.g. deleted in a diff) are skipped.
+fn listed_files(directory: &Path, file_list: &str, root_node: &FileNode) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+    for line in file_list.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let Ok(path) = directory.join(line).canonicalize() else {
+            warn!("Skipping {}: not found", line);
+            continue;
+        };
+        if !path.starts_with(directory) {
+            warn!("Skipping {}: outside {}", line, directory.display());
+            continue;
+        }
+        let paths = if path.is_dir() {
+            root_node.file_paths().into_iter().filter(|file| file.starts_with(&path)).collect()
+        } else {
+            vec![path]
+        };
+        files.extend(paths.into_iter().filter(|path| seen.insert(path.clone())));
+    }
+    files
+}
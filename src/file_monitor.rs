@@ -1,41 +1,113 @@
-use std::collections::HashMap;
-use std::path::PathBuf;
-use std::sync::mpsc;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
-use log::{debug, info, error};
-use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use log::{debug, info, error, warn};
+use notify::{Event, EventKind, PollWatcher, RecursiveMode, Watcher};
+use notify::event::{MetadataKind, ModifyKind, RenameMode};
 
-use crate::constants::DEBOUNCE_DURATION;
+use crate::constants::{DEFAULT_DEBOUNCE_CHECK_INTERVAL_MS, DEFAULT_DEBOUNCE_MS};
+use crate::document_generator::is_output_document_path;
 use crate::error::{AppError, Result};
 use crate::events::AppEvent;
+use crate::file_handler::is_ignore_rule_file;
+use crate::temp_files::is_temp_file;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 enum EventType {
     Modified,
+    Removed,
+    Renamed(PathBuf), // To this path
     StructureChanged,
 }
 
+/// How file system changes are detected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatcherBackend {
+    Native,
+    Polling(Duration),
+}
+
+impl WatcherBackend {
+    pub fn description(&self) -> String {
+        match self {
+            WatcherBackend::Native => "native events".to_string(),
+            WatcherBackend::Polling(interval) => format!("polling every {} s", interval.as_secs()),
+        }
+    }
+}
+
+/// Which events of the watched paths are passed on
+struct EventScope {
+    base_directory: PathBuf,
+    extra_files: Arc<Mutex<BTreeSet<PathBuf>>>,
+    output_path: Arc<Mutex<Option<PathBuf>>>,
+}
+
+impl EventScope {
+    /// The type to report an event of `path` as, or `None` to drop it
+    fn event_type(&self, path: &Path, event_type: EventType) -> Option<EventType> {
+        let is_output = self.output_path.lock().ok()?.as_ref()
+            .is_some_and(|output_path| is_output_document_path(path, output_path));
+        if is_output {
+            return None;
+        }
+        if path.starts_with(&self.base_directory) {
+            return Some(event_type);
+        }
+        // Only the extra files count out there. However they were replaced, their content is what changed.
+        self.extra_files.lock().ok()?.contains(path).then_some(EventType::Modified)
+    }
+}
+
 pub struct FileMonitor {
-    watcher: Option<RecommendedWatcher>,
+    watcher: Option<Box<dyn Watcher + Send>>,
+    backend: Option<WatcherBackend>,
     event_sender: mpsc::Sender<AppEvent>,
+    debounce: Duration,       // Quiet time before a path is reported
+    check_interval: Duration, // How often settled paths are collected
     debounce_map: HashMap<PathBuf, (Instant, EventType)>,
     debounce_thread_handle: Option<thread::JoinHandle<()>>,
     stop_debounce_sender: Option<mpsc::Sender<()>>,
+    base_directory: PathBuf,
+    extra_files: Arc<Mutex<BTreeSet<PathBuf>>>, // Watched files outside the base directory
+    output_path: Arc<Mutex<Option<PathBuf>>>,   // The document's writes are not project changes
+    watched_folders: BTreeSet<PathBuf>,         // Their folders, watched non-recursively
 }
 
 impl FileMonitor {
     pub fn new(event_sender: mpsc::Sender<AppEvent>) -> Self {
         Self {
             watcher: None,
+            backend: None,
             event_sender,
+            debounce: Duration::from_millis(DEFAULT_DEBOUNCE_MS),
+            check_interval: Duration::from_millis(DEFAULT_DEBOUNCE_CHECK_INTERVAL_MS),
             debounce_map: HashMap::new(),
             debounce_thread_handle: None,
             stop_debounce_sender: None,
+            base_directory: PathBuf::new(),
+            extra_files: Arc::new(Mutex::new(BTreeSet::new())),
+            output_path: Arc::new(Mutex::new(None)),
+            watched_folders: BTreeSet::new(),
         }
     }
 
-    pub fn start_monitoring(&mut self, base_directory: PathBuf) -> Result<()> {
+    /// Debounce timing, applied the next time monitoring starts
+    pub fn set_debounce(&mut self, debounce: Duration, check_interval: Duration) {
+        self.debounce = debounce;
+        self.check_interval = check_interval;
+    }
+
+    /// Watches `base_directory` with native events, or with a polling watcher when `force_polling`
+    /// is set, the directory is on a network file system or the native watcher can't be started
+    pub fn start_monitoring(
+        &mut self,
+        base_directory: PathBuf,
+        poll_interval: Duration,
+        force_polling: bool,
+    ) -> Result<WatcherBackend> {
         // Stop any existing monitoring
         self.stop_monitoring()?;
 
@@ -51,13 +123,66 @@ impl FileMonitor {
         let (stop_sender, stop_receiver) = mpsc::channel();
         self.stop_debounce_sender = Some(stop_sender);
         
+        let (debounce, check_interval) = (self.debounce, self.check_interval);
+        let scope = EventScope {
+            base_directory: base_directory.clone(),
+            extra_files: self.extra_files.clone(),
+            output_path: self.output_path.clone(),
+        };
         let debounce_handle = thread::spawn(move || {
-            Self::debounce_thread(file_event_receiver, app_event_sender, stop_receiver);
+            Self::debounce_thread(file_event_receiver, app_event_sender, stop_receiver, debounce, check_interval, &scope);
         });
         self.debounce_thread_handle = Some(debounce_handle);
 
-        // Create the file watcher
-        let mut watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+        // Create the file watcher and watch the base directory recursively for all events
+        let native_watcher = if force_polling || is_on_network_filesystem(&base_directory) {
+            None
+        } else {
+            Self::start_native_watcher(&base_directory, file_event_sender.clone(), self.event_sender.clone())
+                .inspect_err(|e| warn!("Native file watcher unavailable, falling back to polling: {}", e))
+                .ok()
+        };
+
+        let (watcher, backend) = match native_watcher {
+            Some(watcher) => (watcher, WatcherBackend::Native),
+            None => {
+                let config = notify::Config::default().with_poll_interval(poll_interval);
+                let mut watcher = PollWatcher::new(Self::event_handler(file_event_sender, self.event_sender.clone()), config)
+                    .map_err(AppError::Notify)?;
+                watcher.watch(&base_directory, RecursiveMode::Recursive)
+                    .map_err(AppError::Notify)?;
+                let watcher: Box<dyn Watcher + Send> = Box::new(watcher);
+                (watcher, WatcherBackend::Polling(poll_interval))
+            }
+        };
+        debug!("Watching base directory recursively: {:?}", base_directory);
+
+        self.watcher = Some(watcher);
+        self.backend = Some(backend);
+        self.base_directory = base_directory;
+        self.watch_extra_file_folders();
+        info!("File monitoring started successfully ({})", backend.description());
+        Ok(backend)
+    }
+
+    fn start_native_watcher(
+        base_directory: &Path,
+        file_event_sender: mpsc::Sender<Event>,
+        app_event_sender: mpsc::Sender<AppEvent>,
+    ) -> Result<Box<dyn Watcher + Send>> {
+        let mut watcher = notify::recommended_watcher(Self::event_handler(file_event_sender, app_event_sender))
+            .map_err(AppError::Notify)?;
+        watcher.watch(base_directory, RecursiveMode::Recursive)
+            .map_err(AppError::Notify)?;
+        Ok(Box::new(watcher))
+    }
+
+    /// Forwards events to the debounce thread and errors to the app, which restarts the watcher
+    fn event_handler(
+        file_event_sender: mpsc::Sender<Event>,
+        app_event_sender: mpsc::Sender<AppEvent>,
+    ) -> impl Fn(notify::Result<Event>) + Send + 'static {
+        move |result: notify::Result<Event>| {
             match result {
                 Ok(event) => {
                     if let Err(e) = file_event_sender.send(event) {
@@ -66,18 +191,60 @@ impl FileMonitor {
                 }
                 Err(e) => {
                     error!("File watcher error: {}", e);
+                    if let Err(e) = app_event_sender.send(AppEvent::WatcherError(AppError::Notify(e))) {
+                        error!("Failed to send watcher error: {}", e);
+                    }
                 }
             }
-        }).map_err(AppError::Notify)?;
+        }
+    }
 
-        // Watch the base directory recursively for all events
-        watcher.watch(&base_directory, RecursiveMode::Recursive)
-            .map_err(AppError::Notify)?;
-        debug!("Watching base directory recursively: {:?}", base_directory);
+    /// Drops the events of the document written to `output_path` (in any format, its parts and
+    /// its index), so writing it doesn't look like a change to the project
+    pub fn set_output_path(&mut self, output_path: Option<PathBuf>) {
+        if let Ok(mut current) = self.output_path.lock() {
+            *current = output_path;
+        }
+    }
 
-        self.watcher = Some(watcher);
-        info!("File monitoring started successfully");
-        Ok(())
+    /// Also reports changes of these files outside the base directory, as modifications.
+    /// Their folders are watched rather than the files, whose inode changes with every atomic save.
+    pub fn set_extra_files(&mut self, files: BTreeSet<PathBuf>) {
+        if let Ok(mut extra_files) = self.extra_files.lock() {
+            *extra_files = files;
+        }
+        self.watch_extra_file_folders();
+    }
+
+    /// Brings the non-recursive watches in line with the extra files, while a watcher runs
+    fn watch_extra_file_folders(&mut self) {
+        let Some(watcher) = self.watcher.as_mut() else { return };
+        let folders: BTreeSet<PathBuf> = match self.extra_files.lock() {
+            Ok(extra_files) => extra_files.iter()
+                .filter(|path| !path.starts_with(&self.base_directory))
+                .filter_map(|path| path.parent().map(Path::to_path_buf))
+                .collect(),
+            Err(_) => return,
+        };
+
+        for folder in self.watched_folders.difference(&folders) {
+            if let Err(e) = watcher.unwatch(folder) {
+                debug!("Failed to unwatch {:?}: {}", folder, e);
+            }
+        }
+        self.watched_folders.retain(|folder| folders.contains(folder));
+        for folder in folders {
+            if self.watched_folders.contains(&folder) {
+                continue;
+            }
+            match watcher.watch(&folder, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    debug!("Watching folder of extra files: {:?}", folder);
+                    self.watched_folders.insert(folder);
+                }
+                Err(e) => warn!("Failed to watch {:?} for attached files: {}", folder, e),
+            }
+        }
     }
 
     pub fn stop_monitoring(&mut self) -> Result<()> {
@@ -88,6 +255,8 @@ impl FileMonitor {
             // The watcher will be dropped, which stops it
             drop(watcher);
         }
+        self.backend = None;
+        self.watched_folders.clear();
 
         // Stop the debounce thread
         if let Some(stop_sender) = self.stop_debounce_sender.take() {
@@ -107,6 +276,9 @@ impl FileMonitor {
         file_event_receiver: mpsc::Receiver<Event>,
         app_event_sender: mpsc::Sender<AppEvent>,
         stop_receiver: mpsc::Receiver<()>,
+        debounce: Duration,
+        check_interval: Duration,
+        scope: &EventScope,
     ) {
         let mut debounce_map: HashMap<PathBuf, (Instant, EventType)> = HashMap::new();
         let mut last_check = Instant::now();
@@ -120,23 +292,32 @@ impl FileMonitor {
 
             // Process incoming file events (non-blocking)
             while let Ok(event) = file_event_receiver.try_recv() {
-                if let Some((file_path, event_type)) = Self::extract_relevant_file_path(&event) {
+                let relevant = Self::extract_relevant_file_path(&event)
+                    .and_then(|(file_path, event_type)| Some((scope.event_type(&file_path, event_type)?, file_path)));
+                if let Some((event_type, file_path)) = relevant {
                     debug!("File event for: {:?} (Type: {:?})", file_path, event_type);
+                    if let EventType::Renamed(to) = &event_type {
+                        // The rename covers the separate "moved to" half reported before it
+                        debounce_map.remove(to);
+                    }
                     debounce_map.insert(file_path, (Instant::now(), event_type));
                 }
             }
 
-            // Check for debounced events (every 100ms)
+            // Check for debounced events
             let now = Instant::now();
-            if now.duration_since(last_check) >= Duration::from_millis(100) {
+            if now.duration_since(last_check) >= check_interval {
                 let mut to_send = Vec::new();
-                let mut directory_content_changed = false;
+                let mut moves = Vec::new();
+                let mut created = Vec::new();
 
                 debounce_map.retain(|path, (timestamp, event_type)| {
-                    if now.duration_since(*timestamp) >= DEBOUNCE_DURATION {
+                    if now.duration_since(*timestamp) >= debounce {
                         match event_type {
                             EventType::Modified => to_send.push(path.clone()),
-                            EventType::StructureChanged => directory_content_changed = true,
+                            EventType::Removed => moves.push(AppEvent::FileRemoved(path.clone())),
+                            EventType::Renamed(to) => moves.push(AppEvent::FileRenamed { from: path.clone(), to: to.clone() }),
+                            EventType::StructureChanged => created.push(path.clone()),
                         }
                         false // Remove from map
                     } else {
@@ -144,19 +325,29 @@ impl FileMonitor {
                     }
                 });
 
+                // Moves go first so a rescan triggered below sees the document already updated
+                for event in moves {
+                    debug!("Sending debounced {:?}", event);
+                    if let Err(e) = app_event_sender.send(event) {
+                        error!("Failed to send file move event: {}", e);
+                    }
+                }
+
                 // Send debounced events
-                if directory_content_changed {
-                    debug!("Sending debounced DirectoryContentChanged event");
-                    if let Err(e) = app_event_sender.send(AppEvent::DirectoryContentChanged) {
+                if !created.is_empty() {
+                    created.sort();
+                    debug!("Sending debounced DirectoryContentChanged event for {} paths", created.len());
+                    if let Err(e) = app_event_sender.send(AppEvent::DirectoryContentChanged(created)) {
                         error!("Failed to send DirectoryContentChanged event: {}", e);
                     }
                 }
                 
-                for path in to_send {
-                    debug!("Sending debounced FileModifiedDebounced event for: {:?}", path);
-                    if let Err(e) = app_event_sender.send(AppEvent::FileModifiedDebounced(path)) {
+                // Files settled in the same check are handled in one pass
+                if !to_send.is_empty() {
+                    to_send.sort();
+                    debug!("Sending debounced FilesModifiedDebounced event for {} files", to_send.len());
+                    if let Err(e) = app_event_sender.send(AppEvent::FilesModifiedDebounced(to_send)) {
                         error!("Failed to send debounced file event: {}", e);
-                        break; // Channel is closed, stop the thread
                     }
                 }
 
@@ -164,32 +355,173 @@ impl FileMonitor {
             }
 
             // Small sleep to prevent busy waiting
-            thread::sleep(Duration::from_millis(50));
+            thread::sleep(check_interval.min(Duration::from_millis(50)));
         }
 
         debug!("Debounce thread exiting");
     }
 
     fn extract_relevant_file_path(event: &Event) -> Option<(PathBuf, EventType)> {
-        // We're interested in modify, create, and remove events
+        // We're interested in content modifications, creates, removes and renames
         let event_type = match &event.kind {
-            EventKind::Modify(_) => EventType::Modified,
-            EventKind::Create(_) | EventKind::Remove(_) => EventType::StructureChanged,
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => EventType::Renamed(event.paths.get(1)?.clone()),
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => EventType::Removed,
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => EventType::StructureChanged,
+            // Backends that report each side of a rename separately (e.g. FSEvents)
+            EventKind::Modify(ModifyKind::Name(_)) => if event.paths.first()?.exists() {
+                EventType::StructureChanged
+            } else {
+                EventType::Removed
+            },
+            // Polling reports every newer mtime this way, so a bare `touch` can't be told from a write
+            EventKind::Modify(ModifyKind::Metadata(MetadataKind::WriteTime)) => EventType::Modified,
+            // chmod, chown, xattrs and access times (and inotify's report of a `touch`) leave the content alone
+            EventKind::Modify(ModifyKind::Metadata(_)) => return None,
+            // Content writes, and backends that don't say what changed (e.g. Windows)
+            EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any | ModifyKind::Other) => EventType::Modified,
+            EventKind::Remove(_) => EventType::Removed,
+            EventKind::Create(_) => EventType::StructureChanged,
             _ => return None,
         };
 
-        // Take the first path from the event
-        event.paths.first().map(|p| (p.to_path_buf(), event_type))
+        // Take the first path from the event; our temp files come and go with every atomic write
+        let path = event.paths.first().filter(|p| !is_temp_file(p))?;
+        // Edited ignore rules change which files the tree shows, like a created file does
+        let event_type = match event_type {
+            EventType::Modified | EventType::Removed if is_ignore_rule_file(path) => EventType::StructureChanged,
+            event_type => event_type,
+        };
+        Some((path.to_path_buf(), event_type))
     }
 
     #[allow(dead_code)]
     pub fn is_monitoring(&self) -> bool {
         self.watcher.is_some()
     }
+
+    /// The backend of the running watcher, if any
+    pub fn backend(&self) -> Option<WatcherBackend> {
+        self.backend
+    }
+}
+
+/// Whether `path` lives on a file system listed in `NETWORK_FILESYSTEM_TYPES`, judged by the
+/// longest matching mount point in /proc/self/mounts
+#[cfg(target_os = "linux")]
+fn is_on_network_filesystem(path: &Path) -> bool {
+    let Ok(mounts) = std::fs::read_to_string("/proc/self/mounts") else {
+        return false;
+    };
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    let filesystem_type = mounts.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount_point = fields.nth(1)?.replace("\\040", " ");
+            let filesystem_type = fields.next()?;
+            Some((PathBuf::from(mount_point), filesystem_type))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.components().count())
+        .map(|(_, filesystem_type)| filesystem_type);
+
+    debug!("File system of {:?}: {:?}", path, filesystem_type);
+    filesystem_type.is_some_and(|filesystem_type| crate::constants::NETWORK_FILESYSTEM_TYPES.contains(&filesystem_type))
+}
+
+/// Network file systems are only detected on Linux; elsewhere polling has to be chosen in the settings
+#[cfg(not(target_os = "linux"))]
+fn is_on_network_filesystem(_path: &Path) -> bool {
+    false
 }
 
 impl Drop for FileMonitor {
     fn drop(&mut self) {
         let _ = self.stop_monitoring(); // Ignore errors during drop
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, AccessMode, CreateKind, DataChange, RemoveKind};
+
+    fn classify(kind: EventKind, paths: &[&str]) -> Option<(PathBuf, EventType)> {
+        let event = paths.iter().fold(Event::new(kind), |event, path| event.add_path(PathBuf::from(path)));
+        FileMonitor::extract_relevant_file_path(&event)
+    }
+
+    fn modified(path: &str) -> Option<(PathBuf, EventType)> {
+        Some((PathBuf::from(path), EventType::Modified))
+    }
+
+    #[test]
+    fn metadata_only_changes_are_dropped() {
+        for metadata_kind in [MetadataKind::Permissions, MetadataKind::Any, MetadataKind::AccessTime, MetadataKind::Ownership, MetadataKind::Extended] {
+            assert_eq!(classify(EventKind::Modify(ModifyKind::Metadata(metadata_kind)), &["/p/src/main.rs"]), None, "{:?}", metadata_kind);
+        }
+    }
+
+    #[test]
+    fn content_changes_are_kept() {
+        assert_eq!(classify(EventKind::Modify(ModifyKind::Metadata(MetadataKind::WriteTime)), &["/p/src/main.rs"]), modified("/p/src/main.rs"));
+        for data_change in [DataChange::Any, DataChange::Size, DataChange::Content, DataChange::Other] {
+            assert_eq!(classify(EventKind::Modify(ModifyKind::Data(data_change)), &["/p/src/main.rs"]), modified("/p/src/main.rs"), "{:?}", data_change);
+        }
+        assert_eq!(classify(EventKind::Modify(ModifyKind::Any), &["/p/src/main.rs"]), modified("/p/src/main.rs"));
+    }
+
+    #[test]
+    fn renames_map_to_rename_remove_or_structure_change() {
+        assert_eq!(
+            classify(EventKind::Modify(ModifyKind::Name(RenameMode::Both)), &["/p/old.rs", "/p/new.rs"]),
+            Some((PathBuf::from("/p/old.rs"), EventType::Renamed(PathBuf::from("/p/new.rs"))))
+        );
+        // Without the target, a `Both` rename can't be followed
+        assert_eq!(classify(EventKind::Modify(ModifyKind::Name(RenameMode::Both)), &["/p/old.rs"]), None);
+        assert_eq!(
+            classify(EventKind::Modify(ModifyKind::Name(RenameMode::From)), &["/p/old.rs"]),
+            Some((PathBuf::from("/p/old.rs"), EventType::Removed))
+        );
+        assert_eq!(
+            classify(EventKind::Modify(ModifyKind::Name(RenameMode::To)), &["/p/new.rs"]),
+            Some((PathBuf::from("/p/new.rs"), EventType::StructureChanged))
+        );
+
+        // One-sided renames are judged by whether the path still exists
+        let dir = tempfile::tempdir().unwrap();
+        let existing = dir.path().join("here.rs");
+        std::fs::write(&existing, "").unwrap();
+        let missing = dir.path().join("gone.rs");
+        assert_eq!(
+            classify(EventKind::Modify(ModifyKind::Name(RenameMode::Any)), &[existing.to_str().unwrap()]),
+            Some((existing, EventType::StructureChanged))
+        );
+        assert_eq!(
+            classify(EventKind::Modify(ModifyKind::Name(RenameMode::Any)), &[missing.to_str().unwrap()]),
+            Some((missing, EventType::Removed))
+        );
+    }
+
+    #[test]
+    fn accesses_are_dropped() {
+        for access_kind in [AccessKind::Any, AccessKind::Read, AccessKind::Open(AccessMode::Any), AccessKind::Close(AccessMode::Write)] {
+            assert_eq!(classify(EventKind::Access(access_kind), &["/p/src/main.rs"]), None, "{:?}", access_kind);
+        }
+    }
+
+    #[test]
+    fn creates_removes_and_ignore_rules() {
+        assert_eq!(
+            classify(EventKind::Create(CreateKind::File), &["/p/src/new.rs"]),
+            Some((PathBuf::from("/p/src/new.rs"), EventType::StructureChanged))
+        );
+        assert_eq!(
+            classify(EventKind::Remove(RemoveKind::File), &["/p/src/old.rs"]),
+            Some((PathBuf::from("/p/src/old.rs"), EventType::Removed))
+        );
+        assert_eq!(
+            classify(EventKind::Modify(ModifyKind::Data(DataChange::Content)), &["/p/.gitignore"]),
+            Some((PathBuf::from("/p/.gitignore"), EventType::StructureChanged))
+        );
+    }
+}
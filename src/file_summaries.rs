@@ -0,0 +1,86 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use log::{debug, info, warn};
+
+use crate::constants::{APP_DATA_DIR_NAME, FILE_SUMMARY_MAX_INPUT_CHARS, FILE_SUMMARY_MAX_WORDS, FILE_SUMMARY_TIMEOUT_SECS};
+use crate::error::Result;
+use crate::llm_chat::LlmEndpoint;
+
+const SUMMARY_PROMPT: &str = "Summarize the given source file for a developer who needs its gist, not every line: \
+its purpose, its main types and functions with their roles, and anything notable about how it is used. \
+Answer in plain text without preamble.";
+
+/// Writes short summaries of files with the configured model, shown instead of their content.
+/// Summaries are cached on disk by content hash and model, so unchanged files (e.g. vendored
+/// dependencies) are summarized once across generations and projects.
+#[derive(Debug, Clone)]
+pub struct FileSummarizer {
+    endpoint: LlmEndpoint,
+    cache_dir: Option<PathBuf>, // `None` if the platform has no cache directory
+    cached_only: bool,
+}
+
+impl FileSummarizer {
+    pub fn new(endpoint: LlmEndpoint) -> Self {
+        Self {
+            endpoint,
+            cache_dir: dirs::cache_dir().map(|cache_dir| cache_dir.join(APP_DATA_DIR_NAME).join("file_summaries")),
+            cached_only: false,
+        }
+    }
+
+    /// Only looks summaries up, for estimates that must not send requests
+    pub fn with_cached_only(mut self, cached_only: bool) -> Self {
+        self.cached_only = cached_only;
+        self
+    }
+
+    /// The file's summary, from the cache or asked from the model; `None` when cached only and
+    /// there is none
+    pub fn summarize(&self, file_path: &Path, content: &str) -> Result<Option<String>> {
+        let cache_path = self.cache_path(content);
+        if let Some(summary) = cache_path.as_ref().and_then(|cache_path| fs::read_to_string(cache_path).ok()) {
+            debug!("Using the cached summary of {:?}", file_path);
+            return Ok(Some(summary));
+        }
+        if self.cached_only {
+            return Ok(None);
+        }
+
+        let file_name = file_path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        let (input, truncated) = match content.char_indices().nth(FILE_SUMMARY_MAX_INPUT_CHARS) {
+            Some((end, _)) => (&content[..end], true),
+            None => (content, false),
+        };
+        let user_message = format!(
+            "File: {}{}\nAt most {} words.\n\n{}",
+            file_name,
+            if truncated { " (only its start, it is longer)" } else { "" },
+            FILE_SUMMARY_MAX_WORDS,
+            input,
+        );
+        info!("Summarizing {:?} with {}", file_path, self.endpoint.model.trim());
+        let summary = self.endpoint.complete(SUMMARY_PROMPT, user_message, FILE_SUMMARY_TIMEOUT_SECS)?;
+        let summary = summary.trim().to_string();
+
+        if let Some(cache_path) = cache_path {
+            let saved = cache_path.parent().map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| fs::write(&cache_path, &summary));
+            if let Err(e) = saved {
+                warn!("Failed to cache the summary of {:?} at {:?}: {}", file_path, cache_path, e);
+            }
+        }
+        Ok(Some(summary))
+    }
+
+    /// Changes with the content, the model and the prompt, but not the file's location
+    fn cache_path(&self, content: &str) -> Option<PathBuf> {
+        let mut hasher = blake3::Hasher::new();
+        for part in [self.endpoint.provider.name(), self.endpoint.model.trim(), SUMMARY_PROMPT, &FILE_SUMMARY_MAX_WORDS.to_string()] {
+            hasher.update(part.as_bytes());
+            hasher.update(b"\0");
+        }
+        hasher.update(content.as_bytes());
+        Some(self.cache_dir.as_ref()?.join(format!("{}.txt", hasher.finalize().to_hex())))
+    }
+}
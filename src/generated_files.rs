@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use log::{debug, warn};
+
+const GITATTRIBUTES_FILE_NAME: &str = ".gitattributes";
+const LINGUIST_GENERATED_ATTRIBUTE: &str = "linguist-generated";
+
+/// Detects generated files the way GitHub does, using `linguist-generated`
+/// entries from `.gitattributes` files at the root and in subdirectories.
+pub struct GeneratedFileDetector {
+    root: PathBuf,
+    // Parsed `.gitattributes` per directory; `None` if the directory has none
+    matchers: HashMap<PathBuf, Option<Gitignore>>,
+}
+
+impl GeneratedFileDetector {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            matchers: HashMap::new(),
+        }
+    }
+
+    pub fn is_generated(&mut self, path: &Path, is_dir: bool) -> bool {
+        // The deepest `.gitattributes` with a matching pattern decides
+        let mut ancestors: Vec<PathBuf> = path.ancestors()
+            .skip(1)
+            .take_while(|ancestor| ancestor.starts_with(&self.root))
+            .map(Path::to_path_buf)
+            .collect();
+        ancestors.reverse();
+
+        let mut generated = false;
+        for directory in ancestors {
+            if let Some(matcher) = self.matcher_for(&directory) {
+                match matcher.matched(path, is_dir) {
+                    Match::Ignore(_) => generated = true,
+                    Match::Whitelist(_) => generated = false,
+                    Match::None => {}
+                }
+            }
+        }
+        generated
+    }
+
+    fn matcher_for(&mut self, directory: &Path) -> Option<&Gitignore> {
+        self.matchers
+            .entry(directory.to_path_buf())
+            .or_insert_with(|| Self::load_gitattributes(directory))
+            .as_ref()
+    }
+
+    /// Translates `linguist-generated` lines into gitignore rules: set attributes become
+    /// ignore patterns, unset ones (`-linguist-generated`, `=false`) become whitelist patterns.
+    fn load_gitattributes(directory: &Path) -> Option<Gitignore> {
+        let attributes_path = directory.join(GITATTRIBUTES_FILE_NAME);
+        let content = fs::read_to_string(&attributes_path).ok()?;
+
+        let mut builder = GitignoreBuilder::new(directory);
+        let mut rule_count = 0;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            let Some(pattern) = tokens.next() else { continue };
+            let pattern = pattern.trim_matches('"');
+
+            let Some(generated) = tokens.rev().find_map(Self::parse_linguist_generated) else { continue };
+            let rule = if generated { pattern.to_string() } else { format!("!{}", pattern) };
+            if let Err(e) = builder.add_line(Some(attributes_path.clone()), &rule) {
+                warn!("Ignoring invalid pattern '{}' in {:?}: {}", pattern, attributes_path, e);
+            } else {
+                rule_count += 1;
+            }
+        }
+
+        if rule_count == 0 {
+            return None;
+        }
+
+        match builder.build() {
+            Ok(matcher) => {
+                debug!("Loaded {} linguist-generated rules from {:?}", rule_count, attributes_path);
+                Some(matcher)
+            }
+            Err(e) => {
+                warn!("Failed to build linguist-generated rules from {:?}: {}", attributes_path, e);
+                None
+            }
+        }
+    }
+
+    fn parse_linguist_generated(attribute: &str) -> Option<bool> {
+        match attribute {
+            LINGUIST_GENERATED_ATTRIBUTE => Some(true),
+            _ if attribute == format!("-{}", LINGUIST_GENERATED_ATTRIBUTE) => Some(false),
+            _ if attribute == format!("!{}", LINGUIST_GENERATED_ATTRIBUTE) => Some(false),
+            _ => {
+                let value = attribute.strip_prefix(LINGUIST_GENERATED_ATTRIBUTE)?.strip_prefix('=')?;
+                match value {
+                    "true" | "1" => Some(true),
+                    "false" | "0" => Some(false),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use chrono::Local;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+
+use crate::constants::{APP_DATA_DIR_NAME, OutputFormat};
+use crate::document_generator::DocumentGenerator;
+use crate::error::{AppError, Result};
+use crate::utils::write_file_atomically;
+
+const DIFF_CONTEXT_LINES: usize = 3;
+
+/// One full generation: what was written, and a hash of each file section
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: i64, // Unix timestamp in milliseconds; also names the copy
+    pub output_path: PathBuf,
+    pub format: OutputFormat,
+    pub file_count: usize,
+    pub size_bytes: u64,
+    pub sections: BTreeMap<String, String>, // Section title (the file's path) -> blake3 hash of the section
+    pub has_copy: bool,
+}
+
+impl HistoryEntry {
+    pub fn time_label(&self) -> String {
+        chrono::DateTime::from_timestamp_millis(self.id)
+            .map(|time| time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Which file sections differ between two generations
+#[derive(Debug, Default)]
+pub struct GenerationDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+    pub unchanged_count: usize,
+}
+
+/// The last generations of one project, oldest first. Kept in the OS cache directory with the
+/// copies of the documents, when those are enabled.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenerationHistory {
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl GenerationHistory {
+    /// Returns the recorded history, or an empty one if there is none or it can't be read
+    pub fn load(directory: &Path) -> Self {
+        let Some(history_path) = history_dir(directory).map(|dir| dir.join("history.json")) else {
+            return Self::default();
+        };
+        let Ok(bytes) = fs::read(&history_path) else {
+            return Self::default();
+        };
+
+        serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            debug!("Ignoring unreadable generation history {:?}: {}", history_path, e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, directory: &Path) -> Result<()> {
+        let history_path = history_dir(directory).map(|dir| dir.join("history.json")).ok_or_else(|| AppError::SettingsError {
+            path: directory.to_path_buf(),
+            details: "No cache directory available on this platform".to_string(),
+        })?;
+        let bytes = serde_json::to_vec(self).map_err(|e| AppError::SettingsError {
+            path: history_path.clone(),
+            details: format!("Failed to serialize generation history: {}", e),
+        })?;
+
+        write_file_atomically(&history_path, &bytes)?;
+        debug!("Saved generation history to {:?}", history_path);
+        Ok(())
+    }
+
+    /// Appends the entry and drops the oldest entries, with their copies, beyond `max_entries`
+    pub fn push(&mut self, directory: &Path, entry: HistoryEntry, max_entries: usize) {
+        self.entries.push(entry);
+        let excess = self.entries.len().saturating_sub(max_entries);
+        for dropped in self.entries.drain(..excess) {
+            if let Some(copy_path) = copy_path(directory, &dropped) {
+                let _ = fs::remove_file(copy_path);
+            }
+        }
+    }
+
+    /// Removes all entries and copies
+    pub fn clear(&mut self, directory: &Path) {
+        self.entries.clear();
+        if let Some(dir) = history_dir(directory) {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+/// Reads the written document(s) into a history entry; with `keep_copy`, their text is also
+/// copied to the cache directory so section diffs can be shown later
+pub fn record_generation(
+    directory: &Path,
+    output_path: &Path,
+    document_paths: &[PathBuf],
+    format: OutputFormat,
+    file_count: usize,
+    keep_copy: bool,
+) -> Result<HistoryEntry> {
+    let mut text = String::new();
+    for document_path in document_paths {
+        text.push_str(&fs::read_to_string(document_path)
+            .map_err(|e| AppError::new_io_error(e, Some(document_path.clone()), "Failed to read document for history".to_string()))?);
+    }
+    let mut entry = HistoryEntry {
+        id: Local::now().timestamp_millis(),
+        output_path: output_path.to_path_buf(),
+        format,
+        file_count,
+        size_bytes: text.len() as u64,
+        sections: document_sections(directory, &text, format).into_iter()
+            .map(|(title, section)| (title, blake3::hash(section.as_bytes()).to_hex().to_string()))
+            .collect(),
+        has_copy: false,
+    };
+    if keep_copy {
+        if let Some(copy_path) = history_dir(directory).map(|dir| dir.join(format!("{}.{}", entry.id, format.extension()))) {
+            write_file_atomically(&copy_path, text.as_bytes())?;
+            entry.has_copy = true;
+        }
+    }
+    Ok(entry)
+}
+
+/// Sections added, removed and changed from generation `a` to generation `b`
+pub fn diff_generations(a: &HistoryEntry, b: &HistoryEntry) -> GenerationDiff {
+    let mut diff = GenerationDiff::default();
+    for (title, hash) in &b.sections {
+        match a.sections.get(title) {
+            None => diff.added.push(title.clone()),
+            Some(hash_a) if hash_a != hash => diff.changed.push(title.clone()),
+            Some(_) => diff.unchanged_count += 1,
+        }
+    }
+    diff.removed = a.sections.keys().filter(|title| !b.sections.contains_key(*title)).cloned().collect();
+    diff
+}
+
+/// Unified diff of one file section between two generations; both need a copy
+pub fn section_diff(directory: &Path, a: &HistoryEntry, b: &HistoryEntry, title: &str) -> Result<String> {
+    let section_a = copied_section(directory, a, title)?;
+    let section_b = copied_section(directory, b, title)?;
+    Ok(TextDiff::from_lines(&section_a, &section_b)
+        .unified_diff()
+        .context_radius(DIFF_CONTEXT_LINES)
+        .header(&a.time_label(), &b.time_label())
+        .to_string())
+}
+
+fn copied_section(directory: &Path, entry: &HistoryEntry, title: &str) -> Result<String> {
+    let copy_path = copy_path(directory, entry)
+        .ok_or_else(|| AppError::OperationFailed(format!("No copy was kept of the generation of {}", entry.time_label())))?;
+    let text = fs::read_to_string(&copy_path)
+        .map_err(|e| AppError::new_io_error(e, Some(copy_path.clone()), "Failed to read document copy".to_string()))?;
+    Ok(document_sections(directory, &text, entry.format).into_iter()
+        .find(|(section_title, _)| section_title == title)
+        .map(|(_, section)| section)
+        .unwrap_or_default())
+}
+
+/// The file sections of a document, by title, located with the document outline
+fn document_sections(directory: &Path, text: &str, format: OutputFormat) -> Vec<(String, String)> {
+    let outline = DocumentGenerator::new(directory.to_path_buf(), Vec::new()).text_outline(text, format);
+    outline.into_iter()
+        .filter(|entry| entry.file_path.is_some())
+        .filter_map(|entry| {
+            let section = text.get(entry.byte_offset..entry.byte_offset + entry.size_bytes)?;
+            Some((entry.title, section.to_string()))
+        })
+        .collect()
+}
+
+fn copy_path(directory: &Path, entry: &HistoryEntry) -> Option<PathBuf> {
+    entry.has_copy.then(|| history_dir(directory))
+        .flatten()
+        .map(|dir| dir.join(format!("{}.{}", entry.id, entry.format.extension())))
+}
+
+fn history_dir(directory: &Path) -> Option<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    directory.hash(&mut hasher);
+
+    Some(dirs::cache_dir()?
+        .join(APP_DATA_DIR_NAME)
+        .join("history")
+        .join(format!("{:016x}", hasher.finish())))
+}
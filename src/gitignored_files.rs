@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use log::{debug, warn};
+
+const GITIGNORE_FILE_NAME: &str = ".gitignore";
+
+/// Detects files matched by `.gitignore` files at the root and in subdirectories. The scan
+/// only applies `.gitignore` inside git repositories, so ignored files (local secrets, build
+/// outputs) still show up in copies or exports of a project.
+pub struct GitignoredFileDetector {
+    root: PathBuf,
+    // Parsed `.gitignore` per directory; `None` if the directory has none
+    matchers: HashMap<PathBuf, Option<Gitignore>>,
+}
+
+impl GitignoredFileDetector {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            matchers: HashMap::new(),
+        }
+    }
+
+    pub fn is_gitignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        // The deepest `.gitignore` with a matching pattern decides
+        let mut ancestors: Vec<PathBuf> = path.ancestors()
+            .skip(1)
+            .take_while(|ancestor| ancestor.starts_with(&self.root))
+            .map(Path::to_path_buf)
+            .collect();
+        ancestors.reverse();
+
+        let mut ignored = false;
+        for directory in ancestors {
+            if let Some(matcher) = self.matcher_for(&directory) {
+                // Patterns like `build/` also cover everything inside the directory
+                match matcher.matched_path_or_any_parents(path, is_dir) {
+                    Match::Ignore(_) => ignored = true,
+                    Match::Whitelist(_) => ignored = false,
+                    Match::None => {}
+                }
+            }
+        }
+        ignored
+    }
+
+    fn matcher_for(&mut self, directory: &Path) -> Option<&Gitignore> {
+        self.matchers
+            .entry(directory.to_path_buf())
+            .or_insert_with(|| Self::load_gitignore(directory))
+            .as_ref()
+    }
+
+    fn load_gitignore(directory: &Path) -> Option<Gitignore> {
+        let gitignore_path = directory.join(GITIGNORE_FILE_NAME);
+        if !gitignore_path.is_file() {
+            return None;
+        }
+
+        let mut builder = GitignoreBuilder::new(directory);
+        if let Some(e) = builder.add(&gitignore_path) {
+            warn!("Some patterns in {:?} are invalid: {}", gitignore_path, e);
+        }
+
+        match builder.build() {
+            Ok(matcher) if matcher.is_empty() => None,
+            Ok(matcher) => {
+                debug!("Loaded {} gitignore rules from {:?}", matcher.len(), gitignore_path);
+                Some(matcher)
+            }
+            Err(e) => {
+                warn!("Failed to build gitignore rules from {:?}: {}", gitignore_path, e);
+                None
+            }
+        }
+    }
+}
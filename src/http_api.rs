@@ -0,0 +1,220 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use log::{debug, info, warn};
+
+use crate::constants::{OutputFormat, HTTP_API_ACCEPT_POLL_INTERVAL, HTTP_API_IO_TIMEOUT, HTTP_API_REPLY_TIMEOUT};
+use crate::error::{AppError, Result};
+use crate::file_handler::FileNode;
+
+const MAX_REQUEST_HEADERS: usize = 100;
+const USAGE_TEXT: &str = "context_builder local API\n\nGET  /context.md   the latest generated document\nGET  /tree.json    the scanned project tree\nPOST /regenerate   start a generation of the document\n";
+
+/// What a client asked for, answered by the UI thread from the active project tab
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApiRequest {
+    Context,
+    Tree,
+    Regenerate,
+}
+
+#[derive(Debug)]
+pub enum ApiReply {
+    Documents { paths: Vec<PathBuf>, format: OutputFormat }, // Parts of split output in order
+    Tree(FileNode),
+    Accepted(String),
+    Unavailable(String), // Nothing to serve yet, or the generation can't start
+}
+
+/// A request waiting for the UI thread's reply
+pub struct PendingApiRequest {
+    pub request: ApiRequest,
+    reply_sender: Sender<ApiReply>,
+}
+
+impl PendingApiRequest {
+    pub fn reply(self, reply: ApiReply) {
+        let _ = self.reply_sender.send(reply); // The client may have timed out
+    }
+}
+
+/// Local HTTP server that lets other tools fetch the current context. It only listens on
+/// 127.0.0.1, and requests must name that host, so web pages can't read it via DNS rebinding.
+/// Requests made by browsers are refused, so web pages can't trigger a regeneration either.
+pub struct HttpApi {
+    port: u16,
+    request_receiver: Receiver<PendingApiRequest>,
+    stop_flag: Arc<AtomicBool>,
+    thread_handle: Option<thread::JoinHandle<()>>,
+}
+
+impl HttpApi {
+    /// Listens on `port`; the UI is woken through `ctx` to answer each request
+    pub fn start(port: u16, ctx: egui::Context) -> Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+            .map_err(|e| AppError::new_io_error(e, None, format!("Failed to listen on 127.0.0.1:{}", port)))?;
+        let (request_sender, request_receiver) = mpsc::channel();
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let thread_stop_flag = stop_flag.clone();
+
+        let thread_handle = thread::spawn(move || {
+            while !thread_stop_flag.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        if let Err(e) = handle_connection(stream, port, &request_sender, &ctx) {
+                            debug!("Local API connection failed: {}", e);
+                        }
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(HTTP_API_ACCEPT_POLL_INTERVAL),
+                    Err(e) => {
+                        warn!("Local API failed to accept a connection: {}", e);
+                        thread::sleep(HTTP_API_ACCEPT_POLL_INTERVAL);
+                    }
+                }
+            }
+            debug!("Local API on port {} stopped", port);
+        });
+        info!("Local API listening on http://127.0.0.1:{}", port);
+
+        Ok(Self { port, request_receiver, stop_flag, thread_handle: Some(thread_handle) })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Requests received since the last call, each to be replied to
+    pub fn take_requests(&self) -> Vec<PendingApiRequest> {
+        self.request_receiver.try_iter().collect()
+    }
+}
+
+impl Drop for HttpApi {
+    fn drop(&mut self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.thread_handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Serves one request and closes the connection
+fn handle_connection(stream: TcpStream, port: u16, request_sender: &Sender<PendingApiRequest>, ctx: &egui::Context) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(HTTP_API_IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(HTTP_API_IO_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default();
+    let path = target.split('?').next().unwrap_or_default().to_string();
+
+    // The body, if any, is not needed: the connection is closed after the response
+    let mut host = None;
+    let mut from_browser = false;
+    for _ in 0..MAX_REQUEST_HEADERS {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let name = name.trim().to_lowercase();
+            if name == "host" {
+                host = Some(value.trim().to_lowercase());
+            }
+            // Browsers send these with cross-site requests, including form posts that need no
+            // CORS preflight; command line tools and scripts don't
+            if name == "origin" || name.starts_with("sec-fetch-") {
+                from_browser = true;
+            }
+        }
+    }
+
+    let mut stream = stream;
+    if !host.as_deref().is_some_and(|host| is_local_host(host, port)) {
+        return write_response(&mut stream, 403, "text/plain; charset=utf-8", b"Only requests to 127.0.0.1 or localhost are served\n");
+    }
+    if from_browser {
+        return write_response(&mut stream, 403, "text/plain; charset=utf-8", b"Requests from web pages are not served\n");
+    }
+    let request = match (method.as_str(), path.as_str()) {
+        ("GET", "/") => return write_response(&mut stream, 200, "text/plain; charset=utf-8", USAGE_TEXT.as_bytes()),
+        ("GET", "/context.md") => ApiRequest::Context,
+        ("GET", "/tree.json") => ApiRequest::Tree,
+        ("POST", "/regenerate") => ApiRequest::Regenerate,
+        (_, "/" | "/context.md" | "/tree.json" | "/regenerate") => {
+            return write_response(&mut stream, 405, "text/plain; charset=utf-8", b"Method not allowed\n");
+        }
+        _ => return write_response(&mut stream, 404, "text/plain; charset=utf-8", USAGE_TEXT.as_bytes()),
+    };
+    debug!("Local API request: {} {}", method, path);
+
+    let (reply_sender, reply_receiver) = mpsc::channel();
+    if request_sender.send(PendingApiRequest { request, reply_sender }).is_err() {
+        return write_response(&mut stream, 503, "text/plain; charset=utf-8", b"The app is shutting down\n");
+    }
+    ctx.request_repaint();
+    let Ok(reply) = reply_receiver.recv_timeout(HTTP_API_REPLY_TIMEOUT) else {
+        return write_response(&mut stream, 503, "text/plain; charset=utf-8", b"The app did not answer in time\n");
+    };
+
+    match reply {
+        ApiReply::Documents { paths, format } => {
+            let contents: std::io::Result<Vec<String>> = paths.iter().map(fs::read_to_string).collect();
+            match contents {
+                Ok(contents) => {
+                    let content_type = match format {
+                        OutputFormat::Markdown => "text/markdown; charset=utf-8",
+                        _ => "text/plain; charset=utf-8",
+                    };
+                    write_response(&mut stream, 200, content_type, contents.join("\n\n").as_bytes())
+                }
+                Err(e) => write_response(&mut stream, 500, "text/plain; charset=utf-8", format!("Failed to read the document: {}\n", e).as_bytes()),
+            }
+        }
+        ApiReply::Tree(root_node) => match serde_json::to_vec(&root_node) {
+            Ok(json) => write_response(&mut stream, 200, "application/json", &json),
+            Err(e) => write_response(&mut stream, 500, "text/plain; charset=utf-8", format!("Failed to serialize the tree: {}\n", e).as_bytes()),
+        },
+        ApiReply::Accepted(message) => write_response(&mut stream, 202, "text/plain; charset=utf-8", format!("{}\n", message).as_bytes()),
+        ApiReply::Unavailable(message) => {
+            let status = if request == ApiRequest::Regenerate { 409 } else { 404 };
+            write_response(&mut stream, status, "text/plain; charset=utf-8", format!("{}\n", message).as_bytes())
+        }
+    }
+}
+
+/// `127.0.0.1` or `localhost`, with this port or none
+fn is_local_host(host: &str, port: u16) -> bool {
+    let name = host.strip_suffix(&format!(":{}", port)).unwrap_or(host);
+    name == "127.0.0.1" || name == "localhost"
+}
+
+fn write_response(stream: &mut TcpStream, status: u16, content_type: &str, body: &[u8]) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        500 => "Internal Server Error",
+        _ => "Service Unavailable",
+    };
+    let header = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status, reason, content_type, body.len()
+    );
+    stream.write_all(header.as_bytes())?;
+    stream.write_all(body)?;
+    stream.flush()
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::constants::{OutputFormat, ADOC_SECTION_LEVEL_2, MARKDOWN_HEADER_SUMMARY, RST_INDENT};
+
+/// Files, lines and bytes of one language in the selection
+#[derive(Debug, Clone, PartialEq)]
+pub struct LanguageStats {
+    pub language: String,
+    pub files: usize,
+    pub lines: usize,
+    pub bytes: usize,
+}
+
+/// Display name of a file's language, by extension; the extension itself when unknown
+pub fn language_name(path: &Path) -> String {
+    let Some(extension) = path.extension().and_then(|extension| extension.to_str()).map(str::to_lowercase) else {
+        return "Other".to_string();
+    };
+    let name = match extension.as_str() {
+        "rs" => "Rust",
+        "py" | "pyi" => "Python",
+        "ipynb" => "Jupyter Notebook",
+        "js" | "mjs" | "cjs" | "jsx" => "JavaScript",
+        "ts" | "mts" | "cts" | "tsx" => "TypeScript",
+        "go" => "Go",
+        "java" => "Java",
+        "kt" | "kts" => "Kotlin",
+        "c" | "h" => "C",
+        "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => "C++",
+        "cs" => "C#",
+        "rb" => "Ruby",
+        "php" => "PHP",
+        "swift" => "Swift",
+        "scala" => "Scala",
+        "sh" | "bash" | "zsh" => "Shell",
+        "sql" => "SQL",
+        "html" | "htm" => "HTML",
+        "css" | "scss" | "sass" | "less" => "CSS",
+        "vue" => "Vue",
+        "svelte" => "Svelte",
+        "md" | "markdown" => "Markdown",
+        "adoc" | "asciidoc" => "AsciiDoc",
+        "toml" => "TOML",
+        "json" => "JSON",
+        "yaml" | "yml" => "YAML",
+        "xml" => "XML",
+        _ => return extension,
+    };
+    name.to_string()
+}
+
+/// Totals per language of the given files and their contents, most bytes first
+pub fn language_stats<'a>(files: impl IntoIterator<Item = (&'a Path, &'a str)>) -> Vec<LanguageStats> {
+    let mut by_language: HashMap<String, LanguageStats> = HashMap::new();
+    for (path, content) in files {
+        let language = language_name(path);
+        let stats = by_language.entry(language.clone()).or_insert_with(|| LanguageStats { language, files: 0, lines: 0, bytes: 0 });
+        stats.files += 1;
+        stats.lines += content.lines().count();
+        stats.bytes += content.len();
+    }
+
+    let mut stats: Vec<LanguageStats> = by_language.into_values().collect();
+    stats.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.language.cmp(&b.language)));
+    stats
+}
+
+/// The Summary section: a table of the languages with their share of the selected content
+pub fn render_summary(stats: &[LanguageStats], format: OutputFormat) -> String {
+    let total_bytes: usize = stats.iter().map(|language| language.bytes).sum();
+    let share = |bytes: usize| match total_bytes {
+        0 => "0.0%".to_string(),
+        _ => format!("{:.1}%", bytes as f64 * 100.0 / total_bytes as f64),
+    };
+
+    let mut summary = String::new();
+    match format {
+        OutputFormat::Markdown => {
+            summary.push_str(&format!("{}\n\n", MARKDOWN_HEADER_SUMMARY));
+            summary.push_str("| Language | Files | Lines | Share |\n");
+            summary.push_str("|---|---:|---:|---:|\n");
+            for language in stats {
+                summary.push_str(&format!("| {} | {} | {} | {} |\n", language.language, language.files, language.lines, share(language.bytes)));
+            }
+        }
+        OutputFormat::Adoc => {
+            summary.push_str(&format!("{} Summary\n\n", ADOC_SECTION_LEVEL_2));
+            summary.push_str("[cols=\"<,>,>,>\", options=\"header\"]\n|===\n");
+            summary.push_str("|Language |Files |Lines |Share\n");
+            for language in stats {
+                summary.push_str(&format!("|{} |{} |{} |{}\n", language.language, language.files, language.lines, share(language.bytes)));
+            }
+            summary.push_str("|===\n");
+        }
+        OutputFormat::Org => {
+            summary.push_str(&format!("{}\n\n", format.formatter().heading("Summary", 2)));
+            summary.push_str("| Language | Files | Lines | Share |\n");
+            summary.push_str("|---+---+---+---|\n");
+            for language in stats {
+                summary.push_str(&format!("| {} | {} | {} | {} |\n", language.language, language.files, language.lines, share(language.bytes)));
+            }
+        }
+        OutputFormat::Rst => {
+            summary.push_str(&format!("{}\n\n", format.formatter().heading("Summary", 2)));
+            summary.push_str(&format!(".. list-table::\n{}:header-rows: 1\n\n", RST_INDENT));
+            let rows = std::iter::once(["Language".to_string(), "Files".to_string(), "Lines".to_string(), "Share".to_string()])
+                .chain(stats.iter().map(|language| {
+                    [language.language.clone(), language.files.to_string(), language.lines.to_string(), share(language.bytes)]
+                }));
+            for row in rows {
+                for (column, cell) in row.iter().enumerate() {
+                    let bullet = if column == 0 { "* -" } else { "  -" };
+                    summary.push_str(&format!("{}{} {}\n", RST_INDENT, bullet, cell));
+                }
+            }
+        }
+    }
+    summary.trim_end().to_string()
+}
@@ -0,0 +1,57 @@
+//! The core of Context Builder: scanning a project, generating its context document and
+//! keeping it up to date, plus the egui file tree picker. The `context_builder` binary is the
+//! desktop app and the headless and terminal modes built on it.
+
+pub mod batch;
+pub mod budget;
+mod cargo_sources;
+mod compression;
+pub mod constants;
+pub mod crash_report;
+mod dependencies;
+mod directory_compare;
+pub mod error;
+pub mod events;
+mod exporter;
+mod external_open;
+mod fence_language;
+pub mod file_handler;
+pub mod file_list;
+pub mod file_monitor;
+mod file_summaries;
+pub mod document_generator;
+pub mod document_index;
+mod document_metadata;
+mod document_model;
+mod document_template;
+pub mod document_worker;
+mod generated_files;
+mod generation_history;
+mod gitignored_files;
+mod http_api;
+mod language_stats;
+pub mod lifecycle;
+mod llm_chat;
+pub mod log_panel;
+mod log_references;
+mod minify;
+mod notebook;
+mod outline;
+pub mod output_formatter;
+pub mod project_settings;
+pub mod project_tabs;
+pub mod progress;
+mod project_type;
+pub mod remote_source;
+mod scan_cache;
+mod schedule;
+mod section_cache;
+mod section_merge;
+pub mod settings;
+mod temp_files;
+pub mod ui_tree_handler;
+mod update_check;
+mod usage_stats;
+mod utils;
+mod workspace_config;
+mod app;
@@ -0,0 +1,126 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+
+/// A step of a scan or a document generation, for embedders that drive their own progress UI
+/// or metrics. Subscribe with `FileHandler::with_lifecycle_sender` and
+/// `DocumentGenerator::with_lifecycle_sender`; `elapsed` counts from the matching start event.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LifecycleEvent {
+    ScanStarted { directory: PathBuf },
+    ScanCompleted { directory: PathBuf, file_count: usize, elapsed: Duration },
+    ScanFailed { directory: PathBuf, error: String, elapsed: Duration },
+    GenerationStarted { output_path: PathBuf },
+    SectionWritten { output_path: PathBuf, file_path: PathBuf, elapsed: Duration }, // Once the section is written out
+    GenerationCompleted { output_path: PathBuf, elapsed: Duration },
+    GenerationFailed { output_path: PathBuf, error: String, elapsed: Duration },
+}
+
+/// Sends the lifecycle events of the scans and generations it wraps, if anyone subscribed
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LifecycleObserver {
+    sender: Option<Sender<LifecycleEvent>>,
+}
+
+impl LifecycleObserver {
+    pub fn new(sender: Sender<LifecycleEvent>) -> Self {
+        Self { sender: Some(sender) }
+    }
+
+    fn send(&self, event: LifecycleEvent) {
+        // A subscriber that went away doesn't fail the scan or generation
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(event);
+        }
+    }
+
+    pub fn observe_scan<T>(&self, directory: &Path, scan: impl FnOnce() -> Result<T>, file_count: impl FnOnce(&T) -> usize) -> Result<T> {
+        let started = Instant::now();
+        self.send(LifecycleEvent::ScanStarted { directory: directory.to_path_buf() });
+        let result = scan();
+        self.send(match &result {
+            Ok(scanned) => LifecycleEvent::ScanCompleted { directory: directory.to_path_buf(), file_count: file_count(scanned), elapsed: started.elapsed() },
+            Err(e) => LifecycleEvent::ScanFailed { directory: directory.to_path_buf(), error: e.to_string(), elapsed: started.elapsed() },
+        });
+        result
+    }
+
+    /// `generate` reports each file section it has written through the callback it is given
+    pub fn observe_generation<T>(&self, output_path: &Path, generate: impl FnOnce(&dyn Fn(&Path)) -> Result<T>) -> Result<T> {
+        let started = Instant::now();
+        self.send(LifecycleEvent::GenerationStarted { output_path: output_path.to_path_buf() });
+        let section_written = |file_path: &Path| self.send(LifecycleEvent::SectionWritten {
+            output_path: output_path.to_path_buf(),
+            file_path: file_path.to_path_buf(),
+            elapsed: started.elapsed(),
+        });
+        let result = generate(&section_written);
+        self.send(match &result {
+            Ok(_) => LifecycleEvent::GenerationCompleted { output_path: output_path.to_path_buf(), elapsed: started.elapsed() },
+            Err(e) => LifecycleEvent::GenerationFailed { output_path: output_path.to_path_buf(), error: e.to_string(), elapsed: started.elapsed() },
+        });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::mpsc;
+
+    use crate::constants::OutputFormat;
+    use crate::document_generator::DocumentGenerator;
+    use crate::file_handler::FileHandler;
+
+    #[test]
+    fn scans_and_generations_report_their_lifecycle() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let project = temp_dir.path().join("project");
+        fs::create_dir_all(project.join("src")).unwrap();
+        fs::write(project.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(project.join("src/lib.rs"), "pub fn lib() {}\n").unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let root_node = FileHandler::new(project.clone()).unwrap()
+            .with_lifecycle_sender(sender.clone())
+            .scan_directory(Vec::new())
+            .unwrap();
+        let output_path = temp_dir.path().join("context.md");
+        DocumentGenerator::new(project.clone(), vec![project.join("src/main.rs"), project.join("src/lib.rs")])
+            .with_lifecycle_sender(sender)
+            .generate_full_document(&root_node, &output_path, OutputFormat::Markdown)
+            .unwrap();
+
+        let events: Vec<LifecycleEvent> = receiver.try_iter().collect();
+        assert_eq!(events.len(), 6, "{:?}", events);
+        assert_eq!(events[0], LifecycleEvent::ScanStarted { directory: project.clone() });
+        assert!(matches!(&events[1], LifecycleEvent::ScanCompleted { directory, file_count: 2, .. } if *directory == project));
+        assert_eq!(events[2], LifecycleEvent::GenerationStarted { output_path: output_path.clone() });
+        assert!(matches!(&events[3], LifecycleEvent::SectionWritten { file_path, .. } if *file_path == project.join("src/lib.rs")));
+        assert!(matches!(&events[4], LifecycleEvent::SectionWritten { file_path, .. } if *file_path == project.join("src/main.rs")));
+        assert!(matches!(&events[5], LifecycleEvent::GenerationCompleted { output_path: path, .. } if *path == output_path));
+    }
+
+    #[test]
+    fn failed_generations_report_the_error() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::write(temp_dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+        let root_node = FileHandler::new(temp_dir.path().to_path_buf()).unwrap().scan_directory(Vec::new()).unwrap();
+        // The output directory doesn't exist
+        let output_path = temp_dir.path().join("missing/context.md");
+
+        let (sender, receiver) = mpsc::channel();
+        let result = DocumentGenerator::new(temp_dir.path().to_path_buf(), vec![temp_dir.path().join("main.rs")])
+            .with_lifecycle_sender(sender)
+            .generate_full_document(&root_node, &output_path, OutputFormat::Markdown);
+        assert!(result.is_err());
+
+        let events: Vec<LifecycleEvent> = receiver.try_iter().collect();
+        assert_eq!(events.first(), Some(&LifecycleEvent::GenerationStarted { output_path: output_path.clone() }));
+        assert!(matches!(events.last(), Some(LifecycleEvent::GenerationFailed { error, .. }) if !error.is_empty()));
+        assert!(!events.iter().any(|event| matches!(event, LifecycleEvent::SectionWritten { .. })));
+    }
+}
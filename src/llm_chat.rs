@@ -0,0 +1,264 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::constants::LLM_CONNECT_TIMEOUT_SECS;
+use crate::error::{AppError, Result};
+use crate::events::AppEvent;
+use crate::update_check::CURRENT_VERSION;
+
+const SYSTEM_PROMPT: &str = "You are given the context of a software project, generated by context_builder: its structure and the contents of selected files. Answer the user's question about it.";
+
+/// API flavour of the configured model endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LlmProvider {
+    #[default]
+    OpenAiCompatible, // `/chat/completions` with server-sent events, also served by llama.cpp, vLLM, LM Studio, ...
+    Ollama,           // `/api/chat` with JSON lines
+}
+
+impl LlmProvider {
+    pub const ALL: [LlmProvider; 2] = [LlmProvider::OpenAiCompatible, LlmProvider::Ollama];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LlmProvider::OpenAiCompatible => "OpenAI-compatible",
+            LlmProvider::Ollama => "Ollama",
+        }
+    }
+
+    /// Endpoint used when none is configured
+    pub fn default_endpoint(&self) -> &'static str {
+        match self {
+            LlmProvider::OpenAiCompatible => "https://api.openai.com/v1",
+            LlmProvider::Ollama => "http://localhost:11434",
+        }
+    }
+
+    fn chat_url(&self, endpoint: &str) -> String {
+        let endpoint = endpoint.trim().trim_end_matches('/');
+        let endpoint = if endpoint.is_empty() { self.default_endpoint() } else { endpoint };
+        match self {
+            LlmProvider::OpenAiCompatible => format!("{}/chat/completions", endpoint),
+            LlmProvider::Ollama => format!("{}/api/chat", endpoint),
+        }
+    }
+
+    /// Text of one line of the streamed response, if it carries any
+    fn parse_stream_line(&self, line: &str) -> Option<String> {
+        let json = match self {
+            LlmProvider::OpenAiCompatible => line.strip_prefix("data:")?.trim(),
+            LlmProvider::Ollama => line.trim(),
+        };
+        if json.is_empty() || json == "[DONE]" {
+            return None;
+        }
+        let chunk: StreamChunk = serde_json::from_str(json).ok()?;
+        let text = match self {
+            LlmProvider::OpenAiCompatible => chunk.choices.into_iter().next()?.delta.content?,
+            LlmProvider::Ollama => chunk.message?.content,
+        };
+        (!text.is_empty()).then_some(text)
+    }
+}
+
+/// Both providers' streamed chunk shapes; the fields of the other one are absent
+#[derive(Deserialize)]
+struct StreamChunk {
+    #[serde(default)]
+    choices: Vec<StreamChoice>, // OpenAI-compatible
+    message: Option<StreamMessage>, // Ollama
+}
+
+#[derive(Deserialize)]
+struct StreamChoice {
+    delta: StreamDelta,
+}
+
+#[derive(Deserialize)]
+struct StreamDelta {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StreamMessage {
+    content: String,
+}
+
+/// Where requests go, from the LLM settings
+#[derive(Debug, Clone)]
+pub struct LlmEndpoint {
+    pub provider: LlmProvider,
+    pub url: String, // Empty: the provider's default
+    pub model: String,
+    pub api_key: String, // Empty: `$OPENAI_API_KEY` for OpenAI-compatible endpoints, none for Ollama
+}
+
+impl LlmEndpoint {
+    /// Asks once and waits for the whole answer, giving up after `max_time_secs`
+    pub fn complete(&self, system_prompt: &str, user_message: String, max_time_secs: u64) -> Result<String> {
+        let body = self.chat_body(system_prompt, user_message)?;
+        let (mut child, _body_file) = self.spawn_request(body, Some(max_time_secs))?;
+        let mut answer = String::new();
+        if let Some(stdout) = child.stdout.take() {
+            for line in BufReader::new(stdout).lines().map_while(std::io::Result::ok) {
+                if let Some(text) = self.provider.parse_stream_line(&line) {
+                    answer.push_str(&text);
+                }
+            }
+        }
+        let mut message = String::new();
+        if let Some(mut stderr) = child.stderr.take() {
+            let _ = stderr.read_to_string(&mut message);
+        }
+        match child.wait() {
+            Ok(status) if status.success() && !answer.trim().is_empty() => Ok(answer),
+            Ok(status) if status.success() => Err(AppError::LlmError("The response had no answer text; check the provider setting".to_string())),
+            _ if message.trim().is_empty() => Err(AppError::LlmError("curl failed without a message".to_string())),
+            _ => Err(AppError::LlmError(message.trim().to_string())),
+        }
+    }
+
+    /// Starts `curl` posting `body` to the chat API; its stdout is the response
+    fn spawn_request(&self, body: serde_json::Value, max_time_secs: Option<u64>) -> Result<(Child, tempfile::NamedTempFile)> {
+        // The body holds the project's code and the headers the key, so neither goes on the
+        // command line where other processes could read it. Headers come from stdin, the body
+        // from a file that must live until curl has read it.
+        let mut body_file = tempfile::NamedTempFile::new()
+            .map_err(|e| AppError::new_io_error(e, None, "Failed to create the request file".to_string()))?;
+        body_file.write_all(body.to_string().as_bytes())
+            .map_err(|e| AppError::new_io_error(e, Some(body_file.path().to_path_buf()), "Failed to write the request file".to_string()))?;
+        let mut headers = vec!["Content-Type: application/json".to_string()];
+        let api_key = match self.api_key.trim() {
+            "" if self.provider == LlmProvider::OpenAiCompatible => std::env::var("OPENAI_API_KEY").unwrap_or_default(),
+            api_key => api_key.to_string(),
+        };
+        if !api_key.is_empty() {
+            headers.push(format!("Authorization: Bearer {}", api_key));
+        }
+
+        let url = self.provider.chat_url(&self.url);
+        debug!("Posting {} bytes to {}", body_file.as_file().metadata().map(|metadata| metadata.len()).unwrap_or_default(), url);
+        let mut command = Command::new("curl");
+        if let Some(max_time_secs) = max_time_secs {
+            command.args(["--max-time", &max_time_secs.to_string()]);
+        }
+        let mut child = command
+            .args(["--silent", "--show-error", "--fail", "--no-buffer", "--location"])
+            .args(["--connect-timeout", &LLM_CONNECT_TIMEOUT_SECS.to_string()])
+            .args(["--user-agent", &format!("context_builder/{}", CURRENT_VERSION)])
+            .args(["--header", "@-"])
+            .arg("--data-binary")
+            .arg(format!("@{}", body_file.path().display()))
+            .arg(&url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| AppError::new_io_error(e, None, "Failed to run curl for the LLM request".to_string()))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(headers.join("\n").as_bytes()); // Closed on drop, ending the headers
+        }
+        Ok((child, body_file))
+    }
+
+    fn chat_body(&self, system_prompt: &str, user_message: String) -> Result<serde_json::Value> {
+        if self.model.trim().is_empty() {
+            return Err(AppError::LlmError("No model is configured".to_string()));
+        }
+        Ok(json!({
+            "model": self.model.trim(),
+            "stream": true,
+            "messages": [
+                { "role": "system", "content": system_prompt },
+                { "role": "user", "content": user_message },
+            ],
+        }))
+    }
+}
+
+/// A running question about the document; dropping it doesn't stop it, `cancel` does
+pub struct LlmChat {
+    pub id: u64,
+    child: Arc<Mutex<Option<Child>>>,
+}
+
+impl LlmChat {
+    /// Sends the document parts with the question through the system `curl` (as the update
+    /// check does) and streams the answer back as `AppEvent::LlmAnswerChunk`s, then an
+    /// `AppEvent::LlmAnswerDone`
+    pub fn start(id: u64, endpoint: LlmEndpoint, document_paths: Vec<PathBuf>, question: String, event_sender: mpsc::Sender<AppEvent>) -> Self {
+        let child = Arc::new(Mutex::new(None));
+        let thread_child = child.clone();
+        thread::spawn(move || {
+            let result = read_document(&document_paths)
+                .and_then(|document| endpoint.chat_body(SYSTEM_PROMPT, format!("{}\n\n---\n\n{}", document, question.trim())))
+                .and_then(|body| stream_answer(id, &endpoint, body, &thread_child, &event_sender));
+            if let Err(e) = &result {
+                warn!("LLM request failed: {}", e);
+            }
+            if let Err(e) = event_sender.send(AppEvent::LlmAnswerDone { id, result }) {
+                debug!("Failed to send LLM answer result: {}", e);
+            }
+        });
+        Self { id, child }
+    }
+
+    pub fn cancel(&self) {
+        if let Some(child) = self.child.lock().ok().as_mut().and_then(|child| child.as_mut()) {
+            info!("Cancelling LLM request");
+            let _ = child.kill();
+        }
+    }
+}
+
+fn read_document(document_paths: &[PathBuf]) -> Result<String> {
+    let parts = document_paths.iter()
+        .map(|path| fs::read_to_string(path)
+            .map_err(|e| AppError::new_io_error(e, Some(path.clone()), "Failed to read the document".to_string())))
+        .collect::<Result<Vec<String>>>()?;
+    Ok(parts.join("\n\n"))
+}
+
+fn stream_answer(id: u64, endpoint: &LlmEndpoint, body: serde_json::Value, child_slot: &Mutex<Option<Child>>, event_sender: &mpsc::Sender<AppEvent>) -> Result<()> {
+    let (mut child, _body_file) = endpoint.spawn_request(body, None)?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    if let Ok(mut child_slot) = child_slot.lock() {
+        *child_slot = Some(child);
+    }
+
+    let mut received_text = false;
+    if let Some(stdout) = stdout {
+        for line in BufReader::new(stdout).lines().map_while(std::io::Result::ok) {
+            if let Some(text) = endpoint.provider.parse_stream_line(&line) {
+                received_text = true;
+                if event_sender.send(AppEvent::LlmAnswerChunk { id, text }).is_err() {
+                    break; // The window was closed
+                }
+            }
+        }
+    }
+
+    let status = child_slot.lock().ok()
+        .and_then(|mut child_slot| child_slot.take())
+        .and_then(|mut child| child.wait().ok());
+    match status {
+        Some(status) if status.success() && received_text => Ok(()),
+        Some(status) if status.success() => Err(AppError::LlmError("The response had no answer text; check the provider setting".to_string())),
+        _ => {
+            let mut message = String::new();
+            if let Some(mut stderr) = stderr {
+                let _ = stderr.read_to_string(&mut message);
+            }
+            let message = message.trim();
+            Err(AppError::LlmError(if message.is_empty() { "The request was cancelled".to_string() } else { message.to_string() }))
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use chrono::{DateTime, Local};
+use egui::{Context, RichText};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+const MAX_LOG_ENTRIES: usize = 5_000; // Oldest records are dropped beyond this
+
+// Levels of the records passed to the log panel and to the terminal, as `LevelFilter as usize`
+static CAPTURE_LEVEL: AtomicUsize = AtomicUsize::new(LevelFilter::Info as usize);
+static TERMINAL_LEVEL: AtomicUsize = AtomicUsize::new(LevelFilter::Info as usize);
+
+const LEVEL_FILTERS: [LevelFilter; 5] = [LevelFilter::Error, LevelFilter::Warn, LevelFilter::Info, LevelFilter::Debug, LevelFilter::Trace];
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub time: DateTime<Local>,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl LogEntry {
+    fn line(&self) -> String {
+        format!("{} {:<5} {}: {}", self.time.format("%H:%M:%S%.3f"), self.level, self.target, self.message)
+    }
+}
+
+/// Writes records to the terminal like `env_logger` (RUST_LOG applies) and sends them to the
+/// log panel, down to the panel's capture level
+struct ChannelLogger {
+    terminal: env_logger::Logger,
+    sender: Mutex<Sender<LogEntry>>,
+}
+
+impl Log for ChannelLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.terminal.enabled(metadata) || metadata.level() as usize <= CAPTURE_LEVEL.load(Ordering::Relaxed)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.terminal.matches(record) {
+            self.terminal.log(record);
+        }
+        if record.level() as usize <= CAPTURE_LEVEL.load(Ordering::Relaxed) {
+            let entry = LogEntry {
+                time: Local::now(),
+                level: record.level(),
+                target: record.target().to_string(),
+                message: record.args().to_string(),
+            };
+            if let Ok(sender) = self.sender.lock() {
+                let _ = sender.send(entry); // The panel is gone when the window closed
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.terminal.flush();
+    }
+}
+
+/// Installs the logger of the desktop app; the records arrive at the returned receiver
+pub fn init_logger() -> Receiver<LogEntry> {
+    let terminal = env_logger::Builder::from_default_env()
+        .filter_level(LevelFilter::Info)
+        .build();
+    TERMINAL_LEVEL.store(terminal.filter() as usize, Ordering::Relaxed);
+
+    let (sender, receiver) = mpsc::channel();
+    let logger = ChannelLogger { terminal, sender: Mutex::new(sender) };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        set_capture_level(LevelFilter::Info);
+    }
+    receiver
+}
+
+/// Lower levels cost formatting for every record, so the panel captures only down to its filter
+fn set_capture_level(level: LevelFilter) {
+    CAPTURE_LEVEL.store(level as usize, Ordering::Relaxed);
+    let terminal_level = LEVEL_FILTERS.into_iter()
+        .find(|filter| *filter as usize == TERMINAL_LEVEL.load(Ordering::Relaxed))
+        .unwrap_or(LevelFilter::Info);
+    log::set_max_level(level.max(terminal_level));
+}
+
+/// Collapsible panel at the bottom of the window with the app's recent log records, so watcher
+/// and scan problems can be diagnosed without a terminal and RUST_LOG
+pub struct LogPanel {
+    receiver: Receiver<LogEntry>,
+    entries: VecDeque<LogEntry>,
+    level: LevelFilter, // Shown and captured from now on
+    pub open: bool,
+}
+
+impl LogPanel {
+    pub fn new(receiver: Receiver<LogEntry>) -> Self {
+        Self {
+            receiver,
+            entries: VecDeque::new(),
+            level: LevelFilter::Info,
+            open: false,
+        }
+    }
+
+    /// Number of warnings and errors received, for the toggle button
+    pub fn problem_count(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.level <= Level::Warn).count()
+    }
+
+    /// Takes the records logged since the last frame, also while the panel is closed
+    pub fn receive(&mut self) {
+        self.entries.extend(self.receiver.try_iter());
+        let excess = self.entries.len().saturating_sub(MAX_LOG_ENTRIES);
+        self.entries.drain(..excess);
+    }
+
+    pub fn render(&mut self, ctx: &Context) {
+        if !self.open {
+            return;
+        }
+        egui::TopBottomPanel::bottom("log_panel")
+            .resizable(true)
+            .default_height(200.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.strong("Log");
+                    let level_before = self.level;
+                    for level in LEVEL_FILTERS {
+                        ui.radio_value(&mut self.level, level, level.as_str());
+                    }
+                    if self.level != level_before {
+                        set_capture_level(self.level);
+                    }
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("✖").on_hover_text("Close the log").clicked() {
+                            self.open = false;
+                        }
+                        if ui.small_button("Clear").clicked() {
+                            self.entries.clear();
+                        }
+                        if ui.small_button("📋 Copy").on_hover_text("Copy the shown records to the clipboard").clicked() {
+                            let text: Vec<String> = self.shown_entries().map(LogEntry::line).collect();
+                            ui.output_mut(|output| output.copied_text = text.join("\n"));
+                        }
+                    });
+                });
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .id_source("log_panel_scroll_area")
+                    .auto_shrink([false, false])
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for entry in self.shown_entries() {
+                            let text = RichText::new(entry.line()).monospace().small();
+                            let text = match entry.level {
+                                Level::Error => text.color(egui::Color32::from_rgb(200, 40, 40)),
+                                Level::Warn => text.color(egui::Color32::from_rgb(200, 120, 0)),
+                                Level::Info => text,
+                                Level::Debug | Level::Trace => text.weak(),
+                            };
+                            ui.label(text);
+                        }
+                    });
+            });
+    }
+
+    /// Records at or above the selected level. Lower ones captured before a switch stay hidden.
+    fn shown_entries(&self) -> impl Iterator<Item = &LogEntry> {
+        self.entries.iter().filter(|entry| entry.level <= self.level)
+    }
+}
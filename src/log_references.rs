@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use crate::constants::MAX_LOG_EXCERPTS_PER_FILE;
+
+const LOG_MESSAGE_KEYWORDS: &[&str] = &["error", "warning", "panicked", "failed", "exception"];
+
+/// A project file mentioned in a compiler or test log
+#[derive(Debug, Clone)]
+pub struct LogReference {
+    pub path: PathBuf,
+    pub lines: Vec<usize>,     // Line numbers given next to the path, in log order
+    pub excerpts: Vec<String>, // The log lines around each mention
+}
+
+/// Files of `directory` mentioned in `log`, in order of first mention. Recognizes `path:line:col`
+/// (rustc, gcc, pytest, jest), `path(line,col)` (MSVC) and `File "path", line N` (Python
+/// tracebacks). Paths from another checkout, e.g. a CI runner, match by their longest suffix
+/// that exists in the project.
+pub fn find_log_references(log: &str, directory: &Path) -> Vec<LogReference> {
+    let lines: Vec<&str> = log.lines().collect();
+    let mut references: Vec<LogReference> = Vec::new();
+    let mut index_by_path: HashMap<PathBuf, usize> = HashMap::new();
+    let mut resolved: HashMap<String, Option<PathBuf>> = HashMap::new(); // Candidates seen before
+
+    for (line_index, line) in lines.iter().enumerate() {
+        for (candidate, line_number) in line_mentions(line) {
+            let path = resolved.entry(candidate.clone())
+                .or_insert_with(|| resolve_candidate(&candidate, directory))
+                .clone();
+            let Some(path) = path else { continue };
+
+            let index = *index_by_path.entry(path.clone()).or_insert_with(|| {
+                references.push(LogReference { path, lines: Vec::new(), excerpts: Vec::new() });
+                references.len() - 1
+            });
+            let reference = &mut references[index];
+            if let Some(line_number) = line_number.filter(|n| !reference.lines.contains(n)) {
+                reference.lines.push(line_number);
+            }
+            let excerpt = excerpt_at(&lines, line_index);
+            if reference.excerpts.len() < MAX_LOG_EXCERPTS_PER_FILE && !reference.excerpts.contains(&excerpt) {
+                reference.excerpts.push(excerpt);
+            }
+        }
+    }
+    references
+}
+
+/// Path candidates of one log line with the line number given next to them, if any
+fn line_mentions(line: &str) -> Vec<(String, Option<usize>)> {
+    let tokens: Vec<&str> = line
+        .split(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '`' | ',' | '[' | ']' | '<' | '>' | '|' | '='))
+        .filter(|token| !token.is_empty())
+        .collect();
+
+    let mut mentions = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let token = token.trim_end_matches(['.', ':', ';', ')']);
+        if token.contains("://") {
+            continue;
+        }
+        let (candidate, mut line_number) = split_location(token);
+        // Python: `File "app/models.py", line 42, in save`
+        if line_number.is_none() && tokens.get(i + 1) == Some(&"line") {
+            line_number = tokens.get(i + 2).and_then(|n| n.parse().ok());
+        }
+        let candidate = candidate.trim_start_matches(['(', '@']);
+        if looks_like_path(candidate) {
+            mentions.push((candidate.to_string(), line_number));
+        }
+    }
+    mentions
+}
+
+/// `src/main.rs:10:5` -> (`src/main.rs`, 10), `main.cpp(10,5)` -> (`main.cpp`, 10)
+fn split_location(token: &str) -> (&str, Option<usize>) {
+    if let Some((path, rest)) = token.split_once('(') {
+        if rest.starts_with(|c: char| c.is_ascii_digit()) {
+            return (path, leading_number(rest));
+        }
+    }
+    // Skip a Windows drive letter, so `C:\src\main.rs:10` splits after the path
+    let search_from = if token.as_bytes().get(1) == Some(&b':') { 2 } else { 0 };
+    match token[search_from..].find(':') {
+        Some(colon) => {
+            let (path, rest) = token.split_at(search_from + colon);
+            (path, leading_number(&rest[1..]))
+        }
+        None => (token, None),
+    }
+}
+
+fn leading_number(text: &str) -> Option<usize> {
+    let digits: String = text.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+/// Has a file extension or a directory separator; plain words and numbers are left alone
+fn looks_like_path(candidate: &str) -> bool {
+    let file_name = candidate.rsplit(['/', '\\']).next().unwrap_or(candidate);
+    let has_extension = file_name.rsplit_once('.')
+        .is_some_and(|(stem, extension)| !stem.is_empty() && extension.chars().any(|c| c.is_ascii_alphabetic()));
+    has_extension || (candidate.contains(['/', '\\']) && !file_name.is_empty())
+}
+
+/// The project file a candidate names: the candidate itself when it lies inside `directory`,
+/// otherwise its longest suffix that exists there
+fn resolve_candidate(candidate: &str, directory: &Path) -> Option<PathBuf> {
+    let candidate = PathBuf::from(candidate.replace('\\', "/"));
+    if candidate.is_absolute() && candidate.starts_with(directory) {
+        return candidate.is_file().then_some(candidate);
+    }
+    let components: Vec<Component> = candidate.components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect();
+    (0..components.len())
+        .map(|skip| directory.join(components[skip..].iter().collect::<PathBuf>()))
+        .find(|path| path.is_file())
+}
+
+/// The log line at `index`, preceded by the line before it when that one holds the message
+/// (rustc's `error[E0308]: …` above ` --> src/main.rs:10:5`)
+fn excerpt_at(lines: &[&str], index: usize) -> String {
+    let line = lines[index].trim_end();
+    let message = index.checked_sub(1)
+        .map(|previous| lines[previous].trim_end())
+        .filter(|previous| {
+            let lowercase = previous.to_lowercase();
+            LOG_MESSAGE_KEYWORDS.iter().any(|keyword| lowercase.contains(keyword)) && line_mentions(previous).is_empty()
+        });
+    match message {
+        Some(message) => format!("{}\n{}", message, line),
+        None => line.to_string(),
+    }
+}
@@ -1,21 +1,36 @@
-mod constants;
-mod error;
-mod events;
-mod file_handler;
-mod file_monitor;
-mod document_generator;
-mod ui_tree_handler;
-mod app;
+#[cfg(feature = "tui")]
+mod tui;
 
+use std::path::PathBuf;
 use eframe::NativeOptions;
 use log::info;
-use app::ContextBuilderApp;
+use context_builder::progress::{ProgressEvent, ProgressReporter};
+use context_builder::project_tabs::ProjectTabs;
+use context_builder::{batch, crash_report, file_list, log_panel};
+
+const USAGE: &str = "Usage: context_builder [--tui [DIRECTORY | USER@HOST:PATH] [--output FILE]]\n       context_builder batch PROJECTS.toml [--progress json]\n       context_builder --stdin-files [--dir DIRECTORY] [--output FILE] [--progress json]";
 
 fn main() -> Result<(), eframe::Error> {
-    // Initialize logging
-    env_logger::Builder::from_default_env()
-        .filter_level(log::LevelFilter::Info)
-        .init();
+    crash_report::install_panic_hook();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "batch") {
+        env_logger::Builder::from_default_env().init();
+        run_batch(&args[1..]);
+    }
+    if args.iter().any(|arg| arg == "--stdin-files") {
+        env_logger::Builder::from_default_env()
+            .filter_level(log::LevelFilter::Warn)
+            .init();
+        run_stdin_files(&args);
+    }
+    if args.iter().any(|arg| arg == "--tui") {
+        // Log output would garble the terminal UI, so only RUST_LOG enables it there
+        env_logger::Builder::from_default_env().init();
+        run_tui(&args);
+    }
+
+    // Initialize logging; records also go to the in-app log panel
+    let log_receiver = log_panel::init_logger();
     
     info!("Starting Context Builder - Rust Edition");
 
@@ -29,6 +44,120 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "Context Builder",
         options,
-        Box::new(|cc| Box::new(ContextBuilderApp::new(cc))),
+        Box::new(|cc| Box::new(ProjectTabs::new(cc, log_receiver))),
     )
 }
+
+/// `batch PROJECTS.toml`: generates the document of every listed project, prints a summary
+/// table and exits with status 1 if any failed
+fn run_batch(args: &[String]) -> ! {
+    let mut args = args.to_vec();
+    let progress = take_progress_option(&mut args);
+    let [batch_path] = args.as_slice() else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+
+    match batch::run_batch(&PathBuf::from(batch_path), progress) {
+        Ok(outcomes) => {
+            println!("{}", batch::format_summary(&outcomes));
+            let failed = outcomes.iter().any(|outcome| outcome.error.is_some());
+            std::process::exit(if failed { 1 } else { 0 });
+        }
+        Err(e) => {
+            progress.emit(ProgressEvent::Error { directory: None, message: e.to_string() });
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `--stdin-files [--dir DIRECTORY] [--output FILE]`: generates the document for the files listed
+/// on stdin and prints it, or writes it to FILE
+fn run_stdin_files(args: &[String]) -> ! {
+    let mut args = args.to_vec();
+    let progress = take_progress_option(&mut args);
+    let mut directory = PathBuf::from(".");
+    let mut output_path = None;
+    let mut args = args.iter().filter(|arg| *arg != "--stdin-files");
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--dir" | "-d", Some(value)) => directory = PathBuf::from(value),
+            ("--output" | "-o", Some(value)) => output_path = Some(PathBuf::from(value)),
+            _ => {
+                eprintln!("Unexpected argument '{}'\n{}", arg, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let mut file_list = String::new();
+    if let Err(e) = std::io::Read::read_to_string(&mut std::io::stdin(), &mut file_list) {
+        eprintln!("Failed to read the file list from stdin: {}", e);
+        std::process::exit(1);
+    }
+    match file_list::run_file_list(&directory, &file_list, output_path.as_deref(), progress) {
+        Ok(file_count) => {
+            if let Some(output_path) = output_path {
+                eprintln!("Wrote {} with {} files", output_path.display(), file_count);
+            }
+            std::process::exit(0);
+        }
+        Err(e) => {
+            progress.emit(ProgressEvent::Error { directory: Some(&directory), message: e.to_string() });
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Removes `--progress FORMAT` from the arguments of a headless mode
+fn take_progress_option(args: &mut Vec<String>) -> ProgressReporter {
+    let Some(index) = args.iter().position(|arg| arg == "--progress") else {
+        return ProgressReporter::default();
+    };
+    let value = args.get(index + 1).cloned().unwrap_or_default();
+    args.drain(index..(index + 2).min(args.len()));
+    ProgressReporter::from_option(&value).unwrap_or_else(|| {
+        eprintln!("Unknown progress format '{}'; only `json` is supported\n{}", value, USAGE);
+        std::process::exit(2);
+    })
+}
+
+/// `--tui [DIRECTORY | USER@HOST:PATH] [--output FILE]`: runs the terminal front end instead of the window, then exits
+fn run_tui(args: &[String]) -> ! {
+    let mut directory = None;
+    let mut output_path = None;
+    let mut args = args.iter().filter(|arg| *arg != "--tui");
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" | "-o" => output_path = args.next().map(PathBuf::from),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            _ if directory.is_none() && !arg.starts_with('-') => directory = Some(PathBuf::from(arg)),
+            _ => {
+                eprintln!("Unexpected argument '{}'\n{}", arg, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+    let directory = directory.unwrap_or_else(|| PathBuf::from("."));
+
+    #[cfg(feature = "tui")]
+    let result = tui::run(directory, output_path);
+    #[cfg(not(feature = "tui"))]
+    let result: context_builder::error::Result<()> = {
+        let _ = (directory, output_path);
+        Err(context_builder::error::AppError::OperationFailed("This build has no terminal front end; rebuild with `--features tui`".to_string()))
+    };
+
+    match result {
+        Ok(()) => std::process::exit(0),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::path::Path;
+
+/// How comments and strings look in a family of languages. Strings are only tracked so that
+/// comment markers inside them are left alone.
+#[derive(Debug, Clone, Copy)]
+pub struct CommentSyntax {
+    line: Option<&'static str>,
+    block: Option<(&'static str, &'static str)>,
+    nested_blocks: bool,            // Rust block comments nest
+    quotes: &'static [char],
+    char_literals: bool,            // Rust: `'` only opens a char literal, lifetimes stay as they are
+    triple_quotes: bool,            // Python and TOML `"""` strings
+    multiline_strings: bool,        // Otherwise an unclosed string ends with its line
+    line_comment_after_space: bool, // Shell-like: `#` only starts a comment at a word boundary
+}
+
+const RUST: CommentSyntax = CommentSyntax {
+    line: Some("//"),
+    block: Some(("/*", "*/")),
+    nested_blocks: true,
+    quotes: &['"'],
+    char_literals: true,
+    triple_quotes: false,
+    multiline_strings: true,
+    line_comment_after_space: false,
+};
+
+const C_LIKE: CommentSyntax = CommentSyntax {
+    line: Some("//"),
+    block: Some(("/*", "*/")),
+    nested_blocks: false,
+    quotes: &['"', '\''],
+    char_literals: false,
+    triple_quotes: false,
+    multiline_strings: false,
+    line_comment_after_space: false,
+};
+
+// JavaScript, TypeScript and Go: backtick strings span lines
+const C_LIKE_BACKTICK: CommentSyntax = CommentSyntax {
+    quotes: &['"', '\'', '`'],
+    ..C_LIKE
+};
+
+const CSS: CommentSyntax = CommentSyntax {
+    line: None,
+    ..C_LIKE
+};
+
+const HASH: CommentSyntax = CommentSyntax {
+    line: Some("#"),
+    block: None,
+    nested_blocks: false,
+    quotes: &['"', '\''],
+    char_literals: false,
+    triple_quotes: true,
+    multiline_strings: false,
+    line_comment_after_space: true,
+};
+
+impl CommentSyntax {
+    /// Syntax for the file's extension, or `None` for languages that are not minified
+    pub fn for_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        Some(match extension.as_str() {
+            "rs" => RUST,
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "cs" | "java" | "kt" | "kts" | "scala" | "swift" | "dart" | "scss" => C_LIKE,
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "go" => C_LIKE_BACKTICK,
+            "css" => CSS,
+            "py" | "pyi" | "rb" | "sh" | "bash" | "zsh" | "pl" | "r" | "toml" | "yaml" | "yml" | "ex" | "exs" => HASH,
+            _ => return None,
+        })
+    }
+}
+
+/// `content` without comments, trailing whitespace and repeated blank lines. Lines that only held
+/// a comment are dropped; a leading `#!` line is kept.
+pub fn minify(content: &str, syntax: CommentSyntax) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    let mut stripped = String::with_capacity(content.len());
+    let mut line_start = 0; // Byte offset of the current line in `stripped`
+    let mut removed_on_line = false;
+    let mut i = 0;
+
+    if syntax.line == Some("#") && content.starts_with("#!") {
+        while i < chars.len() && chars[i] != '\n' {
+            stripped.push(chars[i]);
+            i += 1;
+        }
+    }
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            if removed_on_line && stripped[line_start..].trim().is_empty() {
+                stripped.truncate(line_start);
+            } else {
+                stripped.push('\n');
+            }
+            line_start = stripped.len();
+            removed_on_line = false;
+            i += 1;
+        } else if let Some((open, close)) = syntax.block.filter(|(open, _)| starts_with_at(&chars, i, open)) {
+            i = skip_block_comment(&chars, i, open, close, syntax.nested_blocks);
+            removed_on_line = true;
+        } else if syntax.line.is_some_and(|marker| starts_with_at(&chars, i, marker))
+            && (!syntax.line_comment_after_space || i == 0 || chars[i - 1].is_whitespace())
+        {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            removed_on_line = true;
+        } else if syntax.char_literals && c == '\'' {
+            let end = char_literal_end(&chars, i).unwrap_or(i + 1);
+            stripped.extend(&chars[i..end]);
+            i = end;
+        } else if syntax.quotes.contains(&c) {
+            let end = string_end(&chars, i, syntax);
+            stripped.extend(&chars[i..end]);
+            i = end;
+        } else {
+            stripped.push(c);
+            i += 1;
+        }
+    }
+    if removed_on_line && stripped[line_start..].trim().is_empty() {
+        stripped.truncate(line_start);
+    }
+
+    collapse_blank_lines(&stripped)
+}
+
+fn starts_with_at(chars: &[char], index: usize, pattern: &str) -> bool {
+    let mut pattern_chars = pattern.chars();
+    chars[index..].iter().take(pattern.len()).all(|c| pattern_chars.next() == Some(*c)) && pattern_chars.next().is_none()
+}
+
+/// Index just past the comment opened at `start`
+fn skip_block_comment(chars: &[char], start: usize, open: &str, close: &str, nested: bool) -> usize {
+    let mut depth = 1;
+    let mut i = start + open.len();
+    while i < chars.len() {
+        if nested && starts_with_at(chars, i, open) {
+            depth += 1;
+            i += open.len();
+        } else if starts_with_at(chars, i, close) {
+            depth -= 1;
+            i += close.len();
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    chars.len()
+}
+
+/// Index just past a Rust char literal such as `'x'` or `'\n'` starting at `start`, or `None`
+/// for a lifetime
+fn char_literal_end(chars: &[char], start: usize) -> Option<usize> {
+    match chars.get(start + 1)? {
+        '\\' => (start + 2..chars.len().min(start + 12))
+            .find(|&i| chars[i] == '\'')
+            .map(|i| i + 1),
+        _ => (chars.get(start + 2) == Some(&'\'')).then_some(start + 3),
+    }
+}
+
+/// Index just past the string opened at `start`
+fn string_end(chars: &[char], start: usize, syntax: CommentSyntax) -> usize {
+    let quote = chars[start];
+    let triple = [quote; 3].iter().collect::<String>();
+    if syntax.triple_quotes && starts_with_at(chars, start, &triple) {
+        let mut i = start + 3;
+        while i < chars.len() {
+            if chars[i] == '\\' {
+                i += 2;
+            } else if starts_with_at(chars, i, &triple) {
+                return i + 3;
+            } else {
+                i += 1;
+            }
+        }
+        return chars.len();
+    }
+
+    let multiline = syntax.multiline_strings || quote == '`';
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '\n' if !multiline => return i,
+            c if c == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+fn collapse_blank_lines(content: &str) -> String {
+    let mut collapsed = String::with_capacity(content.len());
+    let mut previous_blank = true; // Also drops leading blank lines
+    for line in content.lines() {
+        let line = line.trim_end();
+        if line.is_empty() && previous_blank {
+            continue;
+        }
+        previous_blank = line.is_empty();
+        collapsed.push_str(line);
+        collapsed.push('\n');
+    }
+    collapsed.trim_end().to_string()
+}
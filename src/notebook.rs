@@ -0,0 +1,74 @@
+use std::path::Path;
+use log::debug;
+use serde::Deserialize;
+
+/// The parts of an nbformat 4 notebook that make it into the document
+#[derive(Deserialize)]
+struct Notebook {
+    cells: Vec<Cell>,
+}
+
+#[derive(Deserialize)]
+struct Cell {
+    cell_type: String,
+    #[serde(default)]
+    source: CellSource,
+}
+
+/// nbformat allows the source as one string or as a list of lines
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CellSource {
+    Text(String),
+    Lines(Vec<String>),
+}
+
+impl Default for CellSource {
+    fn default() -> Self {
+        CellSource::Text(String::new())
+    }
+}
+
+impl CellSource {
+    fn text(&self) -> String {
+        match self {
+            CellSource::Text(text) => text.clone(),
+            CellSource::Lines(lines) => lines.concat(),
+        }
+    }
+}
+
+pub fn is_notebook(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("ipynb"))
+}
+
+/// The cells of a notebook as a script in the `# %%` "percent" format (as used by Jupytext
+/// and VS Code), without outputs. Markdown cells become `# ` comment lines under
+/// `# %% [markdown]` when `markdown_cells` is set and are left out otherwise. `None` if the
+/// JSON is not an nbformat 4 notebook.
+pub fn notebook_cells(content: &str, markdown_cells: bool) -> Option<String> {
+    let notebook: Notebook = match serde_json::from_str(content) {
+        Ok(notebook) => notebook,
+        Err(e) => {
+            debug!("Not rendering notebook cells: {}", e);
+            return None;
+        }
+    };
+
+    let mut cells = Vec::new();
+    for cell in &notebook.cells {
+        let source = cell.source.text();
+        let source = source.trim_end();
+        match cell.cell_type.as_str() {
+            "code" if !source.trim().is_empty() => cells.push(format!("# %%\n{}", source)),
+            "markdown" if markdown_cells && !source.trim().is_empty() => {
+                let commented: Vec<String> = source.lines()
+                    .map(|line| if line.is_empty() { "#".to_string() } else { format!("# {}", line) })
+                    .collect();
+                cells.push(format!("# %% [markdown]\n{}", commented.join("\n")));
+            }
+            _ => {} // Empty cells and raw cells
+        }
+    }
+    Some(cells.join("\n\n"))
+}
@@ -0,0 +1,149 @@
+use std::path::Path;
+use log::debug;
+use tree_sitter::{Language, Node, Parser};
+
+/// Node kinds of one language that make up its outline
+struct OutlineGrammar {
+    language: fn() -> Language,
+    functions: &'static [&'static str],  // Their `body` is replaced with `{ ... }` or `...`
+    containers: &'static [&'static str], // Their `body` is outlined item by item
+    wrappers: &'static [(&'static str, &'static str)], // Kind and field of the wrapped declaration, e.g. decorators
+    skipped: &'static [&'static str],    // Top-level statements that are not declarations
+    braces: bool,                        // Bodies are `{ ... }` rather than indented blocks
+}
+
+const RUST: OutlineGrammar = OutlineGrammar {
+    language: || tree_sitter_rust::LANGUAGE.into(),
+    functions: &["function_item"],
+    containers: &["impl_item", "trait_item", "mod_item"],
+    wrappers: &[],
+    skipped: &[],
+    braces: true,
+};
+
+const PYTHON: OutlineGrammar = OutlineGrammar {
+    language: || tree_sitter_python::LANGUAGE.into(),
+    functions: &["function_definition"],
+    containers: &["class_definition"],
+    wrappers: &[("decorated_definition", "definition")],
+    skipped: &["if_statement", "for_statement", "while_statement", "try_statement", "with_statement"],
+    braces: false,
+};
+
+const JAVASCRIPT: OutlineGrammar = OutlineGrammar {
+    language: || tree_sitter_javascript::LANGUAGE.into(),
+    functions: &["function_declaration", "generator_function_declaration", "method_definition"],
+    containers: &["class_declaration"],
+    wrappers: &[("export_statement", "declaration")],
+    skipped: &["expression_statement", "if_statement", "for_statement", "while_statement", "try_statement"],
+    braces: true,
+};
+
+const TYPESCRIPT: OutlineGrammar = OutlineGrammar {
+    language: || tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+    containers: &["class_declaration", "abstract_class_declaration"],
+    ..JAVASCRIPT
+};
+
+const TSX: OutlineGrammar = OutlineGrammar {
+    language: || tree_sitter_typescript::LANGUAGE_TSX.into(),
+    ..TYPESCRIPT
+};
+
+const GO: OutlineGrammar = OutlineGrammar {
+    language: || tree_sitter_go::LANGUAGE.into(),
+    functions: &["function_declaration", "method_declaration"],
+    containers: &[],
+    wrappers: &[],
+    skipped: &[],
+    braces: true,
+};
+
+fn grammar_for(path: &Path) -> Option<&'static OutlineGrammar> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    Some(match extension.as_str() {
+        "rs" => &RUST,
+        "py" | "pyi" => &PYTHON,
+        "js" | "jsx" | "mjs" | "cjs" => &JAVASCRIPT,
+        "ts" | "mts" | "cts" => &TYPESCRIPT,
+        "tsx" => &TSX,
+        "go" => &GO,
+        _ => return None,
+    })
+}
+
+/// Whether signatures-only rendering is available for the file's language
+pub fn supports_outline(path: &Path) -> bool {
+    grammar_for(path).is_some()
+}
+
+/// The top-level declarations of `source` with function bodies elided, e.g. `fn load(path: &Path) -> Result<Self> { ... }`.
+/// Items of impls, traits, modules and classes are outlined the same way. `None` if the language
+/// is not supported or the file doesn't parse.
+pub fn outline(path: &Path, source: &str) -> Option<String> {
+    let grammar = grammar_for(path)?;
+    let mut parser = Parser::new();
+    parser.set_language(&(grammar.language)()).ok()?;
+    let tree = parser.parse(source, None)?;
+    if tree.root_node().has_error() {
+        debug!("Not outlining {:?}: it has syntax errors", path);
+        return None;
+    }
+
+    let mut outline = String::new();
+    outline_items(grammar, source, tree.root_node(), 0, &mut outline);
+    Some(outline.trim_end().to_string())
+}
+
+fn outline_items(grammar: &OutlineGrammar, source: &str, parent: Node, depth: usize, outline: &mut String) {
+    let mut cursor = parent.walk();
+    let mut previous_end_row = None;
+    for item in parent.named_children(&mut cursor) {
+        if depth == 0 && grammar.skipped.contains(&item.kind()) {
+            continue;
+        }
+        // Keep blank lines between items, so the outline reads like the file
+        if previous_end_row.is_some_and(|row| item.start_position().row > row + 1) {
+            outline.push('\n');
+        }
+        // Rust line comments end at the start of the next line
+        let end = item.end_position();
+        previous_end_row = Some(if end.column == 0 && end.row > item.start_position().row { end.row - 1 } else { end.row });
+
+        outline.push_str(&"    ".repeat(depth));
+        outline_item(grammar, source, item, depth, outline);
+        outline.push('\n');
+    }
+}
+
+fn outline_item(grammar: &OutlineGrammar, source: &str, item: Node, depth: usize, outline: &mut String) {
+    let kind = item.kind();
+    if let Some(wrapped) = grammar.wrappers.iter()
+        .find(|(wrapper, _)| *wrapper == kind)
+        .and_then(|(_, field)| item.child_by_field_name(field))
+    {
+        outline.push_str(&source[item.start_byte()..wrapped.start_byte()]);
+        outline_item(grammar, source, wrapped, depth, outline);
+        return;
+    }
+
+    let body = item.child_by_field_name("body");
+    match body {
+        Some(body) if grammar.functions.contains(&kind) => {
+            outline.push_str(source[item.start_byte()..body.start_byte()].trim_end());
+            outline.push_str(if grammar.braces { " { ... }" } else { " ..." });
+        }
+        Some(body) if grammar.containers.contains(&kind) => {
+            outline.push_str(source[item.start_byte()..body.start_byte()].trim_end());
+            outline.push_str(if grammar.braces { " {\n" } else { "\n" });
+            outline_items(grammar, source, body, depth + 1, outline);
+            if grammar.braces {
+                outline.push_str(&"    ".repeat(depth));
+                outline.push('}');
+            } else if outline.ends_with('\n') {
+                outline.pop();
+            }
+        }
+        _ => outline.push_str(source[item.byte_range()].trim_end()),
+    }
+}
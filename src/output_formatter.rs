@@ -0,0 +1,360 @@
+use crate::constants::{
+    ADOC_SECTION_LEVEL_1, ADOC_SOURCE_BLOCK_DELIMITER, MARKDOWN_CODE_BLOCK, ORG_HEADING_CHAR, ORG_SOURCE_BLOCK_BEGIN,
+    ORG_SOURCE_BLOCK_END, RST_CODE_BLOCK_DIRECTIVE, RST_HEADING_CHARS, RST_INDENT,
+};
+
+/// How one output format writes the parts of a document, and finds its sections again for the
+/// outline and partial updates. A new format is an `OutputFormat` variant whose `formatter`
+/// returns its implementation; the generator itself doesn't match on formats for rendering.
+pub trait OutputFormatter: Sync {
+    /// A heading of the given level, 1 being the document title
+    fn heading(&self, title: &str, level: usize) -> String;
+
+    /// Level and title of the heading on each of `lines` (which keep their line breaks).
+    /// Lines inside code blocks are never headings.
+    fn headings<'a>(&self, lines: &[&'a str]) -> Vec<Option<(usize, &'a str)>>;
+
+    /// A code block of `content` in the given language, kept verbatim
+    fn code_block(&self, language: &str, content: &str) -> String;
+
+    /// Delimiter of a code block that holds `content` verbatim, for templates. Empty where
+    /// blocks have fixed delimiters and `block_content` keeps the content apart from them.
+    fn code_fence(&self, _content: &str) -> String {
+        String::new()
+    }
+
+    /// `content` as it goes between the block delimiters
+    fn block_content(&self, content: &str) -> String {
+        content.to_string()
+    }
+
+    fn emphasis(&self, text: &str) -> String;
+
+    /// A one-line comment, hidden when the document is rendered
+    fn comment(&self, text: &str) -> String;
+
+    fn strong(&self, text: &str) -> String;
+
+    fn inline_code(&self, text: &str) -> String;
+
+    fn list_bullet(&self) -> &'static str {
+        "-"
+    }
+
+    /// The Project Structure section around the tree lines, which each end with a newline
+    fn structure_block(&self, tree_lines: &str) -> String {
+        format!("{}\n{}", self.heading("Project Structure", 2), self.code_block("text", tree_lines.trim_end()))
+    }
+
+    /// Heading plus code block of one file section
+    fn file_section(&self, display_path: &str, language: &str, content: &str) -> String {
+        format!("{}\n\n{}", self.heading(display_path, 3), self.code_block(language, content))
+    }
+
+    /// Byte offset of the first heading of level `max_level` or higher in `text`, not counting
+    /// one at its very start. `text` must not start inside a code block.
+    fn next_heading(&self, text: &str, max_level: usize) -> Option<usize> {
+        let lines: Vec<&str> = text.split_inclusive('\n').collect();
+        let mut offset = 0;
+        for (index, (line, heading)) in lines.iter().zip(self.headings(&lines)).enumerate() {
+            if index > 0 && heading.is_some_and(|(level, _)| level <= max_level) {
+                return Some(offset);
+            }
+            offset += line.len();
+        }
+        None
+    }
+}
+
+pub struct MarkdownFormatter;
+
+impl OutputFormatter for MarkdownFormatter {
+    fn heading(&self, title: &str, level: usize) -> String {
+        format!("{} {}", "#".repeat(level), title)
+    }
+
+    fn headings<'a>(&self, lines: &[&'a str]) -> Vec<Option<(usize, &'a str)>> {
+        prefixed_headings(lines, '#', MARKDOWN_CODE_BLOCK, |line, fence_length, open_length| {
+            fence_length >= open_length && line[fence_length..].trim().is_empty()
+        })
+    }
+
+    fn code_block(&self, language: &str, content: &str) -> String {
+        let fence = self.code_fence(content);
+        format!("{}{}\n{}\n{}", fence, language, content, fence)
+    }
+
+    /// Backticks, one more than the longest backtick run in `content`
+    fn code_fence(&self, content: &str) -> String {
+        let longest_run = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+        "`".repeat((longest_run + 1).max(MARKDOWN_CODE_BLOCK.len()))
+    }
+
+    fn emphasis(&self, text: &str) -> String {
+        format!("_{}_", text)
+    }
+
+    fn comment(&self, text: &str) -> String {
+        format!("<!-- {} -->", text)
+    }
+
+    fn strong(&self, text: &str) -> String {
+        format!("**{}**", text)
+    }
+
+    fn inline_code(&self, text: &str) -> String {
+        format!("`{}`", text)
+    }
+
+    /// The tree goes into a plain fence, without a language
+    fn structure_block(&self, tree_lines: &str) -> String {
+        let fence = self.code_fence(tree_lines);
+        format!("{}\n{}\n{}{}", self.heading("Project Structure", 2), fence, tree_lines, fence)
+    }
+}
+
+pub struct AdocFormatter;
+
+impl OutputFormatter for AdocFormatter {
+    fn heading(&self, title: &str, level: usize) -> String {
+        format!("{} {}", ADOC_SECTION_LEVEL_1.repeat(level), title)
+    }
+
+    fn headings<'a>(&self, lines: &[&'a str]) -> Vec<Option<(usize, &'a str)>> {
+        prefixed_headings(lines, '=', ADOC_SOURCE_BLOCK_DELIMITER, |line, fence_length, open_length| {
+            fence_length == open_length && line[fence_length..].trim().is_empty()
+        })
+    }
+
+    fn code_block(&self, language: &str, content: &str) -> String {
+        let fence = self.code_fence(content);
+        format!("[source, {}]\n{}\n{}\n{}", language, fence, content, fence)
+    }
+
+    /// Hyphens, one more than the longest line of only hyphens in `content`
+    fn code_fence(&self, content: &str) -> String {
+        let longest_line = content.lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty() && line.chars().all(|c| c == '-'))
+            .map(str::len)
+            .max()
+            .unwrap_or(0);
+        "-".repeat((longest_line + 1).max(ADOC_SOURCE_BLOCK_DELIMITER.len()))
+    }
+
+    fn emphasis(&self, text: &str) -> String {
+        format!("_{}_", text)
+    }
+
+    fn comment(&self, text: &str) -> String {
+        format!("// {}", text)
+    }
+
+    fn strong(&self, text: &str) -> String {
+        format!("*{}*", text)
+    }
+
+    fn inline_code(&self, text: &str) -> String {
+        format!("`+{}+`", text)
+    }
+
+    fn list_bullet(&self) -> &'static str {
+        "*"
+    }
+}
+
+pub struct OrgFormatter;
+
+impl OutputFormatter for OrgFormatter {
+    fn heading(&self, title: &str, level: usize) -> String {
+        format!("{} {}", ORG_HEADING_CHAR.to_string().repeat(level), title)
+    }
+
+    fn headings<'a>(&self, lines: &[&'a str]) -> Vec<Option<(usize, &'a str)>> {
+        prefixed_headings(lines, ORG_HEADING_CHAR, ORG_SOURCE_BLOCK_BEGIN, |line, _, _| line.trim_end() == ORG_SOURCE_BLOCK_END)
+    }
+
+    fn code_block(&self, language: &str, content: &str) -> String {
+        format!(
+            "{}\n{}\n{}",
+            format!("{} {}", ORG_SOURCE_BLOCK_BEGIN, language).trim_end(),
+            self.block_content(content),
+            ORG_SOURCE_BLOCK_END
+        )
+    }
+
+    /// Lines that would read as a heading or a `#+` keyword are comma-escaped; Org strips the
+    /// comma when editing or exporting the block
+    fn block_content(&self, content: &str) -> String {
+        content.split_inclusive('\n')
+            .map(|line| {
+                let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+                if line.trim_start_matches(',').starts_with(ORG_HEADING_CHAR) {
+                    format!(",{}", line)
+                } else if line[indent..].trim_start_matches(',').starts_with("#+") {
+                    format!("{},{}", &line[..indent], &line[indent..])
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect()
+    }
+
+    fn emphasis(&self, text: &str) -> String {
+        format!("/{}/", text)
+    }
+
+    fn comment(&self, text: &str) -> String {
+        format!("# {}", text)
+    }
+
+    fn strong(&self, text: &str) -> String {
+        format!("*{}*", text)
+    }
+
+    fn inline_code(&self, text: &str) -> String {
+        format!("={}=", text)
+    }
+}
+
+pub struct RstFormatter;
+
+impl OutputFormatter for RstFormatter {
+    fn heading(&self, title: &str, level: usize) -> String {
+        // At least as wide as the title; wide characters may take two columns
+        let width = title.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum();
+        let adornment = RST_HEADING_CHARS[level.clamp(1, RST_HEADING_CHARS.len()) - 1].to_string().repeat(width);
+        match level {
+            1 => format!("{}\n{}\n{}", adornment, title, adornment),
+            _ => format!("{}\n{}", title, adornment),
+        }
+    }
+
+    /// Code is indented there, so no heading can start inside a block
+    fn headings<'a>(&self, lines: &[&'a str]) -> Vec<Option<(usize, &'a str)>> {
+        (0..lines.len()).map(|index| parse_rst_heading(lines, index)).collect()
+    }
+
+    fn code_block(&self, language: &str, content: &str) -> String {
+        format!(
+            "{}\n\n{}",
+            format!("{} {}", RST_CODE_BLOCK_DIRECTIVE, language).trim_end(),
+            self.block_content(content)
+        )
+    }
+
+    /// Directive content is indented
+    fn block_content(&self, content: &str) -> String {
+        content.split_inclusive('\n')
+            .map(|line| match line.trim().is_empty() {
+                true => line.trim_start_matches([' ', '\t']).to_string(), // No trailing whitespace
+                false => format!("{}{}", RST_INDENT, line),
+            })
+            .collect()
+    }
+
+    fn emphasis(&self, text: &str) -> String {
+        format!("*{}*", text)
+    }
+
+    fn comment(&self, text: &str) -> String {
+        format!(".. {}", text)
+    }
+
+    fn strong(&self, text: &str) -> String {
+        format!("**{}**", text)
+    }
+
+    fn inline_code(&self, text: &str) -> String {
+        format!("``{}``", text)
+    }
+}
+
+/// Level and title of the heading on each line, for formats whose headings start with a run of
+/// `heading_char` (`##`, `==`, `**`). Lines from one starting with `block_start` up to the one
+/// `closes(line, fence_length, open_length)` accepts are inside a code block.
+fn prefixed_headings<'a>(
+    lines: &[&'a str],
+    heading_char: char,
+    block_start: &str,
+    closes: impl Fn(&str, usize, usize) -> bool,
+) -> Vec<Option<(usize, &'a str)>> {
+    let fence_char = block_start.chars().next().unwrap_or_default();
+    let mut open_fence: Option<usize> = None; // Length of the delimiter of the block we're in
+    lines.iter().map(|line| {
+        let fence_length = line.chars().take_while(|c| *c == fence_char).count();
+        if let Some(open_length) = open_fence {
+            // Fences are sized to their content, so only a delimiter as long closes the block
+            if closes(line, fence_length, open_length) {
+                open_fence = None;
+            }
+            None
+        } else if line.starts_with(block_start) {
+            open_fence = Some(fence_length);
+            None
+        } else {
+            let level = line.chars().take_while(|c| *c == heading_char).count();
+            line[level..].strip_prefix(' ').filter(|_| level > 0).map(|title| (level, title))
+        }
+    }).collect()
+}
+
+/// Level and title of the reStructuredText heading starting at `lines[index]`: a title with an
+/// underline, or for level 1 with an overline and underline
+fn parse_rst_heading<'a>(lines: &[&'a str], index: usize) -> Option<(usize, &'a str)> {
+    let adornment = |index: usize| -> Option<char> {
+        let line = lines.get(index)?.trim_end();
+        let first = line.chars().next()?;
+        (RST_HEADING_CHARS.contains(&first) && line.chars().all(|c| c == first)).then_some(first)
+    };
+    let is_title = |line: &str| !line.trim().is_empty() && !line.starts_with(char::is_whitespace);
+
+    if adornment(index) == Some(RST_HEADING_CHARS[0]) {
+        let title = lines.get(index + 1)?;
+        return (is_title(title) && adornment(index + 2) == Some(RST_HEADING_CHARS[0])).then_some((1, title));
+    }
+    let line = lines.get(index)?;
+    let level = RST_HEADING_CHARS.iter().position(|c| Some(*c) == adornment(index + 1))? + 1;
+    (level > 1 && is_title(line)).then_some((level, line))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::OutputFormat;
+
+    #[test]
+    fn inline_markup_follows_each_format() {
+        let expected = [
+            (OutputFormat::Markdown, ["_x_", "**x**", "`x`", "<!-- x -->"]),
+            (OutputFormat::Adoc, ["_x_", "*x*", "`+x+`", "// x"]),
+            (OutputFormat::Org, ["/x/", "*x*", "=x=", "# x"]),
+            (OutputFormat::Rst, ["*x*", "**x**", "``x``", ".. x"]),
+        ];
+        for (format, [emphasis, strong, inline_code, comment]) in expected {
+            let formatter = format.formatter();
+            assert_eq!(formatter.emphasis("x"), emphasis, "{:?}", format);
+            assert_eq!(formatter.strong("x"), strong, "{:?}", format);
+            assert_eq!(formatter.inline_code("x"), inline_code, "{:?}", format);
+            assert_eq!(formatter.comment("x"), comment, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn headings_are_found_again_and_never_inside_code_blocks() {
+        for format in OutputFormat::ALL {
+            let formatter = format.formatter();
+            for level in 1..=3 {
+                let text = format!(
+                    "{}\n\n{}\n\n{}\n",
+                    formatter.heading("Title", level),
+                    formatter.code_block("text", &formatter.heading("Quoted", level)),
+                    formatter.emphasis("2 lines, ~4 tokens"),
+                );
+                let lines: Vec<&str> = text.split_inclusive('\n').collect();
+                let headings: Vec<(usize, &str)> = formatter.headings(&lines).into_iter().flatten().collect();
+                assert_eq!(headings.len(), 1, "{:?} level {}: {:?}", format, level, headings);
+                assert_eq!((headings[0].0, headings[0].1.trim()), (level, "Title"), "{:?}", format);
+            }
+        }
+    }
+}
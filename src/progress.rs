@@ -0,0 +1,91 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use serde::Serialize;
+
+use crate::budget::estimate_tokens;
+use crate::error::Result;
+use crate::events::AppEvent;
+use crate::file_handler::{FileHandler, FileNode};
+
+/// A progress event of the headless modes, one JSON object per line on stderr with `--progress json`, e.g.
+/// `{"event":"generation_done","path":"/repo/context.md","bytes":51234,"tokens":12808}`
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent<'a> {
+    ScanStarted { directory: &'a Path },
+    ScanProgress { directory: &'a Path, entries: usize },
+    ScanDone { directory: &'a Path, files: usize },
+    GenerationStarted { directory: &'a Path, files: usize },
+    /// `path` is `None` when the document went to stdout
+    GenerationDone { directory: &'a Path, path: Option<&'a Path>, bytes: u64, tokens: usize },
+    Error { directory: Option<&'a Path>, message: String },
+}
+
+/// Emits progress events when enabled, otherwise does nothing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProgressReporter {
+    json: bool,
+}
+
+impl ProgressReporter {
+    /// Parses the value of `--progress`; only `json` is known
+    pub fn from_option(value: &str) -> Option<Self> {
+        (value == "json").then_some(Self { json: true })
+    }
+
+    pub fn emit(&self, event: ProgressEvent) {
+        if !self.json {
+            return;
+        }
+        if let Ok(line) = serde_json::to_string(&event) {
+            // Written in one call so lines of concurrent writers don't interleave
+            let _ = std::io::stderr().write_all(format!("{}\n", line).as_bytes());
+        }
+    }
+
+    /// Scans with the handler, reporting its start, progress and result
+    pub fn scan(&self, directory: &Path, handler: FileHandler, ignore_patterns: Vec<String>) -> Result<FileNode> {
+        self.emit(ProgressEvent::ScanStarted { directory });
+        if !self.json {
+            return handler.scan_directory(ignore_patterns);
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let reporter = *self;
+        let progress_directory = directory.to_path_buf();
+        let forwarder = thread::spawn(move || {
+            for event in receiver {
+                if let AppEvent::ScanProgress(entries) = event {
+                    reporter.emit(ProgressEvent::ScanProgress { directory: &progress_directory, entries });
+                }
+            }
+        });
+        // The handler owns the sender, so the forwarder ends with the scan
+        let result = handler.with_progress_sender(sender).scan_directory(ignore_patterns);
+        let _ = forwarder.join();
+
+        if let Ok(root_node) = &result {
+            self.emit(ProgressEvent::ScanDone { directory, files: root_node.file_paths().len() });
+        }
+        result
+    }
+
+    /// Reports the written document (all parts of split output) with its size and token estimate
+    pub fn generation_done(&self, directory: &Path, output_path: &Path, document_paths: &[PathBuf]) {
+        if !self.json {
+            return;
+        }
+        let contents: Vec<String> = document_paths.iter()
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .collect();
+        self.emit(ProgressEvent::GenerationDone {
+            directory,
+            path: Some(output_path),
+            bytes: contents.iter().map(|content| content.len() as u64).sum(),
+            tokens: contents.iter().map(|content| estimate_tokens(content)).sum(),
+        });
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::APP_DATA_DIR_NAME;
+use crate::error::{AppError, Result};
+use crate::utils::write_file_atomically;
+
+/// Settings remembered per project directory, stored in the OS config directory
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectSettings {
+    /// Free text inserted at the top of the generated document (e.g. instructions for the reader)
+    pub custom_header: String,
+    /// User-defined file groups, keyed by path relative to the project directory
+    pub file_tags: BTreeMap<PathBuf, BTreeSet<String>>,
+    /// Free text (error logs, stack traces, notes) included as a pseudo-file section
+    pub scratch_text: String,
+    /// File name suffixes left out of every scan of this project, e.g. `snap`, `lock` or `min.js`
+    pub excluded_extensions: BTreeSet<String>,
+    /// Files outside the project directory included in the document, by absolute path
+    pub external_files: BTreeSet<PathBuf>,
+    /// The setup suggestions for the detected project type were dismissed
+    pub setup_banner_dismissed: bool,
+    /// Whether the project's `.contextbuilder.toml` may be used; `None` until the trust prompt was answered
+    pub workspace_trusted: Option<bool>,
+}
+
+impl ProjectSettings {
+    /// Returns the saved settings, or defaults if there are none or they can't be read
+    pub fn load(directory: &Path) -> Self {
+        let Some(settings_path) = Self::settings_path(directory) else {
+            return Self::default();
+        };
+        let Ok(bytes) = fs::read(&settings_path) else {
+            return Self::default();
+        };
+
+        serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            debug!("Ignoring unreadable project settings {:?}: {}", settings_path, e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, directory: &Path) -> Result<()> {
+        let settings_path = Self::settings_path(directory).ok_or_else(|| AppError::SettingsError {
+            path: directory.to_path_buf(),
+            details: "No config directory available on this platform".to_string(),
+        })?;
+        let bytes = serde_json::to_vec_pretty(self).map_err(|e| AppError::SettingsError {
+            path: settings_path.clone(),
+            details: format!("Failed to serialize project settings: {}", e),
+        })?;
+
+        write_file_atomically(&settings_path, &bytes)?;
+        debug!("Saved project settings to {:?}", settings_path);
+        Ok(())
+    }
+
+    /// Ignore patterns for the excluded extensions, applied on top of .gitignore and the global patterns
+    pub fn extension_ignore_patterns(&self) -> Vec<String> {
+        excluded_extension_patterns(&self.excluded_extensions)
+    }
+
+    fn settings_path(directory: &Path) -> Option<PathBuf> {
+        let mut hasher = DefaultHasher::new();
+        directory.hash(&mut hasher);
+
+        Some(dirs::config_dir()?
+            .join(APP_DATA_DIR_NAME)
+            .join("projects")
+            .join(format!("{:016x}.json", hasher.finish())))
+    }
+}
+
+/// `*.snap` for `snap`
+pub fn excluded_extension_patterns(extensions: &BTreeSet<String>) -> Vec<String> {
+    extensions.iter().map(|extension| format!("*.{}", extension)).collect()
+}
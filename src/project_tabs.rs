@@ -0,0 +1,216 @@
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+use egui::Context;
+use log::{error, info, warn};
+
+use crate::app::ContextBuilderApp;
+use crate::constants::SHUTDOWN_WORKER_TIMEOUT;
+use crate::crash_report::{set_state_summary, take_last_run_report, take_session_report};
+use crate::external_open::{open_in_editor, reveal_in_file_manager};
+use crate::http_api::HttpApi;
+use crate::log_panel::{LogEntry, LogPanel};
+use crate::temp_files::remove_own_temp_files;
+
+// Background tabs have no input to wake the UI, so their events are polled at this interval
+const BACKGROUND_TAB_POLL_INTERVAL: Duration = Duration::from_secs(1);
+// Records logged by worker threads don't wake the UI either
+const LOG_PANEL_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The desktop window: one `ContextBuilderApp` per open project, shown as tabs. Each tab has
+/// its own watcher, tree, selection and output path; the settings are shared.
+pub struct ProjectTabs {
+    tabs: Vec<ContextBuilderApp>,
+    active: usize,
+    log_panel: LogPanel,
+    crash_report: Option<(PathBuf, bool)>, // Report to offer, and whether it is from this session
+    http_api: Option<HttpApi>,
+    http_api_failed_port: Option<u16>, // Not retried until the port setting changes
+}
+
+impl ProjectTabs {
+    pub fn new(cc: &eframe::CreationContext<'_>, log_receiver: Receiver<LogEntry>) -> Self {
+        Self {
+            tabs: vec![ContextBuilderApp::new(cc)],
+            active: 0,
+            log_panel: LogPanel::new(log_receiver),
+            crash_report: take_last_run_report().map(|report_path| (report_path, false)),
+            http_api: None,
+            http_api_failed_port: None,
+        }
+    }
+
+    fn render_tab_bar(&mut self, ctx: &Context) {
+        let mut close_tab = None;
+        egui::TopBottomPanel::top("project_tabs").show(ctx, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for (index, tab) in self.tabs.iter().enumerate() {
+                    let title = match tab.is_busy() {
+                        true => format!("● {}", tab.tab_title()),
+                        false => tab.tab_title(),
+                    };
+                    if ui.selectable_label(index == self.active, title).clicked() {
+                        self.active = index;
+                    }
+                    if self.tabs.len() > 1 && ui.small_button("×").on_hover_text("Close this project").clicked() {
+                        close_tab = Some(index);
+                    }
+                    ui.separator();
+                }
+                if ui.small_button("➕").on_hover_text("Open another project in a new tab").clicked() {
+                    let settings = self.tabs[self.active].settings().clone();
+                    self.tabs.push(ContextBuilderApp::new_tab(settings));
+                    self.active = self.tabs.len() - 1;
+                }
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let problems = self.log_panel.problem_count();
+                    let label = match problems {
+                        0 => "📜 Log".to_string(),
+                        count => format!("📜 Log ({})", count),
+                    };
+                    if ui.selectable_label(self.log_panel.open, label)
+                        .on_hover_text("Show the app's log records, e.g. to diagnose watcher or scan problems")
+                        .clicked()
+                    {
+                        self.log_panel.open = !self.log_panel.open;
+                    }
+                });
+            });
+        });
+
+        if let Some(index) = close_tab {
+            info!("Closing project tab {}", self.tabs[index].tab_title());
+            self.tabs.remove(index).close();
+            if self.active > index || self.active == self.tabs.len() {
+                self.active -= 1;
+            }
+        }
+    }
+
+    /// Starts, restarts or stops the local HTTP API as the settings say, and answers its
+    /// requests from the active tab
+    fn update_http_api(&mut self, ctx: &Context) {
+        let settings = self.tabs[self.active].settings();
+        let port = settings.http_api_enabled.then_some(settings.http_api_port);
+        if port.is_none() {
+            self.http_api_failed_port = None;
+        }
+        if self.http_api.as_ref().map(HttpApi::port) != port {
+            self.http_api = None; // Stops the previous server
+            if self.http_api_failed_port != port {
+                if let Some(port) = port {
+                    match HttpApi::start(port, ctx.clone()) {
+                        Ok(http_api) => self.http_api = Some(http_api),
+                        Err(e) => {
+                            error!("Local API not started: {}", e);
+                            self.http_api_failed_port = Some(port);
+                        }
+                    }
+                }
+            }
+        }
+
+        let Some(http_api) = &self.http_api else { return };
+        for pending in http_api.take_requests() {
+            let reply = self.tabs[self.active].answer_api_request(pending.request);
+            pending.reply(reply);
+        }
+    }
+
+    /// Offers to open the crash report of the last run or of a background thread that panicked
+    fn render_crash_report_dialog(&mut self, ctx: &Context) {
+        if let Some(report_path) = take_session_report() {
+            self.crash_report = Some((report_path, true));
+        }
+        let Some((report_path, this_session)) = &self.crash_report else { return };
+
+        let mut close = false;
+        egui::Window::new("Crash report")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                if *this_session {
+                    ui.label("A background task crashed. File monitoring or document updates may have stopped; reopening the project restarts them.");
+                } else {
+                    ui.label("Context Builder crashed the last time it ran.");
+                }
+                ui.label("The crash report has the panic message, a backtrace and what the app was doing:");
+                ui.monospace(report_path.display().to_string());
+                ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Open report").clicked() {
+                        let editor_command = self.tabs[self.active].settings().editor_command.clone();
+                        if let Err(e) = open_in_editor(report_path, &editor_command) {
+                            warn!("Failed to open the crash report: {}", e);
+                        }
+                    }
+                    if ui.button("Show in folder").clicked() {
+                        if let Err(e) = reveal_in_file_manager(report_path) {
+                            warn!("Failed to show the crash report: {}", e);
+                        }
+                    }
+                    if ui.button("Dismiss").clicked() {
+                        close = true;
+                    }
+                });
+            });
+        if close {
+            self.crash_report = None;
+        }
+    }
+}
+
+impl eframe::App for ProjectTabs {
+    fn update(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
+        self.log_panel.receive();
+        self.render_tab_bar(ctx);
+        // Before the tab's central panel, which takes the remaining space
+        self.log_panel.render(ctx);
+        if self.log_panel.open {
+            ctx.request_repaint_after(LOG_PANEL_POLL_INTERVAL);
+        }
+
+        let active = self.active;
+        for (index, tab) in self.tabs.iter_mut().enumerate() {
+            if index != active {
+                tab.update_in_background();
+            }
+        }
+        if self.tabs.iter().enumerate().any(|(index, tab)| index != active && tab.is_busy()) {
+            ctx.request_repaint_after(BACKGROUND_TAB_POLL_INTERVAL);
+        }
+
+        let settings_before = self.tabs[active].settings().clone();
+        eframe::App::update(&mut self.tabs[active], ctx, frame);
+        let settings = self.tabs[active].settings();
+        if *settings != settings_before {
+            let settings = settings.clone();
+            for (index, tab) in self.tabs.iter_mut().enumerate() {
+                if index != active {
+                    tab.set_settings(settings.clone());
+                }
+            }
+        }
+
+        self.update_http_api(ctx);
+        self.render_crash_report_dialog(ctx);
+        let state_summary: Vec<String> = self.tabs.iter().map(ContextBuilderApp::state_summary).collect();
+        set_state_summary(state_summary.join("\n"));
+    }
+
+    /// Cancels scans and waits a few seconds for writes in flight, so closing the window doesn't
+    /// leave temp files or half-written documents behind
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.http_api = None;
+        let deadline = Instant::now() + SHUTDOWN_WORKER_TIMEOUT;
+        let mut finished = true;
+        for tab in &mut self.tabs {
+            finished &= tab.shutdown(deadline); // Every tab, also after one ran out of time
+        }
+        if !finished {
+            warn!("Exiting with writes still running after {:?}", SHUTDOWN_WORKER_TIMEOUT);
+        }
+        remove_own_temp_files();
+    }
+}
@@ -0,0 +1,69 @@
+use std::path::Path;
+
+/// A kind of project, recognized by its manifest in the project's root directory
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProjectType {
+    Rust,
+    Node,
+    Python,
+    Go,
+}
+
+impl ProjectType {
+    const ALL: [ProjectType; 4] = [ProjectType::Rust, ProjectType::Node, ProjectType::Python, ProjectType::Go];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ProjectType::Rust => "Rust",
+            ProjectType::Node => "Node",
+            ProjectType::Python => "Python",
+            ProjectType::Go => "Go",
+        }
+    }
+
+    fn manifests(self) -> &'static [&'static str] {
+        match self {
+            ProjectType::Rust => &["Cargo.toml"],
+            ProjectType::Node => &["package.json"],
+            ProjectType::Python => &["pyproject.toml", "setup.py", "setup.cfg", "requirements.txt", "Pipfile"],
+            ProjectType::Go => &["go.mod"],
+        }
+    }
+
+    /// Build outputs, caches and lock files of the toolchain not covered by the default ignore patterns
+    pub fn ignore_patterns(self) -> &'static [&'static str] {
+        match self {
+            ProjectType::Rust => &["Cargo.lock"],
+            ProjectType::Node => &["coverage/", ".next/", ".turbo/", "pnpm-lock.yaml"],
+            ProjectType::Python => &[".pytest_cache/", ".mypy_cache/", ".ruff_cache/", ".tox/", "*.egg-info/", "poetry.lock"],
+            ProjectType::Go => &["vendor/", "go.sum"],
+        }
+    }
+
+    /// Globs of the sources and manifests, for a first selection
+    pub fn source_patterns(self) -> &'static [&'static str] {
+        match self {
+            ProjectType::Rust => &["*.rs", "Cargo.toml"],
+            ProjectType::Node => &["*.js", "*.jsx", "*.mjs", "*.ts", "*.tsx", "package.json", "tsconfig.json"],
+            ProjectType::Python => &["*.py", "pyproject.toml", "setup.py", "setup.cfg", "requirements.txt"],
+            ProjectType::Go => &["*.go", "go.mod"],
+        }
+    }
+
+    /// Source extensions with signatures-only rendering, for display
+    pub fn outline_extensions(self) -> &'static str {
+        match self {
+            ProjectType::Rust => ".rs",
+            ProjectType::Node => ".js/.ts/.tsx",
+            ProjectType::Python => ".py",
+            ProjectType::Go => ".go",
+        }
+    }
+}
+
+/// The project types whose manifest is in `directory`; several for a mixed project
+pub fn detect_project_types(directory: &Path) -> Vec<ProjectType> {
+    ProjectType::ALL.into_iter()
+        .filter(|project_type| project_type.manifests().iter().any(|manifest| directory.join(manifest).is_file()))
+        .collect()
+}
@@ -0,0 +1,208 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use log::{debug, info};
+
+use crate::constants::{APP_DATA_DIR_NAME, REMOTE_CONNECT_TIMEOUT_SECS};
+use crate::document_generator::is_replaceable_document;
+use crate::error::{AppError, Result};
+
+/// A project directory on a server, given as `[user@]host:path`. It is copied into a local
+/// mirror in the cache directory, which is then scanned, monitored and generated from like any
+/// other directory; fetching again refreshes the mirror.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RemoteSource {
+    pub host: String, // `user@host`, or a host alias from `~/.ssh/config`
+    pub path: String, // Absolute, or relative to the login directory
+}
+
+impl RemoteSource {
+    /// Parses `[user@]host:path`; Windows drive paths (`C:\src`) and paths with a slash before
+    /// the colon are not remote
+    pub fn parse(address: &str) -> Option<Self> {
+        let (host, path) = address.trim().split_once(':')?;
+        let is_drive_letter = host.len() == 1 && host.chars().all(|c| c.is_ascii_alphabetic());
+        if host.is_empty() || path.is_empty() || is_drive_letter || host.starts_with('-') || host.contains(['/', '\\']) {
+            return None;
+        }
+        let path = match path.trim_end_matches('/') {
+            "" => "/",
+            path => path,
+        };
+        Some(Self { host: host.to_string(), path: path.to_string() })
+    }
+
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.path)
+    }
+
+    /// `<cache>/context_builder/remote/<hash>/<last path component>`, so the project keeps its name
+    pub fn mirror_dir(&self) -> Option<PathBuf> {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        let name = self.path.rsplit('/').find(|component| !component.is_empty()).unwrap_or(&self.host);
+
+        Some(dirs::cache_dir()?
+            .join(APP_DATA_DIR_NAME)
+            .join("remote")
+            .join(format!("{:016x}", hasher.finish()))
+            .join(name))
+    }
+
+    /// Copies the remote tree, without `.git`, into the mirror and returns the mirror's path.
+    /// Streams a `tar` archive from the system `ssh` (as the update check uses the system `curl`)
+    /// into the local `tar`, both shipped with Windows 10+, macOS and Linux. Authentication has to
+    /// work without a prompt (an SSH agent or an unencrypted key). The mirror is only touched once
+    /// the whole archive arrived.
+    pub fn fetch(&self) -> Result<PathBuf> {
+        let mirror_dir = self.mirror_dir()
+            .ok_or_else(|| AppError::RemoteSourceError("No cache directory to mirror the remote directory into".to_string()))?;
+        let parent_dir = mirror_dir.parent().unwrap_or(&mirror_dir).to_path_buf();
+        fs::create_dir_all(&parent_dir)
+            .map_err(|e| AppError::new_io_error(e, Some(parent_dir.clone()), "Failed to create the remote mirror directory".to_string()))?;
+        let staging_dir = tempfile::Builder::new()
+            .prefix(".fetch")
+            .tempdir_in(&parent_dir)
+            .map_err(|e| AppError::new_io_error(e, Some(parent_dir.clone()), "Failed to create the remote mirror directory".to_string()))?;
+
+        info!("Fetching {} into {:?}", self.address(), mirror_dir);
+        let mut ssh = Command::new("ssh")
+            .args(["-o", "BatchMode=yes"])
+            .args(["-o", &format!("ConnectTimeout={}", REMOTE_CONNECT_TIMEOUT_SECS)])
+            .arg("--")
+            .arg(&self.host)
+            .arg(format!("tar -C {} --exclude=.git -cf - .", shell_quote(&self.path)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| AppError::new_io_error(e, None, "Failed to run ssh for the remote directory".to_string()))?;
+        // Drained on the side, so a chatty remote can't block the archive stream
+        let ssh_stderr = ssh.stderr.take().map(|mut stderr| thread::spawn(move || {
+            let mut message = String::new();
+            let _ = stderr.read_to_string(&mut message);
+            message
+        }));
+        let archive = ssh.stdout.take().map(Stdio::from).unwrap_or_else(Stdio::null);
+        let tar_output = Command::new("tar")
+            .arg("-xf")
+            .arg("-")
+            .arg("-C")
+            .arg(staging_dir.path())
+            .stdin(archive)
+            .output();
+        let ssh_status = ssh.wait()
+            .map_err(|e| AppError::new_io_error(e, None, "Failed to run ssh for the remote directory".to_string()))?;
+        let ssh_stderr = ssh_stderr.and_then(|handle| handle.join().ok()).unwrap_or_default();
+        let tar_output = tar_output
+            .map_err(|e| AppError::new_io_error(e, None, "Failed to run tar for the remote directory".to_string()))?;
+
+        if !ssh_status.success() {
+            return Err(AppError::RemoteSourceError(format!("{}: {}", self.address(), ssh_stderr.trim())));
+        }
+        if !tar_output.status.success() {
+            return Err(AppError::RemoteSourceError(format!("tar: {}", String::from_utf8_lossy(&tar_output.stderr).trim())));
+        }
+
+        sync_mirror(staging_dir.path(), &mirror_dir)
+            .map_err(|e| AppError::new_io_error(e, Some(mirror_dir.clone()), "Failed to update the remote mirror".to_string()))?;
+        debug!("Mirrored {} into {:?}", self.address(), mirror_dir);
+        Ok(mirror_dir)
+    }
+}
+
+/// Makes `mirror_dir` match `fetched_dir` by moving over the files that differ and removing the
+/// ones the server no longer has. Unchanged files are left alone, so the file monitor sees a
+/// refresh as ordinary edits, and documents generated into the mirror are kept.
+fn sync_mirror(fetched_dir: &Path, mirror_dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(mirror_dir)?;
+    for entry in fs::read_dir(mirror_dir)? {
+        let entry = entry?;
+        let mirror_path = entry.path();
+        let is_dir = entry.file_type()?.is_dir();
+        let keep = match fs::symlink_metadata(fetched_dir.join(entry.file_name())) {
+            Ok(metadata) => metadata.is_dir() == is_dir,
+            Err(_) => !is_dir && entry.metadata()?.len() > 0 && is_replaceable_document(&mirror_path),
+        };
+        if keep {
+            continue;
+        }
+        match is_dir {
+            true => fs::remove_dir_all(&mirror_path)?,
+            false => fs::remove_file(&mirror_path)?,
+        }
+    }
+
+    for entry in fs::read_dir(fetched_dir)? {
+        let entry = entry?;
+        let fetched_path = entry.path();
+        let mirror_path = mirror_dir.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            sync_mirror(&fetched_path, &mirror_path)?;
+        } else if fs::read(&fetched_path).ok() != fs::read(&mirror_path).ok() {
+            fs::rename(&fetched_path, &mirror_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Single-quotes `value` for the remote POSIX shell that runs the ssh command
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_are_parsed_and_local_paths_are_not() {
+        let source = RemoteSource::parse("deploy@build-01:/srv/app/").unwrap();
+        assert_eq!(source, RemoteSource { host: "deploy@build-01".to_string(), path: "/srv/app".to_string() });
+        assert_eq!(source.address(), "deploy@build-01:/srv/app");
+        if let Some(mirror_dir) = source.mirror_dir() {
+            assert!(mirror_dir.ends_with("app"), "{:?}", mirror_dir);
+        }
+        assert_eq!(RemoteSource::parse("server:/").map(|source| source.path), Some("/".to_string()));
+        assert_eq!(RemoteSource::parse("server:projects/api").map(|source| source.path), Some("projects/api".to_string()));
+
+        for local in [r"C:\src\app", "C:/src/app", "./notes:old", "/srv/app", "server:", ":/srv/app", "-oProxyCommand=x:/srv"] {
+            assert_eq!(RemoteSource::parse(local), None, "{}", local);
+        }
+    }
+
+    #[test]
+    fn mirrors_take_changes_and_keep_generated_documents() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mirror_dir = temp_dir.path().join("mirror");
+        fs::create_dir_all(mirror_dir.join("src/old")).unwrap();
+        fs::write(mirror_dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(mirror_dir.join("src/lib.rs"), "pub fn lib() {}\n").unwrap();
+        fs::write(mirror_dir.join("src/old/gone.rs"), "// Deleted on the server\n").unwrap();
+        fs::write(mirror_dir.join("context.md"), "# Context\n\n<!-- Generated by context_builder -->\n").unwrap();
+        let unchanged_modified = fs::metadata(mirror_dir.join("src/lib.rs")).unwrap().modified().unwrap();
+
+        let fetched_dir = temp_dir.path().join("fetched");
+        fs::create_dir_all(fetched_dir.join("src")).unwrap();
+        fs::write(fetched_dir.join("src/main.rs"), "fn main() { run(); }\n").unwrap();
+        fs::write(fetched_dir.join("src/lib.rs"), "pub fn lib() {}\n").unwrap();
+        fs::write(fetched_dir.join("README.md"), "# App\n").unwrap();
+
+        sync_mirror(&fetched_dir, &mirror_dir).unwrap();
+        assert_eq!(fs::read_to_string(mirror_dir.join("src/main.rs")).unwrap(), "fn main() { run(); }\n");
+        assert_eq!(fs::read_to_string(mirror_dir.join("README.md")).unwrap(), "# App\n");
+        assert_eq!(fs::metadata(mirror_dir.join("src/lib.rs")).unwrap().modified().unwrap(), unchanged_modified);
+        assert!(!mirror_dir.join("src/old").exists());
+        assert!(mirror_dir.join("context.md").exists());
+    }
+
+    #[test]
+    fn remote_paths_are_quoted_for_the_shell() {
+        assert_eq!(shell_quote("/srv/my app"), "'/srv/my app'");
+        assert_eq!(shell_quote("/srv/it's"), r"'/srv/it'\''s'");
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{APP_DATA_DIR_NAME, SCAN_CACHE_FORMAT_VERSION};
+use crate::error::{AppError, Result};
+use crate::file_handler::FileNode;
+use crate::temp_files::create_temp_file_in;
+
+#[derive(Serialize, Deserialize)]
+struct ScanCacheFile {
+    format_version: u32,
+    root: PathBuf,
+    ignore_settings_hash: u64,
+    scanned_at_secs: u64,
+    root_node: FileNode,
+}
+
+/// On-disk cache of a scanned tree, keyed by project directory and ignore settings.
+/// A cache hit lets the UI show the tree immediately while a validating scan runs.
+pub struct ScanCache {
+    cache_path: PathBuf,
+    ignore_settings_hash: u64,
+}
+
+impl ScanCache {
+    /// Returns `None` if the platform has no cache directory
+    pub fn new(directory: &Path, ignore_patterns: &[String], exclude_generated: bool) -> Option<Self> {
+        let mut dir_hasher = DefaultHasher::new();
+        directory.hash(&mut dir_hasher);
+
+        let mut ignore_hasher = DefaultHasher::new();
+        SCAN_CACHE_FORMAT_VERSION.hash(&mut ignore_hasher);
+        ignore_patterns.hash(&mut ignore_hasher);
+        exclude_generated.hash(&mut ignore_hasher);
+
+        let cache_path = dirs::cache_dir()?
+            .join(APP_DATA_DIR_NAME)
+            .join("scans")
+            .join(format!("{:016x}.json", dir_hasher.finish()));
+
+        Some(Self {
+            cache_path,
+            ignore_settings_hash: ignore_hasher.finish(),
+        })
+    }
+
+    /// Loads the cached tree if it exists and matches the directory and ignore settings
+    pub fn load(&self, directory: &Path) -> Option<FileNode> {
+        let bytes = fs::read(&self.cache_path).ok()?;
+        let cache_file: ScanCacheFile = match serde_json::from_slice(&bytes) {
+            Ok(cache_file) => cache_file,
+            Err(e) => {
+                debug!("Ignoring unreadable scan cache {:?}: {}", self.cache_path, e);
+                return None;
+            }
+        };
+
+        if cache_file.format_version != SCAN_CACHE_FORMAT_VERSION
+            || cache_file.root != directory
+            || cache_file.ignore_settings_hash != self.ignore_settings_hash
+        {
+            debug!("Scan cache {:?} is stale (version, root or ignore settings changed)", self.cache_path);
+            return None;
+        }
+
+        debug!("Loaded scan cache for {:?} (scanned at {})", directory, cache_file.scanned_at_secs);
+        Some(cache_file.root_node)
+    }
+
+    pub fn save(&self, directory: &Path, root_node: &FileNode) -> Result<()> {
+        let cache_dir = self.cache_path.parent().ok_or_else(|| AppError::CacheError {
+            path: self.cache_path.clone(),
+            details: "Cache path has no parent directory".to_string(),
+        })?;
+        fs::create_dir_all(cache_dir)
+            .map_err(|e| AppError::new_io_error(e, Some(cache_dir.to_path_buf()), "Failed to create cache directory".to_string()))?;
+
+        let cache_file = ScanCacheFile {
+            format_version: SCAN_CACHE_FORMAT_VERSION,
+            root: directory.to_path_buf(),
+            ignore_settings_hash: self.ignore_settings_hash,
+            scanned_at_secs: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default(),
+            root_node: root_node.clone(),
+        };
+        let bytes = serde_json::to_vec(&cache_file).map_err(|e| AppError::CacheError {
+            path: self.cache_path.clone(),
+            details: format!("Failed to serialize scan cache: {}", e),
+        })?;
+
+        // Write atomically so a crash never leaves a half-written cache behind
+        let mut temp_file = create_temp_file_in(cache_dir)?;
+        temp_file.write_all(&bytes)
+            .map_err(|e| AppError::new_io_error(e, Some(temp_file.path().to_path_buf()), "Failed to write scan cache".to_string()))?;
+        temp_file.persist(&self.cache_path).map_err(|e| AppError::CacheError {
+            path: self.cache_path.clone(),
+            details: format!("Failed to persist scan cache: {}", e.error),
+        })?;
+
+        debug!("Saved scan cache to {:?}", self.cache_path);
+        Ok(())
+    }
+}
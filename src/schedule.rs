@@ -0,0 +1,36 @@
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Days, Local, NaiveTime};
+
+/// Parses comma-separated `HH:MM` times of day, e.g. `09:00, 13:30`
+pub fn parse_schedule_times(text: &str) -> Result<Vec<NaiveTime>, String> {
+    let mut times: Vec<NaiveTime> = text.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| NaiveTime::parse_from_str(entry, "%H:%M")
+            .map_err(|_| format!("'{}' is not a time like 09:00", entry)))
+        .collect::<Result<_, _>>()?;
+    times.sort();
+    times.dedup();
+    Ok(times)
+}
+
+/// The first scheduled time after `after`, today or tomorrow
+pub fn next_scheduled_time(times: &[NaiveTime], after: DateTime<Local>) -> Option<DateTime<Local>> {
+    let today = after.date_naive();
+    let tomorrow = today.checked_add_days(Days::new(1))?;
+    [today, tomorrow].into_iter()
+        .flat_map(|date| times.iter().map(move |time| date.and_time(*time)))
+        // Times skipped by a daylight saving change don't exist locally
+        .filter_map(|naive| naive.and_local_timezone(Local).earliest())
+        .find(|scheduled| *scheduled > after)
+}
+
+/// `project_structure.md` becomes `project_structure-20240301-0900.md`
+pub fn timestamped_output_path(output_path: &Path, at: DateTime<Local>) -> PathBuf {
+    let stem = output_path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let file_name = match output_path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, at.format("%Y%m%d-%H%M"), extension.to_string_lossy()),
+        None => format!("{}-{}", stem, at.format("%Y%m%d-%H%M")),
+    };
+    output_path.with_file_name(file_name)
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use log::debug;
+
+use crate::budget::{FileSizeLimits, LineRange};
+use crate::constants::{OutputFormat, SECTION_CACHE_MAX_BYTES};
+
+/// Everything besides the file content that affects how a section is rendered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SectionOptions {
+    pub format: OutputFormat,
+    pub file_size_limits: FileSizeLimits,
+    pub footer: bool,
+    pub minified: bool,
+    pub outline: bool,
+    pub line_range: Option<LineRange>,
+    pub notebook_markdown: bool,
+    pub fence_languages: u64, // Hash of the user's fence language patterns
+    pub chars_per_token: usize, // Token counts and footers depend on the estimate
+}
+
+struct CachedSection {
+    content_hash: blake3::Hash,
+    options: SectionOptions,
+    section: String,
+    content_tokens: usize,
+}
+
+/// Rendered file sections from earlier generations, reused while a file's content hash and the
+/// rendering options are unchanged. Shared between the generation threads of one project.
+#[derive(Default)]
+pub struct SectionCache {
+    directory: PathBuf,
+    sections: HashMap<(PathBuf, OutputFormat), CachedSection>, // Formats written side by side are cached separately
+    total_bytes: usize,
+}
+
+impl SectionCache {
+    /// Drops everything cached for a different project directory
+    pub fn use_directory(&mut self, directory: &Path) {
+        if self.directory != directory {
+            self.directory = directory.to_path_buf();
+            self.sections.clear();
+            self.total_bytes = 0;
+        }
+    }
+
+    /// The cached section and token estimate of the file's content, if still valid
+    pub fn get(
+        &self,
+        file_path: &Path,
+        content_hash: &blake3::Hash,
+        options: &SectionOptions,
+    ) -> Option<(String, usize)> {
+        self.sections.get(&(file_path.to_path_buf(), options.format))
+            .filter(|cached| cached.content_hash == *content_hash && cached.options == *options)
+            .map(|cached| (cached.section.clone(), cached.content_tokens))
+    }
+
+    /// The section last rendered for the file, whatever its content and options were
+    pub fn last_section(&self, file_path: &Path, format: OutputFormat) -> Option<String> {
+        self.sections.get(&(file_path.to_path_buf(), format)).map(|cached| cached.section.clone())
+    }
+
+    /// Remembers a rendered section. If the cache is full, the file's outdated section is dropped instead.
+    pub fn insert(
+        &mut self,
+        file_path: &Path,
+        content_hash: blake3::Hash,
+        options: SectionOptions,
+        section: &str,
+        content_tokens: usize,
+    ) {
+        let key = (file_path.to_path_buf(), options.format);
+        if let Some(outdated) = self.sections.remove(&key) {
+            self.total_bytes -= outdated.section.len();
+        }
+        if self.total_bytes + section.len() > SECTION_CACHE_MAX_BYTES {
+            debug!("Section cache is full, not caching {:?}", file_path);
+            return;
+        }
+
+        self.total_bytes += section.len();
+        self.sections.insert(key, CachedSection {
+            content_hash,
+            options,
+            section: section.to_string(),
+            content_tokens,
+        });
+    }
+}
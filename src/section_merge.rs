@@ -0,0 +1,92 @@
+use similar::{capture_diff_slices, Algorithm, DiffTag};
+
+/// A file section merged from the user's edits and a re-rendered section
+pub struct MergedSection {
+    pub text: String,
+    pub conflicts: usize, // Regions both sides changed differently
+}
+
+/// One side's change to the base: base lines `start..end` replaced by `lines`
+struct Change<'a> {
+    start: usize,
+    end: usize,
+    lines: Vec<&'a str>,
+}
+
+/// Line-based three-way merge of a section: `base` is the section as it was last generated,
+/// `ours` the section in the document (possibly annotated by hand) and `theirs` the newly
+/// generated one. Where both changed the same lines, the generated lines win and the lines the
+/// user added there are kept right after them.
+pub fn merge_sections(base: &str, ours: &str, theirs: &str) -> MergedSection {
+    let base_lines: Vec<&str> = base.lines().collect();
+    let our_lines: Vec<&str> = ours.lines().collect();
+    let their_lines: Vec<&str> = theirs.lines().collect();
+    let our_changes = changes(&base_lines, &our_lines);
+    let their_changes = changes(&base_lines, &their_lines);
+
+    let mut merged: Vec<&str> = Vec::new();
+    let mut conflicts = 0;
+    let mut position = 0; // Base lines before it are merged
+    let (mut i, mut j) = (0, 0);
+    while let Some(start) = [our_changes.get(i), their_changes.get(j)].into_iter().flatten().map(|change| change.start).min() {
+        // Gather the changes of both sides that touch the region, until it stops growing
+        let (first_ours, first_theirs) = (i, j);
+        let mut end = start;
+        loop {
+            if let Some(change) = our_changes.get(i).filter(|change| change.start <= end) {
+                end = end.max(change.end);
+                i += 1;
+            } else if let Some(change) = their_changes.get(j).filter(|change| change.start <= end) {
+                end = end.max(change.end);
+                j += 1;
+            } else {
+                break;
+            }
+        }
+
+        merged.extend(&base_lines[position..start]);
+        let our_version = apply_changes(&base_lines, start, end, &our_changes[first_ours..i]);
+        let their_version = apply_changes(&base_lines, start, end, &their_changes[first_theirs..j]);
+        if first_theirs == j || our_version == their_version {
+            merged.extend(our_version);
+        } else if first_ours == i {
+            merged.extend(their_version);
+        } else {
+            conflicts += 1;
+            let base_region = &base_lines[start..end];
+            let added: Vec<&str> = our_version.iter()
+                .filter(|line| !base_region.contains(line) && !their_version.contains(line))
+                .copied()
+                .collect();
+            merged.extend(their_version);
+            merged.extend(added);
+        }
+        position = end;
+    }
+    merged.extend(&base_lines[position..]);
+
+    MergedSection { text: merged.join("\n"), conflicts }
+}
+
+fn changes<'a>(base: &[&str], side: &[&'a str]) -> Vec<Change<'a>> {
+    capture_diff_slices(Algorithm::Myers, base, side).iter()
+        .filter(|op| op.tag() != DiffTag::Equal)
+        .map(|op| {
+            let (_, base_range, side_range) = op.as_tag_tuple();
+            Change { start: base_range.start, end: base_range.end, lines: side[side_range].to_vec() }
+        })
+        .collect()
+}
+
+/// Base lines `start..end` with one side's changes in that region applied
+fn apply_changes<'a>(base: &[&'a str], start: usize, end: usize, changes: &[Change<'a>]) -> Vec<&'a str> {
+    let mut lines = Vec::new();
+    let mut position = start;
+    for change in changes {
+        lines.extend(&base[position..change.start]);
+        lines.extend(&change.lines);
+        position = change.end;
+    }
+    lines.extend(&base[position..end]);
+    lines
+}
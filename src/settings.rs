@@ -0,0 +1,325 @@
+use std::fs;
+use std::path::PathBuf;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::budget::FileSizeLimits;
+use crate::constants::{
+    APP_DATA_DIR_NAME, BUILTIN_SELECTION_PRESETS, DEFAULT_AUTO_REGENERATION_LIMIT_BYTES, DEFAULT_CHUNK_TOKEN_LIMIT, DEFAULT_DEBOUNCE_CHECK_INTERVAL_MS,
+    DEFAULT_DEBOUNCE_MS, DEFAULT_GROWTH_ALERT_PERCENT, DEFAULT_HISTORY_LENGTH, DEFAULT_HTTP_API_PORT, DEFAULT_IGNORE_PATTERNS, DEFAULT_MINIFIED_EXTENSIONS, DEFAULT_OUTPUT_FORMAT, DEFAULT_POLL_INTERVAL_SECS,
+    DEFAULT_SCHEDULED_GENERATION_TIMES, DEFAULT_TOKEN_BUDGET, CONSERVATIVE_CHARS_PER_TOKEN, ESTIMATED_CHARS_PER_TOKEN, OutputFormat
+};
+use crate::error::{AppError, Result};
+use crate::fence_language::parse_fence_languages;
+use crate::llm_chat::{LlmEndpoint, LlmProvider};
+use crate::utils::write_file_atomically;
+
+/// A named group of globs for bulk selection, e.g. `Frontend` = `web/**/*.ts, web/**/*.css`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SelectionPreset {
+    pub name: String,
+    pub patterns: String, // Comma-separated, gitignore glob syntax
+}
+
+impl SelectionPreset {
+    pub fn pattern_list(&self) -> Vec<&str> {
+        self.patterns.split(',')
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .collect()
+    }
+
+    pub fn builtin() -> Vec<SelectionPreset> {
+        BUILTIN_SELECTION_PRESETS.iter()
+            .map(|(name, patterns)| SelectionPreset { name: name.to_string(), patterns: patterns.to_string() })
+            .collect()
+    }
+}
+
+/// How token counts are estimated. No tokenizer is bundled, so both are character ratios.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TokenEstimator {
+    #[default]
+    Standard,     // ~4 characters per token, close for English and typical source code
+    Conservative, // ~3 characters per token, for dense code or non-English text
+}
+
+impl TokenEstimator {
+    pub const ALL: [TokenEstimator; 2] = [TokenEstimator::Standard, TokenEstimator::Conservative];
+
+    pub fn chars_per_token(&self) -> usize {
+        match self {
+            TokenEstimator::Standard => ESTIMATED_CHARS_PER_TOKEN,
+            TokenEstimator::Conservative => CONSERVATIVE_CHARS_PER_TOKEN,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenEstimator::Standard => "Standard (~4 characters per token)",
+            TokenEstimator::Conservative => "Conservative (~3 characters per token)",
+        }
+    }
+}
+
+/// Color theme of the desktop UI
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThemePreference {
+    #[default]
+    System, // Follows the OS where it reports its theme, dark otherwise
+    Dark,
+    Light,
+}
+
+impl ThemePreference {
+    pub const ALL: [ThemePreference; 3] = [ThemePreference::System, ThemePreference::Dark, ThemePreference::Light];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThemePreference::System => "System",
+            ThemePreference::Dark => "Dark",
+            ThemePreference::Light => "Light",
+        }
+    }
+}
+
+/// Arrangement of the main window of the desktop UI
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PanelLayout {
+    #[default]
+    Stacked, // One scrolling column, for small screens
+    Columns, // The file tree in a resizable panel of full height next to the other sections
+}
+
+impl PanelLayout {
+    pub const ALL: [PanelLayout; 2] = [PanelLayout::Stacked, PanelLayout::Columns];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PanelLayout::Stacked => "Stacked",
+            PanelLayout::Columns => "Columns",
+        }
+    }
+}
+
+/// Every user-tunable option that is not specific to a project. Kept free of UI types so
+/// that any front end can load and apply the same settings file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    // Output
+    pub output_format: OutputFormat,
+    pub custom_template_path: Option<PathBuf>, // None means the built-in layout
+    pub anchor_links_enabled: bool,            // Markdown structure links and table of contents
+    pub section_footers_enabled: bool,         // Line and token counts below each file section
+    pub metadata_header_enabled: bool,         // Metadata comment block below the document title
+    pub language_summary_enabled: bool,        // Summary section with the languages of the selection
+    pub fence_languages: String,               // `pattern = language` lines, e.g. `*.tpl = html`
+    pub structure_section_enabled: bool,       // Project Structure section
+    pub full_tree_structure_enabled: bool,     // Structure shows the whole tree, not just the selection
+    pub structure_max_depth: Option<usize>,    // None means the whole depth
+    pub structure_max_entries: Option<usize>,  // Per directory; None means all entries
+    pub structure_annotations_enabled: bool,   // Structure entries show their size and age
+    pub files_section_enabled: bool,           // Files section with the file contents
+    pub split_output_enabled: bool,
+    pub max_tokens_per_chunk: usize,
+    pub snapshot_mode_enabled: bool, // Generate from contents captured at selection time
+    pub dependency_sources_enabled: bool, // Append cargo registry sources of the crates selected Rust files use
+    pub all_formats_enabled: bool,        // Also write the document in the other formats, next to the output path
+    pub adoc_includes_enabled: bool,      // AsciiDoc sections use `include::` directives instead of file contents
+    pub markdown_links_enabled: bool,     // Markdown sections link to the files instead of embedding them
+    pub markdown_line_anchors_enabled: bool,
+    pub document_index_enabled: bool,     // Write `<name>.index.json` with the offsets of each file section
+    pub compressed_copy_enabled: bool,    // Keep a gzip copy `<name>.md.gz` next to the document
+    pub history_length: usize,            // Full generations kept in the generation history
+    pub history_copies_enabled: bool,     // Also keep copies of the documents, for section diffs
+
+    // Budget & limits
+    pub token_budget_enabled: bool,
+    pub max_token_budget: usize,
+    pub token_estimator: TokenEstimator,
+    pub file_size_limits: FileSizeLimits,
+    pub minify_enabled: bool,       // Strip comments and collapse blank lines of the listed file types
+    pub minified_extensions: String, // Comma-separated, e.g. `rs, py`
+    pub exclude_binary_files: bool,  // Otherwise selected binary files are listed with their size
+    pub notebook_markdown_cells: bool, // Jupyter notebooks also show their Markdown cells, not just code
+
+    // Monitoring
+    pub auto_regeneration_limit_bytes: usize,
+    pub growth_alert_enabled: bool, // Warn when the document grows a lot while monitoring
+    pub growth_alert_percent: u32,
+    pub force_polling_watcher: bool, // Poll even where native file events are available
+    pub poll_interval_secs: u64,
+    pub debounce_ms: u64,
+    pub debounce_check_interval_ms: u64,
+    pub scheduled_generation_enabled: bool, // Write timestamped copies of the document at fixed times
+    pub scheduled_generation_times: String, // Comma-separated HH:MM, local time
+
+    // Scanning
+    pub ignore_patterns: Vec<String>,
+    pub exclude_generated_files: bool,
+    pub lazy_scan_enabled: bool,   // Scan directory contents only when a directory is expanded
+    pub show_file_sizes: bool,     // Size next to each file and aggregate sizes on directories
+    pub line_counts_enabled: bool, // Reads every text file during the scan
+
+    // Selection
+    pub selection_presets: Vec<SelectionPreset>, // User-defined, shown after the built-in ones
+    pub editor_command: String, // "Open in editor", e.g. `code -g {path}`; blank uses $EDITOR or the OS default
+
+    // Appearance
+    pub theme: ThemePreference,
+    pub panel_layout: PanelLayout,
+
+    // Updates
+    pub update_check_enabled: bool,             // Off by default: checking contacts GitHub
+    pub skipped_update_version: Option<String>, // No banner for this release
+
+    // Local API
+    pub http_api_enabled: bool, // Serve the document and tree on 127.0.0.1
+    pub http_api_port: u16,
+
+    // LLM chat
+    pub llm_provider: LlmProvider,
+    pub llm_endpoint: String, // Empty: the provider's default
+    pub llm_model: String,
+    pub llm_summary_model: String, // For file summaries; empty: `llm_model`
+    pub llm_api_key: String, // Stored in plain text; empty: `$OPENAI_API_KEY`
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            output_format: DEFAULT_OUTPUT_FORMAT,
+            custom_template_path: None,
+            anchor_links_enabled: false,
+            section_footers_enabled: false,
+            metadata_header_enabled: false,
+            language_summary_enabled: false,
+            fence_languages: String::new(),
+            structure_section_enabled: true,
+            full_tree_structure_enabled: false,
+            structure_max_depth: None,
+            structure_max_entries: None,
+            structure_annotations_enabled: false,
+            files_section_enabled: true,
+            split_output_enabled: false,
+            max_tokens_per_chunk: DEFAULT_CHUNK_TOKEN_LIMIT,
+            snapshot_mode_enabled: false,
+            dependency_sources_enabled: false,
+            all_formats_enabled: false,
+            adoc_includes_enabled: false,
+            markdown_links_enabled: false,
+            markdown_line_anchors_enabled: false,
+            document_index_enabled: false,
+            compressed_copy_enabled: false,
+            history_length: DEFAULT_HISTORY_LENGTH,
+            history_copies_enabled: false,
+            token_budget_enabled: false,
+            max_token_budget: DEFAULT_TOKEN_BUDGET,
+            token_estimator: TokenEstimator::default(),
+            file_size_limits: FileSizeLimits::default(),
+            minify_enabled: false,
+            minified_extensions: DEFAULT_MINIFIED_EXTENSIONS.to_string(),
+            exclude_binary_files: false,
+            notebook_markdown_cells: false,
+            auto_regeneration_limit_bytes: DEFAULT_AUTO_REGENERATION_LIMIT_BYTES,
+            growth_alert_enabled: true,
+            growth_alert_percent: DEFAULT_GROWTH_ALERT_PERCENT,
+            force_polling_watcher: false,
+            poll_interval_secs: DEFAULT_POLL_INTERVAL_SECS,
+            debounce_ms: DEFAULT_DEBOUNCE_MS,
+            debounce_check_interval_ms: DEFAULT_DEBOUNCE_CHECK_INTERVAL_MS,
+            scheduled_generation_enabled: false,
+            scheduled_generation_times: DEFAULT_SCHEDULED_GENERATION_TIMES.to_string(),
+            ignore_patterns: DEFAULT_IGNORE_PATTERNS.iter().map(|pattern| pattern.to_string()).collect(),
+            exclude_generated_files: false,
+            lazy_scan_enabled: false,
+            show_file_sizes: true,
+            line_counts_enabled: false,
+            selection_presets: Vec::new(),
+            editor_command: String::new(),
+            theme: ThemePreference::default(),
+            panel_layout: PanelLayout::default(),
+            update_check_enabled: false,
+            skipped_update_version: None,
+            http_api_enabled: false,
+            http_api_port: DEFAULT_HTTP_API_PORT,
+            llm_provider: LlmProvider::default(),
+            llm_endpoint: String::new(),
+            llm_model: String::new(),
+            llm_summary_model: String::new(),
+            llm_api_key: String::new(),
+        }
+    }
+}
+
+impl AppSettings {
+    /// The chat endpoint with the summary model, if one is set
+    pub fn summary_endpoint_config(&self) -> LlmEndpoint {
+        let mut endpoint = self.llm_endpoint_config();
+        if !self.llm_summary_model.trim().is_empty() {
+            endpoint.model = self.llm_summary_model.clone();
+        }
+        endpoint
+    }
+
+    pub fn llm_endpoint_config(&self) -> LlmEndpoint {
+        LlmEndpoint {
+            provider: self.llm_provider,
+            url: self.llm_endpoint.clone(),
+            model: self.llm_model.clone(),
+            api_key: self.llm_api_key.clone(),
+        }
+    }
+
+    /// Lowercase extensions, without dots, of the file types to minify; empty when disabled
+    pub fn minified_extension_list(&self) -> Vec<String> {
+        if !self.minify_enabled {
+            return Vec::new();
+        }
+        self.minified_extensions.split(',')
+            .map(|extension| extension.trim().trim_start_matches('.').to_lowercase())
+            .filter(|extension| !extension.is_empty())
+            .collect()
+    }
+
+    /// The user's code fence language patterns, in the order given
+    pub fn fence_language_list(&self) -> Vec<(String, String)> {
+        parse_fence_languages(&self.fence_languages)
+    }
+
+    /// Returns the saved settings, or defaults if there are none or they can't be read
+    pub fn load() -> Self {
+        let Some(settings_path) = Self::settings_path() else {
+            return Self::default();
+        };
+        let Ok(bytes) = fs::read(&settings_path) else {
+            return Self::default();
+        };
+
+        serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            debug!("Ignoring unreadable settings {:?}: {}", settings_path, e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let settings_path = Self::settings_path().ok_or_else(|| AppError::SettingsError {
+            path: PathBuf::from(APP_DATA_DIR_NAME),
+            details: "No config directory available on this platform".to_string(),
+        })?;
+        let bytes = serde_json::to_vec_pretty(self).map_err(|e| AppError::SettingsError {
+            path: settings_path.clone(),
+            details: format!("Failed to serialize settings: {}", e),
+        })?;
+
+        write_file_atomically(&settings_path, &bytes)?;
+        debug!("Saved settings to {:?}", settings_path);
+        Ok(())
+    }
+
+    fn settings_path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join(APP_DATA_DIR_NAME).join("settings.json"))
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+use log::{debug, info, warn};
+use tempfile::NamedTempFile;
+
+use crate::constants::{APP_DATA_DIR_NAME, ORPHANED_TEMP_FILE_AGE, TEMP_FILE_PREFIX};
+use crate::error::{AppError, Result};
+
+/// Directories we have created temp files in, mirrored to disk so that a later run can
+/// clean up after a crash between temp-file creation and persist
+fn temp_dirs() -> &'static Mutex<HashSet<PathBuf>> {
+    static TEMP_DIRS: OnceLock<Mutex<HashSet<PathBuf>>> = OnceLock::new();
+    TEMP_DIRS.get_or_init(|| Mutex::new(load_temp_dirs()))
+}
+
+/// Temp files created by this process that may still exist; a writer cut off at exit leaves its file
+fn own_temp_files() -> &'static Mutex<HashSet<PathBuf>> {
+    static OWN_TEMP_FILES: OnceLock<Mutex<HashSet<PathBuf>>> = OnceLock::new();
+    OWN_TEMP_FILES.get_or_init(Mutex::default)
+}
+
+fn registry_path() -> Option<PathBuf> {
+    Some(dirs::cache_dir()?.join(APP_DATA_DIR_NAME).join("temp_dirs.json"))
+}
+
+fn load_temp_dirs() -> HashSet<PathBuf> {
+    registry_path()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+/// Written directly rather than atomically: the registry is best-effort and an atomic
+/// write would need a temp file of its own
+fn save_temp_dirs(temp_dirs: &HashSet<PathBuf>) {
+    let Some(path) = registry_path() else { return };
+    let result = path.parent()
+        .map(fs::create_dir_all)
+        .transpose()
+        .and_then(|_| fs::write(&path, serde_json::to_vec(temp_dirs).unwrap_or_default()));
+    if let Err(e) = result {
+        warn!("Failed to save temp file registry {:?}: {}", path, e);
+    }
+}
+
+/// Whether the path is one of our temp files (by name prefix)
+pub fn is_temp_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with(TEMP_FILE_PREFIX))
+}
+
+/// Creates a temp file with our prefix in `dir` and remembers the directory for cleanup
+pub fn create_temp_file_in(dir: &Path) -> Result<NamedTempFile> {
+    if let Ok(mut temp_dirs) = temp_dirs().lock() {
+        if temp_dirs.insert(dir.to_path_buf()) {
+            save_temp_dirs(&temp_dirs);
+        }
+    }
+
+    let temp_file = tempfile::Builder::new()
+        .prefix(TEMP_FILE_PREFIX)
+        .tempfile_in(dir)
+        .map_err(|e| AppError::new_io_error(e, Some(dir.to_path_buf()), "Failed to create temp file".to_string()))?;
+    if let Ok(mut own_temp_files) = own_temp_files().lock() {
+        // Persisted or dropped ones are gone
+        own_temp_files.retain(|path| path.exists());
+        own_temp_files.insert(temp_file.path().to_path_buf());
+    }
+    Ok(temp_file)
+}
+
+/// Removes the temp files this process created that are still there. Called at exit once the
+/// writers were stopped or given up on. Returns the number of removed files.
+pub fn remove_own_temp_files() -> usize {
+    let Ok(mut own_temp_files) = own_temp_files().lock() else { return 0 };
+    let removed = own_temp_files.drain()
+        .filter(|path| fs::remove_file(path).is_ok())
+        .count();
+    if removed > 0 {
+        info!("Removed {} temp files of unfinished writes", removed);
+    }
+    removed
+}
+
+/// Removes our temp files left behind by earlier runs. Recent files are kept since another
+/// instance may still be writing them. Returns the number of removed files.
+pub fn cleanup_orphaned_temp_files() -> usize {
+    let Ok(mut temp_dirs) = temp_dirs().lock() else { return 0 };
+    let now = SystemTime::now();
+    let mut removed = 0;
+
+    temp_dirs.retain(|dir| {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return false; // The directory is gone; stop tracking it
+        };
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            let is_orphaned = fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .is_some_and(|age| age >= ORPHANED_TEMP_FILE_AGE);
+            if is_temp_file(&path) && is_orphaned {
+                match fs::remove_file(&path) {
+                    Ok(()) => {
+                        debug!("Removed orphaned temp file {:?}", path);
+                        removed += 1;
+                    }
+                    Err(e) => warn!("Failed to remove orphaned temp file {:?}: {}", path, e),
+                }
+            }
+        }
+        true
+    });
+    save_temp_dirs(&temp_dirs);
+
+    if removed > 0 {
+        info!("Removed {} orphaned temp files", removed);
+    }
+    removed
+}
@@ -0,0 +1,455 @@
+use std::collections::HashSet;
+use std::io::{self, Stdout};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::execute;
+use crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode};
+use log::{info, warn};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::{Frame, Terminal};
+
+use context_builder::batch::settings_generator;
+use context_builder::budget::set_chars_per_token;
+use context_builder::constants::DEFAULT_OUTPUT_FILENAME_BASE;
+use context_builder::document_generator::{DocumentGenerator, existing_chunk_paths, is_output_document_path};
+use context_builder::document_index::document_index_path;
+use context_builder::document_worker::{DocumentJob, DocumentWorker, companion_targets};
+use context_builder::error::{AppError, Result};
+use context_builder::events::AppEvent;
+use context_builder::file_handler::{FileHandler, FileNode};
+use context_builder::file_monitor::FileMonitor;
+use context_builder::project_settings::ProjectSettings;
+use context_builder::remote_source::RemoteSource;
+use context_builder::settings::AppSettings;
+
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(200); // Key wait between redraws
+const HELP_TEXT: &str = "↑/↓ move  ←/→ collapse/expand  space select  g generate  w watch  q quit";
+
+/// A visible line of the tree
+struct Row {
+    path: PathBuf,
+    name: String,
+    depth: usize,
+    is_dir: bool,
+}
+
+/// Terminal front end (`--tui`) for SSH sessions: the same tree selection, generation and watch
+/// mode as the GUI, driven by the keyboard. Uses the saved settings of the GUI.
+struct TuiApp {
+    directory: PathBuf,
+    output_path: PathBuf,
+    settings: AppSettings,
+    project_settings: ProjectSettings,
+    root_node: FileNode,
+    expanded: HashSet<PathBuf>,
+    selected_files: HashSet<PathBuf>,
+    rows: Vec<Row>,
+    list_state: ListState,
+    event_receiver: mpsc::Receiver<AppEvent>,
+    file_monitor: FileMonitor,
+    document_worker: DocumentWorker,
+    watching: bool,
+    status: String,
+    should_quit: bool,
+}
+
+/// Runs the terminal front end until the user quits
+pub fn run(directory: PathBuf, output_path: Option<PathBuf>) -> Result<()> {
+    // `user@host:path` opens the local mirror of a server directory, fetched afresh
+    let directory = match directory.to_str().and_then(RemoteSource::parse).filter(|_| !directory.exists()) {
+        Some(source) => {
+            eprintln!("Fetching {}...", source.address());
+            source.fetch()?
+        }
+        None => directory,
+    };
+    let directory = directory.canonicalize()
+        .map_err(|e| AppError::new_io_error(e, Some(directory.clone()), "Failed to open directory".to_string()))?;
+    let mut app = TuiApp::new(directory, output_path)?;
+
+    let mut terminal = setup_terminal()?;
+    let result = app.event_loop(&mut terminal);
+    restore_terminal(&mut terminal)?;
+    result
+}
+
+fn setup_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>> {
+    enable_raw_mode().map_err(terminal_error)?;
+    execute!(io::stdout(), EnterAlternateScreen).map_err(terminal_error)?;
+    Terminal::new(CrosstermBackend::new(io::stdout())).map_err(terminal_error)
+}
+
+fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+    disable_raw_mode().map_err(terminal_error)?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen).map_err(terminal_error)?;
+    terminal.show_cursor().map_err(terminal_error)
+}
+
+fn terminal_error(e: io::Error) -> AppError {
+    AppError::new_io_error(e, None, "Terminal error".to_string())
+}
+
+impl TuiApp {
+    fn new(directory: PathBuf, output_path: Option<PathBuf>) -> Result<Self> {
+        let settings = AppSettings::load();
+        set_chars_per_token(settings.token_estimator.chars_per_token());
+        let project_settings = ProjectSettings::load(&directory);
+        let output_path = output_path.unwrap_or_else(|| {
+            directory.join(format!("{}.{}", DEFAULT_OUTPUT_FILENAME_BASE, settings.output_format.extension()))
+        });
+        let root_node = Self::scan(&directory, &output_path, &settings, &project_settings)?;
+        let (event_sender, event_receiver) = mpsc::channel();
+
+        let status = format!("Output: {}", output_path.display());
+
+        let mut app = Self {
+            project_settings,
+            expanded: HashSet::from([directory.clone()]),
+            directory,
+            output_path,
+            settings,
+            root_node,
+            selected_files: HashSet::new(),
+            rows: Vec::new(),
+            list_state: ListState::default(),
+            event_receiver,
+            file_monitor: FileMonitor::new(event_sender.clone()),
+            document_worker: DocumentWorker::new(event_sender),
+            watching: false,
+            status,
+            should_quit: false,
+        };
+        app.rebuild_rows();
+        app.list_state.select((!app.rows.is_empty()).then_some(0));
+        Ok(app)
+    }
+
+    fn scan(directory: &Path, output_path: &Path, settings: &AppSettings, project_settings: &ProjectSettings) -> Result<FileNode> {
+        let mut ignore_patterns = settings.ignore_patterns.clone();
+        ignore_patterns.extend(project_settings.extension_ignore_patterns());
+        FileHandler::new(directory.to_path_buf())?
+            .with_generated_files_excluded(settings.exclude_generated_files)
+            .with_line_counts(settings.line_counts_enabled)
+            .with_output_path(Some(output_path.to_path_buf()))
+            .scan_directory(ignore_patterns)
+    }
+
+    fn event_loop(&mut self, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+        while !self.should_quit {
+            terminal.draw(|frame| self.draw(frame)).map_err(terminal_error)?;
+
+            if event::poll(EVENT_POLL_INTERVAL).map_err(terminal_error)? {
+                if let Event::Key(key) = event::read().map_err(terminal_error)? {
+                    if key.kind == KeyEventKind::Press {
+                        self.handle_key(key.code);
+                    }
+                }
+            }
+            self.process_events();
+        }
+
+        if let Err(e) = self.file_monitor.stop_monitoring() {
+            warn!("Error stopping file monitor: {}", e);
+        }
+        Ok(())
+    }
+
+    fn handle_key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => self.should_quit = true,
+            KeyCode::Down | KeyCode::Char('j') => self.move_cursor(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_cursor(-1),
+            KeyCode::PageDown => self.move_cursor(20),
+            KeyCode::PageUp => self.move_cursor(-20),
+            KeyCode::Right | KeyCode::Char('l') => self.set_expanded(true),
+            KeyCode::Left | KeyCode::Char('h') => self.set_expanded(false),
+            KeyCode::Char(' ') | KeyCode::Enter => self.toggle_selection(),
+            KeyCode::Char('g') => self.generate(),
+            KeyCode::Char('w') => self.toggle_watching(),
+            _ => {}
+        }
+    }
+
+    fn process_events(&mut self) {
+        while let Ok(event) = self.event_receiver.try_recv() {
+            match event {
+                AppEvent::DocumentGenerationComplete(result) => {
+                    self.status = match result {
+                        Ok(()) => format!("Document generated: {}", self.output_path.display()),
+                        Err(e) => format!("Failed to generate document: {}", e),
+                    };
+                }
+                AppEvent::PartialDocumentUpdateComplete(Err(e)) => {
+                    self.status = format!("Failed to update document: {}", e);
+                }
+                AppEvent::FilesModifiedDebounced(file_paths) => {
+                    let modified: Vec<PathBuf> = file_paths.into_iter()
+                        .filter(|path| self.selected_files.contains(path))
+                        .collect();
+                    if modified.is_empty() {
+                        continue;
+                    }
+                    if let Some(message) = self.selected_output_message() {
+                        self.status = message;
+                        continue;
+                    }
+                    if self.settings.all_formats_enabled
+                        || (self.settings.custom_template_path.is_some() && !self.settings.split_output_enabled)
+                    {
+                        // Sections of a custom layout can't be located reliably, nor those of other formats
+                        self.generate();
+                        continue;
+                    }
+                    self.status = format!("Updating {} modified files", modified.len());
+                    let document_paths = if self.settings.split_output_enabled {
+                        existing_chunk_paths(&self.output_path)
+                    } else {
+                        vec![self.output_path.clone()]
+                    };
+                    self.document_worker.submit(DocumentJob::UpdateSections {
+                        generator: self.configured_generator(),
+                        document_paths,
+                        file_paths: modified,
+                        format: self.settings.output_format,
+                    });
+                }
+                AppEvent::DirectoryContentChanged(_) | AppEvent::FileRemoved(_) | AppEvent::FileRenamed { .. } => {
+                    self.rescan();
+                }
+                AppEvent::WatcherError(e) => {
+                    self.status = format!("Watcher error, watching stopped: {}", e);
+                    self.watching = false;
+                    if let Err(e) = self.file_monitor.stop_monitoring() {
+                        warn!("Error stopping file monitor: {}", e);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Rescans after a structural change, keeping the selection of files that still exist.
+    /// While watching, the document is regenerated so it matches the new structure.
+    fn rescan(&mut self) {
+        match Self::scan(&self.directory, &self.output_path, &self.settings, &self.project_settings) {
+            Ok(root_node) => {
+                let existing = root_node.all_paths();
+                self.selected_files.retain(|path| existing.contains(path));
+                self.root_node = root_node;
+                self.rebuild_rows();
+                self.move_cursor(0);
+                if self.watching && !self.selected_files.is_empty() {
+                    self.generate();
+                }
+            }
+            Err(e) => self.status = format!("Rescan failed: {}", e),
+        }
+    }
+
+    /// The generator batch mode would build from the same settings, plus the output-relative options
+    fn configured_generator(&self) -> DocumentGenerator {
+        let max_tokens = self.settings.token_budget_enabled.then_some(self.settings.max_token_budget);
+        settings_generator(self.directory.clone(), self.selected_files.iter().cloned().collect(), &self.settings, &self.project_settings, max_tokens)
+            .with_document_index(self.settings.document_index_enabled.then(|| document_index_path(&self.output_path)))
+            .with_adoc_includes(self.settings.adoc_includes_enabled.then(|| self.document_dir()).flatten())
+            .with_markdown_links(self.settings.markdown_links_enabled.then(|| self.document_dir()).flatten())
+            .with_line_anchors(self.settings.markdown_line_anchors_enabled)
+    }
+
+    /// Directory file references (AsciiDoc includes, Markdown links) are relative to
+    fn document_dir(&self) -> Option<PathBuf> {
+        let document_dir = self.output_path.parent()?;
+        Some(document_dir.canonicalize().unwrap_or_else(|_| document_dir.to_path_buf()))
+    }
+
+    fn generate(&mut self) {
+        if self.selected_files.is_empty() {
+            self.status = "Select at least one file first".to_string();
+            return;
+        }
+        if let Some(message) = self.selected_output_message() {
+            self.status = message;
+            return;
+        }
+        self.status = "Generating document...".to_string();
+        self.document_worker.submit(DocumentJob::Generate {
+            generator: self.configured_generator(),
+            root_node: self.root_node.clone(),
+            output_path: self.output_path.clone(),
+            format: self.settings.output_format,
+            chunk_token_limit: self.settings.split_output_enabled.then_some(self.settings.max_tokens_per_chunk),
+            template_path: self.settings.custom_template_path.clone(),
+            companion_targets: if self.settings.all_formats_enabled {
+                companion_targets(&self.output_path, self.settings.output_format)
+            } else {
+                Vec::new()
+            },
+        });
+    }
+
+    /// Why generating is blocked when the document itself (in any format or part) is selected
+    fn selected_output_message(&self) -> Option<String> {
+        let selected_output = self.selected_files.iter().find(|path| is_output_document_path(path, &self.output_path))?;
+        Some(format!("{} is selected and is the output; deselect it first", selected_output.display()))
+    }
+
+    fn toggle_watching(&mut self) {
+        if self.watching {
+            if let Err(e) = self.file_monitor.stop_monitoring() {
+                warn!("Error stopping file monitor: {}", e);
+            }
+            self.watching = false;
+            self.status = "Watching stopped".to_string();
+            return;
+        }
+
+        self.file_monitor.set_debounce(
+            Duration::from_millis(self.settings.debounce_ms),
+            Duration::from_millis(self.settings.debounce_check_interval_ms),
+        );
+        self.file_monitor.set_output_path(Some(self.output_path.clone()));
+        let poll_interval = Duration::from_secs(self.settings.poll_interval_secs);
+        match self.file_monitor.start_monitoring(self.directory.clone(), poll_interval, self.settings.force_polling_watcher) {
+            Ok(backend) => {
+                info!("Watching {:?} with {}", self.directory, backend.description());
+                self.watching = true;
+                self.status = format!("Watching ({}); edits to selected files update the document", backend.description());
+                self.generate();
+            }
+            Err(e) => self.status = format!("Failed to start watching: {}", e),
+        }
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        if self.rows.is_empty() {
+            self.list_state.select(None);
+            return;
+        }
+        let current = self.list_state.selected().unwrap_or(0) as isize;
+        let next = (current + delta).clamp(0, self.rows.len() as isize - 1);
+        self.list_state.select(Some(next as usize));
+    }
+
+    fn current_row(&self) -> Option<&Row> {
+        self.list_state.selected().and_then(|index| self.rows.get(index))
+    }
+
+    /// Expands or collapses the directory under the cursor; collapsing a file or a collapsed
+    /// directory jumps to its parent
+    fn set_expanded(&mut self, expand: bool) {
+        let Some(row) = self.current_row() else { return };
+        let (path, is_dir, depth) = (row.path.clone(), row.is_dir, row.depth);
+
+        if is_dir && expand != self.expanded.contains(&path) {
+            if expand {
+                self.expanded.insert(path);
+            } else {
+                self.expanded.remove(&path);
+            }
+            self.rebuild_rows();
+        } else if !expand && depth > 0 {
+            let cursor = self.list_state.selected().unwrap_or(0);
+            let parent = self.rows[..cursor].iter().rposition(|row| row.depth < depth);
+            self.list_state.select(parent);
+        }
+    }
+
+    /// Selects the file, or every file of the directory; a fully selected directory is deselected
+    fn toggle_selection(&mut self) {
+        let Some(path) = self.current_row().map(|row| row.path.clone()) else { return };
+        let Some(node) = self.root_node.find(&path) else { return };
+
+        let mut files = Vec::new();
+        collect_files(node, &mut files);
+        let all_selected = files.iter().all(|file| self.selected_files.contains(file));
+        for file in files {
+            if all_selected {
+                self.selected_files.remove(&file);
+            } else {
+                self.selected_files.insert(file);
+            }
+        }
+        if self.watching {
+            self.generate();
+        }
+    }
+
+    fn rebuild_rows(&mut self) {
+        let mut rows = Vec::new();
+        self.push_rows(&self.root_node, 0, &mut rows);
+        self.rows = rows;
+    }
+
+    fn push_rows(&self, node: &FileNode, depth: usize, rows: &mut Vec<Row>) {
+        rows.push(Row {
+            path: node.path.clone(),
+            name: node.name.clone(),
+            depth,
+            is_dir: node.is_dir,
+        });
+        if node.is_dir && self.expanded.contains(&node.path) {
+            for child in &node.children {
+                self.push_rows(child, depth + 1, rows);
+            }
+        }
+    }
+
+    fn checkbox(&self, path: &Path) -> &'static str {
+        let Some(node) = self.root_node.find(path) else { return "[ ]" };
+        let mut files = Vec::new();
+        collect_files(node, &mut files);
+        let selected = files.iter().filter(|file| self.selected_files.contains(*file)).count();
+        match selected {
+            0 => "[ ]",
+            count if count == files.len() => "[x]",
+            _ => "[-]",
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(3), Constraint::Length(3)])
+            .split(frame.size());
+
+        let items: Vec<ListItem> = self.rows.iter()
+            .map(|row| {
+                let marker = match (row.is_dir, self.expanded.contains(&row.path)) {
+                    (true, true) => "▾ ",
+                    (true, false) => "▸ ",
+                    (false, _) => "  ",
+                };
+                ListItem::new(format!("{}{} {}{}", "  ".repeat(row.depth), self.checkbox(&row.path), marker, row.name))
+            })
+            .collect();
+        let title = format!(
+            " {} — {} files selected{} ",
+            self.directory.display(),
+            self.selected_files.len(),
+            if self.watching { ", watching" } else { "" }
+        );
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, areas[0], &mut self.list_state);
+
+        let footer = Paragraph::new(format!("{}\n{}", self.status, HELP_TEXT))
+            .block(Block::default().borders(Borders::TOP));
+        frame.render_widget(footer, areas[1]);
+    }
+}
+
+fn collect_files(node: &FileNode, files: &mut Vec<PathBuf>) {
+    if node.is_dir {
+        for child in &node.children {
+            collect_files(child, files);
+        }
+    } else {
+        files.push(node.path.clone());
+    }
+}
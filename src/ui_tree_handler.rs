@@ -1,9 +1,14 @@
-use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
-use egui::{Id, Ui, CollapsingHeader, Checkbox};
-use log::debug;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use egui::{Id, Key, Modifiers, Ui, CollapsingHeader, Checkbox, RichText};
+use ignore::overrides::OverrideBuilder;
+use log::{debug, warn};
 
+use crate::budget::{FilePriority, LineRange};
+use crate::constants::LARGE_FILE_WARNING_BYTES;
 use crate::file_handler::FileNode;
+use crate::outline::supports_outline;
+use crate::utils::format_byte_size;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SelectionState {
@@ -18,16 +23,71 @@ pub struct UITreeNode {
     pub file_node_path: PathBuf,
     pub display_name: String,
     pub is_dir: bool,
+    pub is_generated: bool,
+    pub is_gitignored: bool,
+    pub size_bytes: u64,
+    pub line_count: Option<usize>,
+    pub children_pending: bool,
     pub selected_state: SelectionState,
     pub expanded: bool,
     pub children_indices: Vec<usize>,
     pub parent_index: Option<usize>,
 }
 
+type SelectionChangedCallback = Box<dyn FnMut(&HashSet<PathBuf>)>;
+type NodeActivatedCallback = Box<dyn FnMut(&Path)>;
+
 pub struct UITreeHandler {
     pub tree_nodes: Vec<UITreeNode>,
     pub selected_files: HashSet<PathBuf>,
     path_to_index: HashMap<PathBuf, usize>,
+    file_priorities: HashMap<PathBuf, FilePriority>, // Only non-default priorities are stored
+    outline_files: HashSet<PathBuf>,    // Rendered as signatures only
+    summary_files: HashSet<PathBuf>,    // Rendered as an LLM-written summary
+    line_ranges: HashMap<PathBuf, LineRange>, // Files included only in part
+    documented_files: HashSet<PathBuf>, // Files with a section in the current output document
+    read_errors: HashMap<PathBuf, String>, // Files the last document write couldn't read, with the error
+    pending_reveal: Option<PathBuf>,    // Expand ancestors and scroll to this node on the next frame
+    highlighted_path: Option<PathBuf>,  // Last revealed node
+    context_request: Option<PathBuf>,   // Focal file picked via "Suggest minimal context"
+    open_request: Option<(PathBuf, OpenTarget)>, // Node to open outside the app
+    preview_request: Option<PathBuf>,   // File whose name was clicked
+    file_tags: HashMap<PathBuf, BTreeSet<String>>, // User-defined groups, persisted per project
+    new_tag_text: String,               // Tag being typed in a file's context menu
+    tags_changed: bool,                 // Tags were edited since the last `take_tags_changed`
+    show_sizes: bool,                   // File sizes and line counts, aggregate sizes on directories
+    selection_locked: bool,             // Checkbox clicks request an unlock instead of changing the selection
+    unlock_requested: bool,             // A checkbox was clicked while locked, since the last take
+    children_requests: Vec<ChildrenRequest>, // Pending directories expanded or checked since the last take
+    requested_directories: HashSet<PathBuf>, // Already requested, until the tree is rebuilt
+    focused_path: Option<PathBuf>,      // Node the arrow keys move from, set by clicking or moving
+    scroll_to_focused: bool,            // The focused node moved by keyboard and should be scrolled into view
+    pending_openness: Option<(PathBuf, bool)>, // Directory expanded or collapsed by keyboard, applied on the next render
+    filter: String,                     // Lowercase name filter
+    filter_matches: Option<HashSet<usize>>, // Nodes shown while filtering: matches, their ancestors and descendants
+    activated_paths: Vec<PathBuf>,      // Files activated during the current render, reported at its end
+    selection_changed_callback: Option<SelectionChangedCallback>,
+    node_activated_callback: Option<NodeActivatedCallback>,
+}
+
+/// Where a node picked from its context menu should be opened
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpenTarget {
+    Editor,
+    FileManager,
+}
+
+/// A directory of a shallow tree whose children should be scanned
+#[derive(Debug, Clone)]
+pub struct ChildrenRequest {
+    pub directory: PathBuf,
+    pub select_files: bool, // Checked rather than expanded: load the whole subtree and select it
+}
+
+impl Default for UITreeHandler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl UITreeHandler {
@@ -36,18 +96,60 @@ impl UITreeHandler {
             tree_nodes: Vec::new(),
             selected_files: HashSet::new(),
             path_to_index: HashMap::new(),
+            file_priorities: HashMap::new(),
+            outline_files: HashSet::new(),
+            summary_files: HashSet::new(),
+            line_ranges: HashMap::new(),
+            documented_files: HashSet::new(),
+            read_errors: HashMap::new(),
+            pending_reveal: None,
+            highlighted_path: None,
+            context_request: None,
+            open_request: None,
+            preview_request: None,
+            file_tags: HashMap::new(),
+            new_tag_text: String::new(),
+            tags_changed: false,
+            show_sizes: true,
+            selection_locked: false,
+            unlock_requested: false,
+            children_requests: Vec::new(),
+            requested_directories: HashSet::new(),
+            focused_path: None,
+            scroll_to_focused: false,
+            pending_openness: None,
+            filter: String::new(),
+            filter_matches: None,
+            activated_paths: Vec::new(),
+            selection_changed_callback: None,
+            node_activated_callback: None,
         }
     }
 
+    /// Called with the selected files after a render in which the user changed the selection,
+    /// for embedders; the app reads `render_tree`'s return value instead
+    pub fn with_selection_changed_callback(mut self, callback: impl FnMut(&HashSet<PathBuf>) + 'static) -> Self {
+        self.selection_changed_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Called with a file whose name was clicked or that was opened with Enter
+    pub fn with_node_activated_callback(mut self, callback: impl FnMut(&Path) + 'static) -> Self {
+        self.node_activated_callback = Some(Box::new(callback));
+        self
+    }
+
     pub fn build_from_file_node(&mut self, root_node: &FileNode) {
         self.tree_nodes.clear();
         self.path_to_index.clear();
+        self.requested_directories.clear();
         
         debug!("Building UI tree from root node: {:?}", root_node.name);
         debug!("Root node has {} children", root_node.children.len());
         
         self.build_tree_recursive(root_node, None);
         self.update_all_selection_states();
+        self.update_filter_matches();
         
         debug!("Built UI tree with {} nodes total", self.tree_nodes.len());
         
@@ -69,6 +171,11 @@ impl UITreeHandler {
             file_node_path: node.path.clone(),
             display_name: node.name.clone(),
             is_dir: node.is_dir,
+            is_generated: node.is_generated,
+            is_gitignored: node.is_gitignored,
+            size_bytes: node.size_bytes,
+            line_count: node.line_count,
+            children_pending: node.children_pending,
             selected_state: if self.selected_files.contains(&node.path) {
                 SelectionState::Selected
             } else {
@@ -96,20 +203,35 @@ impl UITreeHandler {
     }
 
     pub fn render_tree(&mut self, ui: &mut Ui) -> bool {
-        let mut selection_changed = false;
+        let mut selection_changed = self.handle_keyboard(ui);
         
-        if !self.tree_nodes.is_empty() {
-            selection_changed = self.render_node_recursive(ui, 0);
+        if self.filter_matches.as_ref().is_some_and(HashSet::is_empty) {
+            ui.weak("No file or folder names match the filter");
+        } else if !self.tree_nodes.is_empty() && self.render_node_recursive(ui, 0) {
+            selection_changed = true;
         }
         
         if selection_changed {
             self.update_selected_files();
             self.update_all_selection_states();
+            if let Some(callback) = &mut self.selection_changed_callback {
+                callback(&self.selected_files);
+            }
+        }
+        for path in std::mem::take(&mut self.activated_paths) {
+            if let Some(callback) = &mut self.node_activated_callback {
+                callback(&path);
+            }
         }
         
         selection_changed
     }
 
+    fn activate(&mut self, path: PathBuf) {
+        self.activated_paths.push(path.clone());
+        self.preview_request = Some(path);
+    }
+
     fn render_node_recursive(&mut self, ui: &mut Ui, node_index: usize) -> bool {
         let mut selection_changed = false;
         
@@ -119,32 +241,59 @@ impl UITreeHandler {
         if node.is_dir {
             // Render directory as collapsing header with checkbox
             ui.horizontal(|ui| {
-                // Checkbox for directory
+                // Tri-state checkbox for directory; clicking a partial one selects everything
                 let mut selected = node.selected_state == SelectionState::Selected;
-                let checkbox_response = ui.add(Checkbox::new(&mut selected, ""));
+                let partially_selected = node.selected_state == SelectionState::PartiallySelected;
+                let mut checkbox_response = ui.add(Checkbox::new(&mut selected, "").indeterminate(partially_selected));
+                if partially_selected {
+                    checkbox_response = checkbox_response.on_hover_text("Some files in this directory are selected");
+                }
                 
-                if checkbox_response.clicked() {
+                if checkbox_response.clicked() && self.selection_locked {
+                    self.unlock_requested = true;
+                } else if checkbox_response.clicked() && node.children_pending {
+                    // The files to select are not known yet
+                    self.request_children(&node.file_node_path, true);
+                } else if checkbox_response.clicked() {
                     self.toggle_node_selection(node_index);
                     selection_changed = true;
                 }
                 
-                // Add some visual indication for partially selected directories
-                // let header_icon = match node.selected_state {
-                //     SelectionState::Selected => "📁",
-                //     SelectionState::PartiallySelected => "📂",
-                //     SelectionState::Unselected => "📁",
-                // };
-                
+                // Force ancestors of a node being revealed open, and directories while filtering
+                let reveal_inside = self.pending_reveal.as_ref()
+                    .is_some_and(|path| path != &node.file_node_path && path.starts_with(&node.file_node_path))
+                    || self.filter_matches.is_some();
+                let keyboard_openness = match &self.pending_openness {
+                    Some((path, _)) if *path == node.file_node_path => self.pending_openness.take().map(|(_, open)| open),
+                    _ => None,
+                };
+
+                let header_text = if self.show_sizes && !node.children_pending {
+                    format!(" {}  ({})", node.display_name, format_byte_size(node.size_bytes))
+                } else {
+                    format!(" {}", node.display_name)
+                };
+                let mut header_text = RichText::new(header_text);
+                if self.focused_path.as_ref() == Some(&node.file_node_path) {
+                    header_text = header_text.background_color(ui.visuals().selection.bg_fill);
+                }
                 // Collapsing header for directory with better styling
-                let header_response = CollapsingHeader::new(format!(" {}", node.display_name))
+                let header_response = CollapsingHeader::new(header_text)
                     .id_source(node.id)
                     .default_open(node.expanded)
+                    .open(keyboard_openness.or(reveal_inside.then_some(true)))
                     .show(ui, |ui| {
                         // Add some padding for nested content
                         ui.add_space(2.0);
+                        if node.children_pending {
+                            ui.weak("Loading…");
+                        }
                         
                         // Render children with better indentation
                         for &child_index in &node.children_indices {
+                            if self.filter_matches.as_ref().is_some_and(|matches| !matches.contains(&child_index)) {
+                                continue;
+                            }
                             ui.horizontal(|ui| {
                                 ui.add_space(10.0); // Indent children
                                 ui.vertical(|ui| {
@@ -156,8 +305,22 @@ impl UITreeHandler {
                         }
                     });
                 
+                if header_response.header_response.clicked() || checkbox_response.clicked() {
+                    self.focused_path = Some(node.file_node_path.clone());
+                }
+                if self.scroll_to_focused && self.focused_path.as_ref() == Some(&node.file_node_path) {
+                    header_response.header_response.scroll_to_me(None);
+                    self.scroll_to_focused = false;
+                }
+                header_response.header_response.context_menu(|ui| {
+                    self.render_open_menu_items(ui, &node.file_node_path);
+                });
+
                 // Update expanded state
                 self.tree_nodes[node_index].expanded = header_response.openness > 0.5;
+                if node.children_pending && self.tree_nodes[node_index].expanded {
+                    self.request_children(&node.file_node_path, false);
+                }
             });
         } else {
             // Render file as checkbox with label and appropriate icon
@@ -165,23 +328,301 @@ impl UITreeHandler {
                 let mut selected = node.selected_state == SelectionState::Selected;
                 let checkbox_response = ui.add(Checkbox::new(&mut selected, ""));
                 
-                if checkbox_response.clicked() {
+                if checkbox_response.clicked() && self.selection_locked {
+                    self.unlock_requested = true;
+                } else if checkbox_response.clicked() {
                     self.toggle_node_selection(node_index);
                     selection_changed = true;
                 }
                 
                 // Style the file name based on selection
+                let mut label_text = RichText::new(&node.display_name);
                 if selected {
-                    ui.colored_label(egui::Color32::from_rgb(0, 120, 0), format!("{}", node.display_name));
-                } else {
-                    ui.label(format!("{}", node.display_name));
+                    label_text = label_text.color(egui::Color32::from_rgb(0, 120, 0));
+                }
+                let focused = self.focused_path.as_ref() == Some(&node.file_node_path);
+                if focused || self.highlighted_path.as_ref() == Some(&node.file_node_path) {
+                    label_text = label_text.background_color(ui.visuals().selection.bg_fill);
+                }
+                let label_response = ui.add(egui::Label::new(label_text).sense(egui::Sense::click()));
+                if label_response.clicked() {
+                    self.activate(node.file_node_path.clone());
+                }
+                if label_response.clicked() || checkbox_response.clicked() {
+                    self.focused_path = Some(node.file_node_path.clone());
+                }
+                if focused && self.scroll_to_focused {
+                    label_response.scroll_to_me(None);
+                    self.scroll_to_focused = false;
+                }
+                if self.pending_reveal.as_ref() == Some(&node.file_node_path) {
+                    label_response.scroll_to_me(Some(egui::Align::Center));
+                    self.pending_reveal = None;
+                }
+                if self.show_sizes {
+                    let mut size_text = format_byte_size(node.size_bytes);
+                    if let Some(line_count) = node.line_count {
+                        size_text.push_str(&format!(", {} lines", line_count));
+                    }
+                    if node.size_bytes > LARGE_FILE_WARNING_BYTES {
+                        ui.label(RichText::new(size_text).small().color(egui::Color32::from_rgb(200, 120, 0)))
+                            .on_hover_text("Large file: it will take up a big share of the document");
+                    } else {
+                        ui.label(RichText::new(size_text).small().weak());
+                    }
+                }
+                // Compare against the current output document
+                if self.documented_files.contains(&node.file_node_path) {
+                    ui.label(RichText::new("in doc").small().color(egui::Color32::from_rgb(40, 100, 180)))
+                        .on_hover_text("This file has a section in the current output document");
+                } else if selected && !self.documented_files.is_empty() {
+                    ui.label(RichText::new("new").small().color(egui::Color32::from_rgb(200, 120, 0)))
+                        .on_hover_text("Selected, but not yet in the output document");
                 }
+                if node.is_generated {
+                    ui.weak("(generated)")
+                        .on_hover_text("Marked linguist-generated in .gitattributes");
+                }
+                if node.is_gitignored && selected {
+                    ui.label(RichText::new("⚠ gitignored").small().color(egui::Color32::from_rgb(200, 40, 40)))
+                        .on_hover_text("Matched by .gitignore: likely a local secret or build output. It will be included in the document.");
+                } else if node.is_gitignored {
+                    ui.weak("(gitignored)");
+                }
+                if let Some(read_error) = self.read_errors.get(&node.file_node_path) {
+                    ui.label(RichText::new("⛔ unreadable").small().color(egui::Color32::from_rgb(200, 40, 40)))
+                        .on_hover_text(format!("Could not be read for the last document, which shows an error note instead: {}", read_error));
+                }
+
+                let priority = self.file_priorities.get(&node.file_node_path).copied().unwrap_or_default();
+                if priority != FilePriority::Normal {
+                    ui.weak(format!("({} priority)", priority.name().to_lowercase()));
+                }
+                let outlined = self.outline_files.contains(&node.file_node_path);
+                if outlined {
+                    ui.weak("(signatures)");
+                }
+                let summarized = self.summary_files.contains(&node.file_node_path);
+                if summarized {
+                    ui.weak("(summary)");
+                }
+                let line_range = self.line_ranges.get(&node.file_node_path).copied();
+                if let Some(line_range) = line_range {
+                    ui.weak(format!("(lines {}–{})", line_range.start, line_range.end));
+                }
+                let file_tags = self.file_tags.get(&node.file_node_path).cloned().unwrap_or_default();
+                for tag in &file_tags {
+                    ui.label(RichText::new(format!("#{}", tag)).small().color(egui::Color32::from_rgb(120, 60, 170)));
+                }
+
+                // Right-click menu for per-file options
+                label_response.context_menu(|ui| {
+                    self.render_open_menu_items(ui, &node.file_node_path);
+                    ui.separator();
+                    ui.label("Token budget priority");
+                    for option in FilePriority::ALL {
+                        if ui.radio(priority == option, option.name()).clicked() {
+                            self.set_file_priority(node.file_node_path.clone(), option);
+                            ui.close_menu();
+                        }
+                    }
+                    ui.separator();
+                    if supports_outline(&node.file_node_path) {
+                        let mut signatures_only = outlined;
+                        if ui.checkbox(&mut signatures_only, "Signatures only")
+                            .on_hover_text("Include declarations and signatures without function bodies")
+                            .changed()
+                        {
+                            self.set_outline_file(node.file_node_path.clone(), signatures_only);
+                            ui.close_menu();
+                        }
+                        ui.separator();
+                    }
+                    let mut summary_only = summarized;
+                    if ui.checkbox(&mut summary_only, "LLM summary")
+                        .on_hover_text("Include a short summary written by the model under Settings → LLM instead of the content, \
+                                        e.g. for large dependencies. Summaries are cached until the file changes.")
+                        .changed()
+                    {
+                        self.set_summary_file(node.file_node_path.clone(), summary_only);
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    let mut edited_range = line_range;
+                    ui.horizontal(|ui| {
+                        let mut partial = edited_range.is_some();
+                        if ui.checkbox(&mut partial, "Only lines").changed() {
+                            edited_range = partial.then(|| LineRange { start: 1, end: node.line_count.unwrap_or(100).max(1) });
+                        }
+                        if let Some(range) = &mut edited_range {
+                            ui.add(egui::DragValue::new(&mut range.start).clamp_range(1..=range.end));
+                            ui.label("–");
+                            ui.add(egui::DragValue::new(&mut range.end).clamp_range(range.start..=usize::MAX));
+                        }
+                    }).response.on_hover_text("Include just this slice of the file, e.g. the one relevant function");
+                    if edited_range != line_range {
+                        self.set_line_range(node.file_node_path.clone(), edited_range);
+                    }
+                    ui.separator();
+                    if ui.button("Suggest minimal context")
+                        .on_hover_text("This file, its direct local imports, manifests and the nearest README")
+                        .clicked()
+                    {
+                        self.context_request = Some(node.file_node_path.clone());
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    ui.label("Tags");
+                    for tag in self.all_tags() {
+                        let mut tagged = file_tags.contains(&tag);
+                        if ui.checkbox(&mut tagged, format!("#{}", tag)).changed() {
+                            self.toggle_file_tag(&node.file_node_path, &tag);
+                        }
+                    }
+                    ui.horizontal(|ui| {
+                        let response = ui.add(egui::TextEdit::singleline(&mut self.new_tag_text)
+                            .hint_text("new tag")
+                            .desired_width(100.0));
+                        let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                        if ui.button("Add").clicked() || submitted {
+                            let tag = self.new_tag_text.trim().replace(char::is_whitespace, "-");
+                            if !tag.is_empty() && !file_tags.contains(&tag) {
+                                self.toggle_file_tag(&node.file_node_path, &tag);
+                            }
+                            self.new_tag_text.clear();
+                        }
+                    });
+                });
             });
         }
         
         selection_changed
     }
 
+    /// Arrow keys move the focus through the visible nodes (Right/Left also expand and collapse
+    /// directories), Space toggles the focused node's selection and Enter previews a file.
+    /// Keys are left alone while a text field or other widget has keyboard focus.
+    fn handle_keyboard(&mut self, ui: &mut Ui) -> bool {
+        if ui.ctx().wants_keyboard_input() {
+            return false;
+        }
+        let Some(focused) = self.focused_path.as_ref().and_then(|path| self.path_to_index.get(path)).copied() else {
+            return false;
+        };
+        let pressed = |key| ui.input_mut(|i| i.consume_key(Modifiers::NONE, key));
+        let visible = self.visible_nodes();
+        let position = visible.iter().position(|index| *index == focused).unwrap_or(0);
+        let node = &self.tree_nodes[focused];
+        let (is_dir, expanded, parent_index) = (node.is_dir, node.expanded || self.filter_matches.is_some(), node.parent_index);
+
+        let mut target = None;
+        if pressed(Key::ArrowDown) {
+            target = visible.get(position + 1).copied();
+        } else if pressed(Key::ArrowUp) {
+            target = position.checked_sub(1).and_then(|position| visible.get(position).copied());
+        } else if pressed(Key::ArrowRight) && is_dir {
+            match expanded {
+                true => target = visible.get(position + 1).copied().filter(|index| self.tree_nodes[*index].parent_index == Some(focused)),
+                false => self.pending_openness = Some((self.tree_nodes[focused].file_node_path.clone(), true)),
+            }
+        } else if pressed(Key::ArrowLeft) {
+            match is_dir && expanded && self.filter_matches.is_none() {
+                true => self.pending_openness = Some((self.tree_nodes[focused].file_node_path.clone(), false)),
+                false => target = parent_index,
+            }
+        } else if pressed(Key::Enter) && !is_dir {
+            self.activate(self.tree_nodes[focused].file_node_path.clone());
+        } else if pressed(Key::Space) {
+            let node = &self.tree_nodes[focused];
+            if self.selection_locked {
+                self.unlock_requested = true;
+            } else if node.children_pending {
+                let directory = node.file_node_path.clone();
+                self.request_children(&directory, true);
+            } else {
+                self.toggle_node_selection(focused);
+                return true;
+            }
+        }
+
+        if let Some(target) = target {
+            self.focused_path = Some(self.tree_nodes[target].file_node_path.clone());
+            self.scroll_to_focused = true;
+        }
+        false
+    }
+
+    /// Indices of the rendered nodes, top to bottom
+    fn visible_nodes(&self) -> Vec<usize> {
+        let mut visible = Vec::new();
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            if self.filter_matches.as_ref().is_some_and(|matches| !matches.contains(&index)) {
+                continue;
+            }
+            visible.push(index);
+            let node = &self.tree_nodes[index];
+            if node.is_dir && (node.expanded || self.filter_matches.is_some()) {
+                stack.extend(node.children_indices.iter().rev());
+            }
+        }
+        visible
+    }
+
+    /// Shows only nodes whose names contain `filter` (case-insensitively), with their ancestors
+    /// and everything below matching directories. An empty filter shows the whole tree.
+    pub fn set_filter(&mut self, filter: &str) {
+        let filter = filter.trim().to_lowercase();
+        if filter != self.filter {
+            self.filter = filter;
+            self.update_filter_matches();
+        }
+    }
+
+    fn update_filter_matches(&mut self) {
+        if self.filter.is_empty() {
+            self.filter_matches = None;
+            return;
+        }
+        let mut matches = HashSet::new();
+        for (index, node) in self.tree_nodes.iter().enumerate() {
+            if !node.display_name.to_lowercase().contains(&self.filter) {
+                continue;
+            }
+            let mut stack = vec![index];
+            while let Some(index) = stack.pop() {
+                if matches.insert(index) {
+                    stack.extend(&self.tree_nodes[index].children_indices);
+                }
+            }
+            let mut ancestor = node.parent_index;
+            while let Some(index) = ancestor {
+                matches.insert(index);
+                ancestor = self.tree_nodes[index].parent_index;
+            }
+        }
+        self.filter_matches = Some(matches);
+    }
+
+    /// Gives the arrow keys a starting point, e.g. after focusing the tree from the filter box
+    pub fn focus_first_node(&mut self) {
+        if let Some(index) = self.visible_nodes().into_iter().find(|index| !self.tree_nodes[*index].is_dir) {
+            self.focused_path = Some(self.tree_nodes[index].file_node_path.clone());
+            self.scroll_to_focused = true;
+        }
+    }
+
+    fn render_open_menu_items(&mut self, ui: &mut Ui, path: &Path) {
+        if ui.button("Open in editor").clicked() {
+            self.open_request = Some((path.to_path_buf(), OpenTarget::Editor));
+            ui.close_menu();
+        }
+        if ui.button("Reveal in file manager").clicked() {
+            self.open_request = Some((path.to_path_buf(), OpenTarget::FileManager));
+            ui.close_menu();
+        }
+    }
+
     fn toggle_node_selection(&mut self, node_index: usize) {
         let current_state = &self.tree_nodes[node_index].selected_state;
         let new_state = match current_state {
@@ -285,9 +726,30 @@ impl UITreeHandler {
         self.selected_files.iter().cloned().collect()
     }
 
-    #[allow(dead_code)]
+    /// Total size of the selected files, as of their last scan
+    pub fn selected_size_bytes(&self) -> u64 {
+        self.selected_files.iter()
+            .filter_map(|path| self.path_to_index.get(path))
+            .map(|&index| self.tree_nodes[index].size_bytes)
+            .sum()
+    }
+
+    /// Selected files matched by .gitignore, sorted
+    pub fn selected_gitignored_files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self.selected_files.iter()
+            .filter(|path| self.path_to_index.get(*path)
+                .is_some_and(|&index| self.tree_nodes[index].is_gitignored))
+            .cloned()
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// Replaces the selection; paths that are not part of the current tree are dropped
     pub fn set_selected_files(&mut self, files: HashSet<PathBuf>) {
-        self.selected_files = files;
+        self.selected_files = files.into_iter()
+            .filter(|path| self.path_to_index.contains_key(path))
+            .collect();
         
         // Update UI state to match
         for node in &mut self.tree_nodes {
@@ -303,6 +765,252 @@ impl UITreeHandler {
         self.update_all_selection_states();
     }
 
+    pub fn set_file_priority(&mut self, path: PathBuf, priority: FilePriority) {
+        if priority == FilePriority::default() {
+            self.file_priorities.remove(&path);
+        } else {
+            self.file_priorities.insert(path, priority);
+        }
+    }
+
+    pub fn get_file_priorities(&self) -> HashMap<PathBuf, FilePriority> {
+        self.file_priorities.clone()
+    }
+
+    pub fn set_outline_file(&mut self, path: PathBuf, outline: bool) {
+        if outline {
+            self.outline_files.insert(path);
+        } else {
+            self.outline_files.remove(&path);
+        }
+    }
+
+    pub fn get_outline_files(&self) -> HashSet<PathBuf> {
+        self.outline_files.clone()
+    }
+
+    pub fn set_summary_file(&mut self, path: PathBuf, summary: bool) {
+        if summary {
+            self.summary_files.insert(path);
+        } else {
+            self.summary_files.remove(&path);
+        }
+    }
+
+    pub fn get_summary_files(&self) -> HashSet<PathBuf> {
+        self.summary_files.clone()
+    }
+
+    pub fn set_line_range(&mut self, path: PathBuf, line_range: Option<LineRange>) {
+        match line_range {
+            Some(line_range) => self.line_ranges.insert(path, line_range),
+            None => self.line_ranges.remove(&path),
+        };
+    }
+
+    pub fn get_line_ranges(&self) -> HashMap<PathBuf, LineRange> {
+        self.line_ranges.clone()
+    }
+
+    /// Expands the node's ancestors, scrolls it into view and highlights it
+    pub fn reveal(&mut self, path: PathBuf) {
+        if self.path_to_index.contains_key(&path) {
+            self.highlighted_path = Some(path.clone());
+            self.pending_reveal = Some(path);
+        } else {
+            debug!("Cannot reveal {:?}: not in the tree", path);
+        }
+    }
+
+    pub fn set_file_tags(&mut self, file_tags: HashMap<PathBuf, BTreeSet<String>>) {
+        self.file_tags = file_tags;
+    }
+
+    pub fn get_file_tags(&self) -> HashMap<PathBuf, BTreeSet<String>> {
+        self.file_tags.clone()
+    }
+
+    /// Adds the tag to the file, or removes it if the file already has it
+    pub fn toggle_file_tag(&mut self, path: &Path, tag: &str) {
+        let tags = self.file_tags.entry(path.to_path_buf()).or_default();
+        if !tags.remove(tag) {
+            tags.insert(tag.to_string());
+        }
+        if tags.is_empty() {
+            self.file_tags.remove(path);
+        }
+        self.tags_changed = true;
+    }
+
+    /// Adds the tag to every given file that doesn't have it yet
+    pub fn add_tag_to_files(&mut self, paths: &[PathBuf], tag: &str) {
+        for path in paths {
+            self.file_tags.entry(path.clone()).or_default().insert(tag.to_string());
+        }
+        self.tags_changed = true;
+    }
+
+    /// Carries a renamed file's tags over to its new path
+    pub fn move_file_tags(&mut self, from: &Path, to: &Path) {
+        if let Some(tags) = self.file_tags.remove(from) {
+            self.file_tags.insert(to.to_path_buf(), tags);
+            self.tags_changed = true;
+        }
+    }
+
+    /// Whether tags were edited since the last call, i.e. they need saving
+    pub fn take_tags_changed(&mut self) -> bool {
+        std::mem::take(&mut self.tags_changed)
+    }
+
+    /// Tags used by files in the current tree, sorted
+    pub fn all_tags(&self) -> BTreeSet<String> {
+        self.file_tags.iter()
+            .filter(|(path, _)| self.path_to_index.contains_key(*path))
+            .flat_map(|(_, tags)| tags.iter().cloned())
+            .collect()
+    }
+
+    /// Per tag: how many of its files in the current tree are selected, and how many there are
+    pub fn tag_selection_counts(&self) -> BTreeMap<String, (usize, usize)> {
+        let mut counts: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+        for (path, tags) in &self.file_tags {
+            if !self.path_to_index.contains_key(path) {
+                continue;
+            }
+            let is_selected = self.selected_files.contains(path);
+            for tag in tags {
+                let (selected, total) = counts.entry(tag.clone()).or_default();
+                *total += 1;
+                if is_selected {
+                    *selected += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    /// Selects or deselects every file in the current tree that has the tag
+    pub fn set_tag_selected(&mut self, tag: &str, selected: bool) {
+        let mut selection = self.selected_files.clone();
+        for (path, tags) in &self.file_tags {
+            if !tags.contains(tag) {
+                continue;
+            }
+            if selected {
+                selection.insert(path.clone());
+            } else {
+                selection.remove(path);
+            }
+        }
+        self.set_selected_files(selection);
+    }
+
+    /// Adds every file matching one of the globs (gitignore syntax, e.g. `*.rs` or `docs/**`)
+    /// to the selection. Returns how many files matched.
+    pub fn select_matching(&mut self, patterns: &[&str]) -> usize {
+        let Some(root) = self.tree_nodes.first().map(|node| node.file_node_path.clone()) else {
+            return 0;
+        };
+        let mut builder = OverrideBuilder::new(&root);
+        for pattern in patterns {
+            if let Err(e) = builder.add(pattern) {
+                warn!("Ignoring invalid selection pattern '{}': {}", pattern, e);
+            }
+        }
+        let overrides = match builder.build() {
+            Ok(overrides) if !overrides.is_empty() => overrides,
+            Ok(_) => return 0,
+            Err(e) => {
+                warn!("Failed to build selection patterns {:?}: {}", patterns, e);
+                return 0;
+            }
+        };
+
+        let matching: Vec<PathBuf> = self.tree_nodes.iter()
+            .filter(|node| !node.is_dir && overrides.matched(&node.file_node_path, false).is_whitelist())
+            .map(|node| node.file_node_path.clone())
+            .collect();
+        let match_count = matching.len();
+        let mut selection = self.selected_files.clone();
+        selection.extend(matching);
+        self.set_selected_files(selection);
+        match_count
+    }
+
+    /// Number of files per extension in the current tree, e.g. `rs` -> 42
+    pub fn extension_counts(&self) -> BTreeMap<String, usize> {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for node in self.tree_nodes.iter().filter(|node| !node.is_dir) {
+            if let Some(extension) = node.file_node_path.extension() {
+                *counts.entry(extension.to_string_lossy().to_string()).or_default() += 1;
+            }
+        }
+        counts
+    }
+
+    /// Focal file the user asked a minimal context for, if any since the last call
+    fn request_children(&mut self, directory: &Path, select_files: bool) {
+        if self.requested_directories.insert(directory.to_path_buf()) {
+            debug!("Requesting children of {:?}", directory);
+            self.children_requests.push(ChildrenRequest { directory: directory.to_path_buf(), select_files });
+        }
+    }
+
+    pub fn take_children_requests(&mut self) -> Vec<ChildrenRequest> {
+        std::mem::take(&mut self.children_requests)
+    }
+
+    pub fn set_show_sizes(&mut self, show_sizes: bool) {
+        self.show_sizes = show_sizes;
+    }
+
+    pub fn set_selection_locked(&mut self, selection_locked: bool) {
+        self.selection_locked = selection_locked;
+    }
+
+    pub fn take_unlock_request(&mut self) -> bool {
+        std::mem::take(&mut self.unlock_requested)
+    }
+
+    pub fn take_preview_request(&mut self) -> Option<PathBuf> {
+        self.preview_request.take()
+    }
+
+    pub fn take_open_request(&mut self) -> Option<(PathBuf, OpenTarget)> {
+        self.open_request.take()
+    }
+
+    pub fn take_context_request(&mut self) -> Option<PathBuf> {
+        self.context_request.take()
+    }
+
+    pub fn contains_path(&self, path: &Path) -> bool {
+        self.path_to_index.contains_key(path)
+    }
+
+    pub fn set_documented_files(&mut self, documented_files: HashSet<PathBuf>) {
+        self.documented_files = documented_files;
+    }
+
+    /// Applies the files read by a document job: read errors are marked, files read fine lose
+    /// their mark. `replace` also clears the marks of files that weren't read.
+    pub fn update_read_errors(&mut self, outcomes: HashMap<PathBuf, Option<String>>, replace: bool) {
+        if replace {
+            self.read_errors.clear();
+        }
+        for (path, read_error) in outcomes {
+            match read_error {
+                Some(read_error) => self.read_errors.insert(path, read_error),
+                None => self.read_errors.remove(&path),
+            };
+        }
+    }
+
+    pub fn read_error_count(&self) -> usize {
+        self.read_errors.len()
+    }
+
     pub fn has_selection(&self) -> bool {
         !self.selected_files.is_empty()
     }
@@ -315,4 +1023,58 @@ impl UITreeHandler {
             node.selected_state = SelectionState::Unselected;
         }
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::file_handler::FileHandler;
+
+    /// One frame of the tree with `key` pressed
+    fn render_with_key(ctx: &egui::Context, handler: &mut UITreeHandler, key: Key) {
+        let input = egui::RawInput {
+            events: vec![egui::Event::Key { key, physical_key: None, pressed: true, repeat: false, modifiers: Modifiers::NONE }],
+            ..Default::default()
+        };
+        let _ = ctx.run(input, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                handler.render_tree(ui);
+            });
+        });
+    }
+
+    #[test]
+    fn callbacks_report_selection_changes_and_activated_files() {
+        let project = tempfile::tempdir().unwrap();
+        std::fs::write(project.path().join("a.txt"), "a").unwrap();
+        let root_node = FileHandler::new(project.path().to_path_buf()).unwrap().scan_directory(Vec::new()).unwrap();
+        let file_path = root_node.file_paths()[0].clone();
+
+        let selections = Rc::new(RefCell::new(Vec::new()));
+        let activations = Rc::new(RefCell::new(Vec::new()));
+        let (selections_seen, activations_seen) = (selections.clone(), activations.clone());
+        let mut handler = UITreeHandler::new()
+            .with_selection_changed_callback(move |selected| selections_seen.borrow_mut().push(selected.clone()))
+            .with_node_activated_callback(move |path| activations_seen.borrow_mut().push(path.to_path_buf()));
+        handler.build_from_file_node(&root_node);
+        handler.set_filter("a.txt");
+        handler.focus_first_node();
+
+        let ctx = egui::Context::default();
+        render_with_key(&ctx, &mut handler, Key::Escape); // Nothing to report
+        assert!(selections.borrow().is_empty() && activations.borrow().is_empty());
+
+        render_with_key(&ctx, &mut handler, Key::Space);
+        assert_eq!(*selections.borrow(), [HashSet::from([file_path.clone()])]);
+        render_with_key(&ctx, &mut handler, Key::Enter);
+        assert_eq!(*activations.borrow(), vec![file_path.clone()]);
+        // Reported once, while the app's own request is still there to take
+        render_with_key(&ctx, &mut handler, Key::ArrowDown);
+        assert_eq!(activations.borrow().len(), 1);
+        assert_eq!(handler.take_preview_request(), Some(file_path));
+
+        render_with_key(&ctx, &mut handler, Key::Space);
+        assert_eq!(selections.borrow().last(), Some(&HashSet::new()));
+    }
+}
@@ -0,0 +1,61 @@
+use std::process::Command;
+use log::{debug, info};
+use serde::Deserialize;
+
+use crate::constants::{UPDATE_CHECK_TIMEOUT_SECS, UPDATE_CHECK_URL};
+use crate::error::{AppError, Result};
+use crate::utils::version_key;
+
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The latest published release
+#[derive(Debug, Clone)]
+pub struct ReleaseInfo {
+    pub version: String, // Tag without the leading `v`
+    pub changelog: String,
+    pub url: String, // Release page with the downloads
+}
+
+impl ReleaseInfo {
+    pub fn is_newer_than_current(&self) -> bool {
+        version_key(&self.version) > version_key(CURRENT_VERSION)
+    }
+}
+
+/// Subset of the GitHub releases API response
+#[derive(Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    body: Option<String>,
+    html_url: String,
+}
+
+/// Fetches the latest non-draft, non-prerelease release from GitHub. Uses the system `curl`
+/// (shipped with Windows 10+, macOS and most Linux distributions) rather than bundling an HTTP
+/// and TLS stack for a single request.
+pub fn fetch_latest_release() -> Result<ReleaseInfo> {
+    debug!("Checking for updates at {}", UPDATE_CHECK_URL);
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location"])
+        .args(["--max-time", &UPDATE_CHECK_TIMEOUT_SECS.to_string()])
+        .args(["--header", "Accept: application/vnd.github+json"])
+        .args(["--user-agent", &format!("context_builder/{}", CURRENT_VERSION)])
+        .arg(UPDATE_CHECK_URL)
+        .output()
+        .map_err(|e| AppError::new_io_error(e, None, "Failed to run curl for the update check".to_string()))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::UpdateCheckError(stderr.trim().to_string()));
+    }
+
+    let release: GithubRelease = serde_json::from_slice(&output.stdout)
+        .map_err(|e| AppError::UpdateCheckError(format!("Unexpected response: {}", e)))?;
+    let release = ReleaseInfo {
+        version: release.tag_name.trim_start_matches('v').to_string(),
+        changelog: release.body.unwrap_or_default().trim().to_string(),
+        url: release.html_url,
+    };
+    info!("Latest release is {} (running {})", release.version, CURRENT_VERSION);
+    Ok(release)
+}
@@ -0,0 +1,127 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use chrono::{Duration, Local};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{APP_DATA_DIR_NAME, USAGE_FREQUENT_FILE_RATIO, USAGE_MAX_GENERATION_RECORDS, USAGE_MIN_GENERATIONS_FOR_HABITS};
+use crate::error::{AppError, Result};
+use crate::utils::write_file_atomically;
+
+/// One explicit document generation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenerationRecord {
+    pub at: i64, // Unix timestamp
+    pub file_count: usize,
+    pub size_bytes: u64,
+}
+
+/// Usage of one project, kept only on this machine in the OS data directory
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageStats {
+    pub total_generations: usize,
+    /// Oldest first, capped to the most recent records
+    pub generations: Vec<GenerationRecord>,
+    /// Generations each file was part of, keyed by path relative to the project directory
+    pub file_inclusions: BTreeMap<PathBuf, usize>,
+}
+
+impl UsageStats {
+    /// Returns the recorded stats, or empty ones if there are none or they can't be read
+    pub fn load(directory: &Path) -> Self {
+        let Some(stats_path) = Self::stats_path(directory) else {
+            return Self::default();
+        };
+        let Ok(bytes) = fs::read(&stats_path) else {
+            return Self::default();
+        };
+
+        serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            debug!("Ignoring unreadable usage stats {:?}: {}", stats_path, e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, directory: &Path) -> Result<()> {
+        let stats_path = Self::stats_path(directory).ok_or_else(|| AppError::SettingsError {
+            path: directory.to_path_buf(),
+            details: "No data directory available on this platform".to_string(),
+        })?;
+        let bytes = serde_json::to_vec(self).map_err(|e| AppError::SettingsError {
+            path: stats_path.clone(),
+            details: format!("Failed to serialize usage stats: {}", e),
+        })?;
+
+        write_file_atomically(&stats_path, &bytes)?;
+        debug!("Saved usage stats to {:?}", stats_path);
+        Ok(())
+    }
+
+    pub fn record_generation(&mut self, relative_files: &[PathBuf], size_bytes: u64) {
+        self.total_generations += 1;
+        self.generations.push(GenerationRecord {
+            at: Local::now().timestamp(),
+            file_count: relative_files.len(),
+            size_bytes,
+        });
+        let excess = self.generations.len().saturating_sub(USAGE_MAX_GENERATION_RECORDS);
+        self.generations.drain(..excess);
+
+        for path in relative_files {
+            *self.file_inclusions.entry(path.clone()).or_default() += 1;
+        }
+    }
+
+    pub fn generations_since(&self, duration: Duration) -> usize {
+        let since = (Local::now() - duration).timestamp();
+        self.generations.iter().filter(|record| record.at >= since).count()
+    }
+
+    /// Average file count and document size of the recorded generations
+    pub fn average_context(&self) -> Option<(usize, u64)> {
+        let count = self.generations.len();
+        if count == 0 {
+            return None;
+        }
+        let files: usize = self.generations.iter().map(|record| record.file_count).sum();
+        let bytes: u64 = self.generations.iter().map(|record| record.size_bytes).sum();
+        Some((files / count, bytes / count as u64))
+    }
+
+    /// Files by how often they were included, most first
+    pub fn most_included_files(&self, limit: usize) -> Vec<(PathBuf, usize)> {
+        let mut files: Vec<(PathBuf, usize)> = self.file_inclusions.iter()
+            .map(|(path, count)| (path.clone(), *count))
+            .collect();
+        files.sort_by(|(path_a, count_a), (path_b, count_b)| count_b.cmp(count_a).then_with(|| path_a.cmp(path_b)));
+        files.truncate(limit);
+        files
+    }
+
+    /// Files part of nearly every generation, worth tagging so they can be selected in one click.
+    /// Empty until there are enough generations to call it a habit.
+    pub fn habitual_files(&self) -> Vec<PathBuf> {
+        let total = self.total_generations;
+        if total < USAGE_MIN_GENERATIONS_FOR_HABITS {
+            return Vec::new();
+        }
+        self.file_inclusions.iter()
+            .filter(|(_, count)| **count as f64 >= total as f64 * USAGE_FREQUENT_FILE_RATIO)
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    fn stats_path(directory: &Path) -> Option<PathBuf> {
+        let mut hasher = DefaultHasher::new();
+        directory.hash(&mut hasher);
+
+        Some(dirs::data_local_dir()?
+            .join(APP_DATA_DIR_NAME)
+            .join("usage")
+            .join(format!("{:016x}.json", hasher.finish())))
+    }
+}
@@ -0,0 +1,214 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Component, Path};
+use std::thread;
+use std::time::SystemTime;
+use log::warn;
+
+use crate::constants::{BINARY_DETECTION_BYTES, FILE_READ_RETRY_DELAY};
+use crate::error::{AppError, Result};
+use crate::temp_files::create_temp_file_in;
+
+/// Human-readable size such as `512 B`, `14.2 KB` or `3.1 MB`
+pub fn format_byte_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit_index = 0;
+    while size >= 1024.0 && unit_index < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit_index += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit_index])
+}
+
+/// Time since `modified` such as `just now`, `5m ago`, `2d ago` or `3mo ago`
+pub fn format_age(modified: SystemTime, now: SystemTime) -> String {
+    let seconds = now.duration_since(modified).map(|age| age.as_secs()).unwrap_or(0);
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3_599 => format!("{}m ago", seconds / 60),
+        3_600..=86_399 => format!("{}h ago", seconds / 3_600),
+        86_400..=2_591_999 => format!("{}d ago", seconds / 86_400),
+        2_592_000..=31_535_999 => format!("{}mo ago", seconds / 2_592_000),
+        _ => format!("{}y ago", seconds / 31_536_000),
+    }
+}
+
+/// Approximate token count such as `~850`, `~1.1k` or `~2.3M`
+pub fn format_token_count(tokens: usize) -> String {
+    match tokens {
+        0..=999 => format!("~{}", tokens),
+        1_000..=999_999 => format!("~{:.1}k", tokens as f64 / 1_000.0),
+        _ => format!("~{:.1}M", tokens as f64 / 1_000_000.0),
+    }
+}
+
+/// Numeric components of a version for ordering, e.g. `v1.2.10` before `1.10.0`; pre-release
+/// tags count as 0
+pub fn version_key(version: &str) -> Vec<u64> {
+    version.trim_start_matches('v')
+        .split(['.', '-', '+'])
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+/// `to` relative to the directory `from`, with `/` separators, e.g. `../src/main.rs`. Falls back
+/// to `to` itself when the paths share no root (different drives on Windows).
+pub fn relative_path(from: &Path, to: &Path) -> String {
+    let from_components: Vec<Component> = from.components().collect();
+    let to_components: Vec<Component> = to.components().collect();
+    let common = from_components.iter().zip(&to_components).take_while(|(a, b)| a == b).count();
+    if common == 0 {
+        return to.to_string_lossy().replace('\\', "/");
+    }
+
+    let mut parts: Vec<String> = vec!["..".to_string(); from_components.len() - common];
+    parts.extend(to_components[common..].iter().map(|component| component.as_os_str().to_string_lossy().to_string()));
+    parts.join("/")
+}
+
+/// Writes `bytes` to `path` via a temp file in the same directory, creating the directory
+/// if needed, so readers never see a half-written file
+pub fn write_file_atomically(path: &Path, bytes: &[u8]) -> Result<()> {
+    let parent_dir = path.parent().ok_or_else(|| AppError::AtomicWriteError {
+        path: path.to_path_buf(),
+        details: "Path has no parent directory".to_string(),
+    })?;
+    fs::create_dir_all(parent_dir)
+        .map_err(|e| AppError::new_io_error(e, Some(parent_dir.to_path_buf()), "Failed to create directory".to_string()))?;
+
+    let mut temp_file = create_temp_file_in(parent_dir)?;
+    temp_file.write_all(bytes)
+        .map_err(|e| AppError::new_io_error(e, Some(temp_file.path().to_path_buf()), "Failed to write to temp file.".to_string()))?;
+    temp_file.persist(path).map_err(|e| AppError::AtomicWriteError {
+        path: path.to_path_buf(),
+        details: format!("Failed to persist temp file to target path: {}", e.error),
+    })?;
+
+    Ok(())
+}
+
+/// Reads a file that may be written to at the same time. A read during which the file changed
+/// size or modification time, or that failed, is retried once before giving up.
+pub fn read_file_consistently(path: &Path) -> Result<Vec<u8>> {
+    read_file_consistently_with(path, &mut |path| fs::read(path))
+}
+
+/// `read_file_consistently` with the read itself replaceable, so tests can change the file
+/// between the metadata checks and the read
+fn read_file_consistently_with(path: &Path, read: &mut dyn FnMut(&Path) -> std::io::Result<Vec<u8>>) -> Result<Vec<u8>> {
+    match read_file_unchanged(path, read) {
+        Ok(bytes) => Ok(bytes),
+        Err(e) => {
+            warn!("Retrying read of {:?}: {}", path, e);
+            thread::sleep(FILE_READ_RETRY_DELAY);
+            read_file_unchanged(path, read)
+        }
+    }
+}
+
+fn read_file_unchanged(path: &Path, read: &mut dyn FnMut(&Path) -> std::io::Result<Vec<u8>>) -> Result<Vec<u8>> {
+    let io_error = |e: std::io::Error| {
+        let details = format!("Failed to read file: {}", e);
+        AppError::new_io_error(e, Some(path.to_path_buf()), details)
+    };
+    let before = fs::metadata(path).map_err(io_error)?;
+    let bytes = read(path).map_err(io_error)?;
+    let after = fs::metadata(path).map_err(io_error)?;
+
+    let resized = bytes.len() as u64 != after.len() || before.len() != after.len();
+    if resized || before.modified().ok() != after.modified().ok() {
+        return Err(AppError::OperationFailed(format!("File {:?} changed while it was being read", path)));
+    }
+    Ok(bytes)
+}
+
+/// NUL bytes near the start are the same heuristic git uses to tell binary files apart
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_DETECTION_BYTES)].contains(&0)
+}
+
+/// Checks the start of a file without reading all of it. Fails with `AppError::BinaryFile` for
+/// binary files; files that can't be opened pass, so reading them reports the actual error.
+pub fn ensure_text_file(path: &Path) -> Result<()> {
+    let Ok(file) = fs::File::open(path) else {
+        return Ok(());
+    };
+    let mut head = Vec::with_capacity(BINARY_DETECTION_BYTES);
+    if file.take(BINARY_DETECTION_BYTES as u64).read_to_end(&mut head).is_err() || !looks_binary(&head) {
+        return Ok(());
+    }
+    Err(AppError::BinaryFile {
+        path: path.to_path_buf(),
+        size_bytes: fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document_generator::DocumentGenerator;
+
+    /// Appends to the file before reading it, the way a writer racing the read would
+    fn growing_read(path: &Path) -> std::io::Result<Vec<u8>> {
+        fs::OpenOptions::new().append(true).open(path)?.write_all(b"more\n")?;
+        fs::read(path)
+    }
+
+    #[test]
+    fn healthy_read_returns_the_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("main.rs");
+        fs::write(&path, "fn main() {}\n").unwrap();
+
+        assert_eq!(read_file_consistently(&path).unwrap(), b"fn main() {}\n");
+
+        let generator = DocumentGenerator::new(dir.path().to_path_buf(), vec![path.clone()]);
+        assert_eq!(generator.read_file_content(&path), "fn main() {}");
+        assert_eq!(generator.take_read_outcomes().get(&path), Some(&None));
+    }
+
+    #[test]
+    fn size_change_during_read_is_retried() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("build.log");
+        fs::write(&path, "start\n").unwrap();
+
+        // Only the first read races a writer: the retry sees the settled file
+        let mut reads = 0;
+        let bytes = read_file_consistently_with(&path, &mut |path| {
+            reads += 1;
+            if reads == 1 { growing_read(path) } else { fs::read(path) }
+        }).unwrap();
+        assert_eq!(reads, 2);
+        assert_eq!(bytes, b"start\nmore\n");
+
+        // A file that keeps changing fails after the retry
+        let mut reads = 0;
+        let result = read_file_consistently_with(&path, &mut |path| {
+            reads += 1;
+            growing_read(path)
+        });
+        assert_eq!(reads, 2);
+        assert!(matches!(result, Err(AppError::OperationFailed(message)) if message.contains("changed while it was being read")));
+    }
+
+    #[test]
+    fn deleted_file_gets_a_placeholder_and_a_read_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gone.rs");
+        fs::write(&path, "fn gone() {}\n").unwrap();
+        let generator = DocumentGenerator::new(dir.path().to_path_buf(), vec![path.clone()]);
+        fs::remove_file(&path).unwrap();
+
+        assert!(generator.read_file_content(&path).starts_with("[ERROR: This file could not be read:"));
+        let outcomes = generator.take_read_outcomes();
+        assert!(outcomes.get(&path).is_some_and(|error| error.as_ref().is_some_and(|error| error.contains("Failed to read file"))));
+        assert!(generator.take_read_outcomes().is_empty());
+    }
+}
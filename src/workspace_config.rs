@@ -0,0 +1,119 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use log::info;
+use serde::Deserialize;
+
+use crate::constants::WORKSPACE_CONFIG_FILE_NAME;
+use crate::error::{AppError, Result};
+
+/// Checked-in project configuration, `.contextbuilder.toml` in the project root. A cloned
+/// repository can put a command in it, so the app uses it only for directories the user trusted.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkspaceConfig {
+    template: Option<PathBuf>,      // Tera template for the document, relative to the project root
+    after_generate: Option<String>, // Shell command run in the project root after each full generation
+}
+
+impl WorkspaceConfig {
+    /// The project's configuration, or `None` if it has no `.contextbuilder.toml`
+    pub fn load(directory: &Path) -> Result<Option<Self>> {
+        let config_path = directory.join(WORKSPACE_CONFIG_FILE_NAME);
+        let text = match fs::read_to_string(&config_path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(AppError::new_io_error(e, Some(config_path), "Failed to read the project configuration".to_string())),
+        };
+        toml::from_str(&text)
+            .map(Some)
+            .map_err(|e| AppError::SettingsError { path: config_path, details: e.to_string() })
+    }
+
+    pub fn template_path(&self, directory: &Path) -> Option<PathBuf> {
+        self.template.as_ref().map(|template| directory.join(template))
+    }
+
+    pub fn after_generate(&self) -> Option<&str> {
+        self.after_generate.as_deref().map(str::trim).filter(|command| !command.is_empty())
+    }
+
+    /// What trusting the directory would allow, one line each, for the trust prompt
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(template) = &self.template {
+            lines.push(format!("Render the document with the template {}", template.display()));
+        }
+        if let Some(command) = self.after_generate() {
+            lines.push(format!("Run `{}` after each generation", command));
+        }
+        lines
+    }
+}
+
+/// Runs the `after_generate` command through the system shell in the project root and waits for
+/// it. The document path is passed in `CONTEXT_BUILDER_DOCUMENT`.
+pub fn run_after_generate(command: &str, directory: &Path, document_path: &Path) -> Result<()> {
+    info!("Running the project's after_generate command: {}", command);
+    let mut shell = match cfg!(windows) {
+        true => {
+            let mut shell = Command::new("cmd");
+            shell.arg("/C");
+            shell
+        }
+        false => {
+            let mut shell = Command::new("sh");
+            shell.arg("-c");
+            shell
+        }
+    };
+    let output = shell
+        .arg(command)
+        .current_dir(directory)
+        .env("CONTEXT_BUILDER_DOCUMENT", document_path)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| AppError::new_io_error(e, Some(directory.to_path_buf()), "Failed to run the after_generate command".to_string()))?;
+    if !output.status.success() {
+        return Err(AppError::OperationFailed(format!(
+            "after_generate command `{}` failed ({}): {}",
+            command, output.status, String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configs_are_loaded_and_described() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        assert_eq!(WorkspaceConfig::load(temp_dir.path()).unwrap(), None);
+
+        fs::write(temp_dir.path().join(WORKSPACE_CONFIG_FILE_NAME), "template = \"docs/context.md.tera\"\nafter_generate = \"make docs\"\n").unwrap();
+        let config = WorkspaceConfig::load(temp_dir.path()).unwrap().unwrap();
+        assert_eq!(config.template_path(temp_dir.path()), Some(temp_dir.path().join("docs/context.md.tera")));
+        assert_eq!(config.after_generate(), Some("make docs"));
+        assert_eq!(config.describe(), vec![
+            "Render the document with the template docs/context.md.tera".to_string(),
+            "Run `make docs` after each generation".to_string(),
+        ]);
+
+        fs::write(temp_dir.path().join(WORKSPACE_CONFIG_FILE_NAME), "before_generate = \"rm -rf /\"\n").unwrap();
+        assert!(WorkspaceConfig::load(temp_dir.path()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn after_generate_runs_in_the_project_root_with_the_document_path() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let document_path = temp_dir.path().join("context.md");
+        run_after_generate("printf %s \"$CONTEXT_BUILDER_DOCUMENT\" > hook.out", temp_dir.path(), &document_path).unwrap();
+        assert_eq!(fs::read_to_string(temp_dir.path().join("hook.out")).unwrap(), document_path.display().to_string());
+
+        assert!(run_after_generate("exit 3", temp_dir.path(), &document_path).is_err());
+    }
+}